@@ -0,0 +1,121 @@
+use alloc::vec::Vec;
+
+/// Relative frequencies of the instructions in generated programs. A weight of zero
+/// disables the corresponding instructions.
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionWeights {
+    /// `>` and `<`
+    pub move_pointer: u32,
+    /// `+` and `-`
+    pub change_value: u32,
+    /// `.`
+    pub put_char: u32,
+    /// `,`
+    pub get_char: u32,
+    /// `[` and `]`
+    pub loop_: u32,
+}
+
+impl Default for InstructionWeights {
+    fn default() -> Self {
+        Self {
+            move_pointer: 4,
+            change_value: 6,
+            put_char: 1,
+            get_char: 1,
+            loop_: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GeneratorOptions {
+    /// The exact number of instructions (including brackets) of the generated program.
+    pub size: usize,
+    pub max_depth: usize,
+    pub weights: InstructionWeights,
+}
+
+/// Generates a syntactically valid program. The same seed and options always produce the
+/// same program.
+pub fn generate(seed: u64, options: &GeneratorOptions) -> Vec<u8> {
+    let mut generator = Generator {
+        random: SplitMix64(seed),
+        options,
+        output: Vec::with_capacity(options.size),
+    };
+    generator.block(options.size, 0);
+    generator.output
+}
+
+struct Generator<'a> {
+    random: SplitMix64,
+    options: &'a GeneratorOptions,
+    output: Vec<u8>,
+}
+
+impl Generator<'_> {
+    fn block(&mut self, size: usize, depth: usize) {
+        let mut remaining = size;
+        while remaining > 0 {
+            let loop_allowed = remaining >= 2 && depth < self.options.max_depth;
+            match self.instruction(loop_allowed) {
+                b'[' => {
+                    let body_size = self.random.below(remaining as u64 - 1) as usize;
+                    self.output.push(b'[');
+                    self.block(body_size, depth + 1);
+                    self.output.push(b']');
+                    remaining -= body_size + 2;
+                }
+                instruction => {
+                    self.output.push(instruction);
+                    remaining -= 1;
+                }
+            }
+        }
+    }
+
+    fn instruction(&mut self, loop_allowed: bool) -> u8 {
+        let weights = self.options.weights;
+        let loop_weight = if loop_allowed { weights.loop_ } else { 0 };
+        let choices = [
+            (b'>', weights.move_pointer),
+            (b'<', weights.move_pointer),
+            (b'+', weights.change_value),
+            (b'-', weights.change_value),
+            (b'.', weights.put_char),
+            (b',', weights.get_char),
+            (b'[', loop_weight),
+        ];
+        let total: u64 = choices.iter().map(|&(_, weight)| weight as u64).sum();
+        if total == 0 {
+            return b'+';
+        }
+        let mut choice = self.random.below(total);
+        for (instruction, weight) in choices {
+            if choice < weight as u64 {
+                return instruction;
+            }
+            choice -= weight as u64;
+        }
+        unreachable!("choice is smaller than the sum of all weights")
+    }
+}
+
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut result = self.0;
+        result = (result ^ (result >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        result = (result ^ (result >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        result ^ (result >> 31)
+    }
+
+    /// Returns a number in `0..bound`. The slight bias towards small numbers is irrelevant
+    /// for generating programs.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
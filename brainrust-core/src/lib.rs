@@ -0,0 +1,12 @@
+//! The Brainfuck parser and program representation used by brainrust. This crate only
+//! depends on `core` and `alloc`, so it can be used in embedded and WebAssembly contexts.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod generator;
+pub mod optimizer;
+pub mod parser;
+pub mod plugin;
+pub mod program;
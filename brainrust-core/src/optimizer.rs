@@ -0,0 +1,589 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::program::{rewrite_bodies, CellSize, Program, Span, Statement, StatementKind};
+
+/// A transformation of a program that does not change its behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Collapses runs of `+`/`-` and `>`/`<` into single counted statements, runs that cancel
+    /// out completely are removed.
+    RunLength,
+    /// Replaces clear loops like `[-]` by a single assignment.
+    ClearLoops,
+    /// Replaces multiply loops like `[->+>++<<]` by a single [`StatementKind::MultiplyAdd`].
+    MultiplyLoops,
+    /// Combines the changes of cells between two statements that need the pointer (e.g. loops)
+    /// into at most one statement per cell, addressed relative to the pointer, followed by a
+    /// single pointer movement (e.g. `>+>++<<-` becomes three additions and no movement at all).
+    Offsets,
+    /// Replaces loops that always leave the current cell at zero, and therefore run at most
+    /// once, by a [`StatementKind::If`] (e.g. `[>+<[-]]`).
+    Conditionals,
+    /// Removes loops that can never be entered because the current cell is known to be zero,
+    /// e.g. at the start of the program or directly after another loop.
+    DeadCode,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 6] = [
+        Pass::RunLength,
+        Pass::ClearLoops,
+        Pass::MultiplyLoops,
+        Pass::Offsets,
+        Pass::Conditionals,
+        Pass::DeadCode,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::RunLength => "run-length",
+            Pass::ClearLoops => "clear-loops",
+            Pass::MultiplyLoops => "multiply-loops",
+            Pass::Offsets => "offsets",
+            Pass::Conditionals => "conditionals",
+            Pass::DeadCode => "dead-code",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pass| pass.name() == name)
+    }
+
+    /// Runs the pass for 8-bit cells.
+    pub fn run(self, program: &Program) -> Program {
+        self.run_for(program, CellSize::default())
+    }
+
+    /// Runs the pass for cells of the given size, which determines when additions cancel out.
+    pub fn run_for(self, program: &Program, cell_size: CellSize) -> Program {
+        let statements = program.statements();
+        Program::new(match self {
+            Pass::RunLength => run_length_encode(statements, cell_size),
+            Pass::ClearLoops => fold_loops(statements, &|body| clear_loop(body, cell_size)),
+            Pass::MultiplyLoops => fold_loops(statements, &|body| multiply_loop(body, cell_size)),
+            Pass::Offsets => fuse_pointer_movements(statements, cell_size),
+            Pass::Conditionals => fold_conditionals(statements),
+            Pass::DeadCode => remove_dead_loops(statements),
+        })
+    }
+}
+
+/// The passes run by [`Pipeline::for_level`], starting at level 1.
+const LEVELS: [&[Pass]; 2] = [
+    &[Pass::RunLength, Pass::ClearLoops],
+    &[
+        Pass::RunLength,
+        Pass::ClearLoops,
+        Pass::MultiplyLoops,
+        Pass::Offsets,
+        Pass::Conditionals,
+        Pass::DeadCode,
+    ],
+];
+
+/// An ordered list of passes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    passes: Vec<Pass>,
+    cell_size: CellSize,
+}
+
+impl Pipeline {
+    pub const MAX_LEVEL: u8 = LEVELS.len() as u8;
+    pub const DEFAULT_LEVEL: u8 = Self::MAX_LEVEL;
+
+    pub fn new(passes: Vec<Pass>) -> Self {
+        Self {
+            passes,
+            cell_size: CellSize::default(),
+        }
+    }
+
+    /// Optimizes for cells of the given size instead of 8-bit cells.
+    pub fn with_cell_size(mut self, cell_size: CellSize) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    /// Level 0 does not optimize at all, higher levels run more passes. Levels above
+    /// [`Pipeline::MAX_LEVEL`] are treated as the maximum level.
+    pub fn for_level(level: u8) -> Self {
+        match level.min(Self::MAX_LEVEL) {
+            0 => Self::new(Vec::new()),
+            level => Self::new(LEVELS[level as usize - 1].to_vec()),
+        }
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    pub fn cell_size(&self) -> CellSize {
+        self.cell_size
+    }
+
+    pub fn run(&self, program: &Program) -> Program {
+        self.run_each(program, |pass, program| {
+            pass.run_for(program, self.cell_size)
+        })
+    }
+
+    /// Like [`Pipeline::run`], but lets the caller run every pass, e.g. to measure it.
+    pub fn run_each(
+        &self,
+        program: &Program,
+        mut run_pass: impl FnMut(Pass, &Program) -> Program,
+    ) -> Program {
+        let mut program = Program::new(program.statements().clone());
+        for pass in &self.passes {
+            program = run_pass(*pass, &program);
+        }
+        program
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::for_level(Self::DEFAULT_LEVEL)
+    }
+}
+
+/// Runs the default pipeline.
+pub fn optimize(program: &Program) -> Program {
+    Pipeline::default().run(program)
+}
+
+// Passes the statements and the body of every loop and `if` through `rewrite`, innermost bodies
+// first.
+fn rewrite_blocks(
+    statements: &[Statement],
+    rewrite: &mut dyn FnMut(Vec<Statement>) -> Vec<Statement>,
+) -> Vec<Statement> {
+    let statements = rewrite_bodies(statements, rewrite);
+    rewrite(statements)
+}
+
+fn run_length_encode(statements: &[Statement], cell_size: CellSize) -> Vec<Statement> {
+    rewrite_blocks(statements, &mut |block| encode_runs(block, cell_size))
+}
+
+fn encode_runs(statements: Vec<Statement>, cell_size: CellSize) -> Vec<Statement> {
+    let mut result: Vec<Statement> = Vec::with_capacity(statements.len());
+    for statement in statements {
+        if let Some(previous) = result.last() {
+            let merged_kind = match (previous.kind(), statement.kind()) {
+                (StatementKind::Move(first), StatementKind::Move(second)) => {
+                    Some(StatementKind::Move(first + second))
+                }
+                (first, second) if same_cell(first, second) => merge(first, second, cell_size),
+                _ => None,
+            };
+            if let Some(merged_kind) = merged_kind {
+                let span = join(Some(previous.span()), statement.span());
+                result.pop();
+                if !is_no_op(&merged_kind, cell_size) {
+                    result.push(Statement::new(merged_kind, span));
+                }
+                continue;
+            }
+        }
+        result.push(statement);
+    }
+    result
+}
+
+/// Replaces every loop by what `fold` returns for its body (the loop itself if it cannot be
+/// folded), innermost loops first.
+fn fold_loops(
+    statements: &[Statement],
+    fold: &dyn Fn(Vec<Statement>) -> StatementKind,
+) -> Vec<Statement> {
+    rewrite_blocks(statements, &mut |mut block| {
+        for statement in &mut block {
+            if let StatementKind::Loop(body) = statement.kind_mut() {
+                let body = core::mem::take(body);
+                *statement.kind_mut() = fold(body);
+            }
+        }
+        block
+    })
+}
+
+fn clear_loop(body: Vec<Statement>, cell_size: CellSize) -> StatementKind {
+    match loop_effects(&body, cell_size) {
+        // adding an odd amount reaches zero from any value because the number of values of a
+        // cell is a power of two
+        Some((step, targets)) if targets.is_empty() && step % 2 != 0 => StatementKind::Set {
+            offset: 0,
+            value: 0,
+        },
+        _ => StatementKind::Loop(body),
+    }
+}
+
+fn multiply_loop(body: Vec<Statement>, cell_size: CellSize) -> StatementKind {
+    let Some((step, targets)) = loop_effects(&body, cell_size) else {
+        return StatementKind::Loop(body);
+    };
+    match cell_size.wrap(step) {
+        step if step == cell_size.max_value() => StatementKind::MultiplyAdd(targets),
+        // counting up from `value` takes `-value` iterations
+        1 => StatementKind::MultiplyAdd(
+            targets
+                .into_iter()
+                .map(|(offset, factor)| (offset, -factor))
+                .collect(),
+        ),
+        _ => StatementKind::Loop(body),
+    }
+}
+
+/// Replaces every loop that runs at most once by an `if`.
+fn fold_conditionals(statements: &[Statement]) -> Vec<Statement> {
+    // Whether each rewritten body is balanced and whether its loop runs at most once, for the
+    // loops that have not been replaced yet. Bodies are rewritten innermost first, so those of the
+    // loops of a block are the last ones when the block itself is rewritten.
+    let mut bodies: Vec<(bool, bool)> = Vec::new();
+    rewrite_blocks(statements, &mut |mut block| {
+        let nested = block
+            .iter()
+            .filter(|statement| {
+                matches!(
+                    statement.kind(),
+                    StatementKind::Loop(_) | StatementKind::If(_)
+                )
+            })
+            .count();
+        let nested = bodies.split_off(bodies.len() - nested);
+        let mut nested_bodies = nested.iter();
+        for statement in &mut block {
+            match statement.kind_mut() {
+                StatementKind::Loop(body) => {
+                    let &(_, runs_at_most_once) = nested_bodies.next().expect("counted above");
+                    if runs_at_most_once {
+                        let body = core::mem::take(body);
+                        *statement.kind_mut() = StatementKind::If(body);
+                    }
+                }
+                StatementKind::If(_) => {
+                    nested_bodies.next();
+                }
+                _ => {}
+            }
+        }
+        let nested_balanced: Vec<bool> = nested.iter().map(|&(balanced, _)| balanced).collect();
+        bodies.push((
+            is_balanced(&block, &nested_balanced),
+            runs_at_most_once(&block, &nested_balanced),
+        ));
+        block
+    })
+}
+
+/// Returns whether every execution of the loop body ends with the pointer where it started and
+/// the current cell set to zero, so that the loop is never repeated. `nested_balanced` tells for
+/// each loop and `if` of the body whether its own body is balanced.
+fn runs_at_most_once(body: &[Statement], nested_balanced: &[bool]) -> bool {
+    let mut nested_balanced = nested_balanced.iter();
+    let mut pointer = 0;
+    let mut current_cell_is_zero = false;
+    for statement in body {
+        match statement.kind() {
+            StatementKind::Move(amount) => pointer += amount,
+            StatementKind::Add { offset, .. } if pointer + offset == 0 => {
+                current_cell_is_zero = false
+            }
+            StatementKind::Set { offset, value } if pointer + offset == 0 => {
+                current_cell_is_zero = *value == 0
+            }
+            StatementKind::Add { .. } | StatementKind::Set { .. } => {}
+            StatementKind::MultiplyAdd(_) if pointer == 0 => current_cell_is_zero = true,
+            StatementKind::MultiplyAdd(targets) => {
+                if targets.iter().any(|(offset, _)| pointer + offset == 0) {
+                    current_cell_is_zero = false;
+                }
+            }
+            StatementKind::PutChar | StatementKind::Store | StatementKind::DebugDump => {}
+            StatementKind::GetChar | StatementKind::Load => {
+                if pointer == 0 {
+                    current_cell_is_zero = false;
+                }
+            }
+            StatementKind::Loop(inner) | StatementKind::If(inner) => {
+                if !nested_balanced.next().expect("one for each loop") {
+                    return false;
+                }
+                // both leave the cell they start at zero, unless a loop is left with `break`
+                current_cell_is_zero = pointer == 0
+                    && !inner
+                        .iter()
+                        .any(|statement| matches!(statement.kind(), StatementKind::Break));
+            }
+            StatementKind::Break | StatementKind::Continue => return false,
+        }
+    }
+    pointer == 0 && current_cell_is_zero
+}
+
+/// Returns whether the statements (including nested loops) always return the pointer to where
+/// it started. `nested_balanced` tells for each loop and `if` whether its own body is balanced.
+fn is_balanced(statements: &[Statement], nested_balanced: &[bool]) -> bool {
+    let pointer: i64 = statements
+        .iter()
+        .map(|statement| match statement.kind() {
+            StatementKind::Move(amount) => *amount,
+            _ => 0,
+        })
+        .sum();
+    pointer == 0 && nested_balanced.iter().all(|&balanced| balanced)
+}
+
+/// Returns how much a single iteration of a loop with the given body changes the current
+/// cell and the changes of all other cells (as pairs of offset and amount). Returns `None` if
+/// the body does anything else than moving the pointer and changing cells, or if it does not
+/// return the pointer to where it started.
+fn loop_effects(body: &[Statement], cell_size: CellSize) -> Option<(i64, Vec<(i64, i64)>)> {
+    let mut pointer = 0;
+    let mut deltas = BTreeMap::new();
+    for statement in body {
+        match statement.kind() {
+            StatementKind::Move(amount) => pointer += amount,
+            StatementKind::Add { offset, amount } => {
+                *deltas.entry(pointer + offset).or_insert(0) += amount
+            }
+            _ => return None,
+        }
+    }
+    if pointer != 0 {
+        return None;
+    }
+    let step = deltas.remove(&0).unwrap_or(0);
+    let targets = deltas
+        .into_iter()
+        .filter(|&(_, delta)| cell_size.wrap(delta) != 0)
+        .collect();
+    Some((step, targets))
+}
+
+fn fuse_pointer_movements(statements: &[Statement], cell_size: CellSize) -> Vec<Statement> {
+    rewrite_blocks(statements, &mut |block| fuse_block(block, cell_size))
+}
+
+fn fuse_block(statements: Vec<Statement>, cell_size: CellSize) -> Vec<Statement> {
+    let mut result = Vec::with_capacity(statements.len());
+    let mut run = StraightLineRun {
+        cell_size,
+        ..StraightLineRun::default()
+    };
+    for statement in statements {
+        let span = statement.span();
+        match statement.kind() {
+            StatementKind::Move(amount) => run.move_pointer(*amount, span),
+            kind @ (StatementKind::Add { .. } | StatementKind::Set { .. }) => {
+                run.push(kind.clone(), span)
+            }
+            _ => {
+                run.flush_into(&mut result);
+                result.push(statement);
+            }
+        }
+    }
+    run.flush_into(&mut result);
+    result
+}
+
+/// The statements between two statements that need the pointer to be at its actual position.
+/// Offsets of the collected statements are relative to the pointer at the start of the run.
+#[derive(Default)]
+struct StraightLineRun {
+    cell_size: CellSize,
+    pointer: i64,
+    movement: Option<Span>,
+    statements: Vec<Statement>,
+}
+
+impl StraightLineRun {
+    fn move_pointer(&mut self, amount: i64, span: Span) {
+        self.pointer += amount;
+        self.movement = Some(join(self.movement, span));
+    }
+
+    fn push(&mut self, kind: StatementKind, span: Span) {
+        let kind = match kind {
+            StatementKind::Add { offset, amount } => StatementKind::Add {
+                offset: offset + self.pointer,
+                amount,
+            },
+            StatementKind::Set { offset, value } => StatementKind::Set {
+                offset: offset + self.pointer,
+                value,
+            },
+            _ => unreachable!("only cell changes are collected"),
+        };
+        // changes of different cells are independent of each other, so the change can be merged
+        // with an earlier one of the same cell
+        let previous = self
+            .statements
+            .iter()
+            .rposition(|statement| same_cell(statement.kind(), &kind));
+        if let Some(index) = previous {
+            let previous = &self.statements[index];
+            if let Some(merged_kind) = merge(previous.kind(), &kind, self.cell_size) {
+                let span = join(Some(previous.span()), span);
+                if is_no_op(&merged_kind, self.cell_size) {
+                    self.statements.remove(index);
+                } else {
+                    self.statements[index] = Statement::new(merged_kind, span);
+                }
+                return;
+            }
+        }
+        self.statements.push(Statement::new(kind, span));
+    }
+
+    fn flush_into(&mut self, result: &mut Vec<Statement>) {
+        result.append(&mut self.statements);
+        if let Some(span) = self.movement.take() {
+            if self.pointer != 0 {
+                result.push(Statement::new(StatementKind::Move(self.pointer), span));
+            }
+        }
+        self.pointer = 0;
+    }
+}
+
+fn remove_dead_loops(statements: &[Statement]) -> Vec<Statement> {
+    // the body of a loop or an `if` starts with a current cell that is not zero
+    let statements = rewrite_bodies(statements, &mut |body| {
+        remove_dead_loops_in_block(body, false)
+    });
+    // every cell is zero when the program starts
+    remove_dead_loops_in_block(statements, true)
+}
+
+fn remove_dead_loops_in_block(
+    statements: Vec<Statement>,
+    mut current_cell_is_zero: bool,
+) -> Vec<Statement> {
+    let mut result = Vec::with_capacity(statements.len());
+    for statement in statements {
+        match statement.kind() {
+            StatementKind::Loop(_) | StatementKind::If(_) if current_cell_is_zero => continue,
+            StatementKind::Loop(body) => {
+                // a `break` can leave the loop while the current cell is not zero
+                current_cell_is_zero = !body
+                    .iter()
+                    .any(|statement| matches!(statement.kind(), StatementKind::Break));
+            }
+            StatementKind::If(_) => current_cell_is_zero = true,
+            StatementKind::Set { offset: 0, value } => current_cell_is_zero = *value == 0,
+            StatementKind::MultiplyAdd(_) => current_cell_is_zero = true,
+            StatementKind::Add { offset, .. } | StatementKind::Set { offset, .. }
+                if *offset != 0 => {}
+            StatementKind::PutChar | StatementKind::Store | StatementKind::DebugDump => {}
+            _ => current_cell_is_zero = false,
+        }
+        result.push(statement);
+    }
+    result
+}
+
+fn join(first: Option<Span>, second: Span) -> Span {
+    match first {
+        Some(first) => Span::new(first.start.min(second.start), first.end.max(second.end)),
+        None => second,
+    }
+}
+
+fn same_cell(first: &StatementKind, second: &StatementKind) -> bool {
+    match (cell_offset(first), cell_offset(second)) {
+        (Some(first), Some(second)) => first == second,
+        _ => false,
+    }
+}
+
+fn cell_offset(kind: &StatementKind) -> Option<i64> {
+    match kind {
+        StatementKind::Add { offset, .. } | StatementKind::Set { offset, .. } => Some(*offset),
+        _ => None,
+    }
+}
+
+// Both statements must change the same cell. An addition after an assignment is only merged if
+// the sum is a valid value of an assignment.
+fn merge(
+    first: &StatementKind,
+    second: &StatementKind,
+    cell_size: CellSize,
+) -> Option<StatementKind> {
+    match (first, second) {
+        (
+            StatementKind::Add { offset, amount },
+            StatementKind::Add {
+                amount: second_amount,
+                ..
+            },
+        ) => Some(StatementKind::Add {
+            offset: *offset,
+            amount: amount + second_amount,
+        }),
+        (StatementKind::Set { offset, value }, StatementKind::Add { amount, .. }) => {
+            let value = cell_size.wrap(i64::from(*value).wrapping_add(*amount));
+            Some(StatementKind::Set {
+                offset: *offset,
+                value: u8::try_from(value).ok()?,
+            })
+        }
+        (StatementKind::Add { .. } | StatementKind::Set { .. }, StatementKind::Set { .. }) => {
+            Some(second.clone())
+        }
+        _ => None,
+    }
+}
+
+fn is_no_op(kind: &StatementKind, cell_size: CellSize) -> bool {
+    matches!(kind, StatementKind::Move(0))
+        || matches!(kind, StatementKind::Add { amount, .. } if cell_size.wrap(*amount) == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    const DEPTH: usize = 50_000;
+
+    // `+[[[…[-]…]]]`, with the innermost loop at the given depth
+    fn deeply_nested_program() -> Vec<u8> {
+        let mut source = Vec::from(*b"+");
+        source.extend(core::iter::repeat_n(b'[', DEPTH));
+        source.push(b'-');
+        source.extend(core::iter::repeat_n(b']', DEPTH));
+        source
+    }
+
+    #[test]
+    fn deeply_nested_programs_do_not_overflow_the_stack() {
+        let source = deeply_nested_program();
+        let program = Parser::new(&source, Dialect::default()).parse().unwrap();
+        assert_eq!(program.matching_bracket(1), Some(source.len() - 1));
+        assert_eq!(program.matching_bracket(DEPTH), Some(DEPTH + 2));
+        let copy = program.statements()[1].clone();
+        assert_eq!(copy.span(), program.statements()[1].span());
+
+        let optimized = Pipeline::default().run(&program);
+        // the innermost loop becomes an assignment, which makes every other loop an `if`
+        let mut statements = &optimized.statements()[1..];
+        let mut depth = 0;
+        while let [statement] = statements {
+            match statement.kind() {
+                StatementKind::If(body) => statements = body,
+                StatementKind::Set { offset: 0, value: 0 } => break,
+                kind => panic!("unexpected statement at depth {depth}: {kind:?}"),
+            }
+            depth += 1;
+        }
+        assert_eq!(depth, DEPTH - 1);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::program::{Program, Span, Statement, StatementConversionError, StatementKind};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum ParserError {
+    LoopNotClosed,
+    UnexpectedChar(u8),
+    LoopControlOutsideOfLoop,
+}
+
+impl Display for ParserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl core::error::Error for ParserError {}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dialect {
+    pub loop_control: bool,
+    /// Enables the single-register extension using the given characters.
+    pub register: Option<RegisterCommands>,
+    /// Enables `#`, which dumps the tape for debugging.
+    pub debug_dump: bool,
+}
+
+/// The characters of the single-register extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterCommands {
+    /// Copies the current cell into the register.
+    pub store: u8,
+    /// Copies the register into the current cell.
+    pub load: u8,
+}
+
+impl Default for RegisterCommands {
+    fn default() -> Self {
+        Self {
+            store: b'$',
+            load: b'!',
+        }
+    }
+}
+
+pub struct Parser<'a> {
+    source: &'a [u8],
+    dialect: Dialect,
+    index: usize,
+    // the statements preceding every loop that is still open and the offset of its `[`,
+    // innermost loop last
+    open_loops: Vec<(Vec<Statement>, usize)>,
+}
+
+// what the parser found next in the source
+enum Item {
+    Statement(Statement),
+    OpeningLoop,
+    ClosingLoop,
+    EndOfSource,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a [u8], dialect: Dialect) -> Self {
+        Self {
+            source,
+            dialect,
+            index: 0,
+            open_loops: Vec::new(),
+        }
+    }
+
+    // The body of a loop is collected in `statements` like the program itself, so that the
+    // nesting depth of loops is not limited by the call stack.
+    pub fn parse(mut self) -> Result<Program, ParserError> {
+        let mut statements = Vec::new();
+        loop {
+            match self.item()? {
+                Item::Statement(statement) => statements.push(statement),
+                Item::OpeningLoop => {
+                    self.open_loops
+                        .push((core::mem::take(&mut statements), self.index));
+                    self.advance();
+                }
+                Item::ClosingLoop => {
+                    let Some((enclosing_statements, start)) = self.open_loops.pop() else {
+                        return Err(ParserError::UnexpectedChar(self.current()));
+                    };
+                    self.advance();
+                    let body = core::mem::replace(&mut statements, enclosing_statements);
+                    statements.push(Statement::new(
+                        StatementKind::Loop(body),
+                        Span::new(start, self.index),
+                    ));
+                }
+                Item::EndOfSource if self.open_loops.is_empty() => {
+                    return Ok(Program::new(statements))
+                }
+                Item::EndOfSource => return Err(ParserError::LoopNotClosed),
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, ParserError> {
+        while !self.is_at_end() {
+            if let Some(kind) = self.extension_statement()? {
+                return Ok(Item::Statement(self.single_char_statement(kind)));
+            }
+            match self.current().try_into() {
+                Ok(kind) => return Ok(Item::Statement(self.single_char_statement(kind))),
+                Err(StatementConversionError::InsignificantChar) => {}
+                Err(StatementConversionError::OpeningLoop) => return Ok(Item::OpeningLoop),
+                Err(StatementConversionError::ClosingLoop) => return Ok(Item::ClosingLoop),
+            }
+            self.advance();
+        }
+        Ok(Item::EndOfSource)
+    }
+
+    fn single_char_statement(&mut self, kind: StatementKind) -> Statement {
+        let start = self.index;
+        self.advance();
+        Statement::new(kind, Span::new(start, self.index))
+    }
+
+    fn extension_statement(&self) -> Result<Option<StatementKind>, ParserError> {
+        if let Some(register) = self.dialect.register {
+            if self.current() == register.store {
+                return Ok(Some(StatementKind::Store));
+            } else if self.current() == register.load {
+                return Ok(Some(StatementKind::Load));
+            }
+        }
+        if self.dialect.debug_dump && self.current() == b'#' {
+            return Ok(Some(StatementKind::DebugDump));
+        }
+        let kind = match self.current() {
+            b'@' if self.dialect.loop_control => StatementKind::Break,
+            b'&' if self.dialect.loop_control => StatementKind::Continue,
+            _ => return Ok(None),
+        };
+        if self.open_loops.is_empty() {
+            Err(ParserError::LoopControlOutsideOfLoop)
+        } else {
+            Ok(Some(kind))
+        }
+    }
+
+    fn current(&self) -> u8 {
+        if self.is_at_end() {
+            b'\0'
+        } else {
+            self.source[self.index]
+        }
+    }
+
+    fn advance(&mut self) {
+        self.index += 1;
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.index >= self.source.len()
+    }
+}
@@ -0,0 +1,315 @@
+//! The C ABI of optimization passes that are loaded from dynamic libraries at runtime (see
+//! `--load-pass`). A program crosses the boundary as a flat array of [`RawStatement`]s, in which
+//! loops and conditionals are delimited by start and end statements. Plugins written in Rust
+//! implement [`ExternalPass`] and export it using [`export_pass!`].
+
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use crate::program::{CellSize, Program, Span, Statement, StatementKind};
+
+/// Incremented whenever the layout of [`RawStatement`] or the exported functions change.
+pub const ABI_VERSION: u32 = 2;
+
+/// The symbol of `extern "C" fn() -> u32`, returning the [`ABI_VERSION`] of the plugin.
+pub const ABI_VERSION_SYMBOL: &str = "brainrust_pass_abi_version";
+/// The symbol of `extern "C" fn() -> *const c_char`, returning the NUL-terminated name of the
+/// pass.
+pub const NAME_SYMBOL: &str = "brainrust_pass_name";
+/// The symbol of [`RunFunction`].
+pub const RUN_SYMBOL: &str = "brainrust_pass_run";
+
+/// Receives the statements of the optimized program. They are only valid during the call.
+pub type EmitFunction = unsafe extern "C" fn(
+    context: *mut core::ffi::c_void,
+    statements: *const RawStatement,
+    length: usize,
+);
+
+/// Runs the pass on the given statements of a program whose cells have `cell_bits` bits (8, 16,
+/// 32 or 64) and calls `emit` once with the result. Returns zero on success.
+pub type RunFunction = unsafe extern "C" fn(
+    statements: *const RawStatement,
+    length: usize,
+    cell_bits: u32,
+    emit: EmitFunction,
+    context: *mut core::ffi::c_void,
+) -> i32;
+
+/// A single statement of a program. Which fields are used depends on the kind.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawStatement {
+    pub kind: u32,
+    pub offset: i64,
+    pub value: i64,
+    pub span_start: u64,
+    pub span_end: u64,
+}
+
+impl RawStatement {
+    /// Moves the pointer by `value`.
+    pub const MOVE: u32 = 0;
+    /// Adds `value` to the cell at `offset`.
+    pub const ADD: u32 = 1;
+    /// Assigns `value` (0 to 255) to the cell at `offset`.
+    pub const SET: u32 = 2;
+    /// Followed by `value` statements of kind [`RawStatement::MULTIPLY_ADD_TARGET`].
+    pub const MULTIPLY_ADD: u32 = 3;
+    /// Adds the current cell multiplied by `value` to the cell at `offset`.
+    pub const MULTIPLY_ADD_TARGET: u32 = 4;
+    pub const PUT_CHAR: u32 = 5;
+    pub const GET_CHAR: u32 = 6;
+    /// Both the start and the end of a loop carry the span of the whole loop.
+    pub const LOOP_START: u32 = 7;
+    pub const LOOP_END: u32 = 8;
+    pub const IF_START: u32 = 9;
+    pub const IF_END: u32 = 10;
+    pub const STORE: u32 = 11;
+    pub const LOAD: u32 = 12;
+    pub const BREAK: u32 = 13;
+    pub const CONTINUE: u32 = 14;
+    pub const DEBUG_DUMP: u32 = 15;
+
+    fn new(kind: u32, offset: i64, value: i64, span: Span) -> Self {
+        Self {
+            kind,
+            offset,
+            value,
+            span_start: span.start as u64,
+            span_end: span.end as u64,
+        }
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.span_start as usize, self.span_end as usize)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownKind(u32),
+    UnmatchedEnd { index: usize },
+    UnclosedBlock,
+    InvalidValue { index: usize },
+    MissingMultiplyAddTarget { index: usize },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::UnknownKind(kind) => write!(f, "unknown statement kind {kind}"),
+            DecodeError::UnmatchedEnd { index } => {
+                write!(f, "statement {index} ends a block that was never started")
+            }
+            DecodeError::UnclosedBlock => write!(f, "a loop or conditional is not closed"),
+            DecodeError::InvalidValue { index } => {
+                write!(f, "statement {index} has an invalid value")
+            }
+            DecodeError::MissingMultiplyAddTarget { index } => {
+                write!(f, "statement {index} is missing multiply-add targets")
+            }
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Flattens a program into the representation passed to and returned by plugins.
+pub fn encode(program: &Program) -> Vec<RawStatement> {
+    let mut raw_statements = Vec::new();
+    encode_block(program.statements(), &mut raw_statements);
+    raw_statements
+}
+
+fn encode_block(statements: &[Statement], raw_statements: &mut Vec<RawStatement>) {
+    for statement in statements {
+        let span = statement.span();
+        let (kind, offset, value) = match statement.kind() {
+            StatementKind::Move(amount) => (RawStatement::MOVE, 0, *amount),
+            StatementKind::Add { offset, amount } => (RawStatement::ADD, *offset, *amount),
+            StatementKind::Set { offset, value } => (RawStatement::SET, *offset, *value as i64),
+            StatementKind::MultiplyAdd(targets) => {
+                raw_statements.push(RawStatement::new(
+                    RawStatement::MULTIPLY_ADD,
+                    0,
+                    targets.len() as i64,
+                    span,
+                ));
+                for &(offset, factor) in targets {
+                    raw_statements.push(RawStatement::new(
+                        RawStatement::MULTIPLY_ADD_TARGET,
+                        offset,
+                        factor,
+                        span,
+                    ));
+                }
+                continue;
+            }
+            StatementKind::PutChar => (RawStatement::PUT_CHAR, 0, 0),
+            StatementKind::GetChar => (RawStatement::GET_CHAR, 0, 0),
+            StatementKind::Loop(body) | StatementKind::If(body) => {
+                let (start, end) = match statement.kind() {
+                    StatementKind::Loop(_) => (RawStatement::LOOP_START, RawStatement::LOOP_END),
+                    _ => (RawStatement::IF_START, RawStatement::IF_END),
+                };
+                raw_statements.push(RawStatement::new(start, 0, 0, span));
+                encode_block(body, raw_statements);
+                raw_statements.push(RawStatement::new(end, 0, 0, span));
+                continue;
+            }
+            StatementKind::Store => (RawStatement::STORE, 0, 0),
+            StatementKind::Load => (RawStatement::LOAD, 0, 0),
+            StatementKind::Break => (RawStatement::BREAK, 0, 0),
+            StatementKind::Continue => (RawStatement::CONTINUE, 0, 0),
+            StatementKind::DebugDump => (RawStatement::DEBUG_DUMP, 0, 0),
+        };
+        raw_statements.push(RawStatement::new(kind, offset, value, span));
+    }
+}
+
+/// Rebuilds a program from the representation passed to and returned by plugins.
+pub fn decode(raw_statements: &[RawStatement]) -> Result<Program, DecodeError> {
+    // the statements of the enclosing blocks, each with the statement that started it
+    let mut blocks: Vec<(Option<&RawStatement>, Vec<Statement>)> = Vec::from([(None, Vec::new())]);
+    let mut index = 0;
+    while let Some(raw_statement) = raw_statements.get(index) {
+        let kind = match raw_statement.kind {
+            RawStatement::MOVE => StatementKind::Move(raw_statement.value),
+            RawStatement::ADD => StatementKind::Add {
+                offset: raw_statement.offset,
+                amount: raw_statement.value,
+            },
+            RawStatement::SET => StatementKind::Set {
+                offset: raw_statement.offset,
+                value: u8::try_from(raw_statement.value)
+                    .map_err(|_| DecodeError::InvalidValue { index })?,
+            },
+            RawStatement::MULTIPLY_ADD => {
+                let count = usize::try_from(raw_statement.value)
+                    .map_err(|_| DecodeError::InvalidValue { index })?;
+                let targets = raw_statements
+                    .get(index + 1..)
+                    .and_then(|rest| rest.get(..count))
+                    .filter(|targets| {
+                        targets
+                            .iter()
+                            .all(|target| target.kind == RawStatement::MULTIPLY_ADD_TARGET)
+                    })
+                    .ok_or(DecodeError::MissingMultiplyAddTarget { index })?;
+                index += count;
+                StatementKind::MultiplyAdd(
+                    targets
+                        .iter()
+                        .map(|target| (target.offset, target.value))
+                        .collect(),
+                )
+            }
+            RawStatement::PUT_CHAR => StatementKind::PutChar,
+            RawStatement::GET_CHAR => StatementKind::GetChar,
+            RawStatement::LOOP_START | RawStatement::IF_START => {
+                blocks.push((Some(raw_statement), Vec::new()));
+                index += 1;
+                continue;
+            }
+            RawStatement::LOOP_END | RawStatement::IF_END => {
+                let (Some(start), body) = blocks.pop().expect("the program block is never popped")
+                else {
+                    return Err(DecodeError::UnmatchedEnd { index });
+                };
+                let kind = match (start.kind, raw_statement.kind) {
+                    (RawStatement::LOOP_START, RawStatement::LOOP_END) => StatementKind::Loop(body),
+                    (RawStatement::IF_START, RawStatement::IF_END) => StatementKind::If(body),
+                    _ => return Err(DecodeError::UnmatchedEnd { index }),
+                };
+                blocks
+                    .last_mut()
+                    .expect("checked above")
+                    .1
+                    .push(Statement::new(kind, start.span()));
+                index += 1;
+                continue;
+            }
+            RawStatement::STORE => StatementKind::Store,
+            RawStatement::LOAD => StatementKind::Load,
+            RawStatement::BREAK => StatementKind::Break,
+            RawStatement::CONTINUE => StatementKind::Continue,
+            RawStatement::DEBUG_DUMP => StatementKind::DebugDump,
+            kind => return Err(DecodeError::UnknownKind(kind)),
+        };
+        blocks
+            .last_mut()
+            .expect("the program block is never popped")
+            .1
+            .push(Statement::new(kind, raw_statement.span()));
+        index += 1;
+    }
+    match <[_; 1]>::try_from(blocks) {
+        Ok([(_, statements)]) => Ok(Program::new(statements)),
+        Err(_) => Err(DecodeError::UnclosedBlock),
+    }
+}
+
+/// An optimization pass that is loaded from a dynamic library. It must not change the behavior
+/// of the program, whose additions wrap around at the given cell size.
+pub trait ExternalPass {
+    fn run(&self, program: &Program, cell_size: CellSize) -> Program;
+}
+
+/// Exports the functions brainrust looks up in a plugin. The plugin crate has to be built as a
+/// `cdylib`.
+///
+/// ```ignore
+/// struct Identity;
+///
+/// impl ExternalPass for Identity {
+///     fn run(&self, program: &Program, _cell_size: CellSize) -> Program {
+///         Program::new(program.statements().clone())
+///     }
+/// }
+///
+/// brainrust_core::export_pass!("identity", Identity);
+/// ```
+#[macro_export]
+macro_rules! export_pass {
+    ($name:literal, $pass:expr) => {
+        #[no_mangle]
+        pub extern "C" fn brainrust_pass_abi_version() -> u32 {
+            $crate::plugin::ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn brainrust_pass_name() -> *const ::core::ffi::c_char {
+            concat!($name, "\0").as_ptr().cast()
+        }
+
+        /// # Safety
+        ///
+        /// `statements` has to point to `length` statements.
+        #[no_mangle]
+        pub unsafe extern "C" fn brainrust_pass_run(
+            statements: *const $crate::plugin::RawStatement,
+            length: usize,
+            cell_bits: u32,
+            emit: $crate::plugin::EmitFunction,
+            context: *mut ::core::ffi::c_void,
+        ) -> i32 {
+            let Some(cell_size) = $crate::program::CellSize::from_bits(cell_bits) else {
+                return 2;
+            };
+            let statements: &[$crate::plugin::RawStatement] = if length == 0 {
+                &[]
+            } else {
+                ::core::slice::from_raw_parts(statements, length)
+            };
+            let Ok(program) = $crate::plugin::decode(statements) else {
+                return 1;
+            };
+            let optimized = $crate::plugin::encode(&$crate::plugin::ExternalPass::run(
+                &$pass, &program, cell_size,
+            ));
+            emit(context, optimized.as_ptr(), optimized.len());
+            0
+        }
+    };
+}
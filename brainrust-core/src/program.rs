@@ -0,0 +1,325 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+pub struct Program {
+    statements: Vec<Statement>,
+}
+
+impl Program {
+    pub fn new(statements: Vec<Statement>) -> Self {
+        Self { statements }
+    }
+
+    pub fn statements(&self) -> &Vec<Statement> {
+        &self.statements
+    }
+
+    /// Returns the byte offset of the bracket matching the one at `offset`, or `None` if
+    /// there is no bracket at `offset`.
+    pub fn matching_bracket(&self, offset: usize) -> Option<usize> {
+        matching_bracket(&self.statements, offset)
+    }
+
+    fn fmt_indented(&self, f: &mut Formatter<'_>, indentation: usize) -> core::fmt::Result {
+        const EMPTY: &str = "";
+        write!(f, "{EMPTY:0$}", indentation)?;
+        for statement in &self.statements {
+            match statement.kind() {
+                StatementKind::Move(amount) => write_repeated(f, *amount, '>', '<'),
+                StatementKind::Add { offset, amount } => {
+                    write_repeated(f, *offset, '>', '<')?;
+                    write_repeated(f, *amount, '+', '-')?;
+                    write_repeated(f, -offset, '>', '<')
+                }
+                StatementKind::Set { offset, value } => {
+                    write_repeated(f, *offset, '>', '<')?;
+                    write!(f, "[-]")?;
+                    write_repeated(f, *value as i64, '+', '-')?;
+                    write_repeated(f, -offset, '>', '<')
+                }
+                StatementKind::MultiplyAdd(targets) => {
+                    write!(f, "[-")?;
+                    for &(offset, factor) in targets {
+                        write_repeated(f, offset, '>', '<')?;
+                        write_repeated(f, factor, '+', '-')?;
+                        write_repeated(f, -offset, '>', '<')?;
+                    }
+                    write!(f, "]")
+                }
+                StatementKind::PutChar => write!(f, "."),
+                StatementKind::GetChar => write!(f, ","),
+                StatementKind::Loop(statements) | StatementKind::If(statements) => {
+                    writeln!(f, "[")?;
+                    Program {
+                        statements: statements.clone(),
+                    }
+                    .fmt_indented(f, indentation + 2)?;
+                    writeln!(f, "\n{EMPTY:0$}]", indentation)
+                }
+                StatementKind::Store => write!(f, "$"),
+                StatementKind::Load => write!(f, "!"),
+                StatementKind::Break => write!(f, "@"),
+                StatementKind::Continue => write!(f, "&"),
+                StatementKind::DebugDump => write!(f, "#"),
+            }?;
+        }
+        Ok(())
+    }
+}
+
+fn write_repeated(
+    f: &mut Formatter<'_>,
+    amount: i64,
+    positive: char,
+    negative: char,
+) -> core::fmt::Result {
+    let char = if amount >= 0 { positive } else { negative };
+    for _ in 0..amount.unsigned_abs() {
+        write!(f, "{char}")?;
+    }
+    Ok(())
+}
+
+fn matching_bracket(mut statements: &[Statement], offset: usize) -> Option<usize> {
+    loop {
+        let index = statements.partition_point(|statement| statement.span.end <= offset);
+        let statement = statements.get(index)?;
+        let (StatementKind::Loop(body) | StatementKind::If(body)) = statement.kind() else {
+            return None;
+        };
+        let opening = statement.span.start;
+        let closing = statement.span.end - 1;
+        if offset == opening {
+            return Some(closing);
+        } else if offset == closing {
+            return Some(opening);
+        } else if offset < opening {
+            return None;
+        }
+        statements = body;
+    }
+}
+
+/// Copies the statements and passes the copy of the body of every loop and `if` through
+/// `rewrite`, innermost bodies first. The statements themselves are not passed to `rewrite`.
+///
+/// Uses an explicit stack instead of recursion, so that the nesting depth is only limited by
+/// memory.
+pub fn rewrite_bodies(
+    statements: &[Statement],
+    rewrite: &mut dyn FnMut(Vec<Statement>) -> Vec<Statement>,
+) -> Vec<Statement> {
+    // the blocks that are being copied, each with the statement it is the body of
+    let mut blocks = vec![(
+        None,
+        statements.iter(),
+        Vec::with_capacity(statements.len()),
+    )];
+    loop {
+        let (_, statements, copy) = blocks
+            .last_mut()
+            .expect("the outermost block is popped last");
+        if let Some(statement) = statements.next() {
+            match &statement.kind {
+                StatementKind::Loop(body) | StatementKind::If(body) => {
+                    blocks.push((Some(statement), body.iter(), Vec::with_capacity(body.len())))
+                }
+                kind => copy.push(Statement::new(kind.clone(), statement.span)),
+            }
+            continue;
+        }
+        let (parent, _, copy) = blocks.pop().expect("checked above");
+        let Some(parent) = parent else {
+            return copy;
+        };
+        let body = rewrite(copy);
+        let kind = match parent.kind {
+            StatementKind::Loop(_) => StatementKind::Loop(body),
+            _ => StatementKind::If(body),
+        };
+        let (_, _, copy) = blocks.last_mut().expect("the parent's block is still open");
+        copy.push(Statement::new(kind, parent.span));
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// The width of the cells of the tape. Cells wrap around, so that adding to a cell is the same
+/// as adding modulo 2 to the power of its width.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CellSize {
+    #[default]
+    Bits8,
+    Bits16,
+    Bits32,
+    Bits64,
+}
+
+impl CellSize {
+    pub const ALL: [CellSize; 4] = [
+        CellSize::Bits8,
+        CellSize::Bits16,
+        CellSize::Bits32,
+        CellSize::Bits64,
+    ];
+
+    pub fn bits(self) -> u32 {
+        match self {
+            CellSize::Bits8 => 8,
+            CellSize::Bits16 => 16,
+            CellSize::Bits32 => 32,
+            CellSize::Bits64 => 64,
+        }
+    }
+
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|cell_size| cell_size.bits() == bits)
+    }
+
+    /// The largest value a cell can hold.
+    pub fn max_value(self) -> u64 {
+        u64::MAX >> (64 - self.bits())
+    }
+
+    /// Truncates a value to the width of a cell, e.g. -1 becomes the largest value.
+    pub fn wrap(self, value: i64) -> u64 {
+        value as u64 & self.max_value()
+    }
+}
+
+/// A range of byte offsets into the source code (the end is exclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+pub enum StatementConversionError {
+    InsignificantChar,
+    OpeningLoop,
+    ClosingLoop,
+}
+
+#[derive(Debug)]
+pub struct Statement {
+    kind: StatementKind,
+    span: Span,
+}
+
+// Cloning and dropping do not recurse into the bodies of loops, which would overflow the call
+// stack for deeply nested programs.
+impl Clone for Statement {
+    fn clone(&self) -> Self {
+        let mut copy = rewrite_bodies(core::slice::from_ref(self), &mut |body| body);
+        copy.pop().expect("one statement was copied")
+    }
+}
+
+impl Drop for Statement {
+    fn drop(&mut self) {
+        let (StatementKind::Loop(body) | StatementKind::If(body)) = &mut self.kind else {
+            return;
+        };
+        if body.is_empty() {
+            return;
+        }
+        let mut bodies = vec![core::mem::take(body)];
+        while let Some(body) = bodies.pop() {
+            for mut statement in body {
+                if let StatementKind::Loop(body) | StatementKind::If(body) = &mut statement.kind {
+                    bodies.push(core::mem::take(body));
+                }
+            }
+        }
+    }
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn kind(&self) -> &StatementKind {
+        &self.kind
+    }
+
+    pub fn kind_mut(&mut self) -> &mut StatementKind {
+        &mut self.kind
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    /// Moves the pointer by the given number of cells (`>` and `<`).
+    Move(i64),
+    /// Adds the given amount to the cell at the offset relative to the pointer (`+` and `-`).
+    Add {
+        offset: i64,
+        amount: i64,
+    },
+    /// Assigns a value to the cell at the offset relative to the pointer (e.g. `[-]`). Only
+    /// created by the optimizer.
+    Set {
+        offset: i64,
+        value: u8,
+    },
+    /// Adds the current cell multiplied by the factor to the cell at each offset (relative to
+    /// the pointer), then clears the current cell (e.g. `[->+>++<<]`). The pairs consist of
+    /// offset and factor. Only created by the optimizer.
+    MultiplyAdd(Vec<(i64, i64)>),
+    PutChar,
+    GetChar,
+    Loop(Vec<Statement>),
+    /// Runs the statements once if the current cell is not zero. Created by the optimizer from
+    /// loops that always leave the current cell at zero.
+    If(Vec<Statement>),
+    /// Copies the current cell into the register (single-register extension).
+    Store,
+    /// Copies the register into the current cell (single-register extension).
+    Load,
+    Break,
+    Continue,
+    /// Dumps the tape without changing it (debug extension).
+    DebugDump,
+}
+
+impl TryFrom<u8> for StatementKind {
+    type Error = StatementConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            b'>' => Ok(StatementKind::Move(1)),
+            b'<' => Ok(StatementKind::Move(-1)),
+            b'+' => Ok(StatementKind::Add {
+                offset: 0,
+                amount: 1,
+            }),
+            b'-' => Ok(StatementKind::Add {
+                offset: 0,
+                amount: -1,
+            }),
+            b'.' => Ok(StatementKind::PutChar),
+            b',' => Ok(StatementKind::GetChar),
+            b'[' => Err(StatementConversionError::OpeningLoop),
+            b']' => Err(StatementConversionError::ClosingLoop),
+            _ => Err(StatementConversionError::InsignificantChar),
+        }
+    }
+}
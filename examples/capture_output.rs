@@ -0,0 +1,20 @@
+//! Runs a Brainfuck program with in-memory input and captures its output.
+//!
+//! ```bash
+//! cargo run --example capture_output
+//! ```
+
+use anyhow::Result;
+
+use brainrust::optimizer::optimize;
+use brainrust::parser::{Dialect, Parser};
+
+// converts lowercase letters to uppercase until the input ends
+const SOURCE: &[u8] = b",[>++++[<-------->-]<.,]";
+
+fn main() -> Result<()> {
+    let program = Parser::new(SOURCE, Dialect::default()).parse()?;
+    let output = brainrust::capture_output(&optimize(&program), b"brainrust")?;
+    println!("{}", String::from_utf8_lossy(&output));
+    Ok(())
+}
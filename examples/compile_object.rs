@@ -0,0 +1,50 @@
+//! Compiles a Brainfuck program into an object file in memory, e.g. to hand it to a custom
+//! linker or to store it elsewhere.
+//!
+//! ```bash
+//! cargo run --features llvm --example compile_object
+//! ```
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use brainrust::optimizer::optimize;
+use brainrust::parser::{Dialect, Parser};
+use brainrust::registry::{BackendOptions, Platform};
+use brainrust::semantics::RuntimeConfig;
+use brainrust::EnvironmentReport;
+
+const SOURCE: &[u8] = b"++++++++[>++++++++<-]>+.+.+.";
+
+fn main() -> Result<()> {
+    let program = Parser::new(SOURCE, Dialect::default()).parse()?;
+    let object = brainrust::compile_to_object(
+        &optimize(&program),
+        &BackendOptions {
+            input_filename: PathBuf::from("abc.b"),
+            output_filename: PathBuf::new(),
+            optimization_level: 2,
+            verbose: false,
+            environment: EnvironmentReport::collect(Some(SOURCE)),
+            platform: Platform::Host,
+            prompt: None,
+            no_echo: false,
+            embed_selftest: false,
+            harden: false,
+            constant_output: None,
+            max_output: None,
+            runtime: RuntimeConfig::default(),
+            initial_tape: Vec::new(),
+            guard_pages: false,
+            no_libc: false,
+            bare_metal: false,
+            linker: None,
+            static_executable: false,
+            link_arguments: Vec::new(),
+            jit_profiling: false,
+        },
+    )?;
+    println!("generated an object file of {} bytes", object.len());
+    Ok(())
+}
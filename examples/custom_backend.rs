@@ -0,0 +1,56 @@
+//! Adds a backend to the command line interface that writes the number of statements of the
+//! optimized program instead of generating code.
+//!
+//! ```bash
+//! cargo run --example custom_backend -- --to statement-count -o count.txt programs/hello_world.b
+//! ```
+
+use anyhow::Result;
+
+use brainrust::program::{Program, Statement, StatementKind};
+use brainrust::registry::{Backend, BackendOptions, Registry};
+
+struct StatementCount;
+
+impl Backend for StatementCount {
+    fn name(&self) -> &'static str {
+        "statement-count"
+    }
+
+    fn description(&self) -> &'static str {
+        "Number of statements of the optimized program"
+    }
+
+    fn default_output_filename(&self) -> &'static str {
+        "statement-count.txt"
+    }
+
+    fn emit(&self, program: &Program, options: &BackendOptions) -> Result<()> {
+        let count = count(program.statements());
+        std::fs::write(&options.output_filename, format!("{count}\n"))?;
+        Ok(())
+    }
+
+    fn plan(&self, options: &BackendOptions) -> Vec<String> {
+        vec![format!(
+            "write the number of statements to '{}'",
+            options.output_filename.display()
+        )]
+    }
+}
+
+fn count(statements: &[Statement]) -> usize {
+    statements
+        .iter()
+        .map(|statement| match statement.kind() {
+            StatementKind::Loop(body) | StatementKind::If(body) => 1 + count(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn main() -> Result<()> {
+    let mut registry = Registry::default();
+    registry.register_backend(StatementCount);
+    brainrust::run(registry)
+}
@@ -0,0 +1,25 @@
+//! Parses a Brainfuck program and prints it before and after running the optimization passes.
+//!
+//! ```bash
+//! cargo run --example optimize
+//! ```
+
+use anyhow::Result;
+
+use brainrust::optimizer::Pipeline;
+use brainrust::parser::{Dialect, Parser};
+
+const SOURCE: &[u8] = b"++++++++[>++++[>++>+++<<-]>-]>>[-]<.";
+
+fn main() -> Result<()> {
+    let program = Parser::new(SOURCE, Dialect::default()).parse()?;
+    println!("{program}");
+
+    let pipeline = Pipeline::for_level(2);
+    pipeline.run_each(&program, |pass, program| {
+        let optimized = pass.run(program);
+        println!("after {}:\n{optimized}", pass.name());
+        optimized
+    });
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Runs a Brainfuck program step by step and prints the tape after every few steps.
+//!
+//! ```bash
+//! cargo run --example step
+//! ```
+
+use anyhow::Result;
+
+use brainrust::parser::{Dialect, Parser};
+use brainrust::{Interpreter, Status};
+
+const SOURCE: &[u8] = b"++++[>++<-]>[>+++<-]";
+const STEPS: u64 = 10;
+
+fn main() -> Result<()> {
+    let program = Parser::new(SOURCE, Dialect::default()).parse()?;
+    let mut interpreter = Interpreter::new(&program, &b""[..], Vec::new());
+    loop {
+        let status = interpreter.run_for(STEPS)?;
+        let cells: Vec<String> = interpreter
+            .tape()
+            .into_iter()
+            .map(|(address, value)| {
+                if address == interpreter.pointer() {
+                    format!("[{value}]")
+                } else {
+                    value.to_string()
+                }
+            })
+            .collect();
+        println!("{}", cells.join(" "));
+        if status == Status::Finished {
+            return Ok(());
+        }
+    }
+}
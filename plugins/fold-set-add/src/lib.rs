@@ -0,0 +1,95 @@
+//! A sample pass plugin that folds an assignment followed by an addition to the same cell into
+//! a single assignment, e.g. the clear loop and the additions of `[-]+++`.
+//!
+//! ```bash
+//! cargo build -p fold-set-add
+//! cargo run -- -r --load-pass target/debug/libfold_set_add.so programs/hello_world.b
+//! ```
+
+use brainrust_core::plugin::ExternalPass;
+use brainrust_core::program::{CellSize, Program, Span, Statement, StatementKind};
+
+struct FoldSetAdd;
+
+impl ExternalPass for FoldSetAdd {
+    fn run(&self, program: &Program, cell_size: CellSize) -> Program {
+        Program::new(fold(program.statements(), cell_size))
+    }
+}
+
+// the value of the cell after the addition, unless it doesn't fit into an assignment
+fn folded_value(value: u8, amount: i64, cell_size: CellSize) -> Option<u8> {
+    let sum = (i128::from(value) + i128::from(amount)).rem_euclid(1 << cell_size.bits());
+    u8::try_from(sum).ok()
+}
+
+fn fold(statements: &[Statement], cell_size: CellSize) -> Vec<Statement> {
+    let mut result: Vec<Statement> = Vec::with_capacity(statements.len());
+    for statement in statements {
+        let span = statement.span();
+        let kind = match statement.kind() {
+            StatementKind::Loop(body) => StatementKind::Loop(fold(body, cell_size)),
+            StatementKind::If(body) => StatementKind::If(fold(body, cell_size)),
+            &StatementKind::Add { offset, amount } => match result.last().map(Statement::kind) {
+                Some(&StatementKind::Set {
+                    offset: set_offset,
+                    value,
+                }) if set_offset == offset => {
+                    let Some(value) = folded_value(value, amount, cell_size) else {
+                        result.push(statement.clone());
+                        continue;
+                    };
+                    let previous = result.pop().expect("checked above");
+                    result.push(Statement::new(
+                        StatementKind::Set { offset, value },
+                        Span::new(previous.span().start, span.end),
+                    ));
+                    continue;
+                }
+                _ => StatementKind::Add { offset, amount },
+            },
+            kind => kind.clone(),
+        };
+        result.push(Statement::new(kind, span));
+    }
+    result
+}
+
+brainrust_core::export_pass!("fold-set-add", FoldSetAdd);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_then_add(value: u8, amount: i64) -> Vec<Statement> {
+        vec![
+            Statement::new(StatementKind::Set { offset: 0, value }, Span::new(0, 3)),
+            Statement::new(StatementKind::Add { offset: 0, amount }, Span::new(3, 4)),
+        ]
+    }
+
+    fn folded_value_of(statements: &[Statement]) -> Option<u8> {
+        match statements {
+            [statement] => match statement.kind() {
+                &StatementKind::Set { value, .. } => Some(value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn folds_additions_modulo_the_cell_size() {
+        let folded = fold(&set_then_add(250, 10), CellSize::Bits8);
+        assert_eq!(folded_value_of(&folded), Some(4));
+        assert_eq!(folded[0].span(), Span::new(0, 4));
+        let folded = fold(&set_then_add(3, -1), CellSize::Bits16);
+        assert_eq!(folded_value_of(&folded), Some(2));
+    }
+
+    #[test]
+    fn keeps_additions_whose_result_does_not_fit_into_an_assignment() {
+        assert_eq!(fold(&set_then_add(0, 256), CellSize::Bits16).len(), 2);
+        assert_eq!(fold(&set_then_add(0, -1), CellSize::Bits64).len(), 2);
+    }
+}
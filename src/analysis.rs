@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::program::{CellSize, Program, Statement, StatementKind};
+
+pub(crate) trait Analysis {
+    fn analyze(&self, program: &Program) -> Vec<Diagnostic>;
+}
+
+pub(crate) fn all_analyses() -> Vec<Box<dyn Analysis>> {
+    vec![Box::new(NonTerminatingLoops)]
+}
+
+/// Visits the loops in source order, every loop before the loops nested in it.
+pub(crate) fn for_each_loop<F: FnMut(&Statement, &[Statement])>(
+    statements: &[Statement],
+    visitor: &mut F,
+) {
+    // the blocks that are being visited, innermost last
+    let mut blocks = vec![statements.iter()];
+    while let Some(block) = blocks.last_mut() {
+        let Some(statement) = block.next() else {
+            blocks.pop();
+            continue;
+        };
+        if let StatementKind::Loop(body) = statement.kind() {
+            visitor(statement, body);
+            blocks.push(body.iter());
+        }
+    }
+}
+
+// The combined effect of a block that does not contain any (nested) loops or loop control
+// statements. All offsets are relative to the pointer position at the start of the block.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BlockEffects {
+    pub(crate) pointer_delta: i64,
+    pub(crate) cell_deltas: BTreeMap<i64, i64>,
+    pub(crate) input_offsets: BTreeSet<i64>,
+    pub(crate) output_offsets: BTreeSet<i64>,
+}
+
+impl BlockEffects {
+    pub(crate) fn of(statements: &[Statement]) -> Option<Self> {
+        let mut effects = Self::default();
+        for statement in statements {
+            let offset = effects.pointer_delta;
+            match statement.kind() {
+                StatementKind::Move(amount) => effects.pointer_delta += amount,
+                StatementKind::Add {
+                    offset: cell_offset,
+                    amount,
+                } => *effects.cell_deltas.entry(offset + cell_offset).or_default() += amount,
+                StatementKind::PutChar | StatementKind::Store | StatementKind::DebugDump => {
+                    effects.output_offsets.insert(offset);
+                }
+                // the register is not tracked, so loading behaves like reading input
+                StatementKind::GetChar | StatementKind::Load => {
+                    effects.input_offsets.insert(offset);
+                }
+                StatementKind::Set { .. }
+                | StatementKind::MultiplyAdd(_)
+                | StatementKind::Loop(_)
+                | StatementKind::If(_)
+                | StatementKind::Break
+                | StatementKind::Continue => {
+                    return None;
+                }
+            }
+        }
+        Some(effects)
+    }
+
+    pub(crate) fn cell_delta(&self, offset: i64) -> i64 {
+        self.cell_deltas.get(&offset).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Termination {
+    Guaranteed,
+    ImpossibleIfEntered,
+    Unknown,
+}
+
+pub(crate) fn loop_termination(body: &[Statement]) -> Termination {
+    let Some(effects) = BlockEffects::of(body) else {
+        return Termination::Unknown;
+    };
+    // if the body is balanced, every iteration tests the same cell
+    if effects.pointer_delta != 0 || effects.input_offsets.contains(&0) {
+        return Termination::Unknown;
+    }
+    match effects.cell_delta(0) {
+        // the value never changes
+        0 => Termination::ImpossibleIfEntered,
+        // counting down by one reaches zero from any starting value
+        -1 => Termination::Guaranteed,
+        _ => Termination::Unknown,
+    }
+}
+
+/// An upper bound on the number of steps the statements take, if all of their loops are proven
+/// to terminate. Such a loop counts the cell it tests down by one and contains no further loops,
+/// so it runs at most as many iterations as a cell has nonzero values.
+pub(crate) fn step_bound(statements: &[Statement], cell_size: CellSize) -> Option<u64> {
+    let mut bound = 0u64;
+    let mut pending = vec![statements];
+    while let Some(statements) = pending.pop() {
+        for statement in statements {
+            let steps = match statement.kind() {
+                StatementKind::Loop(body) if loop_termination(body) == Termination::Guaranteed => {
+                    // every iteration runs the body and both ends of the loop
+                    cell_size
+                        .max_value()
+                        .checked_mul(body.len() as u64 + 2)?
+                        .checked_add(1)?
+                }
+                StatementKind::Loop(_) => return None,
+                // the body of an `If` runs at most once
+                StatementKind::If(body) => {
+                    pending.push(body);
+                    1
+                }
+                _ => 1,
+            };
+            bound = bound.checked_add(steps)?;
+        }
+    }
+    Some(bound)
+}
+
+/// Points out the loops that are proven to terminate.
+pub(crate) fn termination_proofs(program: &Program) -> Vec<Diagnostic> {
+    let mut proofs = Vec::new();
+    for_each_loop(program.statements(), &mut |loop_, body| {
+        if loop_termination(body) == Termination::Guaranteed {
+            proofs.push(Diagnostic::help(
+                loop_.span(),
+                "this loop terminates, since every iteration counts the cell it tests down by one",
+            ));
+        }
+    });
+    proofs
+}
+
+// The amount by which the statements move the pointer, if it is known statically. `@` and `&`
+// must be reached with the pointer where it was at the start of the loop body.
+pub(crate) fn pointer_delta(statements: &[Statement]) -> Option<i64> {
+    let mut nested = Vec::new();
+    let delta = shallow_pointer_delta(statements, &mut nested)?;
+    // the bodies of nested loops and conditionals must be balanced
+    while let Some(body) = nested.pop() {
+        if shallow_pointer_delta(body, &mut nested)? != 0 {
+            return None;
+        }
+    }
+    Some(delta)
+}
+
+// The amount by which the statements move the pointer outside of the bodies they contain, which
+// are added to `nested`.
+fn shallow_pointer_delta<'a>(
+    statements: &'a [Statement],
+    nested: &mut Vec<&'a [Statement]>,
+) -> Option<i64> {
+    let mut delta = 0;
+    for statement in statements {
+        match statement.kind() {
+            StatementKind::Move(amount) => delta += amount,
+            StatementKind::Loop(body) | StatementKind::If(body) => nested.push(body),
+            StatementKind::Break | StatementKind::Continue if delta != 0 => return None,
+            _ => {}
+        }
+    }
+    Some(delta)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LoopKind {
+    /// Sets the current cell to zero (e.g. `[-]`).
+    Clear,
+    /// Adds multiples of the current cell to other cells and clears it (e.g. `[->+<]`).
+    Multiply,
+    /// Moves the pointer until it reaches a zero cell (e.g. `[>>]`).
+    Scan,
+    Unknown,
+}
+
+impl LoopKind {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            LoopKind::Clear => "clear",
+            LoopKind::Multiply => "mul",
+            LoopKind::Scan => "scan",
+            LoopKind::Unknown => "unknown",
+        }
+    }
+}
+
+pub(crate) fn classify_loop(body: &[Statement]) -> LoopKind {
+    let Some(effects) = BlockEffects::of(body) else {
+        return LoopKind::Unknown;
+    };
+    if effects.pointer_delta != 0 {
+        let only_moves = body
+            .iter()
+            .all(|statement| matches!(statement.kind(), StatementKind::Move(_)));
+        return if only_moves {
+            LoopKind::Scan
+        } else {
+            LoopKind::Unknown
+        };
+    }
+    if !effects.input_offsets.is_empty() || !effects.output_offsets.is_empty() {
+        return LoopKind::Unknown;
+    }
+    // counting down or up by one reaches zero from any starting value
+    match effects.cell_delta(0) as u8 {
+        1 | u8::MAX
+            if effects
+                .cell_deltas
+                .values()
+                .filter(|&&delta| delta != 0)
+                .count()
+                == 1 =>
+        {
+            LoopKind::Clear
+        }
+        1 | u8::MAX => LoopKind::Multiply,
+        _ => LoopKind::Unknown,
+    }
+}
+
+struct NonTerminatingLoops;
+
+impl Analysis for NonTerminatingLoops {
+    fn analyze(&self, program: &Program) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for_each_loop(program.statements(), &mut |loop_, body| {
+            if loop_termination(body) == Termination::ImpossibleIfEntered {
+                diagnostics.push(Diagnostic::warning(
+                    loop_.span(),
+                    "this loop cannot terminate if entered",
+                ));
+            }
+        });
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    fn parse(source: &[u8]) -> Program {
+        Parser::new(source, Dialect::default()).parse().unwrap()
+    }
+
+    #[test]
+    fn steps_are_bounded_if_all_loops_count_down() {
+        let bound = |source: &[u8], cell_size| step_bound(parse(source).statements(), cell_size);
+        // 255 iterations of the body and both ends of the loop
+        assert_eq!(bound(b"-[-.]", CellSize::Bits8), Some(1 + 1 + 255 * 4));
+        assert_eq!(bound(b"+[>+<]", CellSize::Bits8), None);
+        assert_eq!(bound(b"+[[-]]", CellSize::Bits8), None);
+        // too many iterations to count
+        assert_eq!(bound(b"-[-.]", CellSize::Bits64), None);
+    }
+}
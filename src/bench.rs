@@ -0,0 +1,29 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::interpreter::measure;
+use crate::program::Program;
+
+/// Compares the number of dispatched interpreter ops with and without superinstructions.
+pub(crate) fn bench(program: &Program, input: Option<&Path>) -> Result<()> {
+    let unfused = measure(program, input, false)?;
+    let fused = measure(program, input, true)?;
+
+    println!(
+        "without superinstructions: {} dispatches in {:.2?}",
+        unfused.dispatches, unfused.duration
+    );
+    println!(
+        "with superinstructions: {} dispatches in {:.2?}",
+        fused.dispatches, fused.duration
+    );
+    if unfused.dispatches > 0 {
+        let saved = unfused.dispatches - fused.dispatches;
+        println!(
+            "dispatches saved: {saved} ({:.1}%)",
+            saved as f64 * 100.0 / unfused.dispatches as f64
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+// Runs a program compiled by brainrust with --target wasm32-unknown-unknown in a web page:
+//
+//     import { run } from "./{glue}";
+//     const { output, exitCode, error } = await run("input of the program");
+//
+// The module imports the few functions of the C standard library it uses from here, and its
+// tape lives in its linear memory.
+
+const MODULE_URL = new URL("{module}", import.meta.url);
+const PAGE_SIZE = 65536;
+// every allocation starts with its size, so that realloc knows how much to copy
+const HEADER_SIZE = 8;
+
+class Exit extends Error {
+  constructor(code) {
+    super(`the program exited with exit code ${code}`);
+    this.code = code;
+  }
+}
+
+// Runs the program with the given input (a string or bytes). Resolves to the bytes it has
+// written, its exit code and what it has written to stderr. `onOutput` is called with every
+// byte as soon as it is written.
+export async function run(input = "", { onOutput = () => {} } = {}) {
+  const inputBytes = typeof input === "string" ? new TextEncoder().encode(input) : input;
+  let inputOffset = 0;
+  const output = [];
+  let error = "";
+  let memory;
+  let heapEnd;
+
+  const bytes = () => new Uint8Array(memory.buffer);
+  // WebAssembly passes pointers and sizes as signed 32-bit integers
+  const unsigned = (value) => value >>> 0;
+  const write = (byte) => {
+    output.push(byte);
+    onOutput(byte);
+  };
+  const string = (pointer) => {
+    const memoryBytes = bytes();
+    const end = memoryBytes.indexOf(0, pointer);
+    return new TextDecoder().decode(memoryBytes.subarray(pointer, end));
+  };
+  // supports the conversions the generated code uses: %s, %d, %lld and %%
+  const format = (formatPointer, argumentsPointer) => {
+    const view = new DataView(memory.buffer);
+    let offset = argumentsPointer;
+    const next = (size) => {
+      offset = Math.ceil(offset / size) * size;
+      const value = size === 8 ? view.getBigInt64(offset, true) : view.getInt32(offset, true);
+      offset += size;
+      return value;
+    };
+    return string(formatPointer).replace(/%(lld|d|s|%)/g, (_, conversion) => {
+      switch (conversion) {
+        case "lld":
+          return next(8).toString();
+        case "d":
+          return next(4).toString();
+        case "s":
+          return string(unsigned(next(4)));
+        default:
+          return "%";
+      }
+    });
+  };
+  // returns 0 if the memory cannot grow any further
+  const malloc = (size) => {
+    size = unsigned(size);
+    const start = Math.ceil(heapEnd / HEADER_SIZE) * HEADER_SIZE;
+    const end = start + HEADER_SIZE + size;
+    if (end > memory.buffer.byteLength) {
+      try {
+        memory.grow(Math.ceil((end - memory.buffer.byteLength) / PAGE_SIZE));
+      } catch {
+        return 0;
+      }
+    }
+    heapEnd = end;
+    new DataView(memory.buffer).setUint32(start, size, true);
+    return start + HEADER_SIZE;
+  };
+
+  const env = {
+    putchar(character) {
+      write(character & 0xff);
+      return character;
+    },
+    getchar() {
+      return inputOffset < inputBytes.length ? inputBytes[inputOffset++] : -1;
+    },
+    fwrite(pointer, size, count) {
+      pointer = unsigned(pointer);
+      bytes()
+        .subarray(pointer, pointer + unsigned(size) * unsigned(count))
+        .forEach(write);
+      return count;
+    },
+    printf(formatPointer, argumentsPointer) {
+      const text = new TextEncoder().encode(
+        format(unsigned(formatPointer), unsigned(argumentsPointer)),
+      );
+      text.forEach(write);
+      return text.length;
+    },
+    fprintf(stream, formatPointer, argumentsPointer) {
+      const text = format(unsigned(formatPointer), unsigned(argumentsPointer));
+      error += text;
+      return text.length;
+    },
+    fflush: () => 0,
+    isatty: () => 0,
+    malloc,
+    calloc(count, size) {
+      size = unsigned(count) * unsigned(size);
+      const pointer = malloc(size);
+      bytes().fill(0, pointer, pointer + size);
+      return pointer;
+    },
+    realloc(pointer, size) {
+      pointer = unsigned(pointer);
+      size = unsigned(size);
+      const newPointer = malloc(size);
+      if (pointer !== 0 && newPointer !== 0) {
+        const oldSize = new DataView(memory.buffer).getUint32(pointer - HEADER_SIZE, true);
+        bytes().copyWithin(newPointer, pointer, pointer + Math.min(oldSize, size));
+      }
+      return newPointer;
+    },
+    // the tape only ever grows, so memory is never reused
+    free() {},
+    memmove(destination, source, count) {
+      source = unsigned(source);
+      bytes().copyWithin(unsigned(destination), source, source + unsigned(count));
+      return destination;
+    },
+    memset(destination, value, count) {
+      destination = unsigned(destination);
+      bytes().fill(value, destination, destination + unsigned(count));
+      return destination;
+    },
+    strcmp: (first, second) => (string(unsigned(first)) === string(unsigned(second)) ? 0 : 1),
+    exit(code) {
+      throw new Exit(code);
+    },
+    abort() {
+      throw new Exit(134);
+    },
+  };
+
+  const { instance } = await WebAssembly.instantiateStreaming(fetch(MODULE_URL), { env });
+  memory = instance.exports.memory;
+  heapEnd = instance.exports.__heap_base.value;
+  let exitCode;
+  try {
+    exitCode = instance.exports.main(0, 0);
+  } catch (exception) {
+    if (!(exception instanceof Exit)) {
+      throw exception;
+    }
+    exitCode = exception.code;
+  }
+  return { output: new Uint8Array(output), exitCode, error };
+}
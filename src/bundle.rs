@@ -0,0 +1,140 @@
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::interpreter::{interpret, InterpreterOptions};
+use crate::optimizer::optimize;
+use crate::parser::{Dialect, Parser, RegisterCommands};
+
+// A bundle is a copy of the brainrust executable followed by the program and a trailer:
+// the dialect flags (1 byte), the store and load characters of the register extension
+// (1 byte each), the length of the program (8 bytes, little endian) and the magic bytes. On
+// startup, brainrust checks its own executable for this trailer.
+const MAGIC: &[u8; 8] = b"BFBUNDLE";
+const TRAILER_SIZE: usize = 1 + 2 + 8 + MAGIC.len();
+const LOOP_CONTROL_FLAG: u8 = 0b1;
+const REGISTER_FLAG: u8 = 0b10;
+const DEBUG_DUMP_FLAG: u8 = 0b100;
+
+pub(crate) struct Bundle {
+    source: Vec<u8>,
+    dialect: Dialect,
+}
+
+impl Bundle {
+    /// Returns the program embedded into the running executable, if there is one.
+    pub(crate) fn embedded() -> Result<Option<Self>> {
+        let mut executable = File::open(std::env::current_exe()?)?;
+        let length = executable.seek(SeekFrom::End(0))?;
+        let Some(trailer) = read_trailer(&mut executable, length)? else {
+            return Ok(None);
+        };
+        let mut source = vec![0; trailer.source_length as usize];
+        executable.seek(SeekFrom::Start(trailer.executable_length))?;
+        executable.read_exact(&mut source)?;
+        Ok(Some(Self {
+            source,
+            dialect: Dialect {
+                loop_control: trailer.flags & LOOP_CONTROL_FLAG != 0,
+                register: (trailer.flags & REGISTER_FLAG != 0).then_some(trailer.register),
+                debug_dump: trailer.flags & DEBUG_DUMP_FLAG != 0,
+            },
+        }))
+    }
+
+    pub(crate) fn run(&self) -> Result<()> {
+        let program = optimize(&Parser::new(&self.source, self.dialect).parse()?);
+        interpret(&program, &InterpreterOptions::default())?;
+        Ok(())
+    }
+}
+
+/// Creates an executable that runs the given program using the built-in interpreter.
+pub(crate) fn bundle(
+    input_filename: &Path,
+    output_filename: &Path,
+    dialect: Dialect,
+) -> Result<()> {
+    let source = fs::read(input_filename)?;
+    // reject invalid programs now instead of when the bundle is run
+    Parser::new(&source, dialect).parse()?;
+
+    let mut executable = fs::read(std::env::current_exe()?)?;
+    // bundling from within a bundle must not nest the programs
+    let length = executable.len() as u64;
+    if let Some(trailer) = read_trailer(&mut Cursor::new(&executable), length)? {
+        executable.truncate(trailer.executable_length as usize);
+    }
+
+    let mut flags = 0;
+    if dialect.loop_control {
+        flags |= LOOP_CONTROL_FLAG;
+    }
+    if dialect.register.is_some() {
+        flags |= REGISTER_FLAG;
+    }
+    if dialect.debug_dump {
+        flags |= DEBUG_DUMP_FLAG;
+    }
+    let register = dialect.register.unwrap_or_default();
+    executable.extend_from_slice(&source);
+    executable.push(flags);
+    executable.extend_from_slice(&[register.store, register.load]);
+    executable.extend_from_slice(&(source.len() as u64).to_le_bytes());
+    executable.extend_from_slice(MAGIC);
+    if let Some(parent) = output_filename.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output_filename, executable)
+        .with_context(|| format!("failed to write {}", output_filename.display()))?;
+    make_executable(output_filename)
+}
+
+struct Trailer {
+    flags: u8,
+    register: RegisterCommands,
+    source_length: u64,
+    /// The length of the executable without the embedded program.
+    executable_length: u64,
+}
+
+fn read_trailer(executable: &mut (impl Read + Seek), length: u64) -> Result<Option<Trailer>> {
+    if length < TRAILER_SIZE as u64 {
+        return Ok(None);
+    }
+    let mut trailer = [0; TRAILER_SIZE];
+    executable.seek(SeekFrom::Start(length - TRAILER_SIZE as u64))?;
+    executable.read_exact(&mut trailer)?;
+    if &trailer[11..] != MAGIC {
+        return Ok(None);
+    }
+    let source_length = u64::from_le_bytes(trailer[3..11].try_into().unwrap());
+    Ok((length - TRAILER_SIZE as u64)
+        .checked_sub(source_length)
+        .map(|executable_length| Trailer {
+            flags: trailer[0],
+            register: RegisterCommands {
+                store: trailer[1],
+                load: trailer[2],
+            },
+            source_length,
+            executable_length,
+        }))
+}
+
+#[cfg(unix)]
+fn make_executable(filename: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = fs::metadata(filename)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    fs::set_permissions(filename, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_filename: &Path) -> Result<()> {
+    Ok(())
+}
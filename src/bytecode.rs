@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use crate::program::{Program, Span, Statement, StatementKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Instruction {
+    IncrementPointer,
+    DecrementPointer,
+    IncrementValue,
+    DecrementValue,
+    PutChar,
+    GetChar,
+    StoreRegister,
+    LoadRegister,
+    // jumps behind the matching `JumpIfNotZero` (the start of a loop)
+    JumpIfZero(usize),
+    // jumps behind the matching `JumpIfZero` (the end of a loop)
+    JumpIfNotZero(usize),
+    Jump(usize),
+}
+
+pub(crate) struct Bytecode {
+    instructions: Vec<Instruction>,
+    spans: Vec<Span>,
+}
+
+struct LoopTargets {
+    start: usize,
+    span: Span,
+    pending_breaks: Vec<usize>,
+}
+
+// a block of statements that is being compiled, with the loop it is the body of (`None` for the
+// program)
+type Block<'a> = (std::slice::Iter<'a, Statement>, Option<LoopTargets>);
+
+impl Bytecode {
+    pub(crate) fn compile(program: &Program) -> Self {
+        let mut bytecode = Self {
+            instructions: Vec::new(),
+            spans: Vec::new(),
+        };
+        // an explicit stack of blocks instead of recursion, so that the nesting depth of loops is
+        // not limited by the call stack
+        let mut blocks: Vec<Block> = vec![(program.statements().iter(), None)];
+        while let Some((statements, _)) = blocks.last_mut() {
+            let Some(statement) = statements.next() else {
+                if let (_, Some(targets)) = blocks.pop().expect("checked above") {
+                    bytecode.close_loop(targets);
+                }
+                continue;
+            };
+            match statement.kind() {
+                // an `if` is compiled back into the loop it was created from, which runs at most
+                // once
+                StatementKind::Loop(body) | StatementKind::If(body) => {
+                    let targets = bytecode.open_loop(statement.span());
+                    blocks.push((body.iter(), Some(targets)));
+                }
+                _ => {
+                    let innermost_loop =
+                        blocks.last_mut().and_then(|(_, targets)| targets.as_mut());
+                    bytecode.compile_statement(statement, innermost_loop);
+                }
+            }
+        }
+        bytecode
+    }
+
+    pub(crate) fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub(crate) fn span(&self, index: usize) -> Span {
+        self.spans[index]
+    }
+
+    /// Runs the bytecode for at most `max_steps` steps, discarding its output. Input is read from
+    /// `input` and reads as zero after its end. The index of every executed instruction is
+    /// passed to `visit`, which can stop the execution early. Returns whether the program ended.
+    pub(crate) fn run(
+        &self,
+        max_steps: u64,
+        input: &[u8],
+        mut visit: impl FnMut(usize) -> ControlFlow<()>,
+    ) -> bool {
+        let mut tape: HashMap<i64, u8> = HashMap::new();
+        let mut input = input.iter();
+        let mut pointer = 0;
+        let mut register = 0;
+        let mut program_counter = 0;
+        for _ in 0..max_steps {
+            let Some(&instruction) = self.instructions.get(program_counter) else {
+                return true;
+            };
+            if visit(program_counter).is_break() {
+                return false;
+            }
+            let cell = tape.entry(pointer).or_insert(0);
+            program_counter = match instruction {
+                Instruction::IncrementPointer => {
+                    pointer += 1;
+                    program_counter + 1
+                }
+                Instruction::DecrementPointer => {
+                    pointer -= 1;
+                    program_counter + 1
+                }
+                Instruction::IncrementValue => {
+                    *cell = cell.wrapping_add(1);
+                    program_counter + 1
+                }
+                Instruction::DecrementValue => {
+                    *cell = cell.wrapping_sub(1);
+                    program_counter + 1
+                }
+                Instruction::PutChar => program_counter + 1,
+                Instruction::GetChar => {
+                    *cell = input.next().copied().unwrap_or(0);
+                    program_counter + 1
+                }
+                Instruction::StoreRegister => {
+                    register = *cell;
+                    program_counter + 1
+                }
+                Instruction::LoadRegister => {
+                    *cell = register;
+                    program_counter + 1
+                }
+                Instruction::JumpIfZero(target) if *cell == 0 => target,
+                Instruction::JumpIfNotZero(target) if *cell != 0 => target,
+                Instruction::JumpIfZero(_) | Instruction::JumpIfNotZero(_) => program_counter + 1,
+                Instruction::Jump(target) => target,
+            };
+        }
+        program_counter >= self.instructions.len()
+    }
+
+    fn compile_statement(
+        &mut self,
+        statement: &Statement,
+        innermost_loop: Option<&mut LoopTargets>,
+    ) {
+        let span = statement.span();
+        let instruction = match statement.kind() {
+            StatementKind::Move(amount) => return self.push_move(*amount, span),
+            // does not change the state of the program
+            StatementKind::DebugDump => return,
+            StatementKind::Add { offset, amount } => {
+                let instruction = if *amount > 0 {
+                    Instruction::IncrementValue
+                } else {
+                    Instruction::DecrementValue
+                };
+                self.push_move(*offset, span);
+                self.push_repeated(instruction, amount.unsigned_abs(), span);
+                return self.push_move(-offset, span);
+            }
+            StatementKind::PutChar => Instruction::PutChar,
+            StatementKind::GetChar => Instruction::GetChar,
+            StatementKind::Store => Instruction::StoreRegister,
+            StatementKind::Load => Instruction::LoadRegister,
+            StatementKind::Set { offset, value } => {
+                // equivalent to `[-]` followed by `value` increments
+                self.push_move(*offset, span);
+                let start = self.push(Instruction::JumpIfZero(0), span);
+                self.push(Instruction::DecrementValue, span);
+                let end = self.push(Instruction::JumpIfNotZero(start + 1), span);
+                self.instructions[start] = Instruction::JumpIfZero(end + 1);
+                self.push_repeated(Instruction::IncrementValue, *value as u64, span);
+                return self.push_move(-offset, span);
+            }
+            StatementKind::MultiplyAdd(targets) => {
+                // compiled back into the loop it was created from
+                let mut body = vec![Statement::new(
+                    StatementKind::Add {
+                        offset: 0,
+                        amount: -1,
+                    },
+                    span,
+                )];
+                for &(offset, factor) in targets {
+                    body.push(Statement::new(
+                        StatementKind::Add {
+                            offset,
+                            amount: factor,
+                        },
+                        span,
+                    ));
+                }
+                let targets = self.open_loop(span);
+                for statement in &body {
+                    self.compile_statement(statement, None);
+                }
+                return self.close_loop(targets);
+            }
+            StatementKind::Loop(_) | StatementKind::If(_) => {
+                unreachable!("loops are compiled by Bytecode::compile")
+            }
+            StatementKind::Break => {
+                let index = self.push(Instruction::Jump(0), span);
+                innermost_loop
+                    .expect("checked by the parser")
+                    .pending_breaks
+                    .push(index);
+                return;
+            }
+            StatementKind::Continue => {
+                // the loop condition is re-evaluated at the start of the loop
+                let start = innermost_loop.expect("checked by the parser").start;
+                Instruction::Jump(start)
+            }
+        };
+        self.push(instruction, span);
+    }
+
+    // Counted statements are expanded into single steps so that traps can be attributed to
+    // the exact step at which they occur.
+    fn push_move(&mut self, amount: i64, span: Span) {
+        let instruction = if amount > 0 {
+            Instruction::IncrementPointer
+        } else {
+            Instruction::DecrementPointer
+        };
+        self.push_repeated(instruction, amount.unsigned_abs(), span);
+    }
+
+    fn push_repeated(&mut self, instruction: Instruction, count: u64, span: Span) {
+        for _ in 0..count {
+            self.push(instruction, span);
+        }
+    }
+
+    fn open_loop(&mut self, span: Span) -> LoopTargets {
+        let start = self.push(
+            Instruction::JumpIfZero(0),
+            Span::new(span.start, span.start + 1),
+        );
+        LoopTargets {
+            start,
+            span,
+            pending_breaks: Vec::new(),
+        }
+    }
+
+    fn close_loop(&mut self, targets: LoopTargets) {
+        let span = targets.span;
+        let end = self.push(
+            Instruction::JumpIfNotZero(targets.start + 1),
+            Span::new(span.end - 1, span.end),
+        );
+        let after_loop = end + 1;
+        self.instructions[targets.start] = Instruction::JumpIfZero(after_loop);
+        for index in targets.pending_breaks {
+            self.instructions[index] = Instruction::Jump(after_loop);
+        }
+    }
+
+    fn push(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.instructions.push(instruction);
+        self.spans.push(span);
+        self.instructions.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_programs_do_not_overflow_the_stack() {
+        const DEPTH: usize = 50_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let bytecode = Bytecode::compile(&program);
+        assert_eq!(bytecode.instructions().len(), 2 * DEPTH + 2);
+        assert_eq!(
+            bytecode.instructions()[1],
+            Instruction::JumpIfZero(2 * DEPTH + 2)
+        );
+        assert!(bytecode.run(u64::MAX, &[], |_| ControlFlow::Continue(())));
+    }
+}
@@ -0,0 +1,560 @@
+use std::fmt::Write;
+use std::fs;
+
+use anyhow::{bail, Context};
+
+use crate::program::{Program, Statement, StatementKind};
+use crate::registry::{Backend, BackendOptions, Platform};
+use crate::semantics::{OutOfBounds, Overflow, RuntimeConfig};
+
+/// Lowers the optimized program to portable C99, with the same runtime as the programs compiled
+/// by LLVM: a tape that grows in both directions and the behavior given by [`RuntimeConfig`].
+/// Needs neither LLVM nor a linker, only a C compiler for the generated file.
+pub(crate) struct CBackend;
+
+impl Backend for CBackend {
+    fn name(&self) -> &'static str {
+        "c"
+    }
+
+    fn description(&self) -> &'static str {
+        "C99 source code (compile it with any C compiler)"
+    }
+
+    fn default_output_filename(&self) -> &'static str {
+        "out.c"
+    }
+
+    fn emit(&self, program: &Program, options: &BackendOptions) -> anyhow::Result<()> {
+        // these are implemented by the LLVM backend only
+        if options.platform != Platform::Host {
+            bail!("the C backend does not support --target, pass it to the C compiler instead");
+        }
+        for (enabled, option) in [
+            (options.embed_selftest, "--embed-selftest"),
+            (options.harden, "--harden"),
+            (options.guard_pages, "--guard-pages"),
+            (options.no_libc, "--no-libc"),
+            (options.bare_metal, "--bare-metal"),
+            (options.static_executable, "--static"),
+        ] {
+            if enabled {
+                bail!("the C backend does not support {option}");
+            }
+        }
+
+        let source = generate(program, options);
+        let filename = &options.output_filename;
+        if let Some(parent) = filename.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to write {}", filename.display()))?;
+        }
+        fs::write(filename, source)
+            .with_context(|| format!("failed to write {}", filename.display()))
+    }
+
+    fn plan(&self, options: &BackendOptions) -> Vec<String> {
+        vec![
+            "generate C source code".to_string(),
+            format!(
+                "write C source code to '{}'",
+                options.output_filename.display()
+            ),
+        ]
+    }
+}
+
+fn generate(program: &Program, options: &BackendOptions) -> String {
+    let mut code = format!(
+        "/* generated by brainrust {} from {} */\n\n",
+        env!("CARGO_PKG_VERSION"),
+        options.input_filename.display()
+    );
+    if let Some(output) = &options.constant_output {
+        code.push_str("#include <stdio.h>\n\n");
+        code.push_str(&format!(
+            "static const unsigned char output[{}] = {{{}}};\n\n",
+            output.len().max(1),
+            byte_list(output)
+        ));
+        code.push_str(&format!(
+            "int main(void) {{\n    fwrite(output, 1, {}, stdout);\n    return 0;\n}}\n",
+            output.len()
+        ));
+        return code;
+    }
+
+    code.push_str(&runtime(options));
+    code.push_str("int main(void) {\n");
+    if options.no_echo {
+        code.push_str("    disable_echo();\n");
+    }
+    if contains(program.statements(), &|kind| {
+        matches!(kind, StatementKind::Store | StatementKind::Load)
+    }) {
+        code.push_str("    cell reg = 0;\n");
+    }
+    let initial_extent = options.runtime.initial_tape_extent();
+    code.push_str(&format!(
+        "    ensure_capacity({initial_extent});\n    ensure_capacity(-{initial_extent});\n"
+    ));
+    if !options.initial_tape.is_empty() {
+        let length = options.initial_tape.len();
+        code.push_str(&format!(
+            "    {{\n        static const cell initial_tape[{length}] = {{{}}};\n        \
+             ensure_capacity({});\n        \
+             memcpy(memory + offset, initial_tape, sizeof initial_tape);\n    }}\n",
+            byte_list(&options.initial_tape),
+            length - 1
+        ));
+    }
+    let mut generator = Generator {
+        code,
+        depth: 1,
+        runtime: options.runtime,
+    };
+    generator.statements(program.statements());
+    let mut code = generator.code;
+    code.push_str("    free(memory);\n    return 0;\n}\n");
+    code
+}
+
+// the functions the generated statements call, written for the given options
+fn runtime(options: &BackendOptions) -> String {
+    let runtime = options.runtime;
+    let mut code = String::from(
+        "#include <stdarg.h>\n#include <stdint.h>\n#include <stdio.h>\n#include <stdlib.h>\n\
+         #include <string.h>\n",
+    );
+    if options.no_echo {
+        code.push_str("#include <signal.h>\n#include <termios.h>\n");
+    }
+    if options.prompt.is_some() || options.no_echo {
+        code.push_str("#include <unistd.h>\n");
+    }
+    code.push_str(&format!(
+        "\ntypedef uint{}_t cell;\n\n",
+        runtime.cell_size.bits()
+    ));
+    code.push_str(
+        "\
+// the tape, whose cell at address 0 has the index `offset`
+static cell *memory = NULL;
+static size_t capacity = 0;
+static size_t offset = 0;
+static int64_t pointer = 0;
+
+static inline void fail(const char *format, ...) {
+    va_list arguments;
+    fflush(NULL);
+    va_start(arguments, format);
+    vfprintf(stderr, format, arguments);
+    va_end(arguments);
+    exit(1);
+}
+
+",
+    );
+
+    let limit = match runtime.max_memory {
+        Some(max_memory) => format!(
+            "        if (new_capacity > {max_memory}u) {{\n            \
+             fail(\"error: tape limit of {max_memory} cells exceeded at address %lld\\n\", \
+             (long long)address);\n        }}\n"
+        ),
+        None => String::new(),
+    };
+    code.push_str(&format!(
+        "\
+// grows the tape in either direction until it contains the cell at the address
+static void ensure_capacity(int64_t address) {{
+    int64_t index = (int64_t)offset + address;
+    if (index < 0) {{
+        size_t difference = (size_t)-index;
+        size_t new_capacity = capacity + difference;
+        cell *new_memory;
+{limit}        new_memory = realloc(memory, new_capacity * sizeof(cell));
+        if (new_memory == NULL) {{
+            abort();
+        }}
+        memmove(new_memory + difference, new_memory, capacity * sizeof(cell));
+        memset(new_memory, 0, difference * sizeof(cell));
+        memory = new_memory;
+        capacity = new_capacity;
+        offset += difference;
+    }} else if ((size_t)index >= capacity) {{
+        size_t new_capacity = (size_t)index + 1;
+        cell *new_memory;
+{limit}        new_memory = realloc(memory, new_capacity * sizeof(cell));
+        if (new_memory == NULL) {{
+            abort();
+        }}
+        memset(new_memory + capacity, 0, (new_capacity - capacity) * sizeof(cell));
+        memory = new_memory;
+        capacity = new_capacity;
+    }}
+}}
+
+"
+    ));
+
+    // see `RuntimeConfig::tape_address`
+    let tape_address = match runtime.fixed_tape_size() {
+        None => String::new(),
+        Some(tape_size) => {
+            let tape_size = tape_size.min(i64::MAX as u64);
+            match runtime.out_of_bounds {
+                OutOfBounds::Grow => String::new(),
+                OutOfBounds::Trap => format!(
+                    "    if (address < 0 || address > {}) {{\n        \
+                     fail(\"error: address %lld is outside the tape of {tape_size} cells\\n\", \
+                     (long long)address);\n    }}\n",
+                    tape_size - 1
+                ),
+                OutOfBounds::Clamp => format!(
+                    "    if (address < 0) {{\n        address = 0;\n    }} else if (address > {0}) \
+                     {{\n        address = {0};\n    }}\n",
+                    tape_size - 1
+                ),
+                OutOfBounds::Wrap => format!(
+                    "    address %= {tape_size};\n    if (address < 0) {{\n        \
+                     address += {tape_size};\n    }}\n"
+                ),
+            }
+        }
+    };
+    code.push_str(&format!(
+        "\
+static inline cell *cell_at(int64_t address) {{
+{tape_address}    ensure_capacity(address);
+    return &memory[offset + address];
+}}
+
+static inline void set(int64_t address, cell value) {{
+    *cell_at(address) = value;
+}}
+
+static inline void add(int64_t address, cell amount) {{
+    cell *value = cell_at(address);
+    *value = (cell)(*value + amount);
+}}
+
+"
+    ));
+    if runtime.overflow == Overflow::Trap {
+        code.push_str(&format!(
+            "\
+static inline void add_checked(int64_t address, int64_t amount, long long source_offset) {{
+    cell *value = cell_at(address);
+    if (amount < 0 ? 0 - (uint64_t)amount > *value : (uint64_t)amount > {} - *value) {{
+        fail(\"error: cell overflow at address %lld (offset %lld of the source)\\n\",
+             (long long)address, source_offset);
+    }}
+    *value = (cell)(*value + (cell)amount);
+}}
+
+",
+            cell_literal(runtime.cell_size.max_value())
+        ));
+    }
+
+    match options.max_output {
+        Some(max_output) => code.push_str(&format!(
+            "\
+static uint64_t output_size = 0;
+
+static inline void write_char(cell value) {{
+    if (output_size == {max_output}u) {{
+        fail(\"error: program exceeded the output limit of {max_output} bytes\\n\");
+    }}
+    output_size += 1;
+    putchar((unsigned char)value);
+}}
+
+"
+        )),
+        None => code.push_str(
+            "\
+static inline void write_char(cell value) {
+    putchar((unsigned char)value);
+}
+
+",
+        ),
+    }
+
+    if options.no_echo {
+        code.push_str(
+            "\
+// the settings of the terminal before its echo was turned off
+static struct termios terminal;
+
+static void restore_echo(void) {
+    tcsetattr(0, TCSANOW, &terminal);
+}
+
+static void restore_echo_and_raise(int signal_number) {
+    restore_echo();
+    signal(signal_number, SIG_DFL);
+    raise(signal_number);
+}
+
+static void disable_echo(void) {
+    if (!isatty(0) || tcgetattr(0, &terminal) != 0) {
+        return;
+    }
+    struct termios silent = terminal;
+    silent.c_lflag &= ~(tcflag_t)ECHO;
+    atexit(restore_echo);
+    signal(SIGHUP, restore_echo_and_raise);
+    signal(SIGINT, restore_echo_and_raise);
+    signal(SIGQUIT, restore_echo_and_raise);
+    signal(SIGTERM, restore_echo_and_raise);
+    tcsetattr(0, TCSANOW, &silent);
+}
+
+",
+        );
+    }
+
+    // takes the value of the current cell and returns the value `,` stores in it
+    let (eof_value, unused) = match runtime.eof_value() {
+        Some(value) => (cell_literal(value), "    (void)current;\n"),
+        None => ("current".to_string(), ""),
+    };
+    let prompt = match &options.prompt {
+        Some(prompt) => format!(
+            "\
+    static int at_line_start = 1;
+    if (at_line_start && isatty(0)) {{
+        fputs(\"{}\", stdout);
+        fflush(NULL);
+    }}
+    value = getchar();
+    at_line_start = value == '\\n';
+",
+            escape(prompt.as_bytes())
+        ),
+        None => "    value = getchar();\n".to_string(),
+    };
+    code.push_str(&format!(
+        "\
+static inline cell read_char(cell current) {{
+    int value;
+{unused}{prompt}    return value == EOF ? {eof_value} : (cell)value;
+}}
+
+"
+    ));
+
+    code.push_str(
+        "\
+static inline void mem_dump(void) {
+    size_t i;
+    putchar('M');
+    putchar(':');
+    for (i = 0; i < capacity; ++i) {
+        putchar((unsigned char)(memory[i] + 48));
+    }
+    putchar('\\n');
+}
+
+",
+    );
+    code
+}
+
+// deeper blocks are indented no further, which would make the code grow quadratically with the
+// nesting depth
+const MAX_INDENTATION_DEPTH: usize = 32;
+
+struct Generator {
+    code: String,
+    depth: usize,
+    runtime: RuntimeConfig,
+}
+
+impl Generator {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth.min(MAX_INDENTATION_DEPTH) {
+            self.code.push_str("    ");
+        }
+        self.code.push_str(text);
+        self.code.push('\n');
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        // the blocks that are being generated, innermost last
+        let mut blocks = vec![statements.iter()];
+        while let Some(statements) = blocks.last_mut() {
+            let Some(statement) = statements.next() else {
+                blocks.pop();
+                if !blocks.is_empty() {
+                    self.depth -= 1;
+                    self.line("}");
+                }
+                continue;
+            };
+            let (header, body) = match statement.kind() {
+                StatementKind::Loop(body) => ("while (*cell_at(pointer) != 0)", body),
+                StatementKind::If(body) => ("if (*cell_at(pointer) != 0)", body),
+                _ => {
+                    self.statement(statement);
+                    continue;
+                }
+            };
+            self.line(&format!("{header} {{"));
+            self.depth += 1;
+            blocks.push(body.iter());
+        }
+    }
+
+    // generates a statement that is not a block
+
+    fn statement(&mut self, statement: &Statement) {
+        let cell_size = self.runtime.cell_size;
+        match statement.kind() {
+            StatementKind::Move(amount) if *amount < 0 => {
+                self.line(&format!("pointer -= {};", amount.unsigned_abs()))
+            }
+            StatementKind::Move(amount) => self.line(&format!("pointer += {amount};")),
+            StatementKind::Add { offset, amount } if self.runtime.overflow == Overflow::Trap => {
+                self.line(&format!(
+                    "add_checked({}, {amount}, {});",
+                    address(*offset),
+                    statement.span().start
+                ))
+            }
+            StatementKind::Add { offset, amount } => self.line(&format!(
+                "add({}, {});",
+                address(*offset),
+                cell_literal(cell_size.wrap(*amount))
+            )),
+            StatementKind::Set { offset, value } => {
+                self.line(&format!("set({}, {value});", address(*offset)))
+            }
+            StatementKind::MultiplyAdd(pairs) => {
+                self.line("{");
+                self.depth += 1;
+                self.line("cell value = *cell_at(pointer);");
+                self.line("if (value != 0) {");
+                self.depth += 1;
+                for (offset, factor) in pairs {
+                    // multiplying 64-bit values wraps around like multiplying cells
+                    self.line(&format!(
+                        "add({}, (cell)(value * UINT64_C({})));",
+                        address(*offset),
+                        cell_size.wrap(*factor)
+                    ));
+                }
+                self.line("set(pointer, 0);");
+                self.depth -= 1;
+                self.line("}");
+                self.depth -= 1;
+                self.line("}");
+            }
+            StatementKind::PutChar => self.line("write_char(*cell_at(pointer));"),
+            StatementKind::GetChar => self.line("set(pointer, read_char(*cell_at(pointer)));"),
+            StatementKind::Loop(_) | StatementKind::If(_) => {
+                unreachable!("blocks are generated by `statements`")
+            }
+            StatementKind::Store => self.line("reg = *cell_at(pointer);"),
+            StatementKind::Load => self.line("set(pointer, reg);"),
+            StatementKind::Break => self.line("break;"),
+            StatementKind::Continue => self.line("continue;"),
+            StatementKind::DebugDump => self.line("mem_dump();"),
+        }
+    }
+}
+
+fn contains(statements: &[Statement], predicate: &dyn Fn(&StatementKind) -> bool) -> bool {
+    let mut blocks = vec![statements];
+    while let Some(statements) = blocks.pop() {
+        for statement in statements {
+            match statement.kind() {
+                StatementKind::Loop(body) | StatementKind::If(body) => blocks.push(body),
+                kind if predicate(kind) => return true,
+                _ => {}
+            }
+        }
+    }
+    false
+}
+
+// the address of the cell at the offset relative to the pointer
+fn address(offset: i64) -> String {
+    match offset {
+        0 => "pointer".to_string(),
+        offset if offset < 0 => format!("pointer - {}", offset.unsigned_abs()),
+        offset => format!("pointer + {offset}"),
+    }
+}
+
+// an unsigned literal that is wide enough for any cell
+fn cell_literal(value: u64) -> String {
+    if value > u64::from(u32::MAX) {
+        format!("UINT64_C({value})")
+    } else {
+        format!("{value}u")
+    }
+}
+
+fn byte_list(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "0".to_string();
+    }
+    let mut list = String::new();
+    for (index, byte) in bytes.iter().enumerate() {
+        if index > 0 {
+            list.push_str(if index % 16 == 0 { ",\n    " } else { ", " });
+        }
+        write!(list, "{byte}").expect("writing to a string cannot fail");
+    }
+    list
+}
+
+// the contents of a C string literal; octal escapes always have three digits, so that they
+// cannot swallow a following digit
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' | b'?' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            }
+            b' '..=b'~' => escaped.push(byte as char),
+            _ => write!(escaped, "\\{byte:03o}").expect("writing to a string cannot fail"),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_loops_are_lowered() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let code = generate(
+            &program,
+            &BackendOptions::for_tests(Platform::Host, "prog.c"),
+        );
+        let main = &code[code.find("int main(void) {").unwrap()..];
+        assert_eq!(
+            main.matches("while (*cell_at(pointer) != 0) {").count(),
+            DEPTH
+        );
+        assert_eq!(main.matches('{').count(), main.matches('}').count());
+        let indentation = "    ".repeat(MAX_INDENTATION_DEPTH);
+        assert!(main
+            .lines()
+            .all(|line| !line.starts_with(&format!("{indentation} "))));
+    }
+}
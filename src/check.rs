@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use crate::analysis::{all_analyses, termination_proofs};
+use crate::diagnostics::{report, Diagnostic};
+use crate::program::Program;
+use crate::suggestions::suggestions;
+
+pub(crate) fn diagnostics(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<_> = all_analyses()
+        .iter()
+        .flat_map(|analysis| analysis.analyze(program))
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+pub(crate) fn check(filename: &Path, source: &[u8], program: &Program) {
+    for diagnostic in &check_diagnostics(program) {
+        report(filename, source, diagnostic);
+    }
+}
+
+// the diagnostics of the analyses together with the suggestions and the termination proofs
+fn check_diagnostics(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics = diagnostics(program);
+    diagnostics.extend(suggestions(program));
+    diagnostics.extend(termination_proofs(program));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_loops_are_checked() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let diagnostics = check_diagnostics(&program);
+        // only the innermost loop is proven to terminate
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].span.start, DEPTH);
+    }
+}
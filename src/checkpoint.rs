@@ -0,0 +1,184 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::interpreter::InterpreterError;
+use crate::program::CellSize;
+
+// identifies checkpoint files, the last byte is the version of the format
+const MAGIC: &[u8; 8] = b"BFCKPT\0\x02";
+
+/// Everything needed to continue interpreting a program where a previous run stopped, except
+/// for its input and output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Checkpoint {
+    /// Identifies the compiled program the program counter and the loops refer to.
+    pub(crate) code_hash: u64,
+    pub(crate) program_counter: usize,
+    /// The number of consecutive iterations of each loop, for `--max-loop-iterations`.
+    pub(crate) iterations: Vec<u64>,
+    pub(crate) pointer: i64,
+    pub(crate) register: u64,
+    pub(crate) at_line_start: bool,
+    /// The number of bytes the program has read.
+    pub(crate) input_size: u64,
+    /// A checkpoint can only be resumed with cells of the same size.
+    pub(crate) cell_size: CellSize,
+    /// Runs of consecutive cells, given by the address of their first cell. Cells that are not
+    /// part of a run are zero.
+    pub(crate) cells: Vec<(i64, Vec<u64>)>,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to a temporary file first, so that an interrupted write never
+    /// replaces an existing checkpoint with a broken one.
+    pub(crate) fn write(&self, filename: &Path) -> io::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(self.code_hash.to_le_bytes());
+        bytes.extend((self.program_counter as u64).to_le_bytes());
+        bytes.extend((self.iterations.len() as u64).to_le_bytes());
+        for iterations in &self.iterations {
+            bytes.extend(iterations.to_le_bytes());
+        }
+        bytes.extend(self.pointer.to_le_bytes());
+        bytes.extend(self.register.to_le_bytes());
+        bytes.push(self.at_line_start.into());
+        bytes.extend(self.input_size.to_le_bytes());
+        bytes.push(self.cell_size.bits() as u8);
+        bytes.extend((self.cells.len() as u64).to_le_bytes());
+        // every cell takes 8 bytes, whatever its size
+        for (address, values) in &self.cells {
+            bytes.extend(address.to_le_bytes());
+            bytes.extend((values.len() as u64).to_le_bytes());
+            for value in values {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        // appended to the whole name, so that checkpoints like run.a and run.b don't share it
+        let mut temporary = filename.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, filename)
+    }
+
+    pub(crate) fn read(filename: &Path) -> io::Result<Self> {
+        let bytes = fs::read(filename)?;
+        let Some(rest) = bytes.strip_prefix(MAGIC) else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not a checkpoint of this version of brainrust",
+            ));
+        };
+        let mut reader = Reader(rest);
+        let code_hash = reader.u64()?;
+        let program_counter = reader.u64()? as usize;
+        let iterations = (0..reader.u64()?)
+            .map(|_| reader.u64())
+            .collect::<io::Result<_>>()?;
+        let pointer = reader.u64()? as i64;
+        let register = reader.u64()?;
+        let at_line_start = reader.bytes(1)?[0] != 0;
+        let input_size = reader.u64()?;
+        let cell_size = CellSize::from_bits(reader.bytes(1)?[0].into()).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                "the checkpoint has an invalid cell size",
+            )
+        })?;
+        let cells = (0..reader.u64()?)
+            .map(|_| {
+                let address = reader.u64()? as i64;
+                let values = (0..reader.u64()?)
+                    .map(|_| reader.u64())
+                    .collect::<io::Result<_>>()?;
+                Ok((address, values))
+            })
+            .collect::<io::Result<_>>()?;
+        if !reader.0.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "unexpected data after the checkpoint",
+            ));
+        }
+        Ok(Self {
+            code_hash,
+            program_counter,
+            iterations,
+            pointer,
+            register,
+            at_line_start,
+            input_size,
+            cell_size,
+            cells,
+        })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&mut self, length: usize) -> io::Result<&[u8]> {
+        if self.0.len() < length {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "the checkpoint is truncated",
+            ));
+        }
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_le_bytes(
+            bytes.try_into().expect("8 bytes were read"),
+        ))
+    }
+}
+
+/// Where an execution starts and where its progress is saved.
+#[derive(Default)]
+pub(crate) struct Checkpointing {
+    resume: Option<Checkpoint>,
+    // the file checkpoints are written to and the number of steps between them
+    output: Option<(PathBuf, u64)>,
+    next_checkpoint: u64,
+}
+
+impl Checkpointing {
+    pub(crate) fn new(resume: Option<Checkpoint>, output: Option<(PathBuf, u64)>) -> Self {
+        Self {
+            resume,
+            next_checkpoint: output.as_ref().map_or(0, |&(_, interval)| interval),
+            output,
+        }
+    }
+
+    pub(crate) fn resume(&self) -> Option<&Checkpoint> {
+        self.resume.as_ref()
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Whether a checkpoint should be written after the given number of steps.
+    pub(crate) fn is_due(&self, steps: u64) -> bool {
+        self.output.is_some() && steps >= self.next_checkpoint
+    }
+
+    pub(crate) fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), InterpreterError> {
+        let Some((filename, interval)) = &self.output else {
+            return Ok(());
+        };
+        checkpoint
+            .write(filename)
+            .map_err(|error| InterpreterError::FailedToWriteCheckpoint {
+                filename: filename.clone(),
+                error_message: error.to_string(),
+            })?;
+        self.next_checkpoint += interval;
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+
+use crate::engine::cache_directory;
+use crate::hash::fnv1a;
+
+/// Removes the files brainrust generated from the given source file (see [`record_outputs`])
+/// and optionally the cache of compiled executables used by `run`.
+pub(crate) fn clean(input_filename: &Path, cache: bool, dry_run: bool) -> Result<()> {
+    let mut removed_anything = remove_outputs(&manifest_directory(), input_filename, dry_run)?;
+
+    let cache_directory = cache_directory();
+    if cache && cache_directory.is_dir() {
+        if dry_run {
+            println!("would remove {}", cache_directory.display());
+        } else {
+            fs::remove_dir_all(&cache_directory)
+                .with_context(|| format!("while removing {}", cache_directory.display()))?;
+            println!("removed {}", cache_directory.display());
+        }
+        removed_anything = true;
+    }
+
+    if !removed_anything {
+        println!("nothing to clean");
+    }
+    Ok(())
+}
+
+/// Remembers the files a backend wrote for the given source file, so that `clean` removes
+/// exactly those and never a file of the user. Failing to do so does not fail the command.
+pub(crate) fn record_outputs(input_filename: &Path, outputs: &[PathBuf]) {
+    if let Err(error) = record_outputs_in(&manifest_directory(), input_filename, outputs) {
+        eprintln!("warning: failed to remember the generated files for clean: {error}");
+    }
+}
+
+// kept apart from the cache, which `clean --cache` removes as a whole
+fn manifest_directory() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state"))
+        })
+        .unwrap_or_else(std::env::temp_dir)
+        .join("brainrust")
+        .join("outputs")
+}
+
+// The manifest of a source file lists the generated files, one per line after the time they
+// were modified when brainrust wrote them. A file that was modified since then is kept.
+fn manifest_filename(manifest_directory: &Path, input_filename: &Path) -> io::Result<PathBuf> {
+    let input_filename = fs::canonicalize(input_filename)?;
+    Ok(manifest_directory.join(format!(
+        "{:016x}",
+        fnv1a(input_filename.as_os_str().as_encoded_bytes())
+    )))
+}
+
+fn read_manifest(filename: &Path) -> io::Result<Vec<(u128, PathBuf)>> {
+    let manifest = match fs::read_to_string(filename) {
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        manifest => manifest?,
+    };
+    Ok(manifest
+        .lines()
+        .filter_map(|line| {
+            let (modified, output) = line.split_once(' ')?;
+            Some((modified.parse().ok()?, PathBuf::from(output)))
+        })
+        .collect())
+}
+
+fn modification_time(filename: &Path) -> io::Result<u128> {
+    let modified = fs::metadata(filename)?.modified()?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos()))
+}
+
+fn record_outputs_in(
+    manifest_directory: &Path,
+    input_filename: &Path,
+    outputs: &[PathBuf],
+) -> io::Result<()> {
+    let manifest = manifest_filename(manifest_directory, input_filename)?;
+    let mut entries = read_manifest(&manifest)?;
+    for output in outputs {
+        // e.g. the object file of a failed link
+        let Ok(modified) = modification_time(output) else {
+            continue;
+        };
+        let output = std::path::absolute(output)?;
+        entries.retain(|(_, path)| *path != output);
+        entries.push((modified, output));
+    }
+    fs::create_dir_all(manifest_directory)?;
+    fs::write(
+        &manifest,
+        entries
+            .iter()
+            .map(|(modified, output)| format!("{modified} {}\n", output.display()))
+            .collect::<String>(),
+    )
+}
+
+// returns whether anything was (or would be) removed
+fn remove_outputs(manifest_directory: &Path, input_filename: &Path, dry_run: bool) -> Result<bool> {
+    let Ok(manifest) = manifest_filename(manifest_directory, input_filename) else {
+        return Ok(false);
+    };
+    let entries = read_manifest(&manifest)
+        .with_context(|| format!("while reading {}", manifest.display()))?;
+    let mut removed_anything = false;
+    for (modified, output) in entries {
+        match modification_time(&output) {
+            // removed by the user already
+            Err(_) => continue,
+            Ok(time) if time != modified => {
+                println!(
+                    "kept {}, it was modified after brainrust generated it",
+                    output.display()
+                );
+                continue;
+            }
+            Ok(_) if dry_run => println!("would remove {}", output.display()),
+            Ok(_) => {
+                fs::remove_file(&output)
+                    .with_context(|| format!("while removing {}", output.display()))?;
+                println!("removed {}", output.display());
+            }
+        }
+        removed_anything = true;
+    }
+    if !dry_run {
+        match fs::remove_file(&manifest) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                return Err(error).with_context(|| format!("while removing {}", manifest.display()))
+            }
+            _ => {}
+        }
+    }
+    Ok(removed_anything)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    // a fresh directory with a source file, the manifests and the outputs of a test
+    fn directory(test: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("brainrust-clean-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("prog.b"), "+.").unwrap();
+        directory
+    }
+
+    #[test]
+    fn removes_only_recorded_outputs() {
+        let directory = directory("recorded");
+        let manifests = directory.join("manifests");
+        let source = directory.join("prog.b");
+        for name in ["a.out", "out.c", "prog.s", "prog.d", "prog"] {
+            fs::write(directory.join(name), name).unwrap();
+        }
+        let outputs = [directory.join("a.out"), directory.join("out.c")];
+        record_outputs_in(&manifests, &source, &outputs).unwrap();
+
+        assert!(remove_outputs(&manifests, &source, true).unwrap());
+        assert!(outputs.iter().all(|output| output.is_file()));
+        assert!(remove_outputs(&manifests, &source, false).unwrap());
+        assert!(outputs.iter().all(|output| !output.exists()));
+        // files brainrust did not write survive, even if they look like its outputs
+        for name in ["prog.b", "prog.s", "prog.d", "prog"] {
+            assert!(directory.join(name).is_file(), "{name} was removed");
+        }
+        assert!(!remove_outputs(&manifests, &source, false).unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn keeps_outputs_that_were_modified_afterwards() {
+        let directory = directory("modified");
+        let manifests = directory.join("manifests");
+        let source = directory.join("prog.b");
+        let output = directory.join("prog.c");
+        fs::write(&output, "generated").unwrap();
+        record_outputs_in(&manifests, &source, std::slice::from_ref(&output)).unwrap();
+        // e.g. the user replaced the generated file by a hand-written one of the same name
+        File::options()
+            .write(true)
+            .open(&output)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(!remove_outputs(&manifests, &source, false).unwrap());
+        assert!(output.is_file());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn outputs_are_recorded_per_source_file() {
+        let directory = directory("per-source");
+        let manifests = directory.join("manifests");
+        let source = directory.join("prog.b");
+        let other_source = directory.join("other.b");
+        fs::write(&other_source, "-.").unwrap();
+        let output = directory.join("a.out");
+        fs::write(&output, "generated").unwrap();
+        record_outputs_in(&manifests, &other_source, std::slice::from_ref(&output)).unwrap();
+
+        assert!(!remove_outputs(&manifests, &source, false).unwrap());
+        assert!(output.is_file());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
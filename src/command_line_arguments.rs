@@ -1,5 +1,17 @@
-use clap::ValueEnum;
-use std::path::PathBuf;
+use clap::builder::ArgPredicate;
+use clap::{ArgMatches, ValueEnum};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::SourceLocation;
+use crate::generator::{GeneratorOptions, InstructionWeights};
+use crate::interpreter::InterpreterOptions;
+use crate::optimizer::{Pass, Pipeline};
+use crate::parser::{Dialect, RegisterCommands};
+use crate::program::{CellSize, Span};
+use crate::registry::Platform;
+use crate::semantics::{EofBehavior, OutOfBounds, Overflow, RuntimeConfig};
+use crate::slice::Criterion;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum EmitTarget {
@@ -7,18 +19,124 @@ pub(crate) enum EmitTarget {
     ObjectFile,
     Executable,
     LlvmIr,
+    Bitcode,
+    SharedLibrary,
+    StaticLibrary,
+    Jit,
+}
+
+impl EmitTarget {
+    pub(crate) fn backend_name(self) -> &'static str {
+        match self {
+            EmitTarget::Assembly => "assembly",
+            EmitTarget::ObjectFile => "object",
+            EmitTarget::Executable => "executable",
+            EmitTarget::LlvmIr => "llvm-ir",
+            EmitTarget::Bitcode => "llvm-bc",
+            EmitTarget::SharedLibrary => "shared-lib",
+            EmitTarget::StaticLibrary => "static-lib",
+            EmitTarget::Jit => "jit",
+        }
+    }
+}
+
+/// A format that `--emit` writes the program in instead of compiling it into an executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum EmitFormat {
+    /// Portable C99 with the same runtime as compiled programs (no LLVM required)
+    C,
+    /// LLVM bitcode, e.g. for opt or to be linked with other modules using llvm-link
+    #[value(name = "llvm-bc")]
+    LlvmBitcode,
+    /// Shared library exporting `int bf_run(void)`, which runs the program and returns its exit
+    /// code
+    #[value(name = "shared-lib")]
+    SharedLibrary,
+    /// Static library exporting `int bf_run(void)`, to be linked into other programs
+    #[value(name = "static-lib")]
+    StaticLibrary,
+}
+
+impl EmitFormat {
+    pub(crate) fn backend_name(self) -> &'static str {
+        match self {
+            EmitFormat::C => "c",
+            EmitFormat::LlvmBitcode => EmitTarget::Bitcode.backend_name(),
+            EmitFormat::SharedLibrary => EmitTarget::SharedLibrary.backend_name(),
+            EmitFormat::StaticLibrary => EmitTarget::StaticLibrary.backend_name(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum LoopLimitAction {
+    #[default]
+    Abort,
+    Warn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum TapeKind {
+    /// One block of memory that grows in both directions
+    #[default]
+    Contiguous,
+    /// Pages of 4 KiB that are allocated when they are first accessed
+    Sparse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum FlushMode {
+    /// Flush after every byte
+    Byte,
+    /// Flush after every newline
+    #[default]
+    Line,
+    /// Flush only when the buffer is full
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum Engine {
+    #[default]
+    Auto,
+    Interpreter,
+    Compiled,
+    Jit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ConformanceEngine {
+    /// The interpreter running the optimized program
+    Interpreter,
+    /// The interpreter running the program exactly as parsed
+    Unoptimized,
+    /// An executable compiled by LLVM
+    Compiled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum FormatStyle {
+    /// All commands without whitespace, wrapped at a fixed width
+    Compact,
+    /// Every loop on its own line with indented bodies, suitable for diffs
+    #[default]
+    Canonical,
 }
 
 #[derive(clap::Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 #[clap(group(
             clap::ArgGroup::new("output")
                 .required(false)
-                .args(& ["interpret", "emit_assembly", "only_compile_and_assemble", "emit_llvm"])
+                .args(& ["interpret", "emit_assembly", "only_compile_and_assemble", "emit_llvm", "emit_format", "jit"])
         ))]
 pub(crate) struct CommandLineArguments {
+    #[command(subcommand)]
+    pub(crate) subcommand: Option<Subcommand>,
+
     #[arg(short, long, help = "Name of the file to be generated")]
-    output_filename: Option<PathBuf>,
+    pub(crate) output_filename: Option<PathBuf>,
 
     #[arg(
         short = 'r',
@@ -46,58 +164,1403 @@ pub(crate) struct CommandLineArguments {
     )]
     pub(crate) emit_llvm: bool,
 
-    pub(crate) input_filename: PathBuf,
+    #[arg(
+        long = "emit",
+        value_enum,
+        value_name = "FORMAT",
+        help = "Emit the program in another format instead of compiling it"
+    )]
+    pub(crate) emit_format: Option<EmitFormat>,
+
+    #[arg(
+        long = "jit",
+        action,
+        conflicts_with = "output_filename",
+        help = "Compile the program in memory and run it right away (no linker required)"
+    )]
+    pub(crate) jit: bool,
+
+    #[arg(
+        long = "jit-profiling",
+        requires = "jit",
+        help = "Compile every loop into a function of its own, named after its offset in the source, and write a perf jitdump file (jit-<pid>.dump) of the compiled code"
+    )]
+    pub(crate) jit_profiling: bool,
+
+    #[arg(
+        long = "target",
+        value_enum,
+        default_value_t = Platform::Host,
+        conflicts_with_all = ["interpret", "jit", "guard_pages"],
+        help = "The platform the generated code runs on"
+    )]
+    pub(crate) platform: Platform,
+
+    #[arg(required = true)]
+    input_filename: Option<PathBuf>,
+
+    #[arg(
+        long = "from",
+        value_name = "FRONTEND",
+        default_value = "brainfuck",
+        help = "Language of the input file"
+    )]
+    pub(crate) frontend: String,
+
+    #[arg(
+        long = "to",
+        alias = "backend",
+        value_name = "BACKEND",
+        conflicts_with = "output",
+        help = "Kind of artifact to generate"
+    )]
+    backend: Option<String>,
 
     #[arg(short = 'O', value_parser = clap::value_parser!(u8).range(0..=3), help = "Sets the optimization level", default_value_t = 2)]
-    optimization_level: u8,
+    pub(crate) optimization_level: u8,
+
+    #[arg(
+        long = "bf-opt",
+        value_name = "LEVEL",
+        value_parser = clap::value_parser!(u8).range(0..=Pipeline::MAX_LEVEL as i64),
+        default_value_t = Pipeline::DEFAULT_LEVEL,
+        help = "Sets the level of optimizations applied to the program before it is interpreted or handed to the backend"
+    )]
+    bf_optimization_level: u8,
+
+    #[arg(
+        long = "bf-passes",
+        value_name = "PASSES",
+        value_delimiter = ',',
+        conflicts_with = "bf_optimization_level",
+        value_parser = clap::builder::PossibleValuesParser::new(Pass::ALL.map(Pass::name)),
+        help = "Comma-separated list of optimization passes to run instead of the ones of --bf-opt"
+    )]
+    bf_passes: Option<Vec<String>>,
+
+    #[arg(
+        long = "load-pass",
+        value_name = "LIBRARY",
+        help = "Loads an optimization pass from a dynamic library and runs it after the built-in passes (can be repeated)"
+    )]
+    pub(crate) pass_plugins: Vec<PathBuf>,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action,
+        help = "Print details about the individual compilation steps"
+    )]
+    pub(crate) verbose: bool,
+
+    #[arg(
+        long = "dry-run",
+        action,
+        help = "Print what would be done without actually doing it"
+    )]
+    pub(crate) dry_run: bool,
+
+    #[arg(
+        long = "prompt",
+        value_name = "TEXT",
+        help = "Print this text whenever the program starts reading a line of input from a terminal"
+    )]
+    pub(crate) prompt: Option<String>,
+
+    #[arg(
+        long = "no-echo",
+        action,
+        help = "Keep the terminal from echoing the typed input while the program runs, e.g. for programs that echo their input themselves"
+    )]
+    pub(crate) no_echo: bool,
+
+    #[arg(
+        long = "eof-behavior",
+        value_enum,
+        default_value_t = EofBehavior::Zero,
+        help = "What ',' stores in the current cell once the input has ended"
+    )]
+    pub(crate) eof_behavior: EofBehavior,
+
+    #[arg(
+        long = "cell-size",
+        value_name = "BITS",
+        value_parser = parse_cell_size,
+        default_value = "8",
+        help = "Number of bits of a cell (8, 16, 32 or 64), cells wrap around when they overflow"
+    )]
+    pub(crate) cell_size: CellSize,
+
+    #[arg(
+        long = "overflow",
+        value_enum,
+        default_value_t = Overflow::Wrap,
+        help = "What happens when '+' or '-' takes a cell beyond its range of values (trap disables the optimization passes)"
+    )]
+    pub(crate) overflow: Overflow,
+
+    #[arg(
+        long = "tape-size",
+        value_name = "CELLS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["max_memory", "embed_selftest"],
+        help = "Give the tape a fixed number of cells, starting at address 0 (e.g. 30000)"
+    )]
+    pub(crate) tape_size: Option<u64>,
+
+    #[arg(
+        long = "oob",
+        value_enum,
+        default_value_t = OutOfBounds::Grow,
+        default_value_if("tape_size", ArgPredicate::IsPresent, "trap"),
+        help = "What happens when the program accesses a cell beyond an end of a fixed-size tape (defaults to trap with --tape-size)"
+    )]
+    pub(crate) out_of_bounds: OutOfBounds,
+
+    #[arg(
+        long = "initial-tape",
+        value_name = "FILE",
+        help = "Store the bytes of a file in the cells starting at address 0 before running the program"
+    )]
+    pub(crate) initial_tape: Option<PathBuf>,
+
+    #[arg(
+        long = "embed-selftest",
+        action,
+        conflicts_with = "interpret",
+        help = "Make the generated program check its runtime when started with --bf-selftest"
+    )]
+    pub(crate) embed_selftest: bool,
+
+    #[arg(
+        long = "harden",
+        action,
+        conflicts_with = "interpret",
+        help = "Harden the generated program (stack protector, runtime bounds checks, full RELRO)"
+    )]
+    pub(crate) harden: bool,
+
+    #[arg(
+        long = "guard-pages",
+        action,
+        conflicts_with_all = ["interpret", "jit", "max_memory", "embed_selftest", "debug_extensions"],
+        help = "Reserve a large tape between guard pages instead of checking on every access whether the tape has to grow (Unix only)"
+    )]
+    pub(crate) guard_pages: bool,
+
+    #[arg(
+        long = "no-libc",
+        action,
+        conflicts_with_all = ["interpret", "jit", "platform", "harden", "guard_pages", "no_echo"],
+        help = "Generate a static executable that makes system calls itself instead of using the C standard library (Linux on x86-64 only)"
+    )]
+    pub(crate) no_libc: bool,
+
+    #[arg(
+        long = "bare-metal",
+        action,
+        requires_all = ["output", "tape_size"],
+        required_if_eq_any = [("platform", "thumbv7em-none-eabi"), ("platform", "riscv32-unknown-none-elf")],
+        conflicts_with_all = ["interpret", "jit", "no_libc", "harden", "guard_pages", "const_eval", "prompt", "no_echo", "max_output"],
+        help = "Generate code for a target without an operating system, with a static tape of --tape-size cells and I/O through the functions bf_putchar and bf_getchar (requires -c, -a, --emit-llvm or --emit llvm-bc)"
+    )]
+    pub(crate) bare_metal: bool,
+
+    #[arg(
+        long = "linker",
+        value_name = "LINKER",
+        conflicts_with_all = ["interpret", "jit"],
+        help = "Program used to link executables, e.g. gcc or ld.lld (by default, the first of cc, gcc and clang that is installed)"
+    )]
+    pub(crate) linker: Option<PathBuf>,
+
+    #[arg(
+        long = "static",
+        action,
+        conflicts_with_all = ["interpret", "jit", "platform", "no_libc", "bare_metal"],
+        help = "Link a fully static executable that runs on other Linux machines without the same shared libraries (prefers musl-gcc if installed)"
+    )]
+    pub(crate) static_executable: bool,
+
+    #[arg(
+        short = 'L',
+        value_name = "DIRECTORY",
+        conflicts_with_all = ["interpret", "jit"],
+        help = "Add a directory to the linker's search path for libraries (can be repeated)"
+    )]
+    library_directories: Vec<PathBuf>,
+
+    #[arg(
+        short = 'l',
+        value_name = "LIBRARY",
+        conflicts_with_all = ["interpret", "jit"],
+        help = "Link executables against a library, e.g. -l m for libm.so (can be repeated)"
+    )]
+    libraries: Vec<String>,
+
+    #[arg(
+        long = "link-arg",
+        value_name = "ARGUMENT",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["interpret", "jit"],
+        help = "Pass an argument to the linker, e.g. -Wl,-rpath,/opt/lib (can be repeated)"
+    )]
+    link_arguments: Vec<String>,
+
+    #[arg(
+        long = "const-eval",
+        action,
+        conflicts_with_all = ["interpret", "embed_selftest"],
+        help = "Run the program at compile time and generate a program that only writes its output (requires a program without input)"
+    )]
+    pub(crate) const_eval: bool,
+
+    #[arg(
+        long = "trace-compile",
+        value_name = "FILE",
+        help = "Write the duration of parsing, every optimization pass, code generation and linking to a JSON file in the Chrome trace event format"
+    )]
+    pub(crate) trace_compile: Option<PathBuf>,
+
+    #[arg(
+        long = "stats-log",
+        value_name = "FILE",
+        global = true,
+        help = "Append a JSON line with the command, its duration, the program size and the engine to a local file"
+    )]
+    pub(crate) stats_log: Option<PathBuf>,
+
+    #[arg(
+        long = "max-output",
+        value_name = "BYTES",
+        help = "Terminate the program with an error once it tries to write more than this many bytes"
+    )]
+    pub(crate) max_output: Option<u64>,
+
+    #[arg(
+        long = "max-memory",
+        value_name = "CELLS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Terminate the program with an error once its tape would grow beyond this many cells"
+    )]
+    pub(crate) max_memory: Option<u64>,
+
+    #[arg(
+        long = "max-loop-iterations",
+        value_name = "N",
+        requires = "interpret",
+        help = "Limit the number of consecutive iterations of any single loop"
+    )]
+    max_loop_iterations: Option<u64>,
+
+    #[arg(
+        long = "input",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Read the program's input from a file instead of stdin"
+    )]
+    input: Option<PathBuf>,
+
+    #[arg(
+        long = "require-input-consumed",
+        requires = "input",
+        help = "Exit with an error if the program ends before it has read all of the input file"
+    )]
+    require_input_consumed: bool,
+
+    #[arg(
+        long = "record-input",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Write every byte the program reads using ',' to FILE, e.g. to reproduce an interactive session with --replay-input"
+    )]
+    record_input: Option<PathBuf>,
+
+    #[arg(
+        long = "replay-input",
+        value_name = "FILE",
+        requires = "interpret",
+        conflicts_with_all = ["input", "record_input"],
+        help = "Read the program's input from a file written by --record-input and exit with an error unless the program reads all of it"
+    )]
+    replay_input: Option<PathBuf>,
+
+    #[arg(
+        long = "checkpoint-out",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Save the state of the interpreter to FILE every --checkpoint-interval steps and when the program exceeds --max-steps"
+    )]
+    checkpoint_out: Option<PathBuf>,
+
+    #[arg(
+        long = "checkpoint-interval",
+        value_name = "N",
+        default_value_t = 100_000_000,
+        requires = "checkpoint_out",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "The number of steps between two checkpoints of --checkpoint-out"
+    )]
+    checkpoint_interval: u64,
+
+    #[arg(
+        long = "resume",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Continue the program from a checkpoint written by --checkpoint-out (an --input file is skipped up to where the program had read it)"
+    )]
+    resume: Option<PathBuf>,
+
+    #[arg(
+        long = "fail-on-eof",
+        requires = "interpret",
+        help = "Exit with an error if the program executes ',' after the end of its input"
+    )]
+    fail_on_eof: bool,
+
+    #[arg(
+        long = "idle-yield",
+        value_name = "N",
+        requires = "interpret",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Sleep briefly whenever N instructions in a row neither perform I/O nor write to the tape, counting ',' after the end of the input as idle (e.g. while waiting in an endless loop or polling for input)"
+    )]
+    idle_window: Option<u64>,
+
+    #[arg(
+        long = "max-steps",
+        value_name = "N",
+        requires = "interpret",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Terminate the program with exit code 124 once it has executed N steps (statements and loop condition checks)"
+    )]
+    max_steps: Option<u64>,
+
+    #[arg(
+        long = "profile",
+        requires = "interpret",
+        help = "Print the loops that took the most steps, with their source locations and share of all steps, to stderr"
+    )]
+    pub(crate) profile: bool,
+
+    #[arg(
+        long = "callgrind",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Write the number of executions of every statement to a file in callgrind format, with loops as functions, for KCachegrind or QCachegrind"
+    )]
+    pub(crate) callgrind: Option<PathBuf>,
+
+    #[arg(
+        long = "folded-stacks",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Write the steps taken in every nest of loops to a file in the folded stacks format of flame graph tools"
+    )]
+    pub(crate) folded_stacks: Option<PathBuf>,
+
+    #[arg(
+        long = "coverage",
+        requires = "interpret",
+        help = "Print how many commands were executed and which ones never were to stderr"
+    )]
+    pub(crate) coverage: bool,
+
+    #[arg(
+        long = "coverage-annotated",
+        value_name = "FILE",
+        requires = "coverage",
+        help = "Also write the source with the number of executions of every line to a file"
+    )]
+    pub(crate) coverage_annotated: Option<PathBuf>,
+
+    #[arg(
+        long = "heatmap",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Write the source to an HTML file with every command colored by how often it was executed"
+    )]
+    pub(crate) heatmap: Option<PathBuf>,
+
+    #[arg(
+        long = "runtime-stats",
+        requires = "interpret",
+        conflicts_with_all = [
+            "trace",
+            "profile",
+            "callgrind",
+            "folded_stacks",
+            "coverage",
+            "heatmap"
+        ],
+        help = "Print the number of steps, touched cells, bytes read and written, the farthest pointer movements and the peak tape size to stderr"
+    )]
+    pub(crate) runtime_stats: bool,
+
+    #[arg(
+        long = "export-frames",
+        value_name = "DIR",
+        requires = "interpret",
+        conflicts_with_all = [
+            "trace",
+            "profile",
+            "callgrind",
+            "folded_stacks",
+            "coverage",
+            "heatmap",
+            "runtime_stats"
+        ],
+        help = "Render the cells around the pointer to an SVG file in DIR every --frame-interval steps and once the program has ended"
+    )]
+    pub(crate) export_frames: Option<PathBuf>,
+
+    #[arg(
+        long = "frame-interval",
+        value_name = "N",
+        default_value_t = 1,
+        requires = "export_frames",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "The number of steps between two frames of --export-frames"
+    )]
+    pub(crate) frame_interval: u64,
+
+    #[arg(
+        long = "trace",
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "-",
+        requires = "interpret",
+        conflicts_with_all = ["profile", "callgrind", "folded_stacks", "coverage", "heatmap"],
+        help = "Log every executed statement with the address and value of the current cell to a file, or to stderr if no file is given"
+    )]
+    pub(crate) trace: Option<PathBuf>,
+
+    #[arg(
+        long = "trace-range",
+        value_name = "START..END",
+        requires = "trace",
+        value_parser = parse_source_range,
+        help = "Only trace the statements between these byte offsets of the source file (the end is exclusive)"
+    )]
+    pub(crate) trace_range: Option<Span>,
+
+    #[arg(
+        long = "trace-loop",
+        value_name = "LINE:COLUMN",
+        requires = "trace",
+        conflicts_with = "trace_range",
+        value_parser = parse_source_location,
+        help = "Only trace the statements of the loop whose '[' is at this location"
+    )]
+    pub(crate) trace_loop: Option<SourceLocation>,
+
+    #[arg(
+        long = "assert-tape",
+        value_name = "ADDRESS:VALUE",
+        requires = "interpret",
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        value_parser = parse_cell_assertion,
+        help = "Exit with an error unless the cells at the given addresses hold the given values when the program ends, e.g. \"0:72,1:101\""
+    )]
+    assert_tape: Vec<(i64, u64)>,
+
+    #[arg(
+        long = "dump-tape",
+        value_name = "FILE",
+        requires = "interpret",
+        help = "Write the tape to a file once the program has ended, after a header with the address of its first cell"
+    )]
+    dump_tape: Option<PathBuf>,
+
+    #[arg(
+        long = "tape",
+        value_enum,
+        requires = "interpret",
+        default_value_t = TapeKind::Contiguous,
+        help = "How the interpreter stores the tape; sparse avoids allocating the cells between widely scattered addresses"
+    )]
+    tape: TapeKind,
+
+    #[arg(
+        long = "flush-mode",
+        value_enum,
+        requires = "interpret",
+        default_value_t = FlushMode::Line,
+        help = "When the interpreter flushes the output; it is always flushed before reading input and when the program ends"
+    )]
+    flush_mode: FlushMode,
+
+    #[arg(
+        long = "loop-limit-action",
+        value_enum,
+        requires = "max_loop_iterations",
+        default_value_t = LoopLimitAction::Abort,
+        help = "What to do when a loop exceeds the iteration limit"
+    )]
+    loop_limit_action: LoopLimitAction,
+}
+
+#[derive(clap::Subcommand)]
+pub(crate) enum Subcommand {
+    #[command(
+        about = "Run a program using the interpreter or a cached compiled executable, whichever is expected to be faster"
+    )]
+    Run(RunArguments),
+    #[command(about = "Analyze a program for likely mistakes without running it")]
+    Check(ProgramArguments),
+    #[command(about = "Print statistics about a program")]
+    Stats(ProgramArguments),
+    #[command(
+        about = "Print a JSON summary of every loop (position, size, pointer movement and kind)"
+    )]
+    Loops(ProgramArguments),
+    #[command(
+        about = "Compare the number of interpreter dispatches with and without superinstructions"
+    )]
+    Bench(BenchArguments),
+    #[command(
+        about = "Explore all executions up to a number of steps and report reachable traps (experimental)"
+    )]
+    Verify(VerifyArguments),
+    #[command(
+        about = "Search frequently executed parts of a program for shorter equivalent instruction sequences (experimental)"
+    )]
+    Superopt(SuperoptArguments),
+    #[command(about = "Print information about the environment for use in bug reports")]
+    Info(InfoArguments),
+    #[command(about = "Generate a random syntactically valid program")]
+    GenRandom(GenRandomArguments),
+    #[command(about = "Manage a local corpus of programs listed in a CHECKSUMS manifest")]
+    Corpus(CorpusArguments),
+    #[command(about = "Estimate whether a file actually contains Brainfuck source code")]
+    Detect(DetectArguments),
+    #[command(about = "Print the byte offset of the bracket matching the one at the given offset")]
+    Match(MatchArguments),
+    #[command(
+        about = "Create an executable that runs a program using the built-in interpreter (no LLVM or linker required)"
+    )]
+    Bundle(BundleArguments),
+    #[command(
+        about = "Remove the files generated from a program and optionally the cache of compiled executables"
+    )]
+    Clean(CleanArguments),
+    #[command(about = "Print a program in a consistent layout without comments")]
+    Fmt(FmtArguments),
+    #[command(
+        about = "Print the part of a program that influences the final value of a cell or an output byte"
+    )]
+    Slice(SliceArguments),
+    #[command(
+        about = "Step through a program interactively, with breakpoints at byte offsets of the source"
+    )]
+    Debug(DebugArguments),
+    #[command(
+        about = "Run the programs of a corpus with a reference interpreter and compare their output with brainrust's"
+    )]
+    Conformance(ConformanceArguments),
+    #[command(
+        about = "Serve the Debug Adapter Protocol on stdin and stdout, e.g. to debug programs in VS Code"
+    )]
+    Dap,
+}
+
+#[derive(clap::Args)]
+pub(crate) struct ProgramArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl ProgramArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct RunArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "engine",
+        value_enum,
+        default_value_t = Engine::Auto,
+        help = "How to run the program"
+    )]
+    pub(crate) engine: Engine,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        action,
+        help = "Print which engine was selected and why"
+    )]
+    pub(crate) verbose: bool,
+
+    #[arg(
+        long = "prompt",
+        value_name = "TEXT",
+        help = "Print this text whenever the program starts reading a line of input from a terminal"
+    )]
+    pub(crate) prompt: Option<String>,
+
+    #[arg(
+        long = "no-echo",
+        action,
+        help = "Keep the terminal from echoing the typed input while the program runs, e.g. for programs that echo their input themselves"
+    )]
+    pub(crate) no_echo: bool,
+
+    #[arg(
+        long = "eof-behavior",
+        value_enum,
+        default_value_t = EofBehavior::Zero,
+        help = "What ',' stores in the current cell once the input has ended"
+    )]
+    pub(crate) eof_behavior: EofBehavior,
+
+    #[arg(
+        long = "max-output",
+        value_name = "BYTES",
+        help = "Terminate the program with an error once it tries to write more than this many bytes"
+    )]
+    pub(crate) max_output: Option<u64>,
+
+    #[arg(
+        long = "max-memory",
+        value_name = "CELLS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Terminate the program with an error once its tape would grow beyond this many cells"
+    )]
+    pub(crate) max_memory: Option<u64>,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl RunArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+
+    pub(crate) fn runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            eof_behavior: self.eof_behavior,
+            max_memory: self.max_memory,
+            ..RuntimeConfig::default()
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct BenchArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "input",
+        value_name = "FILE",
+        help = "Read the program's input from a file (required if the program reads input)"
+    )]
+    pub(crate) input: Option<PathBuf>,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl BenchArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct VerifyArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "steps",
+        value_name = "N",
+        help = "Maximum number of steps of every explored execution"
+    )]
+    pub(crate) steps: u64,
+
+    #[arg(
+        long = "tape-size",
+        value_name = "CELLS",
+        help = "Treat moving the pointer beyond this many cells as a trap"
+    )]
+    pub(crate) tape_size: Option<u64>,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl VerifyArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct SuperoptArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "window",
+        value_name = "N",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u8).range(2..=8),
+        help = "Number of consecutive instructions to replace at once"
+    )]
+    pub(crate) window: u8,
+
+    #[arg(
+        long = "steps",
+        value_name = "N",
+        default_value_t = 1_000_000,
+        help = "Maximum number of steps of the traced execution (input reads as zero)"
+    )]
+    pub(crate) steps: u64,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl SuperoptArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct InfoArguments {
+    #[arg(help = "Include the hash of this source file in the report")]
+    pub(crate) input_filename: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub(crate) struct GenRandomArguments {
+    #[arg(
+        long = "size",
+        value_name = "N",
+        default_value_t = 100,
+        help = "Number of instructions of the generated program"
+    )]
+    size: usize,
+
+    #[arg(
+        long = "seed",
+        value_name = "S",
+        default_value_t = 0,
+        help = "Seed of the random number generator"
+    )]
+    pub(crate) seed: u64,
+
+    #[arg(
+        long = "max-depth",
+        value_name = "D",
+        default_value_t = 4,
+        help = "Maximum loop nesting depth"
+    )]
+    max_depth: usize,
+
+    #[arg(long = "move-weight", value_name = "WEIGHT", default_value_t = InstructionWeights::default().move_pointer, help = "Relative frequency of '>' and '<'")]
+    move_weight: u32,
+
+    #[arg(long = "change-weight", value_name = "WEIGHT", default_value_t = InstructionWeights::default().change_value, help = "Relative frequency of '+' and '-'")]
+    change_weight: u32,
+
+    #[arg(long = "output-weight", value_name = "WEIGHT", default_value_t = InstructionWeights::default().put_char, help = "Relative frequency of '.'")]
+    output_weight: u32,
+
+    #[arg(long = "input-weight", value_name = "WEIGHT", default_value_t = InstructionWeights::default().get_char, help = "Relative frequency of ','")]
+    input_weight: u32,
+
+    #[arg(long = "loop-weight", value_name = "WEIGHT", default_value_t = InstructionWeights::default().loop_, help = "Relative frequency of loops")]
+    loop_weight: u32,
+}
+
+impl GenRandomArguments {
+    pub(crate) fn options(&self) -> GeneratorOptions {
+        GeneratorOptions {
+            size: self.size,
+            max_depth: self.max_depth,
+            weights: InstructionWeights {
+                move_pointer: self.move_weight,
+                change_value: self.change_weight,
+                put_char: self.output_weight,
+                get_char: self.input_weight,
+                loop_: self.loop_weight,
+            },
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct DetectArguments {
+    pub(crate) input_filename: PathBuf,
+}
+
+#[derive(clap::Args)]
+pub(crate) struct MatchArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(help = "Zero-based byte offset of a bracket")]
+    pub(crate) offset: usize,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl MatchArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct DebugArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "input",
+        value_name = "FILE",
+        help = "Read the input of the program from FILE instead of treating it as empty, or as stdin with --tty-control (stdin is used for the commands of the debugger otherwise)"
+    )]
+    pub(crate) input: Option<PathBuf>,
+
+    #[arg(
+        long = "tty-control",
+        action,
+        help = "Read the commands of the debugger from the terminal instead of stdin, which is left to the program, e.g. to debug a program whose input is piped"
+    )]
+    pub(crate) tty_control: bool,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl DebugArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct BundleArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(short, long, help = "Name of the executable to be generated")]
+    pub(crate) output_filename: PathBuf,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl BundleArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct CleanArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "cache",
+        action,
+        help = "Also remove the executables cached by the run subcommand"
+    )]
+    pub(crate) cache: bool,
+
+    #[arg(
+        long = "dry-run",
+        action,
+        help = "Only print the files that would be removed"
+    )]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(clap::Args)]
+pub(crate) struct FmtArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "style",
+        value_enum,
+        default_value_t = FormatStyle::Canonical,
+        help = "Layout of the formatted program"
+    )]
+    pub(crate) style: FormatStyle,
+
+    #[arg(
+        long = "in-place",
+        action,
+        help = "Overwrite the input file instead of printing the formatted program"
+    )]
+    pub(crate) in_place: bool,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl FmtArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+}
+
+#[derive(clap::Args)]
+#[clap(group(
+            clap::ArgGroup::new("criterion")
+                .required(true)
+                .args(& ["cell", "output_index"])
+        ))]
+pub(crate) struct SliceArguments {
+    pub(crate) input_filename: PathBuf,
+
+    #[arg(
+        long = "cell",
+        value_name = "N",
+        allow_hyphen_values = true,
+        help = "Address of the cell (relative to the initial cell) whose final value is preserved"
+    )]
+    pub(crate) cell: Option<i64>,
+
+    #[arg(
+        long = "output-index",
+        value_name = "K",
+        help = "Zero-based index of the output byte that is preserved"
+    )]
+    pub(crate) output_index: Option<u64>,
+
+    #[arg(
+        long = "input",
+        value_name = "FILE",
+        help = "Input of the traced execution that finds the command writing the output byte (reads as zero after its end)"
+    )]
+    pub(crate) input: Option<PathBuf>,
+
+    #[arg(
+        long = "steps",
+        value_name = "N",
+        default_value_t = 1_000_000,
+        help = "Maximum number of steps of the traced execution"
+    )]
+    pub(crate) steps: u64,
+
+    #[command(flatten)]
+    dialect_arguments: DialectArguments,
+}
+
+impl SliceArguments {
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+
+    pub(crate) fn criterion(&self) -> Criterion {
+        match (self.cell, self.output_index) {
+            (Some(address), _) => Criterion::Cell(address),
+            (None, Some(index)) => Criterion::OutputByte(index),
+            (None, None) => unreachable!("clap requires one of them"),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub(crate) struct CorpusArguments {
+    #[command(subcommand)]
+    pub(crate) command: CorpusCommand,
+}
+
+#[derive(clap::Subcommand)]
+pub(crate) enum CorpusCommand {
+    #[command(about = "Download the programs that are missing or have a wrong checksum")]
+    Fetch(CorpusDirectoryArguments),
+    #[command(about = "Verify checksums and run every program through check and stats")]
+    Verify(CorpusDirectoryArguments),
+}
+
+#[derive(clap::Args)]
+pub(crate) struct CorpusDirectoryArguments {
+    #[arg(help = "Directory containing the CHECKSUMS manifest")]
+    pub(crate) directory: PathBuf,
+}
+
+#[derive(clap::Args)]
+pub(crate) struct ConformanceArguments {
+    #[arg(help = "Directory containing the CHECKSUMS manifest")]
+    pub(crate) directory: PathBuf,
+
+    #[arg(
+        long = "reference",
+        value_name = "COMMAND",
+        help = "Command line of the reference interpreter, split at whitespace; the path of each program is appended"
+    )]
+    pub(crate) reference: String,
+
+    #[arg(
+        long = "engine",
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [ConformanceEngine::Interpreter, ConformanceEngine::Unoptimized],
+        help = "The engines whose output is compared with the reference"
+    )]
+    pub(crate) engines: Vec<ConformanceEngine>,
+
+    #[arg(
+        long = "input",
+        value_name = "FILE",
+        help = "Pass the contents of FILE as input to every program instead of an empty input"
+    )]
+    pub(crate) input: Option<PathBuf>,
+
+    #[arg(
+        long = "eof-behavior",
+        value_enum,
+        default_value_t = EofBehavior::Zero,
+        help = "What ',' stores in the current cell once the input has ended, which should match the reference"
+    )]
+    pub(crate) eof_behavior: EofBehavior,
+
+    #[arg(
+        long = "cell-size",
+        value_name = "BITS",
+        value_parser = parse_cell_size,
+        default_value = "8",
+        help = "Number of bits of a cell (8, 16, 32 or 64), which should match the reference"
+    )]
+    pub(crate) cell_size: CellSize,
+
+    #[arg(
+        long = "max-output",
+        value_name = "BYTES",
+        default_value_t = 10000,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Only compare the first BYTES bytes of output, e.g. of programs that never end"
+    )]
+    pub(crate) max_output: u64,
+
+    #[arg(
+        long = "max-steps",
+        value_name = "N",
+        default_value_t = 100_000_000,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Stop interpreting a program after N steps and only compare the output up to that point"
+    )]
+    pub(crate) max_steps: u64,
+
+    #[arg(
+        long = "timeout",
+        value_name = "SECONDS",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Stop the reference and compiled executables after this many seconds and only compare the output up to that point"
+    )]
+    pub(crate) timeout: u64,
+}
+
+impl ConformanceArguments {
+    /// Shared by all engines, so that they only differ in how they run a program.
+    pub(crate) fn runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            eof_behavior: self.eof_behavior,
+            cell_size: self.cell_size,
+            ..RuntimeConfig::default()
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct DialectArguments {
+    #[arg(
+        long = "loop-control",
+        action,
+        help = "Enable the '@' (break) and '&' (continue) loop control extension"
+    )]
+    loop_control: bool,
+
+    #[arg(
+        long = "register",
+        value_name = "STORE_LOAD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "$!",
+        value_parser = parse_register_commands,
+        help = "Enable the single-register extension, optionally using other characters than '$' (store) and '!' (load)"
+    )]
+    register: Option<RegisterCommands>,
+
+    #[arg(
+        long = "debug-extensions",
+        action,
+        help = "Enable '#', which prints the cells around the pointer to stderr when interpreting and the whole tape when compiled"
+    )]
+    debug_extensions: bool,
+}
+
+impl DialectArguments {
+    fn dialect(&self) -> Dialect {
+        Dialect {
+            loop_control: self.loop_control,
+            register: self.register,
+            debug_dump: self.debug_extensions,
+        }
+    }
+}
+
+fn parse_register_commands(value: &str) -> Result<RegisterCommands, String> {
+    let &[store, load] = value.as_bytes() else {
+        return Err("expected two characters (store and load)".to_string());
+    };
+    if let Some(&char) = [store, load].iter().find(|char| b"+-<>[].,".contains(char)) {
+        return Err(format!("'{}' is already a Brainfuck command", char as char));
+    }
+    if store == load {
+        return Err("store and load must be different characters".to_string());
+    }
+    Ok(RegisterCommands { store, load })
+}
+
+/// Rejects characters for `--register` that another enabled extension already uses, which
+/// `parse_register_commands` cannot know about.
+pub(crate) fn check_register_commands(matches: &ArgMatches) -> Result<(), String> {
+    let matches = matches.subcommand().map_or(matches, |(_, matches)| matches);
+    let Ok(Some(register)) = matches.try_get_one::<RegisterCommands>("register") else {
+        return Ok(());
+    };
+    let enabled = |id| matches!(matches.try_get_one::<bool>(id), Ok(Some(true)));
+    let extensions = [
+        ("loop_control", "--loop-control", b"@&".as_slice()),
+        ("debug_extensions", "--debug-extensions", b"#".as_slice()),
+    ];
+    for (id, flag, commands) in extensions {
+        if let Some(&char) = [register.store, register.load]
+            .iter()
+            .find(|char| enabled(id) && commands.contains(char))
+        {
+            return Err(format!(
+                "'{}' is already a command of {flag} and cannot be used for --register",
+                char as char
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_source_range(value: &str) -> Result<Span, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or("expected START..END".to_string())?;
+    let start = start
+        .parse()
+        .map_err(|_| format!("'{start}' is not a valid offset"))?;
+    let end = end
+        .parse()
+        .map_err(|_| format!("'{end}' is not a valid offset"))?;
+    if end < start {
+        return Err("the end must not be before the start".to_string());
+    }
+    Ok(Span::new(start, end))
+}
+
+fn parse_source_location(value: &str) -> Result<SourceLocation, String> {
+    let (line, column) = value
+        .split_once(':')
+        .ok_or("expected LINE:COLUMN".to_string())?;
+    let parse = |number: &str| match number.parse() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!(
+            "'{number}' is not a valid line or column (starting at 1)"
+        )),
+    };
+    Ok(SourceLocation {
+        line: parse(line)?,
+        column: parse(column)?,
+    })
+}
+
+fn parse_cell_size(value: &str) -> Result<CellSize, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(CellSize::from_bits)
+        .ok_or_else(|| format!("'{value}' is not a valid cell size (8, 16, 32 or 64)"))
+}
+
+fn parse_cell_assertion(value: &str) -> Result<(i64, u64), String> {
+    let (address, cell_value) = value
+        .split_once(':')
+        .ok_or("expected ADDRESS:VALUE".to_string())?;
+    let address = address
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{address}' is not a valid address"))?;
+    let cell_value = cell_value
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{cell_value}' is not a valid cell value"))?;
+    Ok((address, cell_value))
 }
 
 impl CommandLineArguments {
+    pub(crate) fn input_filename(&self) -> &Path {
+        self.input_filename
+            .as_deref()
+            .expect("clap requires an input file unless a subcommand is given")
+    }
+
     pub(crate) fn emit_target(&self) -> EmitTarget {
         match (
             self.emit_assembly,
             self.only_compile_and_assemble,
             self.emit_llvm,
+            self.jit,
         ) {
-            (true, false, false) => EmitTarget::Assembly,
-            (false, true, false) => EmitTarget::ObjectFile,
-            (false, false, true) => EmitTarget::LlvmIr,
-            (false, false, false) => EmitTarget::Executable,
+            (true, false, false, false) => EmitTarget::Assembly,
+            (false, true, false, false) => EmitTarget::ObjectFile,
+            (false, false, true, false) => EmitTarget::LlvmIr,
+            (false, false, false, true) => EmitTarget::Jit,
+            (false, false, false, false) => EmitTarget::Executable,
             _ => unreachable!(),
         }
     }
 
-    pub(crate) fn optimization_level(&self) -> inkwell::OptimizationLevel {
-        match self.optimization_level {
-            0 => inkwell::OptimizationLevel::None,
-            1 => inkwell::OptimizationLevel::Less,
-            2 => inkwell::OptimizationLevel::Default,
-            3 => inkwell::OptimizationLevel::Aggressive,
-            _ => unreachable!("value was checked by clap"),
+    pub(crate) fn dialect(&self) -> Dialect {
+        self.dialect_arguments.dialect()
+    }
+
+    pub(crate) fn pipeline(&self) -> Pipeline {
+        let pipeline = match &self.bf_passes {
+            // the passes combine and remove additions, which would hide or move overflows
+            _ if self.overflow == Overflow::Trap => Pipeline::new(Vec::new()),
+            Some(names) => Pipeline::new(
+                names
+                    .iter()
+                    .map(|name| Pass::from_name(name).expect("checked by clap"))
+                    .collect(),
+            ),
+            None => Pipeline::for_level(self.bf_optimization_level),
+        };
+        // removing dead code assumes that every cell is zero when the program starts
+        let passes = pipeline
+            .passes()
+            .iter()
+            .copied()
+            .filter(|&pass| self.initial_tape.is_none() || pass != Pass::DeadCode)
+            .collect();
+        Pipeline::new(passes).with_cell_size(self.cell_size)
+    }
+
+    pub(crate) fn interpreter_options(&self) -> InterpreterOptions {
+        InterpreterOptions {
+            max_loop_iterations: self.max_loop_iterations,
+            loop_limit_action: self.loop_limit_action,
+            // a recording is replayed like any other input file
+            input: self.input.clone().or_else(|| self.replay_input.clone()),
+            prompt: self.prompt.clone(),
+            no_echo: self.no_echo,
+            max_output: self.max_output,
+            idle_window: self.idle_window,
+            max_steps: self.max_steps,
+            assert_tape: self.assert_tape.clone(),
+            dump_tape: self.dump_tape.clone(),
+            fail_on_eof: self.fail_on_eof,
+            require_input_consumed: self.require_input_consumed || self.replay_input.is_some(),
+            record_input: self.record_input.clone(),
+            checkpoint_out: self
+                .checkpoint_out
+                .clone()
+                .map(|filename| (filename, self.checkpoint_interval)),
+            resume: self.resume.clone(),
+            tape: self.tape,
+            flush_mode: self.flush_mode,
+            runtime: self.runtime_config(),
+            // the file is read by the driver, which passes it to the compiler as well
+            initial_tape: Vec::new(),
         }
     }
 
-    #[cfg(target_os = "windows")]
-    pub(crate) fn output_filename(&self) -> PathBuf {
-        self.output_filename.as_ref().cloned().unwrap_or_else(|| {
-            PathBuf::from(match self.emit_target() {
-                EmitTarget::Assembly => "out.asm",
-                EmitTarget::ObjectFile => "out.obj",
-                EmitTarget::Executable => "a.exe",
-                EmitTarget::LlvmIr => "out.ll",
-            })
-        })
-    }
-
-    #[cfg(target_os = "linux")]
-    pub(crate) fn output_filename(&self) -> PathBuf {
-        self.output_filename.as_ref().cloned().unwrap_or_else(|| {
-            PathBuf::from(match self.emit_target() {
-                EmitTarget::Assembly => "out.asm",
-                EmitTarget::ObjectFile => "out.o",
-                EmitTarget::Executable => "a.out",
-                EmitTarget::LlvmIr => "out.ll",
-            })
-        })
+    pub(crate) fn runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            eof_behavior: self.eof_behavior,
+            cell_size: self.cell_size,
+            overflow: self.overflow,
+            max_memory: self.max_memory,
+            tape_size: self.tape_size,
+            out_of_bounds: self.out_of_bounds,
+        }
+    }
+
+    // search paths first, so that they apply to all libraries
+    pub(crate) fn link_arguments(&self) -> Vec<OsString> {
+        let library_directories = self.library_directories.iter().map(|directory| {
+            let mut argument = OsString::from("-L");
+            argument.push(directory);
+            argument
+        });
+        let libraries = self
+            .libraries
+            .iter()
+            .map(|library| OsString::from(format!("-l{library}")));
+        library_directories
+            .chain(libraries)
+            .chain(self.link_arguments.iter().map(OsString::from))
+            .collect()
+    }
+
+    pub(crate) fn backend(&self) -> &str {
+        match (&self.backend, self.emit_format) {
+            (Some(backend), _) => backend,
+            (None, Some(format)) => format.backend_name(),
+            (None, None) => self.emit_target().backend_name(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn check(arguments: &[&str]) -> Result<(), String> {
+        let matches = CommandLineArguments::command()
+            .try_get_matches_from(arguments)
+            .map_err(|error| error.to_string())?;
+        check_register_commands(&matches)
+    }
+
+    #[test]
+    fn register_commands_must_not_be_brainfuck_commands() {
+        assert!(check(&["brainrust", "--register=+!", "in.b"]).is_err());
+    }
+
+    #[test]
+    fn register_commands_must_not_clash_with_enabled_extensions() {
+        assert!(check(&["brainrust", "--loop-control", "--register=@!", "in.b"]).is_err());
+        assert!(check(&["brainrust", "--loop-control", "--register=$&", "in.b"]).is_err());
+        assert!(check(&["brainrust", "--debug-extensions", "--register=#!", "in.b"]).is_err());
+        assert!(check(&[
+            "brainrust",
+            "check",
+            "--loop-control",
+            "--register=@!",
+            "in.b"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn register_commands_may_use_characters_of_disabled_extensions() {
+        assert!(check(&["brainrust", "--register=@#", "in.b"]).is_ok());
+        assert!(check(&["brainrust", "--loop-control", "--register", "in.b"]).is_ok());
+        assert!(check(&["brainrust", "--debug-extensions", "--register=@&", "in.b"]).is_ok());
     }
 }
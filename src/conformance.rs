@@ -0,0 +1,593 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use crate::command_line_arguments::{ConformanceArguments, ConformanceEngine, EmitTarget};
+use crate::corpus::{entry_status, read_manifest, EntryStatus};
+use crate::environment::EnvironmentReport;
+use crate::interpreter::{interpret_in_memory, InterpreterError, InterpreterOptions};
+use crate::optimizer::Pipeline;
+use crate::parser::{Dialect, Parser};
+use crate::program::Program;
+use crate::registry::{Backend, BackendOptions, Platform, Registry};
+use crate::semantics::{Overflow, RuntimeConfig};
+
+// how often a running external program is checked for having ended
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+const COMPILED_OPTIMIZATION_LEVEL: u8 = 2;
+
+/// The output of a program and whether it ran to completion. The output of a program that was
+/// stopped by a limit is only a prefix of its complete output.
+struct Run {
+    output: Vec<u8>,
+    complete: bool,
+    // e.g. an exit status indicating failure, which is reported but not a difference by itself
+    note: Option<String>,
+}
+
+#[derive(Default)]
+struct Summary {
+    programs: usize,
+    ok: usize,
+    mismatches: usize,
+    skipped: usize,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "programs: {}", self.programs)?;
+        writeln!(f, "ok: {}", self.ok)?;
+        writeln!(f, "mismatches: {}", self.mismatches)?;
+        write!(f, "skipped: {}", self.skipped)
+    }
+}
+
+/// Runs every program of a corpus with the reference interpreter and each of the engines and
+/// reports where their outputs differ.
+pub(crate) fn conformance(registry: &Registry, arguments: &ConformanceArguments) -> Result<()> {
+    let mut reference = arguments.reference.split_whitespace();
+    let Some(reference_program) = reference.next() else {
+        bail!("the reference command is empty");
+    };
+    let reference_arguments: Vec<_> = reference.collect();
+    let compiler = registry.backend(EmitTarget::Executable.backend_name());
+    if arguments.engines.contains(&ConformanceEngine::Compiled) && compiler.is_none() {
+        bail!(
+            "the compiled engine is not available (brainrust was built without the 'llvm' feature)"
+        );
+    }
+    let input = match &arguments.input {
+        Some(filename) => {
+            fs::read(filename).with_context(|| format!("failed to read {}", filename.display()))?
+        }
+        None => Vec::new(),
+    };
+    let timeout = Duration::from_secs(arguments.timeout);
+    let runtime = arguments.runtime_config();
+    let build_directory =
+        std::env::temp_dir().join(format!("brainrust-conformance-{}", std::process::id()));
+
+    let mut summary = Summary::default();
+    for entry in read_manifest(&arguments.directory)? {
+        summary.programs += 1;
+        let path = arguments.directory.join(&entry.path);
+        match entry_status(&arguments.directory, &entry)? {
+            EntryStatus::Ok => {}
+            EntryStatus::Missing => {
+                println!("{}: skipped, missing", path.display());
+                summary.skipped += 1;
+                continue;
+            }
+            EntryStatus::ChecksumMismatch { .. } => {
+                println!("{}: skipped, checksum mismatch", path.display());
+                summary.skipped += 1;
+                continue;
+            }
+        }
+        let source = fs::read(&path)?;
+        let program = match Parser::new(&source, Dialect::default()).parse() {
+            Ok(program) => program,
+            Err(error) => {
+                println!("{}: skipped, parse error: {error}", path.display());
+                summary.skipped += 1;
+                continue;
+            }
+        };
+
+        let expected = run_external(
+            Command::new(reference_program)
+                .args(&reference_arguments)
+                .arg(&path),
+            &input,
+            arguments.max_output,
+            timeout,
+        )
+        .with_context(|| {
+            format!(
+                "while running the reference interpreter on {}",
+                path.display()
+            )
+        })?;
+        if let Some(note) = &expected.note {
+            println!("{}: reference: {note}", path.display());
+        }
+        let mut differences = 0;
+        for &engine in &arguments.engines {
+            let actual = match engine {
+                ConformanceEngine::Interpreter => {
+                    interpret(&optimize(&program, &runtime), &input, arguments)
+                }
+                ConformanceEngine::Unoptimized => interpret(&program, &input, arguments),
+                ConformanceEngine::Compiled => {
+                    let executable = build_directory.join(format!("program-{}", summary.programs));
+                    compile(
+                        compiler.expect("checked above"),
+                        &path,
+                        &source,
+                        &optimize(&program, &runtime),
+                        &executable,
+                        arguments,
+                    )?;
+                    let run = run_external(
+                        &mut Command::new(&executable),
+                        &input,
+                        arguments.max_output,
+                        timeout,
+                    )
+                    .with_context(|| format!("while running {}", executable.display()));
+                    let _ = fs::remove_file(&executable);
+                    run?
+                }
+            };
+            let engine = engine_name(engine);
+            if let Some(note) = &actual.note {
+                println!("{}: {engine}: {note}", path.display());
+            }
+            if let Some(offset) = first_difference(&expected, &actual) {
+                println!(
+                    "{}: {engine}: output differs from the reference at byte {offset} (expected {}, found {})",
+                    path.display(),
+                    describe_byte(expected.output.get(offset)),
+                    describe_byte(actual.output.get(offset))
+                );
+                differences += 1;
+            }
+        }
+        if differences > 0 {
+            summary.mismatches += 1;
+        } else {
+            println!(
+                "{}: ok ({} bytes of output)",
+                path.display(),
+                expected.output.len()
+            );
+            summary.ok += 1;
+        }
+    }
+    let _ = fs::remove_dir_all(&build_directory);
+    println!("\n{summary}");
+    if summary.mismatches > 0 {
+        bail!(
+            "{} program(s) do not conform to the reference",
+            summary.mismatches
+        );
+    }
+    Ok(())
+}
+
+fn engine_name(engine: ConformanceEngine) -> &'static str {
+    match engine {
+        ConformanceEngine::Interpreter => "interpreter",
+        ConformanceEngine::Unoptimized => "unoptimized interpreter",
+        ConformanceEngine::Compiled => "compiled",
+    }
+}
+
+// optimizes like the driver does for the same runtime semantics
+fn optimize(program: &Program, runtime: &RuntimeConfig) -> Program {
+    let pipeline = match runtime.overflow {
+        // the passes combine and remove additions, which would hide or move overflows
+        Overflow::Trap => Pipeline::new(Vec::new()),
+        Overflow::Wrap => Pipeline::default(),
+    };
+    pipeline.with_cell_size(runtime.cell_size).run(program)
+}
+
+fn interpret(program: &Program, input: &[u8], arguments: &ConformanceArguments) -> Run {
+    let (output, result) = interpret_in_memory(
+        program,
+        input,
+        &InterpreterOptions {
+            max_output: Some(arguments.max_output),
+            max_steps: Some(arguments.max_steps),
+            runtime: arguments.runtime_config(),
+            ..Default::default()
+        },
+    );
+    match result {
+        Ok(()) => Run {
+            output,
+            complete: true,
+            note: None,
+        },
+        Err(
+            InterpreterError::OutputLimitExceeded { .. }
+            | InterpreterError::StepLimitExceeded { .. },
+        ) => Run {
+            output,
+            complete: false,
+            note: None,
+        },
+        Err(error) => Run {
+            output,
+            complete: true,
+            note: Some(error.to_string()),
+        },
+    }
+}
+
+fn compile(
+    compiler: &dyn Backend,
+    path: &Path,
+    source: &[u8],
+    program: &Program,
+    executable: &Path,
+    arguments: &ConformanceArguments,
+) -> Result<()> {
+    if let Some(directory) = executable.parent() {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("while creating {}", directory.display()))?;
+    }
+    compiler.emit(
+        program,
+        &BackendOptions {
+            input_filename: path.to_path_buf(),
+            output_filename: executable.to_path_buf(),
+            optimization_level: COMPILED_OPTIMIZATION_LEVEL,
+            verbose: false,
+            environment: EnvironmentReport::collect(Some(source)),
+            platform: Platform::Host,
+            prompt: None,
+            no_echo: false,
+            embed_selftest: false,
+            harden: false,
+            constant_output: None,
+            // the output is cut off while reading it, like that of the reference
+            max_output: None,
+            runtime: arguments.runtime_config(),
+            initial_tape: Vec::new(),
+            guard_pages: false,
+            no_libc: false,
+            bare_metal: false,
+            linker: None,
+            static_executable: false,
+            link_arguments: Vec::new(),
+            jit_profiling: false,
+        },
+    )
+}
+
+// Runs a program with the given input and reads at most `max_output` bytes of its output. The
+// program is killed once it has written more or once the timeout has passed.
+fn run_external(
+    command: &mut Command,
+    input: &[u8],
+    max_output: u64,
+    timeout: Duration,
+) -> Result<Run> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // writing and reading in separate threads keeps a program that ignores its input or does
+    // not read it all from blocking the other direction
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer = thread::spawn(move || {
+        // a program may end without reading all of its input
+        let _ = stdin.write_all(&input);
+    });
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut reader = Some(thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = stdout.take(max_output + 1).read_to_end(&mut output);
+        output
+    }));
+
+    let start = Instant::now();
+    let mut output = Vec::new();
+    let status = loop {
+        if reader.as_ref().is_some_and(|reader| reader.is_finished()) {
+            output = join(reader.take().expect("checked above"));
+            if output.len() as u64 > max_output {
+                break None;
+            }
+        }
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if start.elapsed() >= timeout {
+            break None;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    if status.is_none() {
+        let _ = child.kill();
+    }
+    child.wait()?;
+    if let Some(reader) = reader {
+        output = join(reader);
+    }
+    join(writer);
+
+    let complete = status.is_some() && output.len() as u64 <= max_output;
+    output.truncate(max_output as usize);
+    let note = match status {
+        Some(status) if !status.success() => Some(format!("exited with {status}")),
+        None if start.elapsed() >= timeout => {
+            Some(format!("stopped after {} seconds", timeout.as_secs()))
+        }
+        _ => None,
+    };
+    Ok(Run {
+        output,
+        complete,
+        note,
+    })
+}
+
+fn join<T>(thread: thread::JoinHandle<T>) -> T {
+    thread.join().expect("the thread does not panic")
+}
+
+// The offset of the first byte at which the outputs differ. An output that is shorter than the
+// other one only differs if its program ran to completion.
+fn first_difference(expected: &Run, actual: &Run) -> Option<usize> {
+    let common = expected.output.len().min(actual.output.len());
+    if let Some(offset) =
+        (0..common).find(|&offset| expected.output[offset] != actual.output[offset])
+    {
+        return Some(offset);
+    }
+    let shorter = if expected.output.len() < actual.output.len() {
+        expected
+    } else {
+        actual
+    };
+    (expected.output.len() != actual.output.len() && shorter.complete).then_some(common)
+}
+
+fn describe_byte(byte: Option<&u8>) -> String {
+    match byte {
+        Some(byte) => format!("'{}'", byte.escape_ascii()),
+        None => "the end of the output".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::c_backend::CBackend;
+    use crate::program::CellSize;
+    use crate::semantics::{EofBehavior, OutOfBounds};
+
+    struct Case {
+        name: &'static str,
+        source: Vec<u8>,
+        input: &'static [u8],
+        runtime: RuntimeConfig,
+        expected: &'static [u8],
+        fails: bool,
+    }
+
+    fn case(name: &'static str, source: impl Into<Vec<u8>>, expected: &'static [u8]) -> Case {
+        Case {
+            name,
+            source: source.into(),
+            input: b"",
+            runtime: RuntimeConfig::default(),
+            expected,
+            fails: false,
+        }
+    }
+
+    fn with_cell_size(cell_size: CellSize) -> RuntimeConfig {
+        RuntimeConfig {
+            cell_size,
+            ..Default::default()
+        }
+    }
+
+    fn with_fixed_tape(out_of_bounds: OutOfBounds) -> RuntimeConfig {
+        RuntimeConfig {
+            tape_size: Some(4),
+            out_of_bounds,
+            ..Default::default()
+        }
+    }
+
+    // 256 is 0 in 8-bit cells only
+    fn count_to_256() -> Vec<u8> {
+        format!("{}[[-]>+++<]>.", "+".repeat(256)).into_bytes()
+    }
+
+    fn cases() -> Vec<Case> {
+        vec![
+            case("decrementing 0 wraps around", "-.", b"\xff"),
+            Case {
+                runtime: with_cell_size(CellSize::Bits8),
+                ..case("8-bit cells", count_to_256(), b"\x00")
+            },
+            Case {
+                runtime: with_cell_size(CellSize::Bits16),
+                ..case("16-bit cells", count_to_256(), b"\x03")
+            },
+            Case {
+                runtime: with_cell_size(CellSize::Bits32),
+                ..case("32-bit cells", count_to_256(), b"\x03")
+            },
+            Case {
+                runtime: with_cell_size(CellSize::Bits64),
+                ..case("64-bit cells", count_to_256(), b"\x03")
+            },
+            Case {
+                input: b"ab",
+                ..case("input is read byte by byte", ",.,.", b"ab")
+            },
+            case("eof stores 0", "+,.", b"\x00"),
+            Case {
+                runtime: RuntimeConfig {
+                    eof_behavior: EofBehavior::MinusOne,
+                    ..Default::default()
+                },
+                ..case("eof stores -1", ",.", b"\xff")
+            },
+            Case {
+                runtime: RuntimeConfig {
+                    eof_behavior: EofBehavior::Unchanged,
+                    ..Default::default()
+                },
+                ..case("eof leaves the cell unchanged", "+,.", b"\x01")
+            },
+            case(
+                "the tape grows to the left beyond its initial extent",
+                format!("{}+.", "<".repeat(20000)),
+                b"\x01",
+            ),
+            case(
+                "the tape grows to the right beyond its initial extent",
+                format!("{}+.", ">".repeat(20000)),
+                b"\x01",
+            ),
+            Case {
+                runtime: with_fixed_tape(OutOfBounds::Wrap),
+                ..case(
+                    "addresses wrap around a fixed tape",
+                    "+>>>>.<<<<<+.",
+                    b"\x01\x01",
+                )
+            },
+            Case {
+                runtime: with_fixed_tape(OutOfBounds::Clamp),
+                ..case(
+                    "addresses are clamped to a fixed tape",
+                    "+<.>>>>>>+.",
+                    b"\x01\x01",
+                )
+            },
+            Case {
+                runtime: with_fixed_tape(OutOfBounds::Trap),
+                fails: true,
+                ..case("leaving a fixed tape traps", "+.<+.", b"\x01")
+            },
+            Case {
+                runtime: RuntimeConfig {
+                    overflow: Overflow::Trap,
+                    ..Default::default()
+                },
+                fails: true,
+                ..case("overflowing a cell traps", "+.--.", b"\x01")
+            },
+            Case {
+                runtime: RuntimeConfig {
+                    max_memory: Some(100),
+                    ..Default::default()
+                },
+                fails: true,
+                ..case("the tape cannot grow beyond the limit", "+.[>+]", b"\x01")
+            },
+        ]
+    }
+
+    // the output and whether the program failed
+    fn interpreted(program: &Program, case: &Case) -> (Vec<u8>, bool) {
+        let (output, result) = interpret_in_memory(
+            program,
+            case.input,
+            &InterpreterOptions {
+                runtime: case.runtime,
+                ..Default::default()
+            },
+        );
+        (output, result.is_err())
+    }
+
+    fn compiled(program: &Program, case: &Case, directory: &Path) -> (Vec<u8>, bool) {
+        let name = case.name.replace(' ', "-");
+        let source_filename = directory.join(format!("{name}.c"));
+        let executable = directory.join(name);
+        CBackend
+            .emit(
+                program,
+                &BackendOptions {
+                    runtime: case.runtime,
+                    ..BackendOptions::for_tests(Platform::Host, &source_filename)
+                },
+            )
+            .unwrap();
+        let status = Command::new("cc")
+            .arg("-o")
+            .arg(&executable)
+            .arg(&source_filename)
+            .status()
+            .unwrap();
+        assert!(status.success(), "{}: cc failed", case.name);
+        let run = run_external(
+            &mut Command::new(&executable),
+            case.input,
+            1 << 20,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(run.complete, "{}: {:?}", case.name, run.note);
+        (run.output, run.note.is_some())
+    }
+
+    #[test]
+    fn interpreter_conforms() {
+        for case in cases() {
+            let program = Parser::new(&case.source, Dialect::default())
+                .parse()
+                .unwrap();
+            let expected = (case.expected.to_vec(), case.fails);
+            assert_eq!(interpreted(&program, &case), expected, "{}", case.name);
+            // the optimizer does not change the behavior
+            let optimized = interpreted(&optimize(&program, &case.runtime), &case);
+            assert_eq!(optimized, expected, "{} (optimized)", case.name);
+        }
+    }
+
+    #[test]
+    fn c_backend_conforms() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            eprintln!("skipped, no C compiler (cc) found");
+            return;
+        }
+        let directory =
+            std::env::temp_dir().join(format!("brainrust-conformance-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for case in cases() {
+            let program = Parser::new(&case.source, Dialect::default())
+                .parse()
+                .unwrap();
+            let expected = (case.expected.to_vec(), case.fails);
+            assert_eq!(
+                compiled(&program, &case, &directory),
+                expected,
+                "{}",
+                case.name
+            );
+            let optimized = compiled(&optimize(&program, &case.runtime), &case, &directory);
+            assert_eq!(optimized, expected, "{} (optimized)", case.name);
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::check::diagnostics;
+use crate::diagnostics::{report, report_tool_output, Severity};
+use crate::hash::fnv1a;
+use crate::parser::{Dialect, Parser};
+use crate::stats::Statistics;
+
+const MANIFEST_FILENAME: &str = "CHECKSUMS";
+
+pub(crate) struct ManifestEntry {
+    checksum: u64,
+    pub(crate) path: PathBuf,
+    url: Option<String>,
+}
+
+pub(crate) fn read_manifest(directory: &Path) -> Result<Vec<ManifestEntry>> {
+    let manifest_path = directory.join(MANIFEST_FILENAME);
+    let manifest = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("failed to read {}", manifest_path.display()))?;
+    let mut entries = Vec::new();
+    for (number, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let (checksum, path, url) = match fields[..] {
+            [checksum, path] => (checksum, path, None),
+            [checksum, path, url] => (checksum, path, Some(url.to_string())),
+            _ => bail!(
+                "{}:{}: expected a checksum, a path and an optional URL",
+                manifest_path.display(),
+                number + 1
+            ),
+        };
+        let checksum = u64::from_str_radix(checksum, 16).with_context(|| {
+            format!(
+                "{}:{}: invalid checksum '{checksum}'",
+                manifest_path.display(),
+                number + 1
+            )
+        })?;
+        let path = PathBuf::from(path);
+        // e.g. fetching must not write outside of the corpus directory
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            bail!(
+                "{}:{}: the path '{}' is not relative to the corpus directory",
+                manifest_path.display(),
+                number + 1,
+                path.display()
+            );
+        }
+        entries.push(ManifestEntry {
+            checksum,
+            path,
+            url,
+        });
+    }
+    Ok(entries)
+}
+
+pub(crate) enum EntryStatus {
+    Ok,
+    Missing,
+    ChecksumMismatch { actual: u64 },
+}
+
+pub(crate) fn entry_status(directory: &Path, entry: &ManifestEntry) -> Result<EntryStatus> {
+    let path = directory.join(&entry.path);
+    if !path.exists() {
+        return Ok(EntryStatus::Missing);
+    }
+    let actual =
+        fnv1a(&fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?);
+    Ok(if actual == entry.checksum {
+        EntryStatus::Ok
+    } else {
+        EntryStatus::ChecksumMismatch { actual }
+    })
+}
+
+pub(crate) fn fetch(directory: &Path) -> Result<()> {
+    let mut failures = 0;
+    for entry in read_manifest(directory)? {
+        let problem = match entry_status(directory, &entry)? {
+            EntryStatus::Ok => continue,
+            EntryStatus::Missing => "missing",
+            EntryStatus::ChecksumMismatch { .. } => "checksum mismatch",
+        };
+        let path = directory.join(&entry.path);
+        let Some(url) = &entry.url else {
+            eprintln!("{}: {problem} and no URL to fetch it from", path.display());
+            failures += 1;
+            continue;
+        };
+        println!("fetching {url}");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let context = format!("while downloading {url} into {}", path.display());
+        let output = Command::new("curl")
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--location",
+                "--output",
+            ])
+            .arg(&path)
+            .arg(url)
+            .output()
+            .with_context(|| context.clone())?;
+        if !output.status.success() {
+            report_tool_output(Severity::Error, &context, &output.stderr);
+            failures += 1;
+            continue;
+        }
+        if let EntryStatus::ChecksumMismatch { actual } = entry_status(directory, &entry)? {
+            eprintln!(
+                "{}: checksum mismatch after download (expected {:016x}, found {actual:016x})",
+                path.display(),
+                entry.checksum
+            );
+            fs::remove_file(&path).with_context(|| format!("while removing {}", path.display()))?;
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        bail!("failed to fetch {failures} program(s)");
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Summary {
+    programs: usize,
+    ok: usize,
+    missing: usize,
+    checksum_mismatches: usize,
+    parse_errors: usize,
+    statements: usize,
+    loops: usize,
+    warnings: usize,
+}
+
+impl Summary {
+    fn failures(&self) -> usize {
+        self.missing + self.checksum_mismatches + self.parse_errors
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "programs: {}", self.programs)?;
+        writeln!(f, "ok: {}", self.ok)?;
+        writeln!(f, "missing: {}", self.missing)?;
+        writeln!(f, "checksum mismatches: {}", self.checksum_mismatches)?;
+        writeln!(f, "parse errors: {}", self.parse_errors)?;
+        writeln!(f, "statements: {}", self.statements)?;
+        writeln!(f, "loops: {}", self.loops)?;
+        write!(f, "warnings: {}", self.warnings)
+    }
+}
+
+pub(crate) fn verify(directory: &Path) -> Result<()> {
+    let mut summary = Summary::default();
+    for entry in read_manifest(directory)? {
+        summary.programs += 1;
+        let path = directory.join(&entry.path);
+        match entry_status(directory, &entry)? {
+            EntryStatus::Ok => {}
+            EntryStatus::Missing => {
+                println!("{}: missing", path.display());
+                summary.missing += 1;
+                continue;
+            }
+            EntryStatus::ChecksumMismatch { actual } => {
+                println!(
+                    "{}: checksum mismatch (expected {:016x}, found {actual:016x})",
+                    path.display(),
+                    entry.checksum
+                );
+                summary.checksum_mismatches += 1;
+                continue;
+            }
+        }
+        let source = fs::read(&path)?;
+        let program = match Parser::new(&source, Dialect::default()).parse() {
+            Ok(program) => program,
+            Err(error) => {
+                println!("{}: parse error: {error}", path.display());
+                summary.parse_errors += 1;
+                continue;
+            }
+        };
+        let diagnostics = diagnostics(&program);
+        for diagnostic in &diagnostics {
+            report(&path, &source, diagnostic);
+        }
+        let statistics = Statistics::of(&program);
+        println!(
+            "{}: ok ({} statements, {} loops, {} warnings)",
+            path.display(),
+            statistics.statements,
+            statistics.loops,
+            diagnostics.len()
+        );
+        summary.ok += 1;
+        summary.statements += statistics.statements;
+        summary.loops += statistics.loops;
+        summary.warnings += diagnostics.len();
+    }
+    println!("\n{summary}");
+    if summary.failures() > 0 {
+        bail!("{} program(s) failed verification", summary.failures());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh corpus directory with the given manifest
+    fn corpus(test: &str, manifest: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("brainrust-corpus-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(MANIFEST_FILENAME), manifest).unwrap();
+        directory
+    }
+
+    #[test]
+    fn paths_outside_of_the_directory_are_rejected() {
+        for path in ["../escape.b", "programs/../../escape.b", "/tmp/escape.b"] {
+            let directory = corpus("paths", &format!("0123 {path} https://example.com/a.b\n"));
+            let error = read_manifest(&directory).err().unwrap().to_string();
+            assert!(error.contains("is not relative"), "{path}: {error}");
+            fs::remove_dir_all(&directory).unwrap();
+        }
+        let directory = corpus("relative", "0123 ./programs/a.b\n");
+        assert_eq!(
+            read_manifest(&directory).unwrap()[0].path,
+            Path::new("./programs/a.b")
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn downloads_with_a_wrong_checksum_are_removed() {
+        if Command::new("curl").arg("--version").output().is_err() {
+            eprintln!("skipped, curl not found");
+            return;
+        }
+        let source = std::env::temp_dir().join(format!(
+            "brainrust-corpus-download-{}.b",
+            std::process::id()
+        ));
+        fs::write(&source, "+.").unwrap();
+        let directory = corpus(
+            "download",
+            &format!("0123 programs/a.b file://{}\n", source.display()),
+        );
+
+        assert!(fetch(&directory).is_err());
+        assert!(!directory.join("programs/a.b").exists());
+        fs::remove_dir_all(&directory).unwrap();
+        fs::remove_file(&source).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::diagnostics::LineIndex;
+use crate::interpreter::Profile;
+use crate::program::{Program, StatementKind};
+
+// longer runs of commands that were never executed are shortened in the report
+const MAX_SNIPPET_LENGTH: usize = 40;
+
+/// How often each command of the source was executed during a profiled run.
+pub(crate) struct Coverage {
+    // indexed by the offset of the command in the source
+    executions: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    /// The commands are taken from the parsed program, since the optimizer merges statements.
+    pub(crate) fn new(parsed_program: &Program, profile: &Profile) -> Self {
+        let mut executions: BTreeMap<_, _> = command_offsets(parsed_program)
+            .into_iter()
+            .map(|offset| (offset, 0))
+            .collect();
+        for &(span, count) in &profile.executed {
+            for (_, executions) in executions.range_mut(span.start..span.end) {
+                *executions += count;
+            }
+        }
+        Self { executions }
+    }
+
+    /// Prints how many commands were executed and lists the runs of commands that never were.
+    pub(crate) fn print_summary(&self, filename: &Path, source: &[u8]) {
+        let executed = self.executions.values().filter(|&&count| count > 0).count();
+        let total = self.executions.len();
+        eprintln!(
+            "coverage: {executed} of {total} command(s) executed ({:.1}%)",
+            if total == 0 {
+                100.0
+            } else {
+                executed as f64 * 100.0 / total as f64
+            }
+        );
+        let lines = LineIndex::new(source);
+        // the offsets of consecutive commands that were never executed
+        let mut runs: Vec<Vec<usize>> = Vec::new();
+        let mut previous_executed = true;
+        for (&offset, &count) in &self.executions {
+            if count == 0 {
+                if previous_executed {
+                    runs.push(Vec::new());
+                }
+                runs.last_mut().expect("pushed above").push(offset);
+            }
+            previous_executed = count > 0;
+        }
+        for run in runs {
+            let mut snippet: String = run
+                .iter()
+                .take(MAX_SNIPPET_LENGTH)
+                .map(|&offset| source[offset] as char)
+                .collect();
+            if run.len() > MAX_SNIPPET_LENGTH {
+                snippet.push_str("...");
+            }
+            eprintln!(
+                "{}:{}: never executed: `{snippet}`",
+                filename.display(),
+                lines.location(run[0])
+            );
+        }
+    }
+
+    /// Prefixes every line of the source with the highest number of executions of its commands,
+    /// `#####` if none of them was executed or `-` if it contains no commands.
+    pub(crate) fn annotate(&self, source: &[u8]) -> Vec<u8> {
+        let mut annotated = Vec::new();
+        let mut line_start = 0;
+        for line in source.split_inclusive(|&char| char == b'\n') {
+            let line_end = line_start + line.len();
+            let counts: Vec<u64> = self
+                .executions
+                .range(line_start..line_end)
+                .map(|(_, &count)| count)
+                .collect();
+            let mut prefix = String::new();
+            let _ = match counts.iter().max() {
+                None => write!(prefix, "{:>9}: ", "-"),
+                Some(0) => write!(prefix, "{:>9}: ", "#####"),
+                Some(count) => write!(prefix, "{count:>9}: "),
+            };
+            annotated.extend_from_slice(prefix.as_bytes());
+            annotated.extend_from_slice(line);
+            line_start = line_end;
+        }
+        if !annotated.ends_with(b"\n") && !annotated.is_empty() {
+            annotated.push(b'\n');
+        }
+        annotated
+    }
+
+    /// Renders the source as an HTML page on which every command is colored by how often it was
+    /// executed, on a logarithmic scale from blue for the least to red for the most executed
+    /// commands. Commands that were never executed are struck through, comments are gray.
+    pub(crate) fn heatmap(&self, filename: &Path, source: &[u8]) -> String {
+        let max = self.executions.values().copied().max().unwrap_or(0);
+        let mut html = String::new();
+        let title = escape_html(&filename.display().to_string());
+        let _ = writeln!(html, "<!DOCTYPE html>");
+        let _ = writeln!(
+            html,
+            "<html><head><meta charset=\"utf-8\"><title>{title}</title></head>"
+        );
+        let _ = writeln!(
+            html,
+            "<body style=\"background: #fff\"><h1 style=\"font: bold 1em sans-serif\">{title}: most executed command ran {max} time(s)</h1><pre>"
+        );
+        // consecutive characters with the same number of executions share an element
+        let mut start = 0;
+        while start < source.len() {
+            let count = self.executions.get(&start).copied();
+            let end = (start + 1..source.len())
+                .find(|offset| self.executions.get(offset).copied() != count)
+                .unwrap_or(source.len());
+            let text = escape_html(&String::from_utf8_lossy(&source[start..end]));
+            let _ = match count {
+                None => write!(html, "<span style=\"color: #999\">{text}</span>"),
+                Some(0) => write!(
+                    html,
+                    "<span style=\"color: #000; text-decoration: line-through\" title=\"never executed\">{text}</span>"
+                ),
+                Some(count) => {
+                    let heat = (count as f64).ln_1p() / (max as f64).ln_1p();
+                    write!(
+                        html,
+                        "<span style=\"background: hsl({:.0}, 100%, 75%)\" title=\"executed {count} time(s)\">{text}</span>",
+                        240.0 * (1.0 - heat)
+                    )
+                }
+            };
+            start = end;
+        }
+        let _ = writeln!(html, "</pre></body></html>");
+        html
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+/// The offsets of all commands of a parsed program in its source.
+pub(crate) fn command_offsets(program: &Program) -> BTreeSet<usize> {
+    let mut offsets = BTreeSet::new();
+    let mut blocks = vec![program.statements()];
+    while let Some(statements) = blocks.pop() {
+        for statement in statements {
+            let span = statement.span();
+            offsets.insert(span.start);
+            if let StatementKind::Loop(body) | StatementKind::If(body) = statement.kind() {
+                offsets.insert(span.end - 1);
+                blocks.push(body);
+            }
+        }
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{interpret_profiled, InterpreterOptions};
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_loops_are_covered() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let (profile, result) = interpret_profiled(&program, &InterpreterOptions::default());
+        result.unwrap();
+        let coverage = Coverage::new(&program, &profile);
+        assert_eq!(coverage.executions.len(), source.len());
+        assert!(coverage.executions.values().all(|&count| count > 0));
+        coverage.print_summary(Path::new("prog.b"), source.as_bytes());
+    }
+}
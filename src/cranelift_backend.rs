@@ -0,0 +1,794 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context as _};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block, Endianness, FuncRef, GlobalValue, InstBuilder, MemFlags, TrapCode,
+    Type, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::{default_libcall_names, DataDescription, DataId, FuncId, Linkage, Module};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+
+use crate::linker::{link, planned_link_command};
+use crate::program::{CellSize, Program, Statement, StatementKind};
+use crate::registry::{Backend, BackendOptions, Platform};
+use crate::semantics::Overflow;
+
+/// Translates the optimized program into machine code for the host using Cranelift and links it,
+/// without LLVM. Compiling takes a fraction of the time LLVM needs, and the generated code only
+/// calls into the runtime when the tape has to grow. Supports the 64-bit architectures Cranelift
+/// does.
+pub(crate) struct CraneliftBackend;
+
+impl Backend for CraneliftBackend {
+    fn name(&self) -> &'static str {
+        "cranelift"
+    }
+
+    fn description(&self) -> &'static str {
+        "Executable compiled using Cranelift (faster to compile than LLVM, slower to run)"
+    }
+
+    fn default_output_filename(&self) -> &'static str {
+        "a.out"
+    }
+
+    fn emit(&self, program: &Program, options: &BackendOptions) -> anyhow::Result<()> {
+        if options.platform != Platform::Host {
+            bail!("the cranelift backend does not support --target");
+        }
+        // the runtime only implements a growing tape with wrapping cells
+        for (enabled, option) in [
+            (options.embed_selftest, "--embed-selftest"),
+            (options.harden, "--harden"),
+            (options.guard_pages, "--guard-pages"),
+            (options.no_libc, "--no-libc"),
+            (options.bare_metal, "--bare-metal"),
+            (options.constant_output.is_some(), "--const-eval"),
+            (options.prompt.is_some(), "--prompt"),
+            (options.no_echo, "--no-echo"),
+            (options.max_output.is_some(), "--max-output"),
+            (options.runtime.max_memory.is_some(), "--max-memory"),
+            (options.runtime.tape_size.is_some(), "--tape-size"),
+            (
+                options.runtime.overflow == Overflow::Trap,
+                "--overflow trap",
+            ),
+        ] {
+            if enabled {
+                bail!("the cranelift backend does not support {option}");
+            }
+        }
+
+        let object = generate(program, options)?;
+        let directory = object_directory();
+        let filename = object_filename(&directory, options);
+        fs::create_dir_all(&directory)
+            .with_context(|| format!("failed to write {}", filename.display()))?;
+        let result = fs::write(&filename, object)
+            .with_context(|| format!("failed to write {}", filename.display()))
+            .and_then(|()| link(&filename, options, false));
+        let _ = fs::remove_dir_all(&directory);
+        result
+    }
+
+    fn plan(&self, options: &BackendOptions) -> Vec<String> {
+        let directory = object_directory();
+        let filename = object_filename(&directory, options);
+        vec![
+            format!(
+                "generate {} machine code using Cranelift",
+                std::env::consts::ARCH
+            ),
+            format!("write object file to '{}'", filename.display()),
+            format!(
+                "link executable: {}",
+                planned_link_command(&filename, options, false)
+            ),
+            format!("remove '{}'", directory.display()),
+        ]
+    }
+}
+
+// the object file is only needed by the linker, so it is not written next to the executable
+fn object_directory() -> PathBuf {
+    std::env::temp_dir().join(format!("brainrust-cranelift-{}", std::process::id()))
+}
+
+fn object_filename(directory: &Path, options: &BackendOptions) -> PathBuf {
+    let name = options
+        .output_filename
+        .file_name()
+        .unwrap_or("a.out".as_ref());
+    directory.join(name).with_extension("o")
+}
+
+// the fields of the variable `bf_tape`; the cell at address 0 has the index stored in `OFFSET`
+const MEMORY: i32 = 0;
+const CAPACITY: i32 = 8;
+const OFFSET: i32 = 16;
+const REGISTER: i32 = 24;
+const TAPE_BYTES: usize = 32;
+
+/// The functions and variables of the object file, declared before any of them is defined.
+struct Runtime {
+    tape: DataId,
+    initial_tape: Option<DataId>,
+    grow: FuncId,
+    dump: FuncId,
+    main: FuncId,
+    putchar: FuncId,
+    getchar: FuncId,
+    realloc: FuncId,
+    memmove: FuncId,
+    memset: FuncId,
+    memcpy: FuncId,
+    free: FuncId,
+    abort: FuncId,
+}
+
+fn generate(program: &Program, options: &BackendOptions) -> anyhow::Result<Vec<u8>> {
+    let mut flags = settings::builder();
+    flags
+        .set("is_pic", "true")
+        .expect("is_pic is a Cranelift setting");
+    let opt_level = if options.optimization_level == 0 {
+        "none"
+    } else {
+        "speed"
+    };
+    flags
+        .set("opt_level", opt_level)
+        .expect("opt_level is a Cranelift setting");
+    let isa = cranelift_native::builder()
+        .map_err(|error| anyhow!("Cranelift does not support the host: {error}"))?
+        .finish(settings::Flags::new(flags))?;
+    if isa.pointer_type() != types::I64 {
+        bail!("the cranelift backend only generates code for 64-bit architectures");
+    }
+    let endianness = isa.endianness();
+    let mut module = ObjectModule::new(ObjectBuilder::new(
+        isa,
+        "brainrust",
+        default_libcall_names(),
+    )?);
+
+    let cell_size = options.runtime.cell_size;
+    let runtime = declare(&mut module, !options.initial_tape.is_empty())?;
+    let mut tape = DataDescription::new();
+    tape.define_zeroinit(TAPE_BYTES);
+    tape.set_align(8);
+    module.define_data(runtime.tape, &tape)?;
+    if let Some(initial_tape) = runtime.initial_tape {
+        let mut data = DataDescription::new();
+        data.define(cells(&options.initial_tape, cell_size, endianness).into_boxed_slice());
+        module.define_data(initial_tape, &data)?;
+    }
+    define_function(&mut module, runtime.grow, |module, builder| {
+        grow(module, builder, &runtime, cell_size)
+    })?;
+    define_function(&mut module, runtime.dump, |module, builder| {
+        dump(module, builder, &runtime, cell_size)
+    })?;
+    define_function(&mut module, runtime.main, |module, builder| {
+        main(module, builder, &runtime, program, options)
+    })?;
+    Ok(module.finish().emit()?)
+}
+
+fn declare(module: &mut ObjectModule, initial_tape: bool) -> anyhow::Result<Runtime> {
+    let tape = module.declare_data("bf_tape", Linkage::Local, true, false)?;
+    let initial_tape = if initial_tape {
+        Some(module.declare_data("bf_initial_tape", Linkage::Local, false, false)?)
+    } else {
+        None
+    };
+    let mut function = |name: &str, linkage, parameters: &[Type], returns: &[Type]| {
+        let mut signature = module.make_signature();
+        signature
+            .params
+            .extend(parameters.iter().copied().map(AbiParam::new));
+        signature
+            .returns
+            .extend(returns.iter().copied().map(AbiParam::new));
+        anyhow::Ok(module.declare_function(name, linkage, &signature)?)
+    };
+    let (i32, i64) = (types::I32, types::I64);
+    Ok(Runtime {
+        tape,
+        initial_tape,
+        grow: function("bf_grow", Linkage::Local, &[i64], &[])?,
+        dump: function("bf_dump", Linkage::Local, &[], &[])?,
+        main: function("main", Linkage::Export, &[], &[i32])?,
+        putchar: function("putchar", Linkage::Import, &[i32], &[i32])?,
+        getchar: function("getchar", Linkage::Import, &[], &[i32])?,
+        realloc: function("realloc", Linkage::Import, &[i64, i64], &[i64])?,
+        memmove: function("memmove", Linkage::Import, &[i64, i64, i64], &[i64])?,
+        memset: function("memset", Linkage::Import, &[i64, i32, i64], &[i64])?,
+        memcpy: function("memcpy", Linkage::Import, &[i64, i64, i64], &[i64])?,
+        free: function("free", Linkage::Import, &[i64], &[])?,
+        abort: function("abort", Linkage::Import, &[], &[])?,
+    })
+}
+
+fn define_function(
+    module: &mut ObjectModule,
+    id: FuncId,
+    build: impl FnOnce(&mut ObjectModule, &mut FunctionBuilder),
+) -> anyhow::Result<()> {
+    let mut context = module.make_context();
+    context.func.signature = module
+        .declarations()
+        .get_function_decl(id)
+        .signature
+        .clone();
+    let mut function_builder_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut function_builder_context);
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    build(module, &mut builder);
+    builder.seal_all_blocks();
+    builder.finalize();
+    module.define_function(id, &mut context)?;
+    Ok(())
+}
+
+// the cells of the initial tape in the byte order of the host
+fn cells(bytes: &[u8], cell_size: CellSize, endianness: Endianness) -> Vec<u8> {
+    let width = (cell_size.bits() / 8) as usize;
+    let mut data = Vec::with_capacity(bytes.len() * width);
+    for &byte in bytes {
+        match endianness {
+            Endianness::Little => data.extend_from_slice(&u64::from(byte).to_le_bytes()[..width]),
+            Endianness::Big => data.extend_from_slice(&u64::from(byte).to_be_bytes()[8 - width..]),
+        }
+    }
+    data
+}
+
+fn cell_type(cell_size: CellSize) -> Type {
+    match cell_size {
+        CellSize::Bits8 => types::I8,
+        CellSize::Bits16 => types::I16,
+        CellSize::Bits32 => types::I32,
+        CellSize::Bits64 => types::I64,
+    }
+}
+
+// log2 of the number of bytes of a cell, to scale indices
+fn cell_shift(cell_size: CellSize) -> i64 {
+    i64::from((cell_size.bits() / 8).trailing_zeros())
+}
+
+// converts an integer to another width, dropping or zero-filling the upper bits
+fn resize(builder: &mut FunctionBuilder, value: Value, to: Type) -> Value {
+    let from = builder.func.dfg.value_type(value);
+    if from.bits() < to.bits() {
+        builder.ins().uextend(to, value)
+    } else if from.bits() > to.bits() {
+        builder.ins().ireduce(to, value)
+    } else {
+        value
+    }
+}
+
+fn load_field(builder: &mut FunctionBuilder, tape: GlobalValue, field: i32) -> Value {
+    let address = builder.ins().global_value(types::I64, tape);
+    builder
+        .ins()
+        .load(types::I64, MemFlags::trusted(), address, field)
+}
+
+fn store_field(builder: &mut FunctionBuilder, tape: GlobalValue, field: i32, value: Value) {
+    let address = builder.ins().global_value(types::I64, tape);
+    builder
+        .ins()
+        .store(MemFlags::trusted(), value, address, field);
+}
+
+fn call(builder: &mut FunctionBuilder, function: FuncRef, arguments: &[Value]) -> Option<Value> {
+    let call = builder.ins().call(function, arguments);
+    builder.inst_results(call).first().copied()
+}
+
+/// `bf_grow(address)`: grows the tape until it contains the cell at the address, which is
+/// outside of the tape.
+fn grow(
+    module: &mut ObjectModule,
+    builder: &mut FunctionBuilder,
+    runtime: &Runtime,
+    cell_size: CellSize,
+) {
+    let tape = module.declare_data_in_func(runtime.tape, builder.func);
+    let [realloc, memmove, memset, abort] = [
+        runtime.realloc,
+        runtime.memmove,
+        runtime.memset,
+        runtime.abort,
+    ]
+    .map(|function| module.declare_func_in_func(function, builder.func));
+    let shift = cell_shift(cell_size);
+    let entry = builder
+        .current_block()
+        .expect("the entry block was created");
+    let address = builder.block_params(entry)[0];
+
+    let memory = load_field(builder, tape, MEMORY);
+    let capacity = load_field(builder, tape, CAPACITY);
+    let offset = load_field(builder, tape, OFFSET);
+    let index = builder.ins().iadd(offset, address);
+    // the number of cells added to the left
+    let negative = builder.ins().icmp_imm(IntCC::SignedLessThan, index, 0);
+    let minus_index = builder.ins().ineg(index);
+    let zero = builder.ins().iconst(types::I64, 0);
+    let added = builder.ins().select(negative, minus_index, zero);
+    let needed = builder.ins().iadd(index, added);
+    let needed = builder.ins().iadd_imm(needed, 1);
+    let grown = builder.ins().iadd(capacity, added);
+    let new_capacity = builder.ins().umax(needed, grown);
+    let bytes = builder.ins().ishl_imm(new_capacity, shift);
+    let new_memory = call(builder, realloc, &[memory, bytes]).expect("realloc returns");
+
+    let failed = builder.create_block();
+    let moved = builder.create_block();
+    builder.set_cold_block(failed);
+    builder.ins().brif(new_memory, moved, &[], failed, &[]);
+    builder.switch_to_block(failed);
+    call(builder, abort, &[]);
+    builder.ins().trap(TrapCode::unwrap_user(1));
+
+    builder.switch_to_block(moved);
+    let added_bytes = builder.ins().ishl_imm(added, shift);
+    let destination = builder.ins().iadd(new_memory, added_bytes);
+    let old_bytes = builder.ins().ishl_imm(capacity, shift);
+    call(builder, memmove, &[destination, new_memory, old_bytes]);
+    let zero_byte = builder.ins().iconst(types::I32, 0);
+    call(builder, memset, &[new_memory, zero_byte, added_bytes]);
+    let end = builder.ins().iadd(added, capacity);
+    let end_bytes = builder.ins().ishl_imm(end, shift);
+    let rest = builder.ins().iadd(new_memory, end_bytes);
+    let rest_bytes = builder.ins().isub(bytes, end_bytes);
+    call(builder, memset, &[rest, zero_byte, rest_bytes]);
+    store_field(builder, tape, MEMORY, new_memory);
+    store_field(builder, tape, CAPACITY, new_capacity);
+    let new_offset = builder.ins().iadd(offset, added);
+    store_field(builder, tape, OFFSET, new_offset);
+    builder.ins().return_(&[]);
+}
+
+/// `bf_dump()`: prints the cells of the tape as digits.
+fn dump(
+    module: &mut ObjectModule,
+    builder: &mut FunctionBuilder,
+    runtime: &Runtime,
+    cell_size: CellSize,
+) {
+    let tape = module.declare_data_in_func(runtime.tape, builder.func);
+    let putchar = module.declare_func_in_func(runtime.putchar, builder.func);
+    let put = |builder: &mut FunctionBuilder, character: i64| {
+        let character = builder.ins().iconst(types::I32, character);
+        call(builder, putchar, &[character]);
+    };
+    put(builder, i64::from(b'M'));
+    put(builder, i64::from(b':'));
+    let header = builder.create_block();
+    let body = builder.create_block();
+    let end = builder.create_block();
+    let index = builder.append_block_param(header, types::I64);
+    let zero = builder.ins().iconst(types::I64, 0);
+    builder.ins().jump(header, &[zero]);
+
+    builder.switch_to_block(header);
+    let capacity = load_field(builder, tape, CAPACITY);
+    let in_tape = builder.ins().icmp(IntCC::UnsignedLessThan, index, capacity);
+    builder.ins().brif(in_tape, body, &[], end, &[]);
+
+    builder.switch_to_block(body);
+    let memory = load_field(builder, tape, MEMORY);
+    let scaled = builder.ins().ishl_imm(index, cell_shift(cell_size));
+    let address = builder.ins().iadd(memory, scaled);
+    let value = builder
+        .ins()
+        .load(cell_type(cell_size), MemFlags::trusted(), address, 0);
+    let value = resize(builder, value, types::I32);
+    let digit = builder.ins().iadd_imm(value, i64::from(b'0'));
+    call(builder, putchar, &[digit]);
+    let next = builder.ins().iadd_imm(index, 1);
+    builder.ins().jump(header, &[next]);
+
+    builder.switch_to_block(end);
+    put(builder, i64::from(b'\n'));
+    builder.ins().return_(&[]);
+}
+
+/// `main()`: runs the program and frees the tape.
+fn main(
+    module: &mut ObjectModule,
+    builder: &mut FunctionBuilder,
+    runtime: &Runtime,
+    program: &Program,
+    options: &BackendOptions,
+) {
+    let cell_size = options.runtime.cell_size;
+    let pointer = Variable::from_u32(0);
+    builder.declare_var(pointer, types::I64);
+    let zero = builder.ins().iconst(types::I64, 0);
+    builder.def_var(pointer, zero);
+    let mut generator = Generator {
+        tape: module.declare_data_in_func(runtime.tape, builder.func),
+        grow: module.declare_func_in_func(runtime.grow, builder.func),
+        dump: module.declare_func_in_func(runtime.dump, builder.func),
+        putchar: module.declare_func_in_func(runtime.putchar, builder.func),
+        getchar: module.declare_func_in_func(runtime.getchar, builder.func),
+        builder,
+        cell_type: cell_type(cell_size),
+        cell_size,
+        eof_value: options.runtime.eof_value(),
+        pointer,
+        loops: Vec::new(),
+    };
+
+    let initial_extent = options.runtime.initial_tape_extent() as i64;
+    generator.cell(initial_extent);
+    generator.cell(-initial_extent);
+    if let Some(initial_tape) = runtime.initial_tape {
+        let length = options.initial_tape.len();
+        generator.cell(length as i64 - 1);
+        let destination = generator.cell(0);
+        let builder = &mut *generator.builder;
+        let initial_tape = module.declare_data_in_func(initial_tape, builder.func);
+        let source = builder.ins().global_value(types::I64, initial_tape);
+        let bytes = builder
+            .ins()
+            .iconst(types::I64, (length << cell_shift(cell_size)) as i64);
+        let memcpy = module.declare_func_in_func(runtime.memcpy, builder.func);
+        call(builder, memcpy, &[destination, source, bytes]);
+    }
+    generator.statements(program.statements());
+
+    let builder = generator.builder;
+    let free = module.declare_func_in_func(runtime.free, builder.func);
+    let memory = load_field(builder, generator.tape, MEMORY);
+    call(builder, free, &[memory]);
+    let exit_code = builder.ins().iconst(types::I32, 0);
+    builder.ins().return_(&[exit_code]);
+}
+
+struct Generator<'a, 'b> {
+    builder: &'a mut FunctionBuilder<'b>,
+    tape: GlobalValue,
+    grow: FuncRef,
+    dump: FuncRef,
+    putchar: FuncRef,
+    getchar: FuncRef,
+    cell_type: Type,
+    cell_size: CellSize,
+    eof_value: Option<u64>,
+    pointer: Variable,
+    // the start and the end of the enclosing loops
+    loops: Vec<(Block, Block)>,
+}
+
+impl Generator<'_, '_> {
+    /// Returns the address of the cell at the offset relative to the pointer and grows the
+    /// tape first if it does not contain the cell yet.
+    fn cell(&mut self, offset: i64) -> Value {
+        let pointer = self.builder.use_var(self.pointer);
+        let address = self.builder.ins().iadd_imm(pointer, offset);
+        let check = self.builder.create_block();
+        let grow = self.builder.create_block();
+        let found = self.builder.create_block();
+        self.builder.set_cold_block(grow);
+        self.builder.ins().jump(check, &[]);
+
+        self.builder.switch_to_block(check);
+        let tape_offset = load_field(self.builder, self.tape, OFFSET);
+        let capacity = load_field(self.builder, self.tape, CAPACITY);
+        let index = self.builder.ins().iadd(tape_offset, address);
+        // a negative index is out of bounds as well when compared unsigned
+        let in_tape = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedLessThan, index, capacity);
+        self.builder.ins().brif(in_tape, found, &[], grow, &[]);
+
+        self.builder.switch_to_block(grow);
+        call(self.builder, self.grow, &[address]);
+        self.builder.ins().jump(check, &[]);
+
+        self.builder.switch_to_block(found);
+        let memory = load_field(self.builder, self.tape, MEMORY);
+        let scaled = self
+            .builder
+            .ins()
+            .ishl_imm(index, cell_shift(self.cell_size));
+        self.builder.ins().iadd(memory, scaled)
+    }
+
+    fn load(&mut self, address: Value) -> Value {
+        self.builder
+            .ins()
+            .load(self.cell_type, MemFlags::trusted(), address, 0)
+    }
+
+    fn store(&mut self, address: Value, value: Value) {
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), value, address, 0);
+    }
+
+    fn constant(&mut self, value: u64) -> Value {
+        self.builder.ins().iconst(self.cell_type, value as i64)
+    }
+
+    // code following a jump is unreachable until the next block, but needs a block as well
+    fn jump(&mut self, block: Block) {
+        self.builder.ins().jump(block, &[]);
+        let unreachable = self.builder.create_block();
+        self.builder.switch_to_block(unreachable);
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement.kind() {
+            StatementKind::Move(amount) => {
+                let pointer = self.builder.use_var(self.pointer);
+                let pointer = self.builder.ins().iadd_imm(pointer, *amount);
+                self.builder.def_var(self.pointer, pointer);
+            }
+            StatementKind::Add { offset, amount } => {
+                let address = self.cell(*offset);
+                let value = self.load(address);
+                let value = self
+                    .builder
+                    .ins()
+                    .iadd_imm(value, self.cell_size.wrap(*amount) as i64);
+                self.store(address, value);
+            }
+            StatementKind::Set { offset, value } => {
+                let address = self.cell(*offset);
+                let value = self.constant(u64::from(*value));
+                self.store(address, value);
+            }
+            StatementKind::MultiplyAdd(pairs) => {
+                let address = self.cell(0);
+                let factor = self.load(address);
+                let add = self.builder.create_block();
+                let end = self.builder.create_block();
+                self.builder.ins().brif(factor, add, &[], end, &[]);
+                self.builder.switch_to_block(add);
+                // multiplying 64-bit values wraps around like multiplying cells
+                let factor = resize(self.builder, factor, types::I64);
+                for (offset, multiplier) in pairs {
+                    let product = self
+                        .builder
+                        .ins()
+                        .imul_imm(factor, self.cell_size.wrap(*multiplier) as i64);
+                    let product = resize(self.builder, product, self.cell_type);
+                    // the address is computed last, growing the tape moves the cells
+                    let address = self.cell(*offset);
+                    let value = self.load(address);
+                    let value = self.builder.ins().iadd(value, product);
+                    self.store(address, value);
+                }
+                let address = self.cell(0);
+                let zero = self.constant(0);
+                self.store(address, zero);
+                self.builder.ins().jump(end, &[]);
+                self.builder.switch_to_block(end);
+            }
+            StatementKind::PutChar => {
+                let address = self.cell(0);
+                let value = self.load(address);
+                let byte = resize(self.builder, value, types::I8);
+                let character = resize(self.builder, byte, types::I32);
+                call(self.builder, self.putchar, &[character]);
+            }
+            StatementKind::GetChar => {
+                let address = self.cell(0);
+                let character = call(self.builder, self.getchar, &[]).expect("getchar returns");
+                let read = self.builder.create_block();
+                let end_of_input = self.builder.create_block();
+                let end = self.builder.create_block();
+                let is_end_of_input = self.builder.ins().icmp_imm(IntCC::Equal, character, -1);
+                self.builder
+                    .ins()
+                    .brif(is_end_of_input, end_of_input, &[], read, &[]);
+
+                self.builder.switch_to_block(read);
+                let byte = resize(self.builder, character, types::I8);
+                let value = resize(self.builder, byte, self.cell_type);
+                self.store(address, value);
+                self.builder.ins().jump(end, &[]);
+
+                self.builder.switch_to_block(end_of_input);
+                if let Some(eof_value) = self.eof_value {
+                    let value = self.constant(eof_value);
+                    self.store(address, value);
+                }
+                self.builder.ins().jump(end, &[]);
+                self.builder.switch_to_block(end);
+            }
+            StatementKind::Loop(body) => {
+                let start = self.builder.create_block();
+                let statements = self.builder.create_block();
+                let end = self.builder.create_block();
+                self.builder.ins().jump(start, &[]);
+                self.builder.switch_to_block(start);
+                let address = self.cell(0);
+                let value = self.load(address);
+                self.builder.ins().brif(value, statements, &[], end, &[]);
+                self.builder.switch_to_block(statements);
+                self.loops.push((start, end));
+                self.statements(body);
+                self.loops.pop();
+                self.builder.ins().jump(start, &[]);
+                self.builder.switch_to_block(end);
+            }
+            StatementKind::If(body) => {
+                let address = self.cell(0);
+                let value = self.load(address);
+                let statements = self.builder.create_block();
+                let end = self.builder.create_block();
+                self.builder.ins().brif(value, statements, &[], end, &[]);
+                self.builder.switch_to_block(statements);
+                self.statements(body);
+                self.builder.ins().jump(end, &[]);
+                self.builder.switch_to_block(end);
+            }
+            StatementKind::Store => {
+                let address = self.cell(0);
+                let value = self.load(address);
+                let register = self.builder.ins().global_value(types::I64, self.tape);
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), value, register, REGISTER);
+            }
+            StatementKind::Load => {
+                let address = self.cell(0);
+                let register = self.builder.ins().global_value(types::I64, self.tape);
+                let value = self.builder.ins().load(
+                    self.cell_type,
+                    MemFlags::trusted(),
+                    register,
+                    REGISTER,
+                );
+                self.store(address, value);
+            }
+            StatementKind::Break => {
+                let (_, end) = *self.loops.last().expect("checked by the parser");
+                self.jump(end);
+            }
+            StatementKind::Continue => {
+                let (start, _) = *self.loops.last().expect("checked by the parser");
+                self.jump(start);
+            }
+            StatementKind::DebugDump => {
+                call(self.builder, self.dump, &[]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::Pipeline;
+    use crate::parser::{Dialect, Parser};
+    use crate::semantics::{EofBehavior, RuntimeConfig};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    // the output of the program compiled with the runtime and fed the input
+    fn run(source: &str, input: &[u8], runtime: RuntimeConfig, directory: &Path) -> Vec<u8> {
+        let dialect = Dialect {
+            loop_control: true,
+            ..Dialect::default()
+        };
+        let program = Parser::new(source.as_bytes(), dialect).parse().unwrap();
+        let program = Pipeline::default()
+            .with_cell_size(runtime.cell_size)
+            .run(&program);
+        let executable = directory.join("program");
+        CraneliftBackend
+            .emit(
+                &program,
+                &BackendOptions {
+                    runtime,
+                    optimization_level: 2,
+                    ..BackendOptions::for_tests(Platform::Host, &executable)
+                },
+            )
+            .unwrap();
+        let mut child = Command::new(&executable)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{source}: {}", output.status);
+        output.stdout
+    }
+
+    #[test]
+    fn compiled_programs_follow_the_runtime_semantics() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            eprintln!("skipped, no C compiler (cc) found");
+            return;
+        }
+        let directory =
+            std::env::temp_dir().join(format!("brainrust-cranelift-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let count_to_256 = format!("{}[[-]>+++<]>.", "+".repeat(256));
+        for (source, input, runtime, expected) in [
+            ("-.", &b""[..], RuntimeConfig::default(), &b"\xff"[..]),
+            (&count_to_256, b"", RuntimeConfig::default(), b"\x00"),
+            (
+                &count_to_256,
+                b"",
+                RuntimeConfig {
+                    cell_size: CellSize::Bits64,
+                    ..Default::default()
+                },
+                b"\x03",
+            ),
+            (",.,.+,.", b"ab", RuntimeConfig::default(), b"ab\x00"),
+            (
+                "+,.",
+                b"",
+                RuntimeConfig {
+                    eof_behavior: EofBehavior::Unchanged,
+                    ..Default::default()
+                },
+                b"\x01",
+            ),
+            ("++++++[->+++++++<]>.", b"", RuntimeConfig::default(), b"*"),
+            (
+                "+[>+++@<].[-&].",
+                b"",
+                RuntimeConfig::default(),
+                b"\x03\x00",
+            ),
+        ] {
+            assert_eq!(
+                run(source, input, runtime, &directory),
+                expected,
+                "{source}"
+            );
+        }
+        // beyond the initial extent of the tape in both directions
+        let grows = format!("{}+.{}+.", ">".repeat(20000), "<".repeat(40000));
+        assert_eq!(
+            run(&grows, b"", RuntimeConfig::default(), &directory),
+            b"\x01\x01"
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn unsupported_options_are_rejected() {
+        let program = Parser::new(b"+.", Dialect::default()).parse().unwrap();
+        let options = BackendOptions {
+            runtime: RuntimeConfig {
+                tape_size: Some(4),
+                ..Default::default()
+            },
+            ..BackendOptions::for_tests(Platform::Host, "a.out")
+        };
+        let error = CraneliftBackend.emit(&program, &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the cranelift backend does not support --tape-size"
+        );
+    }
+}
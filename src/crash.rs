@@ -0,0 +1,66 @@
+use std::backtrace::Backtrace;
+use std::fmt::Write;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::environment::EnvironmentReport;
+
+const ISSUES_URL: &str = "https://github.com/mgerhold/brainrust/issues";
+
+static SOURCE: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Replaces the default panic output by a crash report that is written to a file. Only the
+/// path of the report is printed, together with a request to file an issue.
+pub(crate) fn install_handler() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let path = report_path();
+        match std::fs::write(&path, report(info)) {
+            Ok(()) => {
+                eprintln!("error: brainrust crashed unexpectedly, this is a bug");
+                eprintln!("a crash report has been written to '{}'", path.display());
+                eprintln!("please file an issue at {ISSUES_URL} and attach the report");
+            }
+            Err(_) => default_hook(info),
+        }
+    }));
+}
+
+/// Makes the source of the program part of crash reports (as a hash).
+pub(crate) fn record_source(source: &[u8]) {
+    let _ = SOURCE.set(source.to_vec());
+}
+
+fn report(info: &PanicHookInfo) -> String {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload");
+    let mut report = String::new();
+    let _ = writeln!(report, "panic: {message}");
+    if let Some(location) = info.location() {
+        let _ = writeln!(report, "location: {location}");
+    }
+    let _ = write!(
+        report,
+        "{}",
+        EnvironmentReport::collect(SOURCE.get().map(Vec::as_slice))
+    );
+    let _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+    report
+}
+
+fn report_path() -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    std::env::temp_dir().join(format!(
+        "brainrust-crash-{timestamp}-{}.txt",
+        std::process::id()
+    ))
+}
@@ -0,0 +1,536 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+
+use anyhow::{bail, Context, Result};
+
+use crate::debugger::{Debugger, Stop, TAPE_RADIUS};
+use crate::diagnostics::SourceLocation;
+use crate::interpreter::{InterpreterError, Status};
+use crate::json::Json;
+use crate::parser::{Dialect, Parser};
+
+// programs have a single thread of execution
+const THREAD_ID: i64 = 1;
+const STATE_REFERENCE: i64 = 1;
+const TAPE_REFERENCE: i64 = 2;
+// the number of steps between checks for requests (e.g. to pause) while the program runs
+const STEPS_PER_CHECK: u64 = 10_000;
+
+/// Collects the output of the program, which is sent to the client in output events.
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Session {
+    filename: PathBuf,
+    source: Vec<u8>,
+    debugger: Debugger<Box<dyn BufRead>, SharedOutput>,
+    output: SharedOutput,
+    stop_on_entry: bool,
+}
+
+/// Serves the Debug Adapter Protocol on stdin and stdout, e.g. for editors like VS Code. A
+/// program is debugged using the same interpreter as the `debug` subcommand.
+pub(crate) fn serve() -> Result<()> {
+    // requests are read in the background so that they can arrive while the program runs
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        loop {
+            let message = match read_message(&mut stdin) {
+                Ok(Some(message)) => Ok(message),
+                Ok(None) => return,
+                Err(error) => Err(error),
+            };
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                return;
+            }
+        }
+    });
+    let mut adapter = Adapter {
+        output: io::stdout(),
+        sequence: 0,
+        session: None,
+        running: false,
+        pending_events: Vec::new(),
+    };
+    loop {
+        let message = if adapter.running {
+            match receiver.try_recv() {
+                Ok(message) => Some(message),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match receiver.recv() {
+                Ok(message) => Some(message),
+                Err(_) => return Ok(()),
+            }
+        };
+        if let Some(message) = message {
+            if !adapter.handle(&message?)? {
+                return Ok(());
+            }
+        }
+        if adapter.running {
+            adapter.run(STEPS_PER_CHECK)?;
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = Some(
+                value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("invalid header '{line}'"))?,
+            );
+        }
+    }
+    let Some(length) = length else {
+        bail!("a message has no Content-Length header");
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Json::parse(
+        std::str::from_utf8(&body).context("a message is not valid UTF-8")?,
+    )?))
+}
+
+struct Adapter<W> {
+    output: W,
+    sequence: i64,
+    session: Option<Session>,
+    running: bool,
+    // events that are sent once the response to the current request has been sent
+    pending_events: Vec<(&'static str, Json)>,
+}
+
+impl<W: Write> Adapter<W> {
+    fn send(&mut self, kind: &str, mut members: Vec<(String, Json)>) -> Result<()> {
+        self.sequence += 1;
+        members.splice(
+            0..0,
+            [
+                ("seq".to_string(), Json::from(self.sequence)),
+                ("type".to_string(), Json::from(kind)),
+            ],
+        );
+        let body = Json::Object(members).to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> Result<()> {
+        self.send(
+            "event",
+            vec![
+                ("event".to_string(), Json::from(event)),
+                ("body".to_string(), body),
+            ],
+        )
+    }
+
+    /// Handles a message from the client. Returns `false` once the client disconnects.
+    fn handle(&mut self, message: &Json) -> Result<bool> {
+        if message.get("type").and_then(Json::as_str) != Some("request") {
+            return Ok(true);
+        }
+        let command = message
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        let arguments = message.get("arguments").unwrap_or(&Json::Null);
+        let result = self.respond_to(command, arguments);
+        let mut members = vec![
+            (
+                "request_seq".to_string(),
+                message.get("seq").cloned().unwrap_or(Json::Null),
+            ),
+            ("success".to_string(), Json::from(result.is_ok())),
+            ("command".to_string(), Json::from(command)),
+        ];
+        match result {
+            Ok(body) => members.push(("body".to_string(), body)),
+            Err(error) => members.push(("message".to_string(), Json::from(error))),
+        }
+        self.send("response", members)?;
+        for (event, body) in std::mem::take(&mut self.pending_events) {
+            self.event(event, body)?;
+        }
+        Ok(!matches!(command, "disconnect" | "terminate"))
+    }
+
+    fn respond_to(&mut self, command: &str, arguments: &Json) -> Result<Json, String> {
+        match command {
+            "initialize" => Ok(Json::object([
+                ("supportsConfigurationDoneRequest", Json::from(true)),
+                ("supportsSetVariable", Json::from(true)),
+                ("supportsTerminateRequest", Json::from(true)),
+            ])),
+            "launch" => {
+                self.session = Some(launch(arguments)?);
+                // breakpoints can only be set once the source is known
+                self.pending_events.push(("initialized", Json::Null));
+                Ok(Json::Null)
+            }
+            "configurationDone" => {
+                if self.session()?.stop_on_entry {
+                    self.pending_events.push(("stopped", stopped("entry")));
+                } else {
+                    self.running = true;
+                }
+                Ok(Json::Null)
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "threads" => Ok(Json::object([(
+                "threads",
+                Json::Array(vec![Json::object([
+                    ("id", Json::from(THREAD_ID)),
+                    ("name", Json::from("main")),
+                ])]),
+            )])),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(Json::object([(
+                "scopes",
+                Json::Array(vec![
+                    scope("State", STATE_REFERENCE),
+                    scope("Tape", TAPE_REFERENCE),
+                ]),
+            )])),
+            "variables" => self.variables(arguments),
+            "setVariable" => self.set_variable(arguments),
+            "continue" => {
+                self.session()?;
+                self.running = true;
+                Ok(Json::object([("allThreadsContinued", Json::from(true))]))
+            }
+            "next" | "stepIn" => {
+                let stop = self.session_mut()?.debugger.step(1);
+                self.stop(stop).map_err(|error| error.to_string())?;
+                Ok(Json::Null)
+            }
+            "stepOut" => Err("programs have no functions to step out of".to_string()),
+            "pause" => {
+                self.session()?;
+                if self.running {
+                    self.running = false;
+                    self.pending_events.push(("stopped", stopped("pause")));
+                }
+                Ok(Json::Null)
+            }
+            "disconnect" | "terminate" => Ok(Json::Null),
+            command => Err(format!("unsupported request '{command}'")),
+        }
+    }
+
+    fn session(&self) -> Result<&Session, String> {
+        self.session
+            .as_ref()
+            .ok_or_else(|| "no program has been launched".to_string())
+    }
+
+    fn session_mut(&mut self) -> Result<&mut Session, String> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| "no program has been launched".to_string())
+    }
+
+    /// Runs the program for at most the given number of steps.
+    fn run(&mut self, steps: u64) -> Result<()> {
+        let stop = self
+            .session
+            .as_mut()
+            .expect("only launched programs run")
+            .debugger
+            .step(steps);
+        if matches!(stop, Ok(Stop::Step)) {
+            return self.send_output();
+        }
+        self.running = false;
+        self.stop(stop)?;
+        for (event, body) in std::mem::take(&mut self.pending_events) {
+            self.event(event, body)?;
+        }
+        Ok(())
+    }
+
+    // reports why the program stopped, the events are sent after the current response
+    fn stop(&mut self, stop: Result<Stop, InterpreterError>) -> Result<()> {
+        self.send_output()?;
+        match stop {
+            Ok(Stop::Step) => self.pending_events.push(("stopped", stopped("step"))),
+            Ok(Stop::Breakpoint(_)) => self.pending_events.push(("stopped", stopped("breakpoint"))),
+            Ok(Stop::Watchpoint(_)) => self
+                .pending_events
+                .push(("stopped", stopped("data breakpoint"))),
+            Ok(Stop::Finished) => self.terminate(0),
+            Err(error) => {
+                self.pending_events.push((
+                    "output",
+                    Json::object([
+                        ("category", Json::from("stderr")),
+                        ("output", Json::from(format!("error: {error}\n"))),
+                    ]),
+                ));
+                self.terminate(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn terminate(&mut self, exit_code: i64) {
+        self.pending_events.push((
+            "exited",
+            Json::object([("exitCode", Json::from(exit_code))]),
+        ));
+        self.pending_events.push(("terminated", Json::Null));
+    }
+
+    fn send_output(&mut self) -> Result<()> {
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        let output = std::mem::take(&mut *session.output.0.borrow_mut());
+        if output.is_empty() {
+            return Ok(());
+        }
+        self.event(
+            "output",
+            Json::object([
+                ("category", Json::from("stdout")),
+                (
+                    "output",
+                    Json::from(String::from_utf8_lossy(&output).into_owned()),
+                ),
+            ]),
+        )
+    }
+
+    // breakpoints are set on lines and placed at the first command of the line
+    fn set_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let session = self.session_mut()?;
+        let previous: Vec<_> = session.debugger.breakpoints().iter().copied().collect();
+        for offset in previous {
+            session.debugger.remove_breakpoint(offset);
+        }
+        let lines = arguments
+            .get("breakpoints")
+            .and_then(Json::as_array)
+            .unwrap_or_default();
+        let mut breakpoints = Vec::new();
+        for line in lines
+            .iter()
+            .map(|breakpoint| breakpoint.get("line").and_then(Json::as_i64))
+        {
+            let offset = line
+                .and_then(|line| usize::try_from(line).ok())
+                .and_then(|line| {
+                    line_range(&session.source, line)
+                        .find(|&offset| session.debugger.add_breakpoint(offset))
+                });
+            breakpoints.push(match offset {
+                Some(offset) => {
+                    let location = SourceLocation::from_offset(&session.source, offset);
+                    Json::object([
+                        ("verified", Json::from(true)),
+                        ("line", Json::from(location.line as i64)),
+                        ("column", Json::from(location.column as i64)),
+                    ])
+                }
+                None => Json::object([
+                    ("verified", Json::from(false)),
+                    ("message", Json::from("there is no command on this line")),
+                ]),
+            });
+        }
+        Ok(Json::object([("breakpoints", Json::Array(breakpoints))]))
+    }
+
+    fn stack_trace(&self) -> Result<Json, String> {
+        let session = self.session()?;
+        let frames = match session.debugger.location() {
+            Some(offset) if session.debugger.status() == Status::Running => {
+                let location = SourceLocation::from_offset(&session.source, offset);
+                vec![Json::object([
+                    ("id", Json::from(1)),
+                    (
+                        "name",
+                        Json::from(format!("`{}`", session.source[offset] as char)),
+                    ),
+                    (
+                        "source",
+                        Json::object([(
+                            "path",
+                            Json::from(session.filename.display().to_string()),
+                        )]),
+                    ),
+                    ("line", Json::from(location.line as i64)),
+                    ("column", Json::from(location.column as i64)),
+                ])]
+            }
+            _ => Vec::new(),
+        };
+        Ok(Json::object([
+            ("totalFrames", Json::from(frames.len() as i64)),
+            ("stackFrames", Json::Array(frames)),
+        ]))
+    }
+
+    fn variables(&self, arguments: &Json) -> Result<Json, String> {
+        let debugger = &self.session()?.debugger;
+        let pointer = debugger.pointer();
+        let variables = match arguments.get("variablesReference").and_then(Json::as_i64) {
+            Some(STATE_REFERENCE) => vec![
+                variable("pointer", pointer.to_string()),
+                variable("cell", cell_value(debugger.cell(pointer))),
+                variable("steps", debugger.steps().to_string()),
+            ],
+            Some(TAPE_REFERENCE) => (pointer - TAPE_RADIUS..=pointer + TAPE_RADIUS)
+                .map(|address| variable(&address.to_string(), cell_value(debugger.cell(address))))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Ok(Json::object([("variables", Json::Array(variables))]))
+    }
+
+    // only the cells of the tape can be changed
+    fn set_variable(&mut self, arguments: &Json) -> Result<Json, String> {
+        let debugger = &mut self.session_mut()?.debugger;
+        if arguments.get("variablesReference").and_then(Json::as_i64) != Some(TAPE_REFERENCE) {
+            return Err("only cells of the tape can be changed".to_string());
+        }
+        let address = arguments
+            .get("name")
+            .and_then(Json::as_str)
+            .and_then(|name| name.parse().ok())
+            .ok_or_else(|| "expected the address of a cell".to_string())?;
+        let value = arguments
+            .get("value")
+            .and_then(Json::as_str)
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or_else(|| "expected a value from 0 to 255".to_string())?;
+        debugger.set_cell(address, value);
+        Ok(Json::object([("value", Json::from(cell_value(value)))]))
+    }
+}
+
+// the arguments are the path of the program, optionally the path of its input and whether to
+// stop before the first command
+fn launch(arguments: &Json) -> Result<Session, String> {
+    let filename = PathBuf::from(
+        arguments
+            .get("program")
+            .and_then(Json::as_str)
+            .ok_or_else(|| "the path of the program is missing".to_string())?,
+    );
+    let source = std::fs::read(&filename)
+        .map_err(|error| format!("failed to read {}: {error}", filename.display()))?;
+    let program = Parser::new(&source, Dialect::default())
+        .parse()
+        .map_err(|error| format!("{}: {error}", filename.display()))?;
+    let input: Box<dyn BufRead> = match arguments.get("input").and_then(Json::as_str) {
+        Some(input) => Box::new(io::BufReader::new(
+            std::fs::File::open(input)
+                .map_err(|error| format!("failed to open {input}: {error}"))?,
+        )),
+        None => Box::new(io::empty()),
+    };
+    let output = SharedOutput::default();
+    Ok(Session {
+        debugger: Debugger::new(&program, input, output.clone()),
+        filename,
+        source,
+        output,
+        stop_on_entry: arguments
+            .get("stopOnEntry")
+            .and_then(Json::as_bool)
+            .unwrap_or(false),
+    })
+}
+
+fn stopped(reason: &str) -> Json {
+    Json::object([
+        ("reason", Json::from(reason)),
+        ("threadId", Json::from(THREAD_ID)),
+        ("allThreadsStopped", Json::from(true)),
+    ])
+}
+
+fn scope(name: &str, reference: i64) -> Json {
+    Json::object([
+        ("name", Json::from(name)),
+        ("variablesReference", Json::from(reference)),
+        ("expensive", Json::from(false)),
+    ])
+}
+
+fn variable(name: &str, value: String) -> Json {
+    Json::object([
+        ("name", Json::from(name)),
+        ("value", Json::from(value)),
+        ("variablesReference", Json::from(0)),
+    ])
+}
+
+fn cell_value(value: u8) -> String {
+    if value.is_ascii_graphic() {
+        format!("{value} '{}'", char::from(value))
+    } else {
+        value.to_string()
+    }
+}
+
+// the offsets of the bytes of a line (counted from 1), with the same line breaks as
+// `SourceLocation`
+fn line_range(source: &[u8], line: usize) -> Range<usize> {
+    let mut current = 1;
+    let mut start = 0;
+    for (index, &char) in source.iter().enumerate() {
+        let line_break = match char {
+            b'\n' => true,
+            b'\r' => source.get(index + 1) != Some(&b'\n'),
+            _ => false,
+        };
+        if !line_break {
+            continue;
+        }
+        if current == line {
+            return start..index;
+        }
+        current += 1;
+        start = index + 1;
+    }
+    if current == line {
+        start..source.len()
+    } else {
+        0..0
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::coverage::command_offsets;
+use crate::diagnostics::SourceLocation;
+use crate::interpreter::{Access, Interpreter, InterpreterError, Status};
+use crate::program::Program;
+
+// the number of cells on each side of the pointer that `tape` prints
+pub(crate) const TAPE_RADIUS: i64 = 8;
+
+const HELP: &str = "\
+commands:
+  break OFFSET       pause before the command at this byte offset of the source (b)
+  delete OFFSET      remove the breakpoint at this offset (d)
+  breakpoints        list all breakpoints
+  watch CELL         pause after every step that reads or writes CELL (w)
+  unwatch CELL       remove the watchpoint on CELL
+  watchpoints        list all watchpoints
+  events             list every access to a watched cell so far
+  step [N]           execute N commands, 1 by default (s)
+  continue           run until a breakpoint is reached or the program ends (c)
+  print [ADDRESS]    print the cell at ADDRESS, the current cell by default (p)
+  set ADDRESS VALUE  change the cell at ADDRESS
+  pointer            print the pointer and the next command
+  tape               print the cells around the pointer
+  help               print this list (h)
+  quit               stop debugging (q)
+addresses are relative to the initial cell, a watched CELL is either an ADDRESS or p+K or
+p-K for the cell K cells to the right or left of wherever the pointer is";
+
+/// Why the debugger paused the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stop {
+    /// The requested number of steps has been executed.
+    Step,
+    /// The next command has a breakpoint, given by its offset.
+    Breakpoint(usize),
+    /// The last step accessed watched cells, given by the number of events it added to the log.
+    Watchpoint(usize),
+    Finished,
+}
+
+/// A cell the debugger watches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Watch {
+    Address(i64),
+    /// The cell at this offset from the pointer, which moves along with it.
+    Relative(i64),
+}
+
+impl Watch {
+    /// Parses an address or `p`, optionally followed by `+K` or `-K`.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        match text.strip_prefix('p') {
+            Some("") => Some(Watch::Relative(0)),
+            Some(offset) if offset.starts_with(['+', '-']) => {
+                offset.parse().ok().map(Watch::Relative)
+            }
+            Some(_) => None,
+            None => text.parse().ok().map(Watch::Address),
+        }
+    }
+
+    fn address(self, pointer: i64) -> i64 {
+        match self {
+            Watch::Address(address) => address,
+            Watch::Relative(offset) => pointer + offset,
+        }
+    }
+}
+
+impl Display for Watch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Watch::Address(address) => write!(f, "cell {address}"),
+            Watch::Relative(0) => write!(f, "cell p"),
+            Watch::Relative(offset) => write!(f, "cell p{offset:+}"),
+        }
+    }
+}
+
+/// An access to a watched cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WatchEvent {
+    /// The number of the step that accessed the cell, starting at 1.
+    pub(crate) step: u64,
+    /// The offset of the command that accessed the cell.
+    pub(crate) location: usize,
+    pub(crate) watch: Watch,
+    pub(crate) address: i64,
+    pub(crate) access: Access,
+    pub(crate) value_before: u8,
+    pub(crate) value_after: u8,
+}
+
+/// Runs a parsed program under the control of a user. A step executes a single command.
+pub(crate) struct Debugger<I, O> {
+    interpreter: Interpreter<I, O>,
+    // the offsets of all commands, only these can have breakpoints
+    commands: BTreeSet<usize>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<Watch>,
+    events: Vec<WatchEvent>,
+    steps: u64,
+}
+
+impl<I: BufRead, O: Write> Debugger<I, O> {
+    /// The program must not be optimized, so that every step corresponds to a command.
+    pub(crate) fn new(program: &Program, input: I, output: O) -> Self {
+        Self {
+            interpreter: Interpreter::new(program, input, output),
+            commands: command_offsets(program),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            events: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Returns `false` if there is no command at the offset.
+    pub(crate) fn add_breakpoint(&mut self, offset: usize) -> bool {
+        if !self.commands.contains(&offset) {
+            return false;
+        }
+        self.breakpoints.insert(offset);
+        true
+    }
+
+    /// Returns `false` if there was no breakpoint at the offset.
+    pub(crate) fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.remove(&offset)
+    }
+
+    pub(crate) fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    /// Returns `false` if the cell was already watched.
+    pub(crate) fn add_watchpoint(&mut self, watch: Watch) -> bool {
+        self.watchpoints.insert(watch)
+    }
+
+    /// Returns `false` if the cell was not watched.
+    pub(crate) fn remove_watchpoint(&mut self, watch: Watch) -> bool {
+        self.watchpoints.remove(&watch)
+    }
+
+    pub(crate) fn watchpoints(&self) -> &BTreeSet<Watch> {
+        &self.watchpoints
+    }
+
+    /// Every access to a watched cell so far, in the order they happened.
+    pub(crate) fn events(&self) -> &[WatchEvent] {
+        &self.events
+    }
+
+    /// Executes at most `count` steps. Stops early at a breakpoint, after a step that accessed a
+    /// watched cell or at the end of the program.
+    pub(crate) fn step(&mut self, count: u64) -> Result<Stop, InterpreterError> {
+        for _ in 0..count {
+            if let Some(stop) = self.execute_one()? {
+                return Ok(stop);
+            }
+        }
+        Ok(Stop::Step)
+    }
+
+    /// Runs until the next breakpoint, an access to a watched cell or the end of the program. The
+    /// command at the current location is always executed, even if it has a breakpoint.
+    pub(crate) fn resume(&mut self) -> Result<Stop, InterpreterError> {
+        loop {
+            if let Some(stop) = self.execute_one()? {
+                return Ok(stop);
+            }
+        }
+    }
+
+    fn execute_one(&mut self) -> Result<Option<Stop>, InterpreterError> {
+        if self.status() == Status::Finished {
+            return Ok(Some(Stop::Finished));
+        }
+        let hits = self.watched_accesses();
+        let location = self.interpreter.location();
+        let values_before: Vec<_> = hits
+            .iter()
+            .map(|&(_, address, _)| self.cell(address))
+            .collect();
+        let status = self.interpreter.step()?;
+        self.steps += 1;
+        for (&(watch, address, access), value_before) in hits.iter().zip(values_before) {
+            self.events.push(WatchEvent {
+                step: self.steps,
+                location: location.expect("the program was running"),
+                watch,
+                address,
+                access,
+                value_before,
+                value_after: self.cell(address),
+            });
+        }
+        if !hits.is_empty() {
+            return Ok(Some(Stop::Watchpoint(hits.len())));
+        }
+        if status == Status::Finished {
+            return Ok(Some(Stop::Finished));
+        }
+        Ok(self
+            .interpreter
+            .location()
+            .filter(|location| self.breakpoints.contains(location))
+            .map(Stop::Breakpoint))
+    }
+
+    // the accesses of the next step to watched cells
+    fn watched_accesses(&self) -> Vec<(Watch, i64, Access)> {
+        let pointer = self.pointer();
+        let accesses = self.interpreter.next_accesses();
+        self.watchpoints
+            .iter()
+            .flat_map(|&watch| {
+                accesses
+                    .iter()
+                    .filter(move |&&(address, _)| address == watch.address(pointer))
+                    .map(move |&(address, access)| (watch, address, access))
+            })
+            .collect()
+    }
+
+    pub(crate) fn status(&self) -> Status {
+        self.interpreter.status()
+    }
+
+    /// The number of steps executed so far.
+    pub(crate) fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The offset of the command that is executed next.
+    pub(crate) fn location(&self) -> Option<usize> {
+        self.interpreter.location()
+    }
+
+    pub(crate) fn pointer(&self) -> i64 {
+        self.interpreter.pointer()
+    }
+
+    pub(crate) fn cell(&self, address: i64) -> u8 {
+        self.interpreter.cell(address)
+    }
+
+    pub(crate) fn set_cell(&mut self, address: i64, value: u8) {
+        self.interpreter.set_cell(address, value);
+    }
+}
+
+// the terminal the user types in, even if stdin is redirected
+#[cfg(not(windows))]
+const TERMINAL: &str = "/dev/tty";
+#[cfg(windows)]
+const TERMINAL: &str = "CONIN$";
+
+/// Opens the terminal, which `--tty-control` reads the commands of the debugger from.
+pub(crate) fn open_terminal() -> Result<File> {
+    File::open(TERMINAL).with_context(|| format!("while opening the terminal {TERMINAL}"))
+}
+
+/// Debugs a program interactively, reading `commands` (usually stdin). The program reads from
+/// `input` and writes to stdout, the debugger writes to stderr.
+pub(crate) fn debug(
+    filename: &Path,
+    source: &[u8],
+    program: &Program,
+    input: impl BufRead,
+    mut commands: impl BufRead,
+) -> Result<()> {
+    let mut debugger = Debugger::new(program, input, io::stdout());
+    eprintln!(
+        "debugging {}, type `help` for a list of commands",
+        filename.display()
+    );
+    print_location(filename, source, &debugger);
+    loop {
+        eprint!("(debug) ");
+        let mut line = String::new();
+        if commands.read_line(&mut line)? == 0 {
+            // end of the commands, e.g. Ctrl+D
+            eprintln!();
+            return Ok(());
+        }
+        let words: Vec<_> = line.split_whitespace().collect();
+        let stop = match words.as_slice() {
+            [] => continue,
+            ["quit" | "q"] => return Ok(()),
+            ["help" | "h"] => {
+                eprintln!("{HELP}");
+                None
+            }
+            ["break" | "b", offset] => {
+                match offset.parse() {
+                    Ok(offset) if debugger.add_breakpoint(offset) => {
+                        eprintln!(
+                            "breakpoint at {}:{} (offset {offset})",
+                            filename.display(),
+                            SourceLocation::from_offset(source, offset)
+                        );
+                    }
+                    Ok(offset) => eprintln!("there is no command at offset {offset}"),
+                    Err(_) => eprintln!("expected a byte offset, got '{offset}'"),
+                }
+                None
+            }
+            ["delete" | "d", offset] => {
+                match offset.parse() {
+                    Ok(offset) if debugger.remove_breakpoint(offset) => {
+                        eprintln!("removed the breakpoint at offset {offset}")
+                    }
+                    Ok(offset) => eprintln!("there is no breakpoint at offset {offset}"),
+                    Err(_) => eprintln!("expected a byte offset, got '{offset}'"),
+                }
+                None
+            }
+            ["breakpoints"] => {
+                if debugger.breakpoints().is_empty() {
+                    eprintln!("there are no breakpoints");
+                }
+                for &offset in debugger.breakpoints() {
+                    eprintln!(
+                        "{}:{} (offset {offset})",
+                        filename.display(),
+                        SourceLocation::from_offset(source, offset)
+                    );
+                }
+                None
+            }
+            ["watch" | "w", cell] => {
+                match Watch::parse(cell) {
+                    Some(watch) if debugger.add_watchpoint(watch) => {
+                        eprintln!("watching {watch}")
+                    }
+                    Some(watch) => eprintln!("{watch} is already watched"),
+                    None => eprintln!("expected an address or p+K, got '{cell}'"),
+                }
+                None
+            }
+            ["unwatch", cell] => {
+                match Watch::parse(cell) {
+                    Some(watch) if debugger.remove_watchpoint(watch) => {
+                        eprintln!("stopped watching {watch}")
+                    }
+                    Some(watch) => eprintln!("{watch} is not watched"),
+                    None => eprintln!("expected an address or p+K, got '{cell}'"),
+                }
+                None
+            }
+            ["watchpoints"] => {
+                if debugger.watchpoints().is_empty() {
+                    eprintln!("there are no watchpoints");
+                }
+                for watch in debugger.watchpoints() {
+                    eprintln!("{watch}");
+                }
+                None
+            }
+            ["events"] => {
+                if debugger.events().is_empty() {
+                    eprintln!("no watched cell has been accessed");
+                }
+                for event in debugger.events() {
+                    print_event(filename, source, event);
+                }
+                None
+            }
+            ["step" | "s"] => Some(debugger.step(1)),
+            ["step" | "s", count] => match count.parse() {
+                Ok(count) => Some(debugger.step(count)),
+                Err(_) => {
+                    eprintln!("expected a number of steps, got '{count}'");
+                    None
+                }
+            },
+            ["continue" | "c"] => Some(debugger.resume()),
+            ["print" | "p"] => {
+                let pointer = debugger.pointer();
+                eprintln!("cell {pointer}: {}", debugger.cell(pointer));
+                None
+            }
+            ["print" | "p", address] => {
+                match address.parse() {
+                    Ok(address) => eprintln!("cell {address}: {}", debugger.cell(address)),
+                    Err(_) => eprintln!("expected an address, got '{address}'"),
+                }
+                None
+            }
+            ["set", address, value] => {
+                match (address.parse(), value.parse()) {
+                    (Ok(address), Ok(value)) => {
+                        debugger.set_cell(address, value);
+                        eprintln!("cell {address}: {value}");
+                    }
+                    _ => eprintln!("expected an address and a value from 0 to 255"),
+                }
+                None
+            }
+            ["pointer"] => {
+                print_location(filename, source, &debugger);
+                None
+            }
+            ["tape"] => {
+                let pointer = debugger.pointer();
+                let cells: Vec<_> = (pointer - TAPE_RADIUS..=pointer + TAPE_RADIUS)
+                    .map(|address| match debugger.cell(address) {
+                        value if address == pointer => format!("[{value}]"),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                eprintln!(
+                    "cells {} to {}: {}",
+                    pointer - TAPE_RADIUS,
+                    pointer + TAPE_RADIUS,
+                    cells.join(" ")
+                );
+                None
+            }
+            _ => {
+                eprintln!("unknown command, type `help` for a list of commands");
+                None
+            }
+        };
+        let Some(stop) = stop else {
+            continue;
+        };
+        // the output should appear before the location the program stopped at
+        io::stdout().flush()?;
+        match stop? {
+            Stop::Breakpoint(offset) => eprintln!("breakpoint at offset {offset}"),
+            Stop::Watchpoint(count) => {
+                let events = debugger.events();
+                for event in &events[events.len() - count..] {
+                    print_event(filename, source, event);
+                }
+            }
+            Stop::Step | Stop::Finished => {}
+        }
+        print_location(filename, source, &debugger);
+    }
+}
+
+fn print_event(filename: &Path, source: &[u8], event: &WatchEvent) {
+    let access = match event.access {
+        Access::Read => format!("read {}", event.value_before),
+        Access::Write => format!("written {} -> {}", event.value_before, event.value_after),
+    };
+    let cell = match event.watch {
+        Watch::Address(_) => event.watch.to_string(),
+        Watch::Relative(_) => format!("{} (cell {})", event.watch, event.address),
+    };
+    eprintln!(
+        "{}:{}: {cell} {access} in step {}",
+        filename.display(),
+        SourceLocation::from_offset(source, event.location),
+        event.step
+    );
+}
+
+fn print_location<I: BufRead, O: Write>(filename: &Path, source: &[u8], debugger: &Debugger<I, O>) {
+    let pointer = debugger.pointer();
+    match debugger.location() {
+        Some(offset) if debugger.status() == Status::Running => eprintln!(
+            "{}:{}: next `{}` (pointer {pointer}, cell {}, step {})",
+            filename.display(),
+            SourceLocation::from_offset(source, offset),
+            source[offset] as char,
+            debugger.cell(pointer),
+            debugger.steps()
+        ),
+        _ => eprintln!(
+            "the program has ended after {} step(s) (pointer {pointer}, cell {})",
+            debugger.steps(),
+            debugger.cell(pointer)
+        ),
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt::{Display, Formatter};
+
+use crate::diagnostics::BYTE_ORDER_MARK;
+
+const MIN_COMMAND_DENSITY: f64 = 0.25;
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signature {
+    Ook,
+    Brainloller,
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Signature::Ook => write!(f, "Ook!"),
+            Signature::Brainloller => write!(f, "Brainloller"),
+        }
+    }
+}
+
+/// Heuristics that tell whether a file is likely to contain Brainfuck source code.
+pub(crate) struct Detection {
+    /// The fraction of non-whitespace bytes that are Brainfuck commands, `None` if the file only
+    /// contains whitespace (an empty program is still a valid program).
+    pub(crate) command_density: Option<f64>,
+    pub(crate) unmatched_closing_brackets: usize,
+    pub(crate) unclosed_opening_brackets: usize,
+    pub(crate) signature: Option<Signature>,
+}
+
+impl Detection {
+    pub(crate) fn of(source: &[u8]) -> Self {
+        let source = source.strip_prefix(BYTE_ORDER_MARK).unwrap_or(source);
+        let mut non_whitespace = 0;
+        let mut commands = 0;
+        let mut depth = 0;
+        let mut unmatched_closing_brackets = 0;
+        for &char in source {
+            if char.is_ascii_whitespace() {
+                continue;
+            }
+            non_whitespace += 1;
+            match char {
+                b'+' | b'-' | b'<' | b'>' | b'.' | b',' => commands += 1,
+                b'[' => {
+                    commands += 1;
+                    depth += 1;
+                }
+                b']' => {
+                    commands += 1;
+                    if depth == 0 {
+                        unmatched_closing_brackets += 1;
+                    } else {
+                        depth -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            command_density: (non_whitespace > 0)
+                .then(|| commands as f64 / non_whitespace as f64),
+            unmatched_closing_brackets,
+            unclosed_opening_brackets: depth,
+            signature: signature(source),
+        }
+    }
+
+    pub(crate) fn is_balanced(&self) -> bool {
+        self.unmatched_closing_brackets == 0 && self.unclosed_opening_brackets == 0
+    }
+
+    /// Describes why the file is probably not Brainfuck, or returns `None` if it probably is.
+    pub(crate) fn problem(&self) -> Option<String> {
+        if let Some(signature) = self.signature {
+            Some(format!(
+                "it looks like {signature} source code, select a matching frontend using --from"
+            ))
+        } else if let Some(density) = self
+            .command_density
+            .filter(|&density| density < MIN_COMMAND_DENSITY)
+        {
+            Some(format!(
+                "only {:.1}% of its characters are Brainfuck commands",
+                density * 100.0
+            ))
+        } else if !self.is_balanced() {
+            Some("its brackets are not balanced".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Detection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.command_density {
+            Some(density) => writeln!(f, "command density: {:.1}%", density * 100.0)?,
+            None => writeln!(f, "command density: - (the file is empty)")?,
+        }
+        if self.is_balanced() {
+            writeln!(f, "brackets: balanced")?;
+        } else {
+            writeln!(
+                f,
+                "brackets: {} unmatched ']', {} unclosed '['",
+                self.unmatched_closing_brackets, self.unclosed_opening_brackets
+            )?;
+        }
+        if let Some(signature) = self.signature {
+            writeln!(f, "signature: {signature}")?;
+        }
+        match self.problem() {
+            None => write!(f, "verdict: probably Brainfuck"),
+            Some(problem) => write!(f, "verdict: probably not Brainfuck ({problem})"),
+        }
+    }
+}
+
+fn signature(source: &[u8]) -> Option<Signature> {
+    if source.starts_with(PNG_MAGIC) {
+        return Some(Signature::Brainloller);
+    }
+    let words = source
+        .split(|char| char.is_ascii_whitespace())
+        .filter(|word| !word.is_empty());
+    let (mut total, mut ook) = (0, 0);
+    for word in words {
+        total += 1;
+        if matches!(word, b"Ook." | b"Ook?" | b"Ook!") {
+            ook += 1;
+        }
+    }
+    (ook >= 2 && ook * 2 >= total).then_some(Signature::Ook)
+}
@@ -0,0 +1,257 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use crate::program::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Help,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Help => write!(f, "help"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) span: Span,
+    pub(crate) message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn warning(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn help(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Help,
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// The UTF-8 byte order mark some Windows editors put at the start of a file.
+pub(crate) const BYTE_ORDER_MARK: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SourceLocation {
+    pub(crate) line: usize,
+    /// Counted in characters, not bytes.
+    pub(crate) column: usize,
+}
+
+impl SourceLocation {
+    /// Lines end with `\n`, `\r\n` or a lone `\r`. A byte order mark is not part of the first
+    /// line.
+    pub(crate) fn from_offset(source: &[u8], offset: usize) -> Self {
+        let preceding = &source[..offset.min(source.len())];
+        let mut line = 1;
+        let mut line_start = if source.starts_with(BYTE_ORDER_MARK) {
+            BYTE_ORDER_MARK.len().min(preceding.len())
+        } else {
+            0
+        };
+        for (index, &char) in preceding.iter().enumerate() {
+            let line_break = match char {
+                b'\n' => true,
+                b'\r' => source.get(index + 1) != Some(&b'\n'),
+                _ => false,
+            };
+            if line_break {
+                line += 1;
+                line_start = index + 1;
+            }
+        }
+        // continuation bytes of multi-byte UTF-8 characters do not start a new column
+        let column = preceding[line_start..]
+            .iter()
+            .filter(|&&byte| byte & 0xC0 != 0x80)
+            .count()
+            + 1;
+        Self { line, column }
+    }
+}
+
+/// Finds the source locations of many offsets into the same source without scanning it from its
+/// start for each of them.
+pub(crate) struct LineIndex<'a> {
+    source: &'a [u8],
+    // the offset of the first character of every line
+    line_starts: Vec<usize>,
+    // the number of characters before every offset, so that columns on long lines are found
+    // without counting them
+    characters: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(source: &'a [u8]) -> Self {
+        let mut line_starts = vec![if source.starts_with(BYTE_ORDER_MARK) {
+            BYTE_ORDER_MARK.len()
+        } else {
+            0
+        }];
+        for (index, &char) in source.iter().enumerate() {
+            let line_break = match char {
+                b'\n' => true,
+                b'\r' => source.get(index + 1) != Some(&b'\n'),
+                _ => false,
+            };
+            if line_break {
+                line_starts.push(index + 1);
+            }
+        }
+        // continuation bytes of multi-byte UTF-8 characters do not start a new column
+        let characters = std::iter::once(0)
+            .chain(source.iter().scan(0, |characters, &byte| {
+                *characters += usize::from(byte & 0xC0 != 0x80);
+                Some(*characters)
+            }))
+            .collect();
+        Self {
+            source,
+            line_starts,
+            characters,
+        }
+    }
+
+    /// The same location as [`SourceLocation::from_offset`].
+    pub(crate) fn location(&self, offset: usize) -> SourceLocation {
+        let offset = offset.min(self.source.len());
+        let line = self
+            .line_starts
+            .partition_point(|&line_start| line_start <= offset)
+            .max(1);
+        let line_start = self.line_starts[line - 1].min(offset);
+        let column = self.characters[offset] - self.characters[line_start] + 1;
+        SourceLocation { line, column }
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+pub(crate) fn report(filename: &Path, source: &[u8], diagnostic: &Diagnostic) {
+    let location = SourceLocation::from_offset(source, diagnostic.span.start);
+    eprintln!(
+        "{}:{}: {}: {}",
+        filename.display(),
+        location,
+        diagnostic.severity,
+        diagnostic.message
+    );
+}
+
+pub(crate) fn report_tool_output(severity: Severity, context: &str, output: &[u8]) {
+    eprintln!("{severity}: {context}:");
+    for line in String::from_utf8_lossy(output).lines() {
+        eprintln!("    {line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect::Detection;
+    use crate::parser::{Dialect, Parser};
+
+    fn location(line: usize, column: usize) -> SourceLocation {
+        SourceLocation { line, column }
+    }
+
+    // the locations of all offsets, including the end of the source
+    fn locations(source: &[u8]) -> Vec<SourceLocation> {
+        (0..=source.len())
+            .map(|offset| SourceLocation::from_offset(source, offset))
+            .collect()
+    }
+
+    #[test]
+    fn crlf_ends_a_line() {
+        let source = b"+\r\n-\r\n>";
+        assert_eq!(SourceLocation::from_offset(source, 1), location(1, 2));
+        assert_eq!(SourceLocation::from_offset(source, 2), location(1, 3));
+        assert_eq!(SourceLocation::from_offset(source, 3), location(2, 1));
+        assert_eq!(SourceLocation::from_offset(source, 6), location(3, 1));
+    }
+
+    #[test]
+    fn lone_cr_ends_a_line() {
+        let source = b"+\r-\n>";
+        assert_eq!(SourceLocation::from_offset(source, 2), location(2, 1));
+        assert_eq!(SourceLocation::from_offset(source, 4), location(3, 1));
+    }
+
+    #[test]
+    fn byte_order_mark_is_not_part_of_the_first_line() {
+        let source = b"\xEF\xBB\xBF+-\r\n.";
+        assert_eq!(SourceLocation::from_offset(source, 3), location(1, 1));
+        assert_eq!(SourceLocation::from_offset(source, 4), location(1, 2));
+        assert_eq!(SourceLocation::from_offset(source, 7), location(2, 1));
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let source = "ä+\r\n€-".as_bytes();
+        assert_eq!(SourceLocation::from_offset(source, 2), location(1, 2));
+        assert_eq!(SourceLocation::from_offset(source, 8), location(2, 2));
+    }
+
+    #[test]
+    fn line_index_agrees_with_from_offset() {
+        for source in [
+            b"\xEF\xBB\xBF+\r\n[-]\r\n\r\n.".as_slice(),
+            b"+\r-\n\r\n>\r",
+            b"\r\n\r\n",
+            b"",
+        ] {
+            let index = LineIndex::new(source);
+            let indexed: Vec<_> = (0..=source.len())
+                .map(|offset| index.location(offset))
+                .collect();
+            assert_eq!(indexed, locations(source), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn windows_files_parse_with_the_same_locations() {
+        let unix = b"+\n[-]\n.";
+        let windows = b"\xEF\xBB\xBF+\r\n[-]\r\n.";
+        let statement_locations = |source: &[u8]| {
+            let program = Parser::new(source, Dialect::default()).parse().unwrap();
+            program
+                .statements()
+                .iter()
+                .map(|statement| SourceLocation::from_offset(source, statement.span().start))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statement_locations(windows),
+            [location(1, 1), location(2, 1), location(3, 1)]
+        );
+        assert_eq!(statement_locations(windows), statement_locations(unix));
+    }
+
+    #[test]
+    fn byte_order_mark_does_not_count_against_detection() {
+        let detection = Detection::of(b"\xEF\xBB\xBF+[-]\r\n");
+        assert_eq!(detection.command_density, Some(1.0));
+    }
+}
@@ -0,0 +1,965 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+
+use crate::linker::{link, planned_link_command};
+use crate::program::{CellSize, Program, Statement, StatementKind};
+use crate::registry::{Backend, BackendOptions, Platform};
+use crate::semantics::Overflow;
+
+/// Translates the optimized program straight into assembly for the host and has a C compiler
+/// assemble and link it, without LLVM. Compiling takes next to no time, but the code is about
+/// as fast as LLVM's at `-O0`: every access of a cell calls a function of the runtime that
+/// grows the tape when needed. Supports x86-64 and AArch64 on Linux.
+pub(crate) struct DirectBackend;
+
+impl Backend for DirectBackend {
+    fn name(&self) -> &'static str {
+        "direct"
+    }
+
+    fn description(&self) -> &'static str {
+        "Executable assembled from code generated without LLVM (fast to compile, slow to run)"
+    }
+
+    fn default_output_filename(&self) -> &'static str {
+        "a.out"
+    }
+
+    fn emit(&self, program: &Program, options: &BackendOptions) -> anyhow::Result<()> {
+        let Some(architecture) = host_architecture() else {
+            bail!("the direct backend only generates code for x86-64 and AArch64 on Linux");
+        };
+        if options.platform != Platform::Host {
+            bail!("the direct backend does not support --target");
+        }
+        // the runtime only implements a growing tape with wrapping cells
+        for (enabled, option) in [
+            (options.embed_selftest, "--embed-selftest"),
+            (options.harden, "--harden"),
+            (options.guard_pages, "--guard-pages"),
+            (options.no_libc, "--no-libc"),
+            (options.bare_metal, "--bare-metal"),
+            (options.constant_output.is_some(), "--const-eval"),
+            (options.prompt.is_some(), "--prompt"),
+            (options.no_echo, "--no-echo"),
+            (options.max_output.is_some(), "--max-output"),
+            (options.runtime.max_memory.is_some(), "--max-memory"),
+            (options.runtime.tape_size.is_some(), "--tape-size"),
+            (
+                options.runtime.overflow == Overflow::Trap,
+                "--overflow trap",
+            ),
+        ] {
+            if enabled {
+                bail!("the direct backend does not support {option}");
+            }
+        }
+
+        let assembly = generate(program, options, architecture);
+        let directory = assembly_directory();
+        let filename = assembly_filename(&directory, options);
+        fs::create_dir_all(&directory)
+            .with_context(|| format!("failed to write {}", filename.display()))?;
+        let result = fs::write(&filename, assembly)
+            .with_context(|| format!("failed to write {}", filename.display()))
+            .and_then(|()| link(&filename, options, false));
+        let _ = fs::remove_dir_all(&directory);
+        result
+    }
+
+    fn plan(&self, options: &BackendOptions) -> Vec<String> {
+        let architecture = host_architecture().map_or("host", |architecture| architecture.name());
+        let directory = assembly_directory();
+        let filename = assembly_filename(&directory, options);
+        vec![
+            format!("generate {architecture} assembly"),
+            format!("write assembly to '{}'", filename.display()),
+            format!(
+                "assemble and link executable: {}",
+                planned_link_command(&filename, options, false)
+            ),
+            format!("remove '{}'", directory.display()),
+        ]
+    }
+}
+
+// the assembly is only needed by the linker, so it is not written next to the executable
+fn assembly_directory() -> PathBuf {
+    std::env::temp_dir().join(format!("brainrust-direct-{}", std::process::id()))
+}
+
+fn assembly_filename(directory: &Path, options: &BackendOptions) -> PathBuf {
+    let name = options
+        .output_filename
+        .file_name()
+        .unwrap_or("a.out".as_ref());
+    directory.join(name).with_extension("s")
+}
+
+fn host_architecture() -> Option<&'static dyn Architecture> {
+    if !cfg!(target_os = "linux") {
+        None
+    } else if cfg!(target_arch = "x86_64") {
+        Some(&X86_64)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(&Aarch64)
+    } else {
+        None
+    }
+}
+
+fn generate(
+    program: &Program,
+    options: &BackendOptions,
+    architecture: &dyn Architecture,
+) -> String {
+    let cell_size = options.runtime.cell_size;
+    let mut code = format!(
+        "/* generated by brainrust {} from {} */\n\n",
+        env!("CARGO_PKG_VERSION"),
+        options.input_filename.display()
+    );
+    code.push_str(&architecture.runtime(cell_size));
+    if !options.initial_tape.is_empty() {
+        code.push_str(&format!(
+            "\t.section .rodata\nbf_initial_tape:\n{}",
+            data(&options.initial_tape, cell_size)
+        ));
+    }
+    code.push_str("\t.text\n\t.globl main\nmain:\n");
+    code.push_str(&architecture.main_start());
+    let initial_extent = options.runtime.initial_tape_extent() as i64;
+    code.push_str(&architecture.cell(initial_extent));
+    code.push_str(&architecture.cell(-initial_extent));
+    if !options.initial_tape.is_empty() {
+        let length = options.initial_tape.len();
+        code.push_str(&architecture.cell(length as i64 - 1));
+        code.push_str(&architecture.cell(0));
+        code.push_str(&architecture.copy_initial_tape(length as u64 * cell_bytes(cell_size)));
+    }
+    let mut generator = Generator {
+        code,
+        architecture,
+        cell_size,
+        eof_value: options.runtime.eof_value(),
+        labels: 0,
+        loops: Vec::new(),
+    };
+    generator.statements(program.statements());
+    let mut code = generator.code;
+    code.push_str(&architecture.main_end());
+    // the stack of the program does not need to be executable
+    code.push_str("\t.section .note.GNU-stack,\"\",%progbits\n");
+    code
+}
+
+// the cells of the initial tape, one directive per cell
+fn data(bytes: &[u8], cell_size: CellSize) -> String {
+    let directive = match cell_size {
+        CellSize::Bits8 => ".byte",
+        CellSize::Bits16 => ".2byte",
+        CellSize::Bits32 => ".4byte",
+        CellSize::Bits64 => ".8byte",
+    };
+    let mut data = String::new();
+    for chunk in bytes.chunks(16) {
+        let values: Vec<_> = chunk.iter().map(u8::to_string).collect();
+        writeln!(data, "\t{directive} {}", values.join(", "))
+            .expect("writing to a string cannot fail");
+    }
+    data
+}
+
+fn cell_bytes(cell_size: CellSize) -> u64 {
+    u64::from(cell_size.bits() / 8)
+}
+
+// log2 of the number of bytes of a cell, to scale indices
+fn cell_shift(cell_size: CellSize) -> u32 {
+    (cell_size.bits() / 8).trailing_zeros()
+}
+
+/// The instructions of one architecture. Every function returns complete lines of assembly.
+/// The generated code keeps the pointer in a callee-saved register and asks the runtime for the
+/// address of a cell whenever it accesses one; the address is then in the "result register".
+trait Architecture {
+    fn name(&self) -> &'static str;
+
+    /// The functions `bf_cell`, which returns the address of the cell at an address and grows
+    /// the tape as needed, `bf_grow` and `bf_dump`, and the variables of the tape.
+    fn runtime(&self, cell_size: CellSize) -> String;
+
+    /// Saves the registers `main` uses and sets the pointer to 0.
+    fn main_start(&self) -> String;
+
+    /// Frees the tape and returns 0 from `main`.
+    fn main_end(&self) -> String;
+
+    /// Puts the address of the cell at the offset relative to the pointer in the result
+    /// register.
+    fn cell(&self, offset: i64) -> String;
+
+    /// Copies `bf_initial_tape` to the cell in the result register.
+    fn copy_initial_tape(&self, bytes: u64) -> String;
+
+    fn move_pointer(&self, amount: i64) -> String;
+
+    fn add(&self, amount: u64, cell_size: CellSize) -> String;
+
+    fn set(&self, value: u64, cell_size: CellSize) -> String;
+
+    /// Keeps the value of the cell in a callee-saved register for `multiply_add`.
+    fn load_factor(&self, cell_size: CellSize) -> String;
+
+    fn branch_if_factor_is_zero(&self, label: usize) -> String;
+
+    /// Adds the kept value multiplied by the factor to the cell.
+    fn multiply_add(&self, factor: u64, cell_size: CellSize) -> String;
+
+    fn branch_if_zero(&self, label: usize, cell_size: CellSize) -> String;
+
+    fn jump(&self, label: usize) -> String;
+
+    fn put_char(&self) -> String;
+
+    /// Reads a byte into the cell, or stores `eof_value` in it (if any) at the end of the input.
+    fn get_char(&self, eof_value: Option<u64>, labels: [usize; 2], cell_size: CellSize) -> String;
+
+    fn store_register(&self, cell_size: CellSize) -> String;
+
+    fn load_register(&self, cell_size: CellSize) -> String;
+
+    fn dump(&self) -> String;
+}
+
+struct Generator<'a> {
+    code: String,
+    architecture: &'a dyn Architecture,
+    cell_size: CellSize,
+    eof_value: Option<u64>,
+    labels: usize,
+    // the labels of the start and the end of the enclosing loops
+    loops: Vec<(usize, usize)>,
+}
+
+impl Generator<'_> {
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn place(&mut self, label: usize) {
+        writeln!(self.code, ".L{label}:").expect("writing to a string cannot fail");
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let architecture = self.architecture;
+        let cell_size = self.cell_size;
+        match statement.kind() {
+            StatementKind::Move(amount) => self.code.push_str(&architecture.move_pointer(*amount)),
+            StatementKind::Add { offset, amount } => {
+                self.code.push_str(&architecture.cell(*offset));
+                self.code
+                    .push_str(&architecture.add(cell_size.wrap(*amount), cell_size));
+            }
+            StatementKind::Set { offset, value } => {
+                self.code.push_str(&architecture.cell(*offset));
+                self.code
+                    .push_str(&architecture.set(u64::from(*value), cell_size));
+            }
+            StatementKind::MultiplyAdd(pairs) => {
+                let end = self.label();
+                self.code.push_str(&architecture.cell(0));
+                self.code.push_str(&architecture.load_factor(cell_size));
+                self.code
+                    .push_str(&architecture.branch_if_factor_is_zero(end));
+                for (offset, factor) in pairs {
+                    self.code.push_str(&architecture.cell(*offset));
+                    self.code
+                        .push_str(&architecture.multiply_add(cell_size.wrap(*factor), cell_size));
+                }
+                self.code.push_str(&architecture.cell(0));
+                self.code.push_str(&architecture.set(0, cell_size));
+                self.place(end);
+            }
+            StatementKind::PutChar => {
+                self.code.push_str(&architecture.cell(0));
+                self.code.push_str(&architecture.put_char());
+            }
+            StatementKind::GetChar => {
+                let labels = [self.label(), self.label()];
+                self.code.push_str(&architecture.cell(0));
+                self.code
+                    .push_str(&architecture.get_char(self.eof_value, labels, cell_size));
+            }
+            StatementKind::Loop(body) => {
+                let (start, end) = (self.label(), self.label());
+                self.place(start);
+                self.code.push_str(&architecture.cell(0));
+                self.code
+                    .push_str(&architecture.branch_if_zero(end, cell_size));
+                self.loops.push((start, end));
+                self.statements(body);
+                self.loops.pop();
+                self.code.push_str(&architecture.jump(start));
+                self.place(end);
+            }
+            StatementKind::If(body) => {
+                let end = self.label();
+                self.code.push_str(&architecture.cell(0));
+                self.code
+                    .push_str(&architecture.branch_if_zero(end, cell_size));
+                self.statements(body);
+                self.place(end);
+            }
+            StatementKind::Store => {
+                self.code.push_str(&architecture.cell(0));
+                self.code.push_str(&architecture.store_register(cell_size));
+            }
+            StatementKind::Load => {
+                self.code.push_str(&architecture.cell(0));
+                self.code.push_str(&architecture.load_register(cell_size));
+            }
+            StatementKind::Break => {
+                let (_, end) = *self.loops.last().expect("checked by the parser");
+                self.code.push_str(&architecture.jump(end));
+            }
+            StatementKind::Continue => {
+                let (start, _) = *self.loops.last().expect("checked by the parser");
+                self.code.push_str(&architecture.jump(start));
+            }
+            StatementKind::DebugDump => self.code.push_str(&architecture.dump()),
+        }
+    }
+}
+
+// joins instructions into lines of assembly
+fn lines(instructions: &[&str]) -> String {
+    let mut code = String::new();
+    for instruction in instructions {
+        if instruction.ends_with(':') {
+            code.push_str(instruction);
+        } else {
+            code.push('\t');
+            code.push_str(instruction);
+        }
+        code.push('\n');
+    }
+    code
+}
+
+/// x86-64 with the System V calling convention. The pointer is kept in `%r12`, the result
+/// register is `%rax` and `%rbx` holds values across calls.
+struct X86_64;
+
+impl X86_64 {
+    // the suffix of instructions and the names of `%rax` and `%rcx` for a cell
+    fn operands(cell_size: CellSize) -> (&'static str, &'static str, &'static str) {
+        match cell_size {
+            CellSize::Bits8 => ("b", "%al", "%cl"),
+            CellSize::Bits16 => ("w", "%ax", "%cx"),
+            CellSize::Bits32 => ("l", "%eax", "%ecx"),
+            CellSize::Bits64 => ("q", "%rax", "%rcx"),
+        }
+    }
+}
+
+impl Architecture for X86_64 {
+    fn name(&self) -> &'static str {
+        "x86-64"
+    }
+
+    fn runtime(&self, cell_size: CellSize) -> String {
+        let scale = cell_bytes(cell_size);
+        let load = match cell_size {
+            CellSize::Bits8 => "movzbl (%rax,%rbx,1), %edi",
+            CellSize::Bits16 => "movzwl (%rax,%rbx,2), %edi",
+            CellSize::Bits32 => "movl (%rax,%rbx,4), %edi",
+            CellSize::Bits64 => "movl (%rax,%rbx,8), %edi",
+        };
+        format!(
+            "\
+\t.bss
+\t.p2align 3
+/* the tape, whose cell at address 0 has the index bf_offset */
+bf_memory:
+\t.zero 8
+bf_capacity:
+\t.zero 8
+bf_offset:
+\t.zero 8
+bf_register:
+\t.zero 8
+
+\t.text
+/* %rdi: address, returns the address of the cell in %rax */
+bf_cell:
+\tmovq bf_offset(%rip), %rax
+\taddq %rdi, %rax
+\tjs 1f
+\tcmpq bf_capacity(%rip), %rax
+\tjae 1f
+\tmovq bf_memory(%rip), %rcx
+\tleaq (%rcx,%rax,{scale}), %rax
+\tret
+1:
+\tpushq %rdi
+\tcallq bf_grow
+\tpopq %rdi
+\tjmp bf_cell
+
+/* %rdi: address outside the tape, grows the tape until it contains the cell */
+bf_grow:
+\tpushq %rbx
+\tpushq %r13
+\tpushq %r14
+\tpushq %r15
+\tsubq $8, %rsp
+\tmovq bf_offset(%rip), %rax
+\taddq %rdi, %rax
+\txorl %r13d, %r13d
+\ttestq %rax, %rax
+\tjns 1f
+\tmovq %rax, %r13
+\tnegq %r13
+1:
+\tmovq bf_capacity(%rip), %r14
+\tleaq 1(%rax,%r13), %r15
+\tleaq (%r14,%r13), %rcx
+\tcmpq %rcx, %r15
+\tjae 2f
+\tmovq %rcx, %r15
+2:
+\tmovq bf_memory(%rip), %rdi
+\tleaq (,%r15,{scale}), %rsi
+\tcallq realloc@PLT
+\ttestq %rax, %rax
+\tjz 3f
+\tmovq %rax, %rbx
+\tleaq (%rbx,%r13,{scale}), %rdi
+\tmovq %rbx, %rsi
+\tleaq (,%r14,{scale}), %rdx
+\tcallq memmove@PLT
+\tmovq %rbx, %rdi
+\txorl %esi, %esi
+\tleaq (,%r13,{scale}), %rdx
+\tcallq memset@PLT
+\tleaq (%r13,%r14), %rax
+\tleaq (%rbx,%rax,{scale}), %rdi
+\txorl %esi, %esi
+\tmovq %r15, %rdx
+\tsubq %rax, %rdx
+\tleaq (,%rdx,{scale}), %rdx
+\tcallq memset@PLT
+\tmovq %rbx, bf_memory(%rip)
+\tmovq %r15, bf_capacity(%rip)
+\taddq %r13, bf_offset(%rip)
+\taddq $8, %rsp
+\tpopq %r15
+\tpopq %r14
+\tpopq %r13
+\tpopq %rbx
+\tret
+3:
+\tcallq abort@PLT
+
+bf_dump:
+\tpushq %rbx
+\tmovl $77, %edi
+\tcallq putchar@PLT
+\tmovl $58, %edi
+\tcallq putchar@PLT
+\txorl %ebx, %ebx
+1:
+\tcmpq bf_capacity(%rip), %rbx
+\tjae 2f
+\tmovq bf_memory(%rip), %rax
+\t{load}
+\taddl $48, %edi
+\tcallq putchar@PLT
+\tincq %rbx
+\tjmp 1b
+2:
+\tmovl $10, %edi
+\tcallq putchar@PLT
+\tpopq %rbx
+\tret
+
+"
+        )
+    }
+
+    fn main_start(&self) -> String {
+        lines(&[
+            "pushq %rbx",
+            "pushq %r12",
+            "subq $8, %rsp",
+            "xorl %r12d, %r12d",
+        ])
+    }
+
+    fn main_end(&self) -> String {
+        lines(&[
+            "movq bf_memory(%rip), %rdi",
+            "callq free@PLT",
+            "xorl %eax, %eax",
+            "addq $8, %rsp",
+            "popq %r12",
+            "popq %rbx",
+            "ret",
+        ])
+    }
+
+    fn cell(&self, offset: i64) -> String {
+        if offset == 0 {
+            lines(&["movq %r12, %rdi", "callq bf_cell"])
+        } else if i32::try_from(offset).is_ok() {
+            lines(&[&format!("leaq {offset}(%r12), %rdi"), "callq bf_cell"])
+        } else {
+            lines(&[
+                &format!("movabsq ${offset}, %rdi"),
+                "addq %r12, %rdi",
+                "callq bf_cell",
+            ])
+        }
+    }
+
+    fn copy_initial_tape(&self, bytes: u64) -> String {
+        lines(&[
+            "movq %rax, %rdi",
+            "leaq bf_initial_tape(%rip), %rsi",
+            &format!("movabsq ${bytes}, %rdx"),
+            "callq memcpy@PLT",
+        ])
+    }
+
+    fn move_pointer(&self, amount: i64) -> String {
+        if i32::try_from(amount).is_ok() {
+            lines(&[&format!("addq ${amount}, %r12")])
+        } else {
+            lines(&[&format!("movabsq ${amount}, %rax"), "addq %rax, %r12"])
+        }
+    }
+
+    fn add(&self, amount: u64, cell_size: CellSize) -> String {
+        let (suffix, _, rcx) = Self::operands(cell_size);
+        lines(&[
+            &format!("movabsq ${amount}, %rcx"),
+            &format!("add{suffix} {rcx}, (%rax)"),
+        ])
+    }
+
+    fn set(&self, value: u64, cell_size: CellSize) -> String {
+        let (suffix, _, rcx) = Self::operands(cell_size);
+        lines(&[
+            &format!("movabsq ${value}, %rcx"),
+            &format!("mov{suffix} {rcx}, (%rax)"),
+        ])
+    }
+
+    fn load_factor(&self, cell_size: CellSize) -> String {
+        lines(&[match cell_size {
+            CellSize::Bits8 => "movzbl (%rax), %ebx",
+            CellSize::Bits16 => "movzwl (%rax), %ebx",
+            CellSize::Bits32 => "movl (%rax), %ebx",
+            CellSize::Bits64 => "movq (%rax), %rbx",
+        }])
+    }
+
+    fn branch_if_factor_is_zero(&self, label: usize) -> String {
+        lines(&["testq %rbx, %rbx", &format!("jz .L{label}")])
+    }
+
+    fn multiply_add(&self, factor: u64, cell_size: CellSize) -> String {
+        let (suffix, _, rcx) = Self::operands(cell_size);
+        // multiplying 64-bit values wraps around like multiplying cells
+        lines(&[
+            &format!("movabsq ${factor}, %rcx"),
+            "imulq %rbx, %rcx",
+            &format!("add{suffix} {rcx}, (%rax)"),
+        ])
+    }
+
+    fn branch_if_zero(&self, label: usize, cell_size: CellSize) -> String {
+        let (suffix, _, _) = Self::operands(cell_size);
+        lines(&[&format!("cmp{suffix} $0, (%rax)"), &format!("je .L{label}")])
+    }
+
+    fn jump(&self, label: usize) -> String {
+        lines(&[&format!("jmp .L{label}")])
+    }
+
+    fn put_char(&self) -> String {
+        lines(&["movzbl (%rax), %edi", "callq putchar@PLT"])
+    }
+
+    fn get_char(&self, eof_value: Option<u64>, labels: [usize; 2], cell_size: CellSize) -> String {
+        let (suffix, rax, rcx) = Self::operands(cell_size);
+        let [eof, end] = labels;
+        let mut code = lines(&[
+            "movq %rax, %rbx",
+            "callq getchar@PLT",
+            "cmpl $-1, %eax",
+            &format!("je .L{eof}"),
+            "movzbl %al, %eax",
+            &format!("mov{suffix} {rax}, (%rbx)"),
+            &format!("jmp .L{end}"),
+            &format!(".L{eof}:"),
+        ]);
+        if let Some(value) = eof_value {
+            code.push_str(&lines(&[
+                &format!("movabsq ${value}, %rcx"),
+                &format!("mov{suffix} {rcx}, (%rbx)"),
+            ]));
+        }
+        code.push_str(&lines(&[&format!(".L{end}:")]));
+        code
+    }
+
+    fn store_register(&self, cell_size: CellSize) -> String {
+        let (suffix, _, rcx) = Self::operands(cell_size);
+        lines(&[
+            &format!("mov{suffix} (%rax), {rcx}"),
+            &format!("mov{suffix} {rcx}, bf_register(%rip)"),
+        ])
+    }
+
+    fn load_register(&self, cell_size: CellSize) -> String {
+        let (suffix, _, rcx) = Self::operands(cell_size);
+        lines(&[
+            &format!("mov{suffix} bf_register(%rip), {rcx}"),
+            &format!("mov{suffix} {rcx}, (%rax)"),
+        ])
+    }
+
+    fn dump(&self) -> String {
+        lines(&["callq bf_dump"])
+    }
+}
+
+/// AArch64 with the standard procedure call standard. The pointer is kept in `x19`, the result
+/// register is `x0` and `x20` and `x21` hold values across calls.
+struct Aarch64;
+
+impl Aarch64 {
+    // the suffix of loads and stores and the kind of register for a cell
+    fn operands(cell_size: CellSize) -> (&'static str, &'static str) {
+        match cell_size {
+            CellSize::Bits8 => ("b", "w"),
+            CellSize::Bits16 => ("h", "w"),
+            CellSize::Bits32 => ("", "w"),
+            CellSize::Bits64 => ("", "x"),
+        }
+    }
+
+    // moves a 64-bit constant into a register, 16 bits at a time
+    fn constant(register: &str, value: u64) -> String {
+        let mut code = lines(&[&format!("movz {register}, #{}", value & 0xffff)]);
+        for shift in [16, 32, 48] {
+            let part = (value >> shift) & 0xffff;
+            if part != 0 {
+                code.push_str(&lines(&[&format!(
+                    "movk {register}, #{part}, lsl #{shift}"
+                )]));
+            }
+        }
+        code
+    }
+
+    // the address of a variable of the runtime
+    fn variable(register: &str, name: &str) -> String {
+        lines(&[
+            &format!("adrp {register}, {name}"),
+            &format!("add {register}, {register}, :lo12:{name}"),
+        ])
+    }
+}
+
+impl Architecture for Aarch64 {
+    fn name(&self) -> &'static str {
+        "AArch64"
+    }
+
+    fn runtime(&self, cell_size: CellSize) -> String {
+        let shift = cell_shift(cell_size);
+        let (suffix, kind) = Self::operands(cell_size);
+        format!(
+            "\
+\t.bss
+\t.p2align 3
+/* the tape, whose cell at address 0 has the index bf_offset */
+bf_memory:
+\t.zero 8
+bf_capacity:
+\t.zero 8
+bf_offset:
+\t.zero 8
+bf_register:
+\t.zero 8
+
+\t.text
+/* x0: address, returns the address of the cell in x0 */
+bf_cell:
+\tadrp x9, bf_offset
+\tldr x10, [x9, :lo12:bf_offset]
+\tadd x10, x10, x0
+\ttbnz x10, #63, 1f
+\tadrp x11, bf_capacity
+\tldr x11, [x11, :lo12:bf_capacity]
+\tcmp x10, x11
+\tb.hs 1f
+\tadrp x11, bf_memory
+\tldr x11, [x11, :lo12:bf_memory]
+\tadd x0, x11, x10, lsl #{shift}
+\tret
+1:
+\tstp x0, x30, [sp, #-16]!
+\tbl bf_grow
+\tldp x0, x30, [sp], #16
+\tb bf_cell
+
+/* x0: address outside the tape, grows the tape until it contains the cell */
+bf_grow:
+\tstp x29, x30, [sp, #-48]!
+\tmov x29, sp
+\tstp x19, x20, [sp, #16]
+\tstp x21, x22, [sp, #32]
+\tadrp x9, bf_offset
+\tldr x9, [x9, :lo12:bf_offset]
+\tadd x9, x9, x0
+\tmov x20, #0
+\tcmp x9, #0
+\tb.ge 1f
+\tneg x20, x9
+1:
+\tadrp x10, bf_capacity
+\tldr x21, [x10, :lo12:bf_capacity]
+\tadd x22, x9, x20
+\tadd x22, x22, #1
+\tadd x10, x21, x20
+\tcmp x22, x10
+\tcsel x22, x22, x10, hs
+\tadrp x10, bf_memory
+\tldr x0, [x10, :lo12:bf_memory]
+\tlsl x1, x22, #{shift}
+\tbl realloc
+\tcbz x0, 2f
+\tmov x19, x0
+\tadd x0, x19, x20, lsl #{shift}
+\tmov x1, x19
+\tlsl x2, x21, #{shift}
+\tbl memmove
+\tmov x0, x19
+\tmov w1, #0
+\tlsl x2, x20, #{shift}
+\tbl memset
+\tadd x9, x20, x21
+\tadd x0, x19, x9, lsl #{shift}
+\tmov w1, #0
+\tsub x2, x22, x9
+\tlsl x2, x2, #{shift}
+\tbl memset
+\tadrp x10, bf_memory
+\tstr x19, [x10, :lo12:bf_memory]
+\tadrp x10, bf_capacity
+\tstr x22, [x10, :lo12:bf_capacity]
+\tadrp x10, bf_offset
+\tldr x9, [x10, :lo12:bf_offset]
+\tadd x9, x9, x20
+\tstr x9, [x10, :lo12:bf_offset]
+\tldp x21, x22, [sp, #32]
+\tldp x19, x20, [sp, #16]
+\tldp x29, x30, [sp], #48
+\tret
+2:
+\tbl abort
+
+bf_dump:
+\tstp x29, x30, [sp, #-32]!
+\tmov x29, sp
+\tstr x19, [sp, #16]
+\tmov w0, #77
+\tbl putchar
+\tmov w0, #58
+\tbl putchar
+\tmov x19, #0
+1:
+\tadrp x9, bf_capacity
+\tldr x9, [x9, :lo12:bf_capacity]
+\tcmp x19, x9
+\tb.hs 2f
+\tadrp x9, bf_memory
+\tldr x9, [x9, :lo12:bf_memory]
+\tldr{suffix} {kind}0, [x9, x19, lsl #{shift}]
+\tadd w0, w0, #48
+\tbl putchar
+\tadd x19, x19, #1
+\tb 1b
+2:
+\tmov w0, #10
+\tbl putchar
+\tldr x19, [sp, #16]
+\tldp x29, x30, [sp], #32
+\tret
+
+"
+        )
+    }
+
+    fn main_start(&self) -> String {
+        lines(&[
+            "stp x29, x30, [sp, #-32]!",
+            "mov x29, sp",
+            "stp x19, x20, [sp, #16]",
+            "str x21, [sp, #-16]!",
+            "mov x19, #0",
+        ])
+    }
+
+    fn main_end(&self) -> String {
+        let mut code = Self::variable("x0", "bf_memory");
+        code.push_str(&lines(&[
+            "ldr x0, [x0]",
+            "bl free",
+            "mov w0, #0",
+            "ldr x21, [sp], #16",
+            "ldp x19, x20, [sp, #16]",
+            "ldp x29, x30, [sp], #32",
+            "ret",
+        ]));
+        code
+    }
+
+    fn cell(&self, offset: i64) -> String {
+        if offset == 0 {
+            return lines(&["mov x0, x19", "bl bf_cell"]);
+        }
+        let mut code = Self::constant("x9", offset as u64);
+        code.push_str(&lines(&["add x0, x19, x9", "bl bf_cell"]));
+        code
+    }
+
+    fn copy_initial_tape(&self, bytes: u64) -> String {
+        let mut code = Self::variable("x1", "bf_initial_tape");
+        code.push_str(&Self::constant("x2", bytes));
+        code.push_str(&lines(&["bl memcpy"]));
+        code
+    }
+
+    fn move_pointer(&self, amount: i64) -> String {
+        let mut code = Self::constant("x9", amount as u64);
+        code.push_str(&lines(&["add x19, x19, x9"]));
+        code
+    }
+
+    fn add(&self, amount: u64, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        let mut code = Self::constant("x2", amount);
+        code.push_str(&lines(&[
+            &format!("ldr{suffix} {kind}1, [x0]"),
+            &format!("add {kind}1, {kind}1, {kind}2"),
+            &format!("str{suffix} {kind}1, [x0]"),
+        ]));
+        code
+    }
+
+    fn set(&self, value: u64, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        let mut code = Self::constant("x1", value);
+        code.push_str(&lines(&[&format!("str{suffix} {kind}1, [x0]")]));
+        code
+    }
+
+    fn load_factor(&self, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        lines(&[&format!("ldr{suffix} {kind}20, [x0]")])
+    }
+
+    fn branch_if_factor_is_zero(&self, label: usize) -> String {
+        lines(&[&format!("cbz x20, .L{label}")])
+    }
+
+    fn multiply_add(&self, factor: u64, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        // multiplying 64-bit values wraps around like multiplying cells
+        let mut code = Self::constant("x2", factor);
+        code.push_str(&lines(&[
+            "mul x2, x20, x2",
+            &format!("ldr{suffix} {kind}1, [x0]"),
+            &format!("add {kind}1, {kind}1, {kind}2"),
+            &format!("str{suffix} {kind}1, [x0]"),
+        ]));
+        code
+    }
+
+    fn branch_if_zero(&self, label: usize, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        // conditional branches only reach 1 MiB, the end of a loop may be further away
+        lines(&[
+            &format!("ldr{suffix} {kind}1, [x0]"),
+            "cbnz x1, 1f",
+            &format!("b .L{label}"),
+            "1:",
+        ])
+    }
+
+    fn jump(&self, label: usize) -> String {
+        lines(&[&format!("b .L{label}")])
+    }
+
+    fn put_char(&self) -> String {
+        lines(&["ldrb w0, [x0]", "bl putchar"])
+    }
+
+    fn get_char(&self, eof_value: Option<u64>, labels: [usize; 2], cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        let [eof, end] = labels;
+        let mut code = lines(&[
+            "mov x21, x0",
+            "bl getchar",
+            "cmn w0, #1",
+            &format!("b.eq .L{eof}"),
+            "and w0, w0, #0xff",
+            &format!("str{suffix} {kind}0, [x21]"),
+            &format!("b .L{end}"),
+            &format!(".L{eof}:"),
+        ]);
+        if let Some(value) = eof_value {
+            code.push_str(&Self::constant("x1", value));
+            code.push_str(&lines(&[&format!("str{suffix} {kind}1, [x21]")]));
+        }
+        code.push_str(&lines(&[&format!(".L{end}:")]));
+        code
+    }
+
+    fn store_register(&self, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        let mut code = lines(&[&format!("ldr{suffix} {kind}1, [x0]")]);
+        code.push_str(&Self::variable("x2", "bf_register"));
+        code.push_str(&lines(&[&format!("str{suffix} {kind}1, [x2]")]));
+        code
+    }
+
+    fn load_register(&self, cell_size: CellSize) -> String {
+        let (suffix, kind) = Self::operands(cell_size);
+        let mut code = Self::variable("x2", "bf_register");
+        code.push_str(&lines(&[
+            &format!("ldr{suffix} {kind}1, [x2]"),
+            &format!("str{suffix} {kind}1, [x0]"),
+        ]));
+        code
+    }
+
+    fn dump(&self) -> String {
+        lines(&["bl bf_dump"])
+    }
+}
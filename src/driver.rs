@@ -0,0 +1,680 @@
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches};
+
+use crate::analysis::{for_each_loop, step_bound};
+use crate::bench::bench;
+use crate::bundle::{bundle, Bundle};
+use crate::check::check;
+use crate::clean::{clean, record_outputs};
+use crate::command_line_arguments::{
+    check_register_commands, CommandLineArguments, CorpusCommand, FormatStyle, Subcommand,
+};
+use crate::conformance::conformance;
+use crate::corpus;
+use crate::coverage::Coverage;
+use crate::crash;
+use crate::dap;
+use crate::debugger::{debug, open_terminal};
+use crate::detect::Detection;
+use crate::diagnostics::SourceLocation;
+use crate::engine;
+use crate::environment::EnvironmentReport;
+use crate::format::format;
+use crate::frames::FrameExporter;
+use crate::generator::generate;
+use crate::interpreter::{
+    evaluate, interpret, interpret_profiled, interpret_traced, interpret_with_frames,
+    interpret_with_statistics, InterpreterError, InterpreterOptions, Tracer,
+};
+use crate::loops::loop_summary;
+use crate::optimizer::optimize;
+use crate::parser::Parser;
+use crate::pass_plugin::PassPlugin;
+use crate::profile::{callgrind, folded_stacks, print_profile, print_runtime_statistics};
+use crate::program::{CellSize, Program, Span};
+use crate::registry::{Backend, BackendOptions, BrainfuckFrontend, Frontend, Registry};
+use crate::slice::slice;
+use crate::stats::stats;
+use crate::superopt::superopt;
+use crate::trace;
+use crate::usage_log;
+use crate::verify::verify;
+
+// the exit code of `timeout`, distinguishes programs that ran too long from other errors
+const STEP_LIMIT_EXIT_CODE: i32 = 124;
+
+// --const-eval gives up on programs that run longer than this, they are compiled normally
+const CONST_EVAL_STEP_LIMIT: u64 = 100_000_000;
+
+/// Runs the command line interface using the frontends and backends of the given registry.
+/// If the running executable is a bundle, the embedded program is run instead. Panics produce
+/// a crash report instead of the default panic output.
+pub fn run(registry: Registry) -> Result<()> {
+    crash::install_handler();
+
+    if let Some(bundle) = Bundle::embedded()? {
+        return bundle.run();
+    }
+
+    let frontends = registry
+        .frontends()
+        .map(|frontend| PossibleValue::new(frontend.name()).help(frontend.description()));
+    let backends = registry
+        .backends()
+        .map(|backend| PossibleValue::new(backend.name()).help(backend.description()));
+    let matches = CommandLineArguments::command()
+        .mut_arg("frontend", |arg| {
+            arg.value_parser(PossibleValuesParser::new(frontends))
+        })
+        .mut_arg("backend", |arg| {
+            arg.value_parser(PossibleValuesParser::new(backends))
+        })
+        .get_matches();
+    if let Err(message) = check_register_commands(&matches) {
+        CommandLineArguments::command()
+            .error(ErrorKind::ArgumentConflict, message)
+            .exit();
+    }
+    let command_line_arguments =
+        CommandLineArguments::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+
+    let start = Instant::now();
+    let result = execute_command(&registry, &command_line_arguments);
+    if let Some(filename) = &command_line_arguments.stats_log {
+        let command = match matches.subcommand_name() {
+            Some(name) => name,
+            None if command_line_arguments.interpret => "interpret",
+            None => "compile",
+        };
+        usage_log::append(filename, command, start.elapsed(), result.is_ok());
+    }
+    if let Some(error @ InterpreterError::StepLimitExceeded { .. }) =
+        result.as_ref().err().and_then(|error| error.downcast_ref())
+    {
+        eprintln!("Error: {error}");
+        std::process::exit(STEP_LIMIT_EXIT_CODE);
+    }
+    result
+}
+
+fn execute_command(
+    registry: &Registry,
+    command_line_arguments: &CommandLineArguments,
+) -> Result<()> {
+    match &command_line_arguments.subcommand {
+        Some(Subcommand::Run(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = optimize(&Parser::new(&source, arguments.dialect()).parse()?);
+            engine::run(registry, arguments, &source, &program)
+        }
+        Some(Subcommand::Check(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            check(&arguments.input_filename, &source, &program);
+            Ok(())
+        }
+        Some(Subcommand::Stats(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            stats(&program);
+            Ok(())
+        }
+        Some(Subcommand::Loops(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            print!("{}", loop_summary(&source, &program));
+            Ok(())
+        }
+        Some(Subcommand::Bench(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = optimize(&Parser::new(&source, arguments.dialect()).parse()?);
+            bench(&program, arguments.input.as_deref())
+        }
+        Some(Subcommand::Verify(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            verify(
+                &arguments.input_filename,
+                &source,
+                &program,
+                arguments.steps,
+                arguments.tape_size,
+            )
+        }
+        Some(Subcommand::Superopt(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            superopt(
+                &arguments.input_filename,
+                &source,
+                &program,
+                arguments.window.into(),
+                arguments.steps,
+            );
+            Ok(())
+        }
+        Some(Subcommand::Info(arguments)) => {
+            let source = arguments
+                .input_filename
+                .as_deref()
+                .map(read_source)
+                .transpose()?;
+            print!("{}", EnvironmentReport::collect(source.as_deref()));
+            Ok(())
+        }
+        Some(Subcommand::GenRandom(arguments)) => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&generate(arguments.seed, &arguments.options()))?;
+            writeln!(stdout)?;
+            Ok(())
+        }
+        Some(Subcommand::Corpus(arguments)) => match &arguments.command {
+            CorpusCommand::Fetch(arguments) => corpus::fetch(&arguments.directory),
+            CorpusCommand::Verify(arguments) => corpus::verify(&arguments.directory),
+        },
+        Some(Subcommand::Detect(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            println!("{}", Detection::of(&source));
+            Ok(())
+        }
+        Some(Subcommand::Match(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            match program.matching_bracket(arguments.offset) {
+                Some(offset) => {
+                    println!("{offset}");
+                    Ok(())
+                }
+                None => bail!("there is no bracket at offset {}", arguments.offset),
+            }
+        }
+        Some(Subcommand::Bundle(arguments)) => bundle(
+            &arguments.input_filename,
+            &arguments.output_filename,
+            arguments.dialect(),
+        ),
+        Some(Subcommand::Clean(arguments)) => clean(
+            &arguments.input_filename,
+            arguments.cache,
+            arguments.dry_run,
+        ),
+        Some(Subcommand::Fmt(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            let formatted = format(&program, arguments.dialect(), arguments.style);
+            if arguments.in_place {
+                std::fs::write(&arguments.input_filename, formatted)?;
+            } else {
+                io::stdout().write_all(&formatted)?;
+            }
+            Ok(())
+        }
+        Some(Subcommand::Slice(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            let input = match &arguments.input {
+                Some(filename) => std::fs::read(filename)?,
+                None => Vec::new(),
+            };
+            let sliced = slice(&program, arguments.criterion(), &input, arguments.steps)?;
+            io::stdout().write_all(&format(
+                &sliced,
+                arguments.dialect(),
+                FormatStyle::Canonical,
+            ))?;
+            Ok(())
+        }
+        Some(Subcommand::Debug(arguments)) => {
+            let source = read_source(&arguments.input_filename)?;
+            let program = Parser::new(&source, arguments.dialect()).parse()?;
+            let input: Box<dyn BufRead> = match &arguments.input {
+                Some(filename) => Box::new(io::BufReader::new(
+                    std::fs::File::open(filename)
+                        .with_context(|| format!("while opening {}", filename.display()))?,
+                )),
+                // the commands are read from the terminal, which leaves stdin to the program
+                None if arguments.tty_control => Box::new(io::stdin().lock()),
+                None => Box::new(io::empty()),
+            };
+            let commands: Box<dyn BufRead> = if arguments.tty_control {
+                Box::new(io::BufReader::new(open_terminal()?))
+            } else {
+                Box::new(io::stdin().lock())
+            };
+            debug(
+                &arguments.input_filename,
+                &source,
+                &program,
+                input,
+                commands,
+            )
+        }
+        Some(Subcommand::Conformance(arguments)) => conformance(registry, arguments),
+        Some(Subcommand::Dap) => dap::serve(),
+        None => compile_or_interpret(registry, command_line_arguments),
+    }
+}
+
+fn read_source(filename: &Path) -> io::Result<Vec<u8>> {
+    let source = std::fs::read(filename)?;
+    crash::record_source(&source);
+    usage_log::record_program_size(source.len());
+    Ok(source)
+}
+
+fn frontend<'a>(
+    registry: &'a Registry,
+    command_line_arguments: &CommandLineArguments,
+) -> Result<&'a dyn Frontend> {
+    registry
+        .frontend(&command_line_arguments.frontend)
+        .ok_or_else(|| anyhow!("unknown frontend '{}'", command_line_arguments.frontend))
+}
+
+fn backend<'a>(
+    registry: &'a Registry,
+    command_line_arguments: &CommandLineArguments,
+) -> Result<&'a dyn Backend> {
+    registry
+        .backend(command_line_arguments.backend())
+        .ok_or_else(|| {
+            let available = registry
+                .backends()
+                .map(|backend| backend.name())
+                .collect::<Vec<_>>();
+            anyhow!(
+                "unknown backend '{}' (available backends: {})",
+                command_line_arguments.backend(),
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )
+        })
+}
+
+fn backend_options(
+    backend: &dyn Backend,
+    command_line_arguments: &CommandLineArguments,
+    source: Option<&[u8]>,
+) -> BackendOptions {
+    BackendOptions {
+        input_filename: command_line_arguments.input_filename().to_path_buf(),
+        output_filename: command_line_arguments
+            .output_filename
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(backend.default_output_filename())),
+        optimization_level: command_line_arguments.optimization_level,
+        verbose: command_line_arguments.verbose,
+        environment: EnvironmentReport::collect(source),
+        platform: command_line_arguments.platform,
+        prompt: command_line_arguments.prompt.clone(),
+        no_echo: command_line_arguments.no_echo,
+        embed_selftest: command_line_arguments.embed_selftest,
+        harden: command_line_arguments.harden,
+        constant_output: None,
+        max_output: command_line_arguments.max_output,
+        runtime: command_line_arguments.runtime_config(),
+        initial_tape: Vec::new(),
+        guard_pages: command_line_arguments.guard_pages,
+        no_libc: command_line_arguments.no_libc,
+        bare_metal: command_line_arguments.bare_metal,
+        linker: command_line_arguments.linker.clone(),
+        static_executable: command_line_arguments.static_executable,
+        link_arguments: command_line_arguments.link_arguments(),
+        jit_profiling: command_line_arguments.jit_profiling,
+    }
+}
+
+fn print_plan(registry: &Registry, command_line_arguments: &CommandLineArguments) -> Result<()> {
+    let mut steps = vec![
+        format!(
+            "read source file '{}'",
+            command_line_arguments.input_filename().display()
+        ),
+        format!(
+            "parse program using the '{}' frontend",
+            frontend(registry, command_line_arguments)?.name()
+        ),
+    ];
+    let pipeline = command_line_arguments.pipeline();
+    if !pipeline.passes().is_empty() {
+        steps.push(format!(
+            "optimize program using the passes {}",
+            pipeline
+                .passes()
+                .iter()
+                .map(|pass| pass.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    for filename in &command_line_arguments.pass_plugins {
+        steps.push(format!(
+            "optimize program using the pass loaded from '{}'",
+            filename.display()
+        ));
+    }
+    if command_line_arguments.interpret {
+        steps.push("interpret program".to_string());
+    } else {
+        if command_line_arguments.const_eval {
+            steps.push(format!(
+                "evaluate program to precompute its output, unless not all of its loops are proven to terminate and it runs for more than {CONST_EVAL_STEP_LIMIT} steps"
+            ));
+        }
+        let backend = backend(registry, command_line_arguments)?;
+        steps.extend(backend.plan(&backend_options(backend, command_line_arguments, None)));
+    }
+    for (number, step) in steps.iter().enumerate() {
+        println!("{}. {step}", number + 1);
+    }
+    Ok(())
+}
+
+fn compile_or_interpret(
+    registry: &Registry,
+    command_line_arguments: &CommandLineArguments,
+) -> Result<()> {
+    if command_line_arguments.dry_run {
+        return print_plan(registry, command_line_arguments);
+    }
+
+    let Some(trace_filename) = &command_line_arguments.trace_compile else {
+        return process(registry, command_line_arguments);
+    };
+    trace::start();
+    let result = process(registry, command_line_arguments);
+    trace::write(trace_filename)?;
+    result
+}
+
+fn process(registry: &Registry, command_line_arguments: &CommandLineArguments) -> Result<()> {
+    let source = read_source(command_line_arguments.input_filename())?;
+
+    let frontend = frontend(registry, command_line_arguments)?;
+    if frontend.name() == BrainfuckFrontend.name() {
+        if let Some(problem) = Detection::of(&source).problem() {
+            eprintln!(
+                "{}: warning: this file does not look like Brainfuck: {problem}",
+                command_line_arguments.input_filename().display()
+            );
+        }
+    }
+    let parsed_program = {
+        let _span = trace::span("frontend", format!("parse ({})", frontend.name()));
+        frontend.parse(&source, command_line_arguments.dialect())?
+    };
+    // located in the parsed program, since the optimizer replaces some loops
+    let trace_range = match command_line_arguments.trace_loop {
+        Some(location) => Some(loop_span(&parsed_program, &source, location)?),
+        None => command_line_arguments.trace_range,
+    };
+    let pipeline = command_line_arguments.pipeline();
+    let program = pipeline.run_each(&parsed_program, |pass, program| {
+        let _span = trace::span("optimizer", pass.name());
+        pass.run_for(program, pipeline.cell_size())
+    });
+    let program = run_pass_plugins(
+        program,
+        &command_line_arguments.pass_plugins,
+        pipeline.cell_size(),
+    )?;
+    let initial_tape = match &command_line_arguments.initial_tape {
+        Some(filename) => std::fs::read(filename)
+            .with_context(|| format!("while reading {}", filename.display()))?,
+        None => Vec::new(),
+    };
+    let interpreter_options = InterpreterOptions {
+        initial_tape: initial_tape.clone(),
+        ..command_line_arguments.interpreter_options()
+    };
+
+    if command_line_arguments.interpret {
+        usage_log::record_engine("interpreter");
+        let _span = trace::span("interpreter", "interpret");
+        if let Some(trace_filename) = &command_line_arguments.trace {
+            let output: Box<dyn Write> = if trace_filename == Path::new("-") {
+                Box::new(io::BufWriter::new(io::stderr()))
+            } else {
+                Box::new(io::BufWriter::new(
+                    std::fs::File::create(trace_filename)
+                        .with_context(|| format!("while creating {}", trace_filename.display()))?,
+                ))
+            };
+            let mut tracer = Tracer::new(output, &source, trace_range);
+            interpret_traced(&program, &interpreter_options, &mut tracer)?;
+        } else if command_line_arguments.profile
+            || command_line_arguments.callgrind.is_some()
+            || command_line_arguments.folded_stacks.is_some()
+            || command_line_arguments.coverage
+            || command_line_arguments.heatmap.is_some()
+        {
+            let (profile, result) = interpret_profiled(&program, &interpreter_options);
+            if command_line_arguments.profile {
+                print_profile(command_line_arguments.input_filename(), &source, &profile);
+            }
+            if let Some(filename) = &command_line_arguments.callgrind {
+                std::fs::write(
+                    filename,
+                    callgrind(command_line_arguments.input_filename(), &source, &profile),
+                )
+                .with_context(|| format!("while writing {}", filename.display()))?;
+            }
+            if let Some(filename) = &command_line_arguments.folded_stacks {
+                std::fs::write(filename, folded_stacks(&source, &profile))
+                    .with_context(|| format!("while writing {}", filename.display()))?;
+            }
+            let coverage = Coverage::new(&parsed_program, &profile);
+            if command_line_arguments.coverage {
+                coverage.print_summary(command_line_arguments.input_filename(), &source);
+                if let Some(filename) = &command_line_arguments.coverage_annotated {
+                    std::fs::write(filename, coverage.annotate(&source))
+                        .with_context(|| format!("while writing {}", filename.display()))?;
+                }
+            }
+            if let Some(filename) = &command_line_arguments.heatmap {
+                std::fs::write(
+                    filename,
+                    coverage.heatmap(command_line_arguments.input_filename(), &source),
+                )
+                .with_context(|| format!("while writing {}", filename.display()))?;
+            }
+            result?;
+        } else if command_line_arguments.runtime_stats {
+            let (statistics, result) = interpret_with_statistics(&program, &interpreter_options);
+            print_runtime_statistics(&statistics);
+            result?;
+        } else if let Some(directory) = &command_line_arguments.export_frames {
+            std::fs::create_dir_all(directory)
+                .with_context(|| format!("while creating {}", directory.display()))?;
+            let mut frames =
+                FrameExporter::new(directory.clone(), command_line_arguments.frame_interval);
+            let result = interpret_with_frames(&program, &interpreter_options, &mut frames);
+            eprintln!(
+                "wrote {} frame(s) to {}",
+                frames.frames(),
+                directory.display()
+            );
+            result?;
+        } else {
+            interpret(&program, &interpreter_options)?;
+        }
+    } else {
+        let backend = backend(registry, command_line_arguments)?;
+        usage_log::record_engine(backend.name());
+        let mut options = backend_options(backend, command_line_arguments, Some(&source));
+        options.initial_tape = initial_tape;
+        if command_line_arguments.const_eval {
+            let _span = trace::span("interpreter", "evaluate");
+            options.constant_output =
+                constant_output(&program, interpreter_options, CONST_EVAL_STEP_LIMIT)?;
+        }
+        let _span = trace::span("backend", format!("emit ({})", backend.name()));
+        backend.emit(&program, &options)?;
+        record_outputs(
+            command_line_arguments.input_filename(),
+            &backend.output_files(&options),
+        );
+    }
+
+    Ok(())
+}
+
+// The output of the program for --const-eval, or None if the program is still running after
+// `step_limit` steps (it may never terminate) and has to be compiled normally.
+fn constant_output(
+    program: &Program,
+    options: InterpreterOptions,
+    step_limit: u64,
+) -> Result<Option<Vec<u8>>> {
+    // a program whose loops all terminate is evaluated to completion, however long it runs
+    let proven = step_bound(program.statements(), options.runtime.cell_size).is_some();
+    let options = InterpreterOptions {
+        max_steps: (!proven).then_some(step_limit),
+        ..options
+    };
+    match evaluate(program, &options) {
+        Err(InterpreterError::StepLimitExceeded { .. }) => {
+            eprintln!(
+                "warning: the program is still running after {step_limit} steps, compiling it without --const-eval"
+            );
+            Ok(None)
+        }
+        result => Ok(Some(result?)),
+    }
+}
+
+// The span of the loop whose `[` is at the given location.
+fn loop_span(program: &Program, source: &[u8], location: SourceLocation) -> Result<Span> {
+    let mut span = None;
+    for_each_loop(program.statements(), &mut |statement, _| {
+        if span.is_none() && SourceLocation::from_offset(source, statement.span().start) == location
+        {
+            span = Some(statement.span());
+        }
+    });
+    span.ok_or_else(|| anyhow!("there is no loop at {location}"))
+}
+
+fn run_pass_plugins(
+    mut program: Program,
+    filenames: &[PathBuf],
+    cell_size: CellSize,
+) -> Result<Program> {
+    for filename in filenames {
+        let plugin = PassPlugin::load(filename)?;
+        let _span = trace::span("optimizer", plugin.name());
+        program = plugin.run(&program, cell_size)?;
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Dialect;
+    use crate::registry::Platform;
+    use clap::Parser as _;
+
+    fn parse(source: &[u8]) -> Program {
+        Parser::new(source, Dialect::default()).parse().unwrap()
+    }
+
+    fn backend_options_for(arguments: &[&str]) -> BackendOptions {
+        let registry = Registry::default();
+        let arguments = CommandLineArguments::try_parse_from(arguments).unwrap();
+        backend_options(backend(&registry, &arguments).unwrap(), &arguments, None)
+    }
+
+    #[test]
+    fn backends_choose_the_output_filename_unless_it_is_given() {
+        let options = backend_options_for(&["brainrust", "--to", "c", "prog.b"]);
+        assert_eq!(options.output_filename, Path::new("out.c"));
+        let options = backend_options_for(&["brainrust", "--backend", "direct", "prog.b"]);
+        assert_eq!(options.output_filename, Path::new("a.out"));
+        let options =
+            backend_options_for(&["brainrust", "--to", "c", "-o", "build/prog", "prog.b"]);
+        assert_eq!(options.output_filename, Path::new("build/prog"));
+    }
+
+    #[test]
+    fn target_selects_the_platform() {
+        let options = backend_options_for(&["brainrust", "--to", "c", "prog.b"]);
+        assert_eq!(options.platform, Platform::Host);
+        assert_eq!(options.platform.triple(), None);
+        for (target, platform) in [
+            ("wasm32-wasi", Platform::Wasm32Wasi),
+            ("wasm32-unknown-unknown", Platform::Wasm32Browser),
+        ] {
+            let options =
+                backend_options_for(&["brainrust", "--to", "c", "--target", target, "prog.b"]);
+            assert_eq!(options.platform, platform);
+        }
+        assert_eq!(Platform::Wasm32Wasi.triple(), Some("wasm32-unknown-wasi"));
+        assert_eq!(
+            Platform::Wasm32Browser.triple(),
+            Some("wasm32-unknown-unknown")
+        );
+    }
+
+    #[test]
+    fn microcontrollers_require_bare_metal() {
+        for (target, platform) in [
+            ("thumbv7em-none-eabi", Platform::ThumbV7em),
+            ("riscv32-unknown-none-elf", Platform::Riscv32),
+        ] {
+            let arguments = ["brainrust", "--target", target, "-c", "prog.b"];
+            assert!(CommandLineArguments::try_parse_from(arguments).is_err());
+            let arguments = CommandLineArguments::try_parse_from(
+                [
+                    &arguments[..4],
+                    &["--bare-metal", "--tape-size", "4096", "prog.b"],
+                ]
+                .concat(),
+            )
+            .unwrap();
+            assert_eq!(arguments.platform, platform);
+            assert_eq!(platform.triple(), Some(target));
+        }
+    }
+
+    #[test]
+    fn unknown_backends_are_reported_with_the_available_ones() {
+        let registry = Registry::default();
+        let arguments =
+            CommandLineArguments::try_parse_from(["brainrust", "--to", "nope", "prog.b"]).unwrap();
+        let error = backend(&registry, &arguments).err().unwrap().to_string();
+        assert!(error.starts_with("unknown backend 'nope' (available backends: c, direct"));
+    }
+
+    #[test]
+    fn const_eval_precomputes_the_output() {
+        let program = parse(b"++++++++[>++++++++<-]>+.");
+        let output = constant_output(&program, InterpreterOptions::default(), 1_000).unwrap();
+        assert_eq!(output.as_deref(), Some(b"A".as_slice()));
+    }
+
+    #[test]
+    fn const_eval_runs_programs_proven_to_terminate_to_completion() {
+        // runs for more than the step limit, but only contains counting loops
+        let program = parse(b"-[-.]");
+        let output = constant_output(&program, InterpreterOptions::default(), 100).unwrap();
+        assert_eq!(output.map(|output| output.len()), Some(255));
+    }
+
+    #[test]
+    fn const_eval_gives_up_on_programs_that_never_terminate() {
+        let program = parse(b"+[]");
+        let output = constant_output(&program, InterpreterOptions::default(), 1_000).unwrap();
+        assert_eq!(output, None);
+    }
+}
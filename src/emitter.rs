@@ -1,13 +1,19 @@
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use anyhow::bail;
+use inkwell::builder::BuilderError;
 use inkwell::context::Context;
 use inkwell::OptimizationLevel;
 use thiserror::Error;
 
-use crate::command_line_arguments::{CommandLineArguments, EmitTarget};
+use crate::command_line_arguments::EmitTarget;
 use crate::emitter::state::State;
+use crate::linker::{archive, link, planned_archive_command, planned_link_command};
 use crate::program::Program;
+use crate::registry::{Backend, BackendOptions, Platform};
+use crate::terminal::EchoSuppression;
+use crate::trace;
 
 #[derive(Error, Debug)]
 pub(crate) enum EmitError {
@@ -16,6 +22,15 @@ pub(crate) enum EmitError {
         error_message: String,
     },
     ModuleVerificationFailed(String),
+    UnsupportedTarget {
+        triple: String,
+        error_message: String,
+    },
+    TargetMachineUnavailable(String),
+    CodeGeneration(#[from] BuilderError),
+    JitUnavailable(String),
+    MachineCode(String),
+    FreestandingRuntime(String),
 }
 
 impl Display for EmitError {
@@ -35,34 +50,232 @@ impl Display for EmitError {
             EmitError::ModuleVerificationFailed(error) => {
                 write!(f, "module verification failed: {error}")
             }
+            EmitError::UnsupportedTarget {
+                triple,
+                error_message,
+            } => {
+                write!(f, "target '{triple}' is not supported: {error_message}")
+            }
+            EmitError::TargetMachineUnavailable(triple) => {
+                write!(f, "failed to create a target machine for '{triple}'")
+            }
+            EmitError::CodeGeneration(error) => {
+                write!(f, "failed to generate code: {error}")
+            }
+            EmitError::JitUnavailable(error) => {
+                write!(f, "failed to create the JIT compiler: {error}")
+            }
+            EmitError::MachineCode(error) => {
+                write!(f, "failed to generate machine code: {error}")
+            }
+            EmitError::FreestandingRuntime(error) => {
+                write!(f, "failed to link the freestanding runtime: {error}")
+            }
+        }
+    }
+}
+
+mod control_flow {
+    use inkwell::basic_block::BasicBlock;
+    use inkwell::builder::{Builder, BuilderError};
+    use inkwell::context::Context;
+    use inkwell::values::{BasicValue, FunctionValue, IntValue};
+
+    // Every construct leaves the builder positioned at its join block. Arms that terminate their
+    // block themselves (e.g. by returning early) do not get a branch to the join block, and the
+    // block for the unreachable code after the terminator is removed again if it stayed empty.
+    pub(super) struct ControlFlowBuilder<'a, 'b> {
+        context: &'a Context,
+        builder: &'b Builder<'a>,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub(super) struct LoopBlocks<'a> {
+        header: BasicBlock<'a>,
+        exit: BasicBlock<'a>,
+    }
+
+    impl<'a, 'b> ControlFlowBuilder<'a, 'b> {
+        pub(super) fn new(context: &'a Context, builder: &'b Builder<'a>) -> Self {
+            Self { context, builder }
+        }
+
+        pub(super) fn if_then<ThenEmitter: FnOnce() -> Result<(), BuilderError>>(
+            &self,
+            condition: IntValue<'a>,
+            then_emitter: ThenEmitter,
+        ) -> Result<(), BuilderError> {
+            let then_block = self.append_block("then");
+            let after_if = self.append_block("after_if");
+            self.builder
+                .build_conditional_branch(condition, then_block, after_if)?;
+
+            self.builder.position_at_end(then_block);
+            then_emitter()?;
+            self.branch_if_unterminated(after_if)?;
+
+            self.builder.position_at_end(after_if);
+            Ok(())
+        }
+
+        pub(super) fn if_then_else<
+            ThenEmitter: FnOnce() -> Result<(), BuilderError>,
+            ElseEmitter: FnOnce() -> Result<(), BuilderError>,
+        >(
+            &self,
+            condition: IntValue<'a>,
+            then_emitter: ThenEmitter,
+            else_emitter: ElseEmitter,
+        ) -> Result<(), BuilderError> {
+            let then_block = self.append_block("then");
+            let else_block = self.append_block("else");
+            let after_if = self.append_block("after_if");
+            self.builder
+                .build_conditional_branch(condition, then_block, else_block)?;
+
+            self.builder.position_at_end(then_block);
+            then_emitter()?;
+            self.branch_if_unterminated(after_if)?;
+
+            self.builder.position_at_end(else_block);
+            else_emitter()?;
+            self.branch_if_unterminated(after_if)?;
+
+            self.builder.position_at_end(after_if);
+            Ok(())
+        }
+
+        /// Emits a loop that evaluates the condition before every iteration (like C's `while`).
+        pub(super) fn while_loop<
+            ConditionEmitter: FnOnce() -> Result<IntValue<'a>, BuilderError>,
+            BodyEmitter: FnOnce(LoopBlocks<'a>) -> Result<(), BuilderError>,
+        >(
+            &self,
+            condition_emitter: ConditionEmitter,
+            body_emitter: BodyEmitter,
+        ) -> Result<(), BuilderError> {
+            let loop_header = self.append_block("loop_header");
+            let loop_body = self.append_block("loop_body");
+            let after_loop = self.append_block("after_loop");
+            self.builder.build_unconditional_branch(loop_header)?;
+
+            self.builder.position_at_end(loop_header);
+            let condition = condition_emitter()?;
+            self.builder
+                .build_conditional_branch(condition, loop_body, after_loop)?;
+
+            self.builder.position_at_end(loop_body);
+            body_emitter(LoopBlocks {
+                header: loop_header,
+                exit: after_loop,
+            })?;
+            self.branch_if_unterminated(loop_header)?;
+
+            self.builder.position_at_end(after_loop);
+            Ok(())
+        }
+
+        pub(super) fn break_loop(&self, loop_blocks: LoopBlocks<'a>) -> Result<(), BuilderError> {
+            self.jump(loop_blocks.exit, "after_break")
+        }
+
+        pub(super) fn continue_loop(
+            &self,
+            loop_blocks: LoopBlocks<'a>,
+        ) -> Result<(), BuilderError> {
+            self.jump(loop_blocks.header, "after_continue")
+        }
+
+        /// Returns from the current function, e.g. inside an arm of [`ControlFlowBuilder::if_then`].
+        pub(super) fn return_early(
+            &self,
+            value: Option<&dyn BasicValue<'a>>,
+        ) -> Result<(), BuilderError> {
+            self.builder.build_return(value)?;
+            self.continue_after_terminator("after_return");
+            Ok(())
+        }
+
+        fn jump(
+            &self,
+            target: BasicBlock<'a>,
+            continuation_name: &str,
+        ) -> Result<(), BuilderError> {
+            self.builder.build_unconditional_branch(target)?;
+            self.continue_after_terminator(continuation_name);
+            Ok(())
+        }
+
+        // Code following a jump or a return is unreachable, but it still has to be placed in a
+        // block of its own.
+        fn continue_after_terminator(&self, continuation_name: &str) {
+            let continuation = self.append_block(continuation_name);
+            self.builder.position_at_end(continuation);
+        }
+
+        fn current_function(&self) -> FunctionValue<'a> {
+            self.builder
+                .get_insert_block()
+                .unwrap()
+                .get_parent()
+                .unwrap()
+        }
+
+        fn append_block(&self, name: &str) -> BasicBlock<'a> {
+            self.context
+                .append_basic_block(self.current_function(), name)
+        }
+
+        fn branch_if_unterminated(&self, target: BasicBlock<'a>) -> Result<(), BuilderError> {
+            let current_block = self.builder.get_insert_block().unwrap();
+            let is_unused_continuation = current_block.get_first_instruction().is_none()
+                && current_block.get_first_use().is_none();
+            if is_unused_continuation {
+                // the builder is positioned at the join block right afterwards
+                unsafe { current_block.delete() }.expect("the block belongs to a function");
+            } else if current_block.get_terminator().is_none() {
+                self.builder.build_unconditional_branch(target)?;
+            }
+            Ok(())
         }
     }
 }
 
 mod state {
     use std::collections::HashMap;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
-    use inkwell::basic_block::BasicBlock;
-    use inkwell::builder::Builder;
+    use inkwell::attributes::{Attribute, AttributeLoc};
+    use inkwell::builder::{Builder, BuilderError};
     use inkwell::context::Context;
+    use inkwell::memory_buffer::MemoryBuffer;
     use inkwell::module::Linkage;
     use inkwell::module::Module;
     use inkwell::passes::{PassManager, PassManagerBuilder};
     use inkwell::targets::{
-        CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+        CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
     };
     use inkwell::types::{BasicMetadataTypeEnum, BasicType, IntType, PointerType, VoidType};
-    use inkwell::values::{BasicMetadataValueEnum, FunctionValue, IntValue};
-    use inkwell::{AddressSpace, IntPredicate, OptimizationLevel};
+    use inkwell::values::{
+        BasicMetadataValueEnum, FunctionValue, GlobalValue, IntValue, PointerValue,
+    };
+    use inkwell::{AddressSpace, DLLStorageClass, IntPredicate, OptimizationLevel};
 
+    use crate::command_line_arguments::EmitTarget;
+    use crate::emitter::control_flow::{ControlFlowBuilder, LoopBlocks};
     use crate::emitter::state::FunctionDeclaration::Memset;
-    use crate::emitter::EmitError;
-    use crate::program::{Program, Statement};
+    use crate::emitter::{write_output_file, EmitError, FREESTANDING_RUNTIME};
+    use crate::environment::EnvironmentReport;
+    use crate::jitdump::{registered_with_gdb, write_jitdump};
+    use crate::program::{Program, Statement, StatementKind};
+    use crate::registry::{BackendOptions, Platform};
+    use crate::semantics::{OutOfBounds, Overflow, RuntimeConfig};
 
     trait TypeHolder<'a> {
         fn void(&self) -> VoidType<'a>;
         fn char(&self) -> IntType<'a>;
+        /// The type of the cells of the tape, which is as wide as `--cell-size`.
+        fn cell(&self) -> IntType<'a>;
         fn int(&self) -> IntType<'a>;
         fn size(&self) -> IntType<'a>;
         fn pointer(&self) -> PointerType<'a>;
@@ -84,11 +297,22 @@ mod state {
         Read,
         Write,
         GetChar,
+        Isatty,
+        Fflush,
+        ReadChar,
+        WriteChar,
+        Strcmp,
+        Abort,
+        Selftest,
+        CellOverflow,
+        LeftTape,
+        DisableEcho,
     }
 
     struct TypeContainer<'a> {
         void_type: VoidType<'a>,
         char_type: IntType<'a>,
+        cell_type: IntType<'a>,
         int_type: IntType<'a>,
         size_type: IntType<'a>,
         pointer_type: PointerType<'a>,
@@ -103,6 +327,10 @@ mod state {
             self.char_type
         }
 
+        fn cell(&self) -> IntType<'a> {
+            self.cell_type
+        }
+
         fn int(&self) -> IntType<'a> {
             self.int_type
         }
@@ -118,17 +346,225 @@ mod state {
 
     type Functions<'a> = HashMap<FunctionDeclaration, FunctionValue<'a>>;
 
+    // the tape reserved by `--guard-pages` has address 0 in its middle. The guard regions on both
+    // sides are large enough that a move of the pointer between two accesses cannot skip them,
+    // unless it moves by more than 4 GiB.
+    const GUARDED_TAPE_CELLS: u64 = 1 << 31;
+    const GUARD_BYTES: u64 = 1 << 32;
+    const PROT_NONE: u64 = 0;
+    const PROT_READ_WRITE: u64 = 3;
+    const MAP_PRIVATE: u64 = 2;
+    #[cfg(target_os = "macos")]
+    const MAP_ANONYMOUS_NORESERVE: u64 = 0x1000 | 0x40;
+    #[cfg(not(target_os = "macos"))]
+    const MAP_ANONYMOUS_NORESERVE: u64 = 0x20 | 0x4000;
+    const SIGSEGV: u64 = 11;
+    // macOS reports accesses of inaccessible pages as SIGBUS
+    #[cfg(target_os = "macos")]
+    const SIGBUS: u64 = 10;
+    #[cfg(not(target_os = "macos"))]
+    const SIGBUS: u64 = 7;
+    // the signals that terminate a program while it waits for input, which would leave the shell
+    // without echo: SIGHUP, SIGINT, SIGQUIT and SIGTERM
+    const TERMINATING_SIGNALS: [u64; 4] = [1, 2, 3, 15];
+    const TCSANOW: u64 = 0;
+    const ECHO: u64 = 0o10;
+    // `struct termios` is treated as opaque bytes, except for its local flags
+    const TERMIOS_SIZE: u32 = 128;
+    #[cfg(target_os = "macos")]
+    const TERMIOS_FLAG_BITS: u32 = 64;
+    #[cfg(not(target_os = "macos"))]
+    const TERMIOS_FLAG_BITS: u32 = 32;
+    const TERMIOS_LOCAL_FLAGS_OFFSET: u64 = 3 * TERMIOS_FLAG_BITS as u64 / 8;
+
+    type ModulePass = (&'static str, fn(&PassManager<Module>));
+
+    const MODULE_PASSES: &[ModulePass] = &[
+        ("constant_merge", |pass_manager| {
+            pass_manager.add_constant_merge_pass()
+        }),
+        ("merge_functions", |pass_manager| {
+            pass_manager.add_merge_functions_pass()
+        }),
+        ("dead_arg_elimination", |pass_manager| {
+            pass_manager.add_dead_arg_elimination_pass()
+        }),
+        ("function_attrs", |pass_manager| {
+            pass_manager.add_function_attrs_pass()
+        }),
+        ("function_inlining", |pass_manager| {
+            pass_manager.add_function_inlining_pass()
+        }),
+        ("always_inliner", |pass_manager| {
+            pass_manager.add_always_inliner_pass()
+        }),
+        ("global_dce", |pass_manager| {
+            pass_manager.add_global_dce_pass()
+        }),
+        ("global_optimizer", |pass_manager| {
+            pass_manager.add_global_optimizer_pass()
+        }),
+        ("ipsccp", |pass_manager| pass_manager.add_ipsccp_pass()),
+        ("internalize", |pass_manager| {
+            pass_manager.add_internalize_pass(true)
+        }),
+        ("strip_dead_prototypes", |pass_manager| {
+            pass_manager.add_strip_dead_prototypes_pass()
+        }),
+        ("strip_symbol", |pass_manager| {
+            pass_manager.add_strip_symbol_pass()
+        }),
+        ("loop_vectorize", |pass_manager| {
+            pass_manager.add_loop_vectorize_pass()
+        }),
+        ("slp_vectorize", |pass_manager| {
+            pass_manager.add_slp_vectorize_pass()
+        }),
+        ("aggressive_dce", |pass_manager| {
+            pass_manager.add_aggressive_dce_pass()
+        }),
+        ("bit_tracking_dce", |pass_manager| {
+            pass_manager.add_bit_tracking_dce_pass()
+        }),
+        ("alignment_from_assumptions", |pass_manager| {
+            pass_manager.add_alignment_from_assumptions_pass()
+        }),
+        ("cfg_simplification", |pass_manager| {
+            pass_manager.add_cfg_simplification_pass()
+        }),
+        ("dead_store_elimination", |pass_manager| {
+            pass_manager.add_dead_store_elimination_pass()
+        }),
+        ("scalarizer", |pass_manager| {
+            pass_manager.add_scalarizer_pass()
+        }),
+        ("merged_load_store_motion", |pass_manager| {
+            pass_manager.add_merged_load_store_motion_pass()
+        }),
+        ("gvn", |pass_manager| pass_manager.add_gvn_pass()),
+        ("ind_var_simplify", |pass_manager| {
+            pass_manager.add_ind_var_simplify_pass()
+        }),
+        ("instruction_combining", |pass_manager| {
+            pass_manager.add_instruction_combining_pass()
+        }),
+        ("jump_threading", |pass_manager| {
+            pass_manager.add_jump_threading_pass()
+        }),
+        ("licm", |pass_manager| pass_manager.add_licm_pass()),
+        ("loop_deletion", |pass_manager| {
+            pass_manager.add_loop_deletion_pass()
+        }),
+        ("loop_idiom", |pass_manager| {
+            pass_manager.add_loop_idiom_pass()
+        }),
+        ("loop_rotate", |pass_manager| {
+            pass_manager.add_loop_rotate_pass()
+        }),
+        ("loop_reroll", |pass_manager| {
+            pass_manager.add_loop_reroll_pass()
+        }),
+        ("loop_unroll", |pass_manager| {
+            pass_manager.add_loop_unroll_pass()
+        }),
+        ("memcpy_optimize", |pass_manager| {
+            pass_manager.add_memcpy_optimize_pass()
+        }),
+        ("partially_inline_lib_calls", |pass_manager| {
+            pass_manager.add_partially_inline_lib_calls_pass()
+        }),
+        ("lower_switch", |pass_manager| {
+            pass_manager.add_lower_switch_pass()
+        }),
+        ("promote_memory_to_register", |pass_manager| {
+            pass_manager.add_promote_memory_to_register_pass()
+        }),
+        ("reassociate", |pass_manager| {
+            pass_manager.add_reassociate_pass()
+        }),
+        ("sccp", |pass_manager| pass_manager.add_sccp_pass()),
+        ("scalar_repl_aggregates", |pass_manager| {
+            pass_manager.add_scalar_repl_aggregates_pass()
+        }),
+        ("scalar_repl_aggregates_ssa", |pass_manager| {
+            pass_manager.add_scalar_repl_aggregates_pass_ssa()
+        }),
+        ("scalar_repl_aggregates_with_threshold", |pass_manager| {
+            pass_manager.add_scalar_repl_aggregates_pass_with_threshold(1)
+        }),
+        ("simplify_lib_calls", |pass_manager| {
+            pass_manager.add_simplify_lib_calls_pass()
+        }),
+        ("tail_call_elimination", |pass_manager| {
+            pass_manager.add_tail_call_elimination_pass()
+        }),
+        ("demote_memory_to_register", |pass_manager| {
+            pass_manager.add_demote_memory_to_register_pass()
+        }),
+        ("verifier", |pass_manager| pass_manager.add_verifier_pass()),
+        ("correlated_value_propagation", |pass_manager| {
+            pass_manager.add_correlated_value_propagation_pass()
+        }),
+        ("early_cse", |pass_manager| {
+            pass_manager.add_early_cse_pass()
+        }),
+        ("lower_expect_intrinsic", |pass_manager| {
+            pass_manager.add_lower_expect_intrinsic_pass()
+        }),
+        ("type_based_alias_analysis", |pass_manager| {
+            pass_manager.add_type_based_alias_analysis_pass()
+        }),
+        ("scoped_no_alias_aa", |pass_manager| {
+            pass_manager.add_scoped_no_alias_aa_pass()
+        }),
+        ("basic_alias_analysis", |pass_manager| {
+            pass_manager.add_basic_alias_analysis_pass()
+        }),
+        ("early_cse_mem_ssa", |pass_manager| {
+            pass_manager.add_early_cse_mem_ssa_pass()
+        }),
+        ("new_gvn", |pass_manager| pass_manager.add_new_gvn_pass()),
+        ("loop_unroll_and_jam", |pass_manager| {
+            pass_manager.add_loop_unroll_and_jam_pass()
+        }),
+    ];
+
+    // passes that merge functions or remove their names, which profilers attribute time to
+    const SYMBOL_PASSES: &[&str] = &["merge_functions", "strip_symbol"];
+
+    fn module_passes(keep_symbols: bool) -> impl Iterator<Item = &'static ModulePass> {
+        MODULE_PASSES
+            .iter()
+            .filter(move |(name, _)| !keep_symbols || !SYMBOL_PASSES.contains(name))
+    }
+
+    pub(super) fn module_pass_names(keep_symbols: bool) -> impl Iterator<Item = &'static str> {
+        module_passes(keep_symbols).map(|(name, _)| *name)
+    }
+
     pub(super) struct State<'a> {
         pub(super) module: Module<'a>,
         target_machine: TargetMachine,
     }
 
     impl<'a> State<'a> {
-        pub(super) fn new(context: &'a Context, module_name: &str, program: &Program) -> Self {
+        pub(super) fn new(
+            context: &'a Context,
+            module_name: &str,
+            program: &Program,
+            options: &BackendOptions,
+            target: EmitTarget,
+        ) -> Result<Self, EmitError> {
+            let library = matches!(
+                target,
+                EmitTarget::SharedLibrary | EmitTarget::StaticLibrary
+            );
+            // the handlers JIT-compiled code registers would outlive it, so brainrust turns off
+            // the echo itself while running it
+            let echo_control = options.no_echo && target != EmitTarget::Jit;
             let builder = context.create_builder();
             let module = context.create_module(module_name);
 
-            let default_triplet = TargetMachine::get_default_triple();
             Target::initialize_all(&InitializationConfig {
                 asm_parser: true,
                 asm_printer: true,
@@ -137,46 +573,208 @@ mod state {
                 info: true,
                 machine_code: true,
             });
-            let target = Target::from_triple(&default_triplet).unwrap();
+            // code for another platform is generated for its generic CPU
+            let (target_triple, cpu, features, reloc_mode) = match options.platform.triple() {
+                None => (
+                    TargetMachine::get_default_triple(),
+                    TargetMachine::get_host_cpu_name().to_string(),
+                    TargetMachine::get_host_cpu_features().to_string(),
+                    RelocMode::PIC,
+                ),
+                Some(triple) => (
+                    TargetTriple::create(triple),
+                    // the generic CPUs of RISC-V are named after the width of their registers
+                    if options.platform == Platform::Riscv32 {
+                        "generic-rv32".to_string()
+                    } else {
+                        "generic".to_string()
+                    },
+                    String::new(),
+                    RelocMode::Default,
+                ),
+            };
+            let triple = target_triple.as_str().to_string_lossy().into_owned();
+            let target = Target::from_triple(&target_triple).map_err(|error| {
+                EmitError::UnsupportedTarget {
+                    triple: triple.clone(),
+                    error_message: error.to_string(),
+                }
+            })?;
             let target_machine = target
                 .create_target_machine(
-                    &default_triplet,
-                    TargetMachine::get_host_cpu_name().to_str().unwrap(),
-                    TargetMachine::get_host_cpu_features().to_str().unwrap(),
+                    &target_triple,
+                    &cpu,
+                    &features,
                     OptimizationLevel::Aggressive,
-                    RelocMode::PIC,
+                    reloc_mode,
                     CodeModel::Default,
                 )
-                .unwrap();
+                .ok_or(EmitError::TargetMachineUnavailable(triple.clone()))?;
+            // the stack protector needs a guard variable from the C standard library
+            if options.harden && options.platform == Platform::Wasm32Browser {
+                return Err(EmitError::UnsupportedTarget {
+                    triple,
+                    error_message: "hardening requires a C standard library".to_string(),
+                });
+            }
+            // the freestanding runtime makes the system calls of Linux on x86-64
+            if options.no_libc && !(triple.starts_with("x86_64-") && triple.contains("-linux")) {
+                return Err(EmitError::UnsupportedTarget {
+                    triple,
+                    error_message: "--no-libc requires Linux on x86-64".to_string(),
+                });
+            }
+            // the terminal is controlled through the C standard library of a Unix system
+            if options.no_echo && options.platform != Platform::Host {
+                return Err(EmitError::UnsupportedTarget {
+                    triple,
+                    error_message: "--no-echo requires the terminal of the host".to_string(),
+                });
+            }
+            // the tape of bare-metal code is a static array
+            if options.bare_metal && Self::static_tape_size(options).is_none() {
+                return Err(EmitError::UnsupportedTarget {
+                    triple,
+                    error_message: format!(
+                        "--bare-metal requires a tape of at most {} cells that does not grow",
+                        u32::MAX
+                    ),
+                });
+            }
+            module.set_triple(&target_triple);
+            module.set_data_layout(&target_machine.get_target_data().get_data_layout());
 
             let types = TypeContainer {
                 void_type: context.void_type(),
                 char_type: context.i8_type(),
+                cell_type: context.custom_width_int_type(options.runtime.cell_size.bits()),
                 int_type: context.i32_type(),
                 size_type: context.ptr_sized_int_type(&target_machine.get_target_data(), None),
                 pointer_type: context.i8_type().ptr_type(AddressSpace::default()),
             };
 
+            let entry_point = Self::entry_point(options, library);
+            if let Some(output) = &options.constant_output {
+                Self::generate_function_main_writing(
+                    entry_point,
+                    output,
+                    context,
+                    &builder,
+                    &module,
+                    &types,
+                )?;
+                if options.harden {
+                    Self::enable_stack_protector(context, &module);
+                }
+                if library {
+                    Self::export_function(entry_point, &module, &types);
+                }
+                return Ok(Self {
+                    module,
+                    target_machine,
+                });
+            }
+
             let mut functions = HashMap::new();
 
-            Self::declare_libc_functions(&mut functions, &module, &types);
-            Self::generate_function_mem_dump(context, &builder, &mut functions, &module, &types);
+            if options.bare_metal {
+                Self::declare_bare_metal_functions(
+                    context,
+                    &builder,
+                    &mut functions,
+                    &module,
+                    &types,
+                )?;
+            } else {
+                Self::declare_libc_functions(&mut functions, &module, &types);
+            }
+            if options.harden {
+                functions.insert(
+                    FunctionDeclaration::Abort,
+                    Self::create_function(
+                        "abort",
+                        &[],
+                        None,
+                        Some(Linkage::External),
+                        false,
+                        &module,
+                        &types,
+                    ),
+                );
+            }
+            Self::generate_function_mem_dump(context, &builder, &mut functions, &module, &types)?;
             Self::generate_function_address_to_index(
                 context,
                 &builder,
                 &mut functions,
                 &module,
                 &types,
-            );
-            Self::generate_function_ensure_sufficient_memory_capacity(
+            )?;
+            // a static tape never grows
+            let is_static_tape = options.guard_pages || options.bare_metal;
+            if !is_static_tape {
+                Self::generate_function_ensure_sufficient_memory_capacity(
+                    options.runtime.max_memory,
+                    context,
+                    &builder,
+                    &mut functions,
+                    &module,
+                    &types,
+                )?;
+            }
+            Self::generate_function_read(
+                options.runtime,
+                is_static_tape,
                 context,
                 &builder,
                 &mut functions,
                 &module,
                 &types,
-            );
-            Self::generate_function_read(context, &builder, &mut functions, &module, &types);
-            Self::generate_function_write(context, &builder, &mut functions, &module, &types);
+            )?;
+            Self::generate_function_write(
+                options.runtime,
+                is_static_tape,
+                context,
+                &builder,
+                &mut functions,
+                &module,
+                &types,
+            )?;
+            if options.runtime.overflow == Overflow::Trap {
+                Self::generate_function_cell_overflow(
+                    context,
+                    &builder,
+                    &mut functions,
+                    &module,
+                    &types,
+                )?;
+            }
+            if options.guard_pages {
+                Self::generate_function_left_tape(
+                    context,
+                    &builder,
+                    &mut functions,
+                    &module,
+                    &types,
+                )?;
+            }
+            Self::generate_function_read_char(
+                options.prompt.as_deref(),
+                options.runtime,
+                context,
+                &builder,
+                &mut functions,
+                &module,
+                &types,
+            )?;
+            Self::generate_function_write_char(
+                options.max_output,
+                context,
+                &builder,
+                &mut functions,
+                &module,
+                &types,
+            )?;
 
             let run = Self::create_function(
                 "run",
@@ -196,19 +794,216 @@ mod state {
             builder.position_at_end(entry);
             for statement in program.statements() {
                 Self::emit_code_for_statement(
-                    statement, context, &builder, &functions, &module, &types,
-                );
+                    statement,
+                    None,
+                    options.jit_profiling,
+                    context,
+                    &builder,
+                    &functions,
+                    &module,
+                    &types,
+                )?;
+            }
+            builder.build_return(None)?;
+
+            if echo_control {
+                Self::generate_function_disable_echo(
+                    context,
+                    &builder,
+                    &mut functions,
+                    &module,
+                    &types,
+                )?;
+            }
+            if options.embed_selftest {
+                Self::generate_function_selftest(
+                    context,
+                    &builder,
+                    &mut functions,
+                    &module,
+                    &types,
+                )?;
             }
-            builder.build_return(None).unwrap();
+            Self::generate_function_main(
+                entry_point,
+                run,
+                options,
+                context,
+                &builder,
+                &mut functions,
+                &module,
+                &types,
+            )?;
 
-            Self::generate_function_main(run, context, &builder, &mut functions, &module, &types);
+            if options.harden {
+                Self::enable_stack_protector(context, &module);
+            }
+            if options.bare_metal {
+                Self::disable_builtins(&module);
+                // the firmware calls `bf_main` and may replace the I/O functions
+                Self::preserve_functions(&["bf_main", "bf_getchar", "bf_putchar"], &module, &types);
+            }
+            if library {
+                Self::export_function(entry_point, &module, &types);
+            }
 
-            Self {
+            Ok(Self {
                 module,
                 target_machine,
+            })
+        }
+
+        // The number of cells of the static array `--bare-metal` uses as the tape.
+        fn static_tape_size(options: &BackendOptions) -> Option<u32> {
+            options
+                .runtime
+                .fixed_tape_size()
+                .and_then(|tape_size| u32::try_from(tape_size).ok())
+        }
+
+        // The name of the function that runs the program. Bare-metal code is called by the
+        // firmware, which has a `main` function of its own, and libraries by the program that
+        // loads or links them.
+        fn entry_point(options: &BackendOptions, library: bool) -> &'static str {
+            if library {
+                "bf_run"
+            } else if options.bare_metal {
+                "bf_main"
+            } else {
+                "main"
             }
         }
 
+        // Makes a function callable from outside of the library.
+        fn export_function(name: &str, module: &Module<'a>, type_holder: &dyn TypeHolder<'a>) {
+            Self::preserve_functions(&[name], module, type_holder);
+            // only exported functions of a DLL can be called
+            if cfg!(target_os = "windows") {
+                module
+                    .get_function(name)
+                    .expect("the function has been generated")
+                    .as_global_value()
+                    .set_dll_storage_class(DLLStorageClass::Export);
+            }
+        }
+
+        // Keeps the internalize pass from hiding the functions from the code linked with the module.
+        fn preserve_functions(
+            names: &[&str],
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) {
+            let functions: Vec<_> = names
+                .iter()
+                .map(|name| {
+                    module
+                        .get_function(name)
+                        .expect("the function has been generated")
+                        .as_global_value()
+                        .as_pointer_value()
+                })
+                .collect();
+            let used = module.add_global(
+                type_holder.pointer().array_type(functions.len() as u32),
+                None,
+                "llvm.used",
+            );
+            used.set_linkage(Linkage::Appending);
+            used.set_section(Some("llvm.metadata"));
+            used.set_initializer(&type_holder.pointer().const_array(&functions));
+        }
+
+        // keeps LLVM from turning code into calls of library functions that may not exist
+        fn disable_builtins(module: &Module<'a>) {
+            let no_builtins = module
+                .get_context()
+                .create_string_attribute("no-builtins", "");
+            for function in module.get_functions() {
+                if function.count_basic_blocks() > 0 {
+                    function.add_attribute(AttributeLoc::Function, no_builtins);
+                }
+            }
+        }
+
+        fn enable_stack_protector(context: &'a Context, module: &Module<'a>) {
+            let stack_protector =
+                context.create_enum_attribute(Attribute::get_named_enum_kind_id("sspstrong"), 0);
+            for function in module.get_functions() {
+                if function.count_basic_blocks() > 0 {
+                    function.add_attribute(AttributeLoc::Function, stack_protector);
+                }
+            }
+        }
+
+        /// Generates the entry point as a function that writes `output` to stdout using a single
+        /// `fwrite`.
+        fn generate_function_main_writing(
+            name: &str,
+            output: &[u8],
+            context: &'a Context,
+            builder: &Builder<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            /*
+            int main() {
+                fwrite(output, 1, sizeof(output), stdout);
+                return 0;
+            }
+             */
+            let fwrite = Self::create_function(
+                "fwrite",
+                &[
+                    type_holder.pointer().into(),
+                    type_holder.size().into(),
+                    type_holder.size().into(),
+                    type_holder.pointer().into(),
+                ],
+                Some(&type_holder.size()),
+                Some(Linkage::External),
+                false,
+                module,
+                type_holder,
+            );
+            let stdout = Self::stream_global("stdout", module, type_holder);
+
+            let data = context.const_string(output, false);
+            let output_global = module.add_global(data.get_type(), None, "output");
+            output_global.set_linkage(Linkage::Private);
+            output_global.set_constant(true);
+            output_global.set_initializer(&data);
+
+            let main = Self::create_function(
+                name,
+                &[],
+                Some(&type_holder.int()),
+                Some(Linkage::External),
+                false,
+                module,
+                type_holder,
+            );
+            let entry = context.append_basic_block(main, "entry");
+            builder.position_at_end(entry);
+
+            let stream =
+                builder.build_load(type_holder.pointer(), stdout.as_pointer_value(), "stdout")?;
+            builder.build_direct_call(
+                fwrite,
+                &[
+                    output_global.as_pointer_value().into(),
+                    type_holder.size().const_int(1, false).into(),
+                    type_holder
+                        .size()
+                        .const_int(output.len() as u64, false)
+                        .into(),
+                    stream.into(),
+                ],
+                "",
+            )?;
+            builder.build_return(Some(&type_holder.int().const_zero()))?;
+            Ok(())
+        }
+
         fn function(
             function_declaration: FunctionDeclaration,
             functions: &Functions<'a>,
@@ -216,163 +1011,98 @@ mod state {
             *functions.get(&function_declaration).unwrap()
         }
 
+        /// Links the C standard library functions the module uses, implemented with system calls,
+        /// and the entry point `_start` into the module, see `freestanding.ll`.
+        pub(super) fn link_freestanding_runtime(&self) -> Result<(), EmitError> {
+            Self::disable_builtins(&self.module);
+            let context = self.module.get_context();
+            let buffer = MemoryBuffer::create_from_memory_range_copy(
+                FREESTANDING_RUNTIME.as_bytes(),
+                "freestanding",
+            );
+            let runtime = context
+                .create_module_from_ir(buffer)
+                .map_err(|error| EmitError::FreestandingRuntime(error.to_string()))?;
+            runtime.set_triple(&self.module.get_triple());
+            runtime.set_data_layout(&self.module.get_data_layout());
+            self.module
+                .link_in_module(runtime)
+                .map_err(|error| EmitError::FreestandingRuntime(error.to_string()))
+        }
+
+        pub(super) fn embed_environment(&self, environment: &EnvironmentReport) {
+            let context = self.module.get_context();
+            let producer = context.metadata_string(&environment.producer());
+            self.module
+                .add_global_metadata("llvm.ident", &context.metadata_node(&[producer.into()]))
+                .unwrap();
+            for (key, value) in environment.entries() {
+                let entry = context.metadata_node(&[
+                    context.metadata_string(key).into(),
+                    context.metadata_string(&value).into(),
+                ]);
+                self.module
+                    .add_global_metadata("brainrust.environment", &entry)
+                    .unwrap();
+            }
+        }
+
+        /// Compiles the module in memory and calls its `main` function. Returns the exit code.
+        /// With `jitdump`, a perf jitdump file of the compiled functions is written first.
+        pub(super) fn run_jit(&self, program_name: &str, jitdump: bool) -> Result<i32, EmitError> {
+            let execution_engine = self
+                .module
+                .create_jit_execution_engine(OptimizationLevel::None)
+                .map_err(|error| EmitError::JitUnavailable(error.to_string()))?;
+            let main = self
+                .module
+                .get_function("main")
+                .expect("every module has a main function");
+            // looking up a function compiles the module
+            let address = execution_engine
+                .get_function_address("main")
+                .map_err(|error| EmitError::JitUnavailable(error.to_string()))?;
+            if !registered_with_gdb(address as u64).unwrap_or(false) {
+                eprintln!(
+                    "warning: the JIT-compiled code is not registered with the GDB JIT interface, \
+                     so debuggers cannot show its functions"
+                );
+            }
+            if jitdump {
+                let filename = PathBuf::from(format!("jit-{}.dump", std::process::id()));
+                write_jitdump(&filename).map_err(|error| EmitError::FailedToWriteToFile {
+                    filename: filename.clone(),
+                    error_message: error.to_string(),
+                })?;
+                eprintln!("wrote {}", filename.display());
+            }
+            // the generated code only calls the C standard library, which is part of this process
+            Ok(unsafe { execution_engine.run_function_as_main(main, &[program_name]) })
+        }
+
         pub(super) fn verify(&self) -> anyhow::Result<(), EmitError> {
             self.module
                 .verify()
                 .map_err(|error| EmitError::ModuleVerificationFailed(error.to_string()))
         }
 
-        pub(super) fn optimize(&self, level: OptimizationLevel) {
-            println!("optimization level: {level:?}");
+        pub(super) fn optimize(&self, level: OptimizationLevel, verbose: bool, keep_symbols: bool) {
+            if verbose {
+                eprintln!("optimization level: {level:?}");
+            }
             let pass_manager_builder = PassManagerBuilder::create();
             pass_manager_builder.set_optimization_level(level);
 
             let module_pass_manager = PassManager::create(());
             pass_manager_builder.populate_module_pass_manager(&module_pass_manager);
-            #[cfg(any(
-                feature = "llvm4-0",
-                feature = "llvm5-0",
-                feature = "llvm6-0",
-                feature = "llvm7-0",
-                feature = "llvm8-0",
-                feature = "llvm9-0",
-                feature = "llvm10-0",
-                feature = "llvm11-0",
-                feature = "llvm12-0",
-                feature = "llvm13-0",
-                feature = "llvm14-0"
-            ))]
-            module_pass_manager.add_argument_promotion_pass();
-            module_pass_manager.add_constant_merge_pass();
-            #[cfg(not(any(
-                feature = "llvm4-0",
-                feature = "llvm5-0",
-                feature = "llvm6-0",
-                feature = "llvm7-0",
-                feature = "llvm8-0",
-                feature = "llvm9-0"
-            )))]
-            module_pass_manager.add_merge_functions_pass();
-            module_pass_manager.add_dead_arg_elimination_pass();
-            module_pass_manager.add_function_attrs_pass();
-            module_pass_manager.add_function_inlining_pass();
-            module_pass_manager.add_always_inliner_pass();
-            module_pass_manager.add_global_dce_pass();
-            module_pass_manager.add_global_optimizer_pass();
-            #[cfg(not(any(
-                feature = "llvm12-0",
-                feature = "llvm13-0",
-                feature = "llvm14-0",
-                feature = "llvm15-0",
-                feature = "llvm16-0"
-            )))]
-            //module_pass_manager.add_ip_constant_propagation_pass();
-            /*#[cfg(not(feature = "llvm16-0"))]
-            module_pass_manager.add_prune_eh_pass();*/
-            module_pass_manager.add_ipsccp_pass();
-            module_pass_manager.add_internalize_pass(true);
-            module_pass_manager.add_strip_dead_prototypes_pass();
-            module_pass_manager.add_strip_symbol_pass();
-            #[cfg(feature = "llvm4-0")]
-            module_pass_manager.add_bb_vectorize_pass();
-            module_pass_manager.add_loop_vectorize_pass();
-            module_pass_manager.add_slp_vectorize_pass();
-            module_pass_manager.add_aggressive_dce_pass();
-            module_pass_manager.add_bit_tracking_dce_pass();
-            module_pass_manager.add_alignment_from_assumptions_pass();
-            module_pass_manager.add_cfg_simplification_pass();
-            module_pass_manager.add_dead_store_elimination_pass();
-            module_pass_manager.add_scalarizer_pass();
-            module_pass_manager.add_merged_load_store_motion_pass();
-            module_pass_manager.add_gvn_pass();
-            module_pass_manager.add_ind_var_simplify_pass();
-            module_pass_manager.add_instruction_combining_pass();
-            module_pass_manager.add_jump_threading_pass();
-            module_pass_manager.add_licm_pass();
-            module_pass_manager.add_loop_deletion_pass();
-            module_pass_manager.add_loop_idiom_pass();
-            module_pass_manager.add_loop_rotate_pass();
-            module_pass_manager.add_loop_reroll_pass();
-            module_pass_manager.add_loop_unroll_pass();
-            #[cfg(any(
-                feature = "llvm4-0",
-                feature = "llvm5-0",
-                feature = "llvm6-0",
-                feature = "llvm7-0",
-                feature = "llvm8-0",
-                feature = "llvm9-0",
-                feature = "llvm10-0",
-                feature = "llvm11-0",
-                feature = "llvm12-0",
-                feature = "llvm13-0",
-                feature = "llvm14-0"
-            ))]
-            module_pass_manager.add_loop_unswitch_pass();
-            module_pass_manager.add_memcpy_optimize_pass();
-            module_pass_manager.add_partially_inline_lib_calls_pass();
-            module_pass_manager.add_lower_switch_pass();
-            module_pass_manager.add_promote_memory_to_register_pass();
-            module_pass_manager.add_reassociate_pass();
-            module_pass_manager.add_sccp_pass();
-            module_pass_manager.add_scalar_repl_aggregates_pass();
-            module_pass_manager.add_scalar_repl_aggregates_pass_ssa();
-            module_pass_manager.add_scalar_repl_aggregates_pass_with_threshold(1);
-            module_pass_manager.add_simplify_lib_calls_pass();
-            module_pass_manager.add_tail_call_elimination_pass();
-            /*#[cfg(not(any(
-                feature = "llvm12-0",
-                feature = "llvm13-0",
-                feature = "llvm14-0",
-                feature = "llvm15-0",
-                feature = "llvm16-0"
-            )))]
-            module_pass_manager.add_constant_propagation_pass();*/
-            #[cfg(any(
-                feature = "llvm12-0",
-                feature = "llvm13-0",
-                feature = "llvm14-0",
-                feature = "llvm15-0",
-                feature = "llvm16-0"
-            ))]
-            module_pass_manager.add_instruction_simplify_pass();
-            module_pass_manager.add_demote_memory_to_register_pass();
-            module_pass_manager.add_verifier_pass();
-            module_pass_manager.add_correlated_value_propagation_pass();
-            module_pass_manager.add_early_cse_pass();
-            module_pass_manager.add_lower_expect_intrinsic_pass();
-            module_pass_manager.add_type_based_alias_analysis_pass();
-            module_pass_manager.add_scoped_no_alias_aa_pass();
-            module_pass_manager.add_basic_alias_analysis_pass();
-            module_pass_manager.add_early_cse_mem_ssa_pass();
-            module_pass_manager.add_new_gvn_pass();
-
-            /*#[cfg(not(any(
-                feature = "llvm4-0",
-                feature = "llvm5-0",
-                feature = "llvm6-0",
-                feature = "llvm16-0"
-            )))]
-            module_pass_manager.add_aggressive_inst_combiner_pass();*/
-            #[cfg(not(any(feature = "llvm4-0", feature = "llvm5-0", feature = "llvm6-0")))]
-            module_pass_manager.add_loop_unroll_and_jam_pass();
-
-            /*#[cfg(not(any(
-                feature = "llvm4-0",
-                feature = "llvm5-0",
-                feature = "llvm6-0",
-                feature = "llvm7-0",
-                feature = "llvm15-0",
-                feature = "llvm16-0"
-            )))]
-            {
-                module_pass_manager.add_coroutine_early_pass();
-                module_pass_manager.add_coroutine_split_pass();
-                module_pass_manager.add_coroutine_elide_pass();
-                module_pass_manager.add_coroutine_cleanup_pass();
-            }*/
+            for (_, add_pass) in module_passes(keep_symbols) {
+                add_pass(&module_pass_manager);
+            }
 
             let optimized_module = module_pass_manager.run_on(&self.module);
-            dbg!(optimized_module);
+            if verbose {
+                eprintln!("module changed by optimization passes: {optimized_module}");
+            }
 
             let function_pass_manager = PassManager::create(&self.module);
             pass_manager_builder.populate_function_pass_manager(&function_pass_manager);
@@ -384,30 +1114,40 @@ mod state {
         }
 
         pub(super) fn emit_assembly(&self, filename: &Path) -> anyhow::Result<(), EmitError> {
-            self.target_machine
-                .write_to_file(&self.module, FileType::Assembly, filename)
-                .map_err(|error| EmitError::FailedToWriteToFile {
-                    filename: filename.to_path_buf(),
-                    error_message: error.to_string(),
-                })
+            self.emit_machine_code(filename, FileType::Assembly)
         }
 
         pub(super) fn emit_object_file(&self, filename: &Path) -> anyhow::Result<(), EmitError> {
-            self.target_machine
-                .write_to_file(&self.module, FileType::Object, filename)
-                .map_err(|error| EmitError::FailedToWriteToFile {
-                    filename: filename.to_path_buf(),
-                    error_message: error.to_string(),
-                })
+            self.emit_machine_code(filename, FileType::Object)
+        }
+
+        pub(super) fn object_code(&self) -> Result<Vec<u8>, EmitError> {
+            let buffer = self
+                .target_machine
+                .write_to_memory_buffer(&self.module, FileType::Object)
+                .map_err(|error| EmitError::MachineCode(error.to_string()))?;
+            Ok(buffer.as_slice().to_vec())
         }
 
         pub(super) fn emit_llvm_ir(&self, filename: &Path) -> anyhow::Result<(), EmitError> {
-            self.module
-                .print_to_file(filename)
+            write_output_file(filename, self.module.print_to_string().to_bytes())
+        }
+
+        pub(super) fn emit_bitcode(&self, filename: &Path) -> anyhow::Result<(), EmitError> {
+            write_output_file(filename, self.module.write_bitcode_to_memory().as_slice())
+        }
+
+        // LLVM only writes to paths that are valid Unicode, so the code is generated in memory
+        // and written by us instead
+        fn emit_machine_code(&self, filename: &Path, file_type: FileType) -> Result<(), EmitError> {
+            let buffer = self
+                .target_machine
+                .write_to_memory_buffer(&self.module, file_type)
                 .map_err(|error| EmitError::FailedToWriteToFile {
                     filename: filename.to_path_buf(),
                     error_message: error.to_string(),
-                })
+                })?;
+            write_output_file(filename, buffer.as_slice())
         }
 
         fn create_function(
@@ -426,6 +1166,63 @@ mod state {
             module.add_function(name, function_type, linkage)
         }
 
+        // Declares a libc function unless another part of the runtime already declared it.
+        fn external_function(
+            name: &str,
+            parameter_types: &[BasicMetadataTypeEnum<'a>],
+            return_type: Option<&dyn BasicType<'a>>,
+            is_var_args: bool,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> FunctionValue<'a> {
+            module.get_function(name).unwrap_or_else(|| {
+                Self::create_function(
+                    name,
+                    parameter_types,
+                    return_type,
+                    Some(Linkage::External),
+                    is_var_args,
+                    module,
+                    type_holder,
+                )
+            })
+        }
+
+        // the C standard library of macOS does not export `stdout` and `stderr` under their
+        // standard names
+        fn stream_symbol(name: &str, module: &Module<'a>) -> String {
+            if module
+                .get_triple()
+                .as_str()
+                .to_string_lossy()
+                .contains("-apple-")
+            {
+                format!("__{name}p")
+            } else {
+                name.to_string()
+            }
+        }
+
+        fn stream_global(
+            name: &str,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> GlobalValue<'a> {
+            let symbol = Self::stream_symbol(name, module);
+            module.get_global(&symbol).unwrap_or_else(|| {
+                let stream = module.add_global(type_holder.pointer(), None, &symbol);
+                // the JavaScript glue code of web pages tells the streams apart by their functions
+                // and cannot provide variables
+                if module.get_triple().as_str().to_bytes() == b"wasm32-unknown-unknown" {
+                    stream.set_linkage(Linkage::Internal);
+                    stream.set_initializer(&type_holder.pointer().const_zero());
+                } else {
+                    stream.set_linkage(Linkage::External);
+                }
+                stream
+            })
+        }
+
         fn declare_libc_functions(
             functions: &mut Functions<'a>,
             module: &Module<'a>,
@@ -544,26 +1341,160 @@ mod state {
             );
 
             functions.insert(
-                FunctionDeclaration::Printf,
+                FunctionDeclaration::Isatty,
                 Self::create_function(
-                    "printf",
-                    &[type_holder.pointer().into()],
+                    "isatty",
+                    &[type_holder.int().into()],
                     Some(&type_holder.int()),
                     Some(Linkage::External),
-                    true,
-                    &module,
+                    false,
+                    module,
                     type_holder,
                 ),
             );
-        }
 
-        fn generate_function_address_to_index(
-            context: &Context,
-            builder: &Builder<'a>,
-            functions: &mut Functions<'a>,
+            functions.insert(
+                FunctionDeclaration::Fflush,
+                Self::create_function(
+                    "fflush",
+                    &[type_holder.pointer().into()],
+                    Some(&type_holder.int()),
+                    Some(Linkage::External),
+                    false,
+                    module,
+                    type_holder,
+                ),
+            );
+
+            functions.insert(
+                FunctionDeclaration::Strcmp,
+                Self::create_function(
+                    "strcmp",
+                    &[type_holder.pointer().into(), type_holder.pointer().into()],
+                    Some(&type_holder.int()),
+                    Some(Linkage::External),
+                    false,
+                    module,
+                    type_holder,
+                ),
+            );
+
+            functions.insert(
+                FunctionDeclaration::Printf,
+                Self::create_function(
+                    "printf",
+                    &[type_holder.pointer().into()],
+                    Some(&type_holder.int()),
+                    Some(Linkage::External),
+                    true,
+                    &module,
+                    type_holder,
+                ),
+            );
+        }
+
+        // Defines the functions the generated code of `--bare-metal` uses for I/O instead of
+        // `getchar` and `putchar`. The definitions are weak, so that those of the user replace
+        // them, and write nothing and report the end of the input.
+        fn declare_bare_metal_functions(
+            context: &Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
+        ) -> Result<(), BuilderError> {
+            /*
+            __attribute__((weak)) int bf_getchar(void) { return EOF; }
+            __attribute__((weak)) int bf_putchar(int value) { return value; }
+             */
+            // LLVM cannot tell that a weak function does not unwind, which would require unwind
+            // tables and their personality function on Arm
+            let nounwind =
+                context.create_enum_attribute(Attribute::get_named_enum_kind_id("nounwind"), 0);
+            let get_char = Self::create_function(
+                "bf_getchar",
+                &[],
+                Some(&type_holder.int()),
+                Some(Linkage::WeakAny),
+                false,
+                module,
+                type_holder,
+            );
+            builder.position_at_end(context.append_basic_block(get_char, "entry"));
+            builder.build_return(Some(&type_holder.int().const_all_ones()))?;
+            get_char.add_attribute(AttributeLoc::Function, nounwind);
+            functions.insert(FunctionDeclaration::GetChar, get_char);
+
+            let putchar = Self::create_function(
+                "bf_putchar",
+                &[type_holder.int().into()],
+                Some(&type_holder.int()),
+                Some(Linkage::WeakAny),
+                false,
+                module,
+                type_holder,
+            );
+            builder.position_at_end(context.append_basic_block(putchar, "entry"));
+            builder.build_return(Some(&putchar.get_nth_param(0).unwrap()))?;
+            putchar.add_attribute(AttributeLoc::Function, nounwind);
+            functions.insert(FunctionDeclaration::Putchar, putchar);
+            Ok(())
+        }
+
+        /// Emits a call to `abort` if `pointer` is null. Only has an effect when hardening, i.e.
+        /// if `abort` has been declared.
+        fn abort_if_null(
+            pointer: PointerValue<'a>,
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+        ) -> Result<(), BuilderError> {
+            let Some(&abort) = functions.get(&FunctionDeclaration::Abort) else {
+                return Ok(());
+            };
+            let is_null = builder.build_is_null(pointer, "is_null")?;
+            ControlFlowBuilder::new(context, builder).if_then(is_null, || {
+                builder.build_direct_call(abort, &[], "")?;
+                Ok(())
+            })?;
+            Ok(())
+        }
+
+        /// Emits a call to `abort` if `index` is not within the allocated memory. Only has an
+        /// effect when hardening, i.e. if `abort` has been declared.
+        fn abort_if_out_of_bounds(
+            index: IntValue<'a>,
+            capacity_ptr: PointerValue<'a>,
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            let Some(&abort) = functions.get(&FunctionDeclaration::Abort) else {
+                return Ok(());
+            };
+            let is_out_of_bounds = builder.build_int_compare(
+                IntPredicate::UGE,
+                index,
+                builder
+                    .build_load(type_holder.size(), capacity_ptr, "capacity")?
+                    .into_int_value(),
+                "is_out_of_bounds",
+            )?;
+            ControlFlowBuilder::new(context, builder).if_then(is_out_of_bounds, || {
+                builder.build_direct_call(abort, &[], "")?;
+                Ok(())
+            })?;
+            Ok(())
+        }
+
+        fn generate_function_address_to_index(
+            context: &Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
             /* int64_t address_to_index(size_t offset, int64_t address) {
                    return offset + (size_t)address;
                }
@@ -586,18 +1517,21 @@ mod state {
             let entry = context.append_basic_block(address_to_index, "entry");
             builder.position_at_end(entry);
 
-            let sum = builder.build_int_add(offset, address, "sum").unwrap();
+            let sum = builder.build_int_add(offset, address, "sum")?;
 
-            builder.build_return(Some(&sum)).unwrap();
+            builder.build_return(Some(&sum))?;
+            Ok(())
         }
 
         fn generate_function_write(
-            context: &Context,
+            runtime: RuntimeConfig,
+            is_static_tape: bool,
+            context: &'a Context,
             builder: &Builder<'a>,
             functions: &mut Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
+        ) -> Result<(), BuilderError> {
             /*
             void write(
                 int64_t address,
@@ -611,7 +1545,7 @@ mod state {
                 "write",
                 &[
                     type_holder.size().into(),
-                    type_holder.char().into(),
+                    type_holder.cell().into(),
                     type_holder.pointer().into(),
                     type_holder.pointer().into(),
                     type_holder.pointer().into(),
@@ -634,8 +1568,19 @@ mod state {
             let entry = context.append_basic_block(write, "entry");
             builder.position_at_end(entry);
 
-            builder
-                .build_direct_call(
+            let address = Self::tape_address(
+                address,
+                runtime,
+                context,
+                builder,
+                functions,
+                module,
+                type_holder,
+            )?;
+
+            // a static tape exists in its entirety from the start
+            if !is_static_tape {
+                builder.build_direct_call(
                     Self::function(
                         FunctionDeclaration::EnsureSufficientMemoryCapacity,
                         functions,
@@ -647,8 +1592,8 @@ mod state {
                         address.into(),
                     ],
                     "",
-                )
-                .unwrap();
+                )?;
+            }
 
             let index = builder
                 .build_direct_call(
@@ -656,43 +1601,50 @@ mod state {
                     &[
                         address.into(),
                         builder
-                            .build_load(type_holder.size(), offset_ptr, "offset")
-                            .unwrap()
+                            .build_load(type_holder.size(), offset_ptr, "offset")?
                             .into_int_value()
                             .into(),
                     ],
                     "index",
-                )
-                .unwrap()
+                )?
                 .try_as_basic_value()
                 .unwrap_left()
                 .into_int_value();
 
+            Self::abort_if_out_of_bounds(
+                index,
+                capacity_ptr,
+                context,
+                builder,
+                functions,
+                type_holder,
+            )?;
             let memory_address = unsafe {
                 builder.build_gep(
-                    type_holder.char(),
+                    type_holder.cell(),
                     builder
-                        .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")
-                        .unwrap()
+                        .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")?
                         .into_pointer_value(),
                     &[index],
                     "memory_address",
                 )
-            }
-            .unwrap();
+            }?;
 
-            builder.build_store(memory_address, value).unwrap();
+            builder.build_store(memory_address, value)?;
 
-            builder.build_return(None).unwrap();
+            builder.build_return(None)?;
+            Ok(())
         }
 
         fn generate_function_read(
-            context: &Context,
+            runtime: RuntimeConfig,
+            is_static_tape: bool,
+            context: &'a Context,
             builder: &Builder<'a>,
             functions: &mut Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
+        ) -> Result<(), BuilderError> {
             /*
             char read(
                 int64_t address,
@@ -709,7 +1661,7 @@ mod state {
                     type_holder.pointer().into(),
                     type_holder.pointer().into(),
                 ],
-                Some(&type_holder.char()),
+                Some(&type_holder.cell()),
                 Some(Linkage::Internal),
                 false,
                 module,
@@ -726,24 +1678,19 @@ mod state {
             let entry = context.append_basic_block(read, "entry");
             builder.position_at_end(entry);
 
-            builder
-                .build_direct_call(
-                    Self::function(
-                        FunctionDeclaration::EnsureSufficientMemoryCapacity,
-                        functions,
-                    ),
-                    &[
-                        memory_ptr_ptr.into(),
-                        capacity_ptr.into(),
-                        offset_ptr.into(),
-                        address.into(),
-                    ],
-                    "",
-                )
-                .unwrap();
+            let address = Self::tape_address(
+                address,
+                runtime,
+                context,
+                builder,
+                functions,
+                module,
+                type_holder,
+            )?;
 
-            builder
-                .build_direct_call(
+            // a static tape exists in its entirety from the start
+            if !is_static_tape {
+                builder.build_direct_call(
                     Self::function(
                         FunctionDeclaration::EnsureSufficientMemoryCapacity,
                         functions,
@@ -755,8 +1702,8 @@ mod state {
                         address.into(),
                     ],
                     "",
-                )
-                .unwrap();
+                )?;
+            }
 
             let index = builder
                 .build_direct_call(
@@ -764,507 +1711,1387 @@ mod state {
                     &[
                         address.into(),
                         builder
-                            .build_load(type_holder.size(), offset_ptr, "offset")
-                            .unwrap()
+                            .build_load(type_holder.size(), offset_ptr, "offset")?
                             .into_int_value()
                             .into(),
                     ],
                     "index",
-                )
-                .unwrap()
+                )?
                 .try_as_basic_value()
                 .unwrap_left()
                 .into_int_value();
 
+            Self::abort_if_out_of_bounds(
+                index,
+                capacity_ptr,
+                context,
+                builder,
+                functions,
+                type_holder,
+            )?;
             let memory_address = unsafe {
                 builder.build_gep(
-                    type_holder.char(),
+                    type_holder.cell(),
                     builder
-                        .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")
-                        .unwrap()
+                        .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")?
                         .into_pointer_value(),
                     &[index],
                     "memory_address",
                 )
-            }
-            .unwrap();
+            }?;
 
-            let result = builder
-                .build_load(type_holder.char(), memory_address, "result")
-                .unwrap();
+            let result = builder.build_load(type_holder.cell(), memory_address, "result")?;
 
-            builder.build_return(Some(&result)).unwrap();
+            builder.build_return(Some(&result))?;
+            Ok(())
         }
 
-        fn generate_function_ensure_sufficient_memory_capacity(
+        // Flushes the output, prints the message formatted with the given arguments to stderr and
+        // exits with status 1. Without the C standard library (`--bare-metal`), i.e. if `fflush`
+        // has not been declared, there is no stderr and a trap instruction is executed instead.
+        fn exit_with_error(
+            message: &str,
+            arguments: &[IntValue<'a>],
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            let Some(&fflush) = functions.get(&FunctionDeclaration::Fflush) else {
+                let trap =
+                    Self::external_function("llvm.trap", &[], None, false, module, type_holder);
+                builder.build_direct_call(trap, &[], "")?;
+                builder.build_unreachable()?;
+                return Ok(());
+            };
+            builder.build_direct_call(fflush, &[type_holder.pointer().const_zero().into()], "")?;
+            let fprintf = Self::external_function(
+                "fprintf",
+                &[type_holder.pointer().into(), type_holder.pointer().into()],
+                Some(&type_holder.int()),
+                true,
+                module,
+                type_holder,
+            );
+            let exit = Self::external_function(
+                "exit",
+                &[type_holder.int().into()],
+                None,
+                false,
+                module,
+                type_holder,
+            );
+            let message = unsafe { builder.build_global_string(message, "error_message")? };
+            let stream = builder.build_load(
+                type_holder.pointer(),
+                Self::stream_global("stderr", module, type_holder).as_pointer_value(),
+                "stderr",
+            )?;
+            let mut fprintf_arguments: Vec<BasicMetadataValueEnum> =
+                vec![stream.into(), message.as_pointer_value().into()];
+            // the arguments are printed with %lld, even where addresses have 32 bits
+            for &argument in arguments {
+                let long_long = argument.get_type().get_context().i64_type();
+                fprintf_arguments.push(
+                    builder
+                        .build_int_s_extend_or_bit_cast(argument, long_long, "argument")?
+                        .into(),
+                );
+            }
+            builder.build_direct_call(fprintf, &fprintf_arguments, "")?;
+            builder.build_direct_call(exit, &[type_holder.int().const_int(1, false).into()], "")?;
+            builder.build_unreachable()?;
+            Ok(())
+        }
+
+        fn generate_function_cell_overflow(
             context: &'a Context,
             builder: &Builder<'a>,
             functions: &mut Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
-            /* void ensure_sufficient_memory_capacity(
-                    char** memory_ptr_ptr,
-                    size_t* capacity_ptr,
-                    size_t* offset_ptr,
-                    int64_t address
-               )
-            */
-            let ensure_sufficient_memory_capacity = Self::create_function(
-                "ensure_sufficient_memory_capacity",
-                &[
-                    type_holder.pointer().into(), // memory_ptr_ptr
-                    type_holder.pointer().into(), // capacity_ptr
-                    type_holder.pointer().into(), // offset_ptr
-                    type_holder.size().into(),    // address
-                ],
+        ) -> Result<(), BuilderError> {
+            /* void cell_overflow(int64_t address, int64_t source_offset) */
+            let cell_overflow = Self::create_function(
+                "cell_overflow",
+                &[type_holder.size().into(), type_holder.size().into()],
                 None,
                 Some(Linkage::Internal),
                 false,
-                &module,
+                module,
                 type_holder,
             );
+            functions.insert(FunctionDeclaration::CellOverflow, cell_overflow);
 
-            functions.insert(
-                FunctionDeclaration::EnsureSufficientMemoryCapacity,
-                ensure_sufficient_memory_capacity,
-            );
-
-            let memory_ptr_ptr = ensure_sufficient_memory_capacity
-                .get_nth_param(0)
-                .unwrap()
-                .into_pointer_value();
-            let capacity_ptr = ensure_sufficient_memory_capacity
-                .get_nth_param(1)
-                .unwrap()
-                .into_pointer_value();
-            let offset_ptr = ensure_sufficient_memory_capacity
-                .get_nth_param(2)
-                .unwrap()
-                .into_pointer_value();
-            let address = ensure_sufficient_memory_capacity
-                .get_nth_param(3)
-                .unwrap()
-                .into_int_value();
+            let address = cell_overflow.get_nth_param(0).unwrap().into_int_value();
+            let source_offset = cell_overflow.get_nth_param(1).unwrap().into_int_value();
 
-            let entry = context.append_basic_block(ensure_sufficient_memory_capacity, "entry");
+            let entry = context.append_basic_block(cell_overflow, "entry");
             builder.position_at_end(entry);
+            Self::exit_with_error(
+                "error: cell overflow at address %lld (offset %lld of the source)\n",
+                &[address, source_offset],
+                builder,
+                functions,
+                module,
+                type_holder,
+            )
+        }
 
-            let index = builder
-                .build_direct_call(
-                    Self::function(FunctionDeclaration::AddressToIndex, functions),
-                    &[
-                        builder
-                            .build_load(type_holder.size(), offset_ptr, "offset")
-                            .unwrap()
-                            .into(),
-                        address.into(),
-                    ],
-                    "index",
-                )
-                .unwrap()
-                .try_as_basic_value()
-                .unwrap_left()
-                .into_int_value();
+        fn generate_function_left_tape(
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            /* void left_tape(int signal) */
+            let left_tape = Self::create_function(
+                "left_tape",
+                &[type_holder.int().into()],
+                None,
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
+            );
 
-            let is_index_negative = builder
-                .build_int_compare(
-                    IntPredicate::SLT,
-                    index,
-                    type_holder.size().const_zero(),
-                    "is_index_negative",
-                )
-                .unwrap();
+            functions.insert(FunctionDeclaration::LeftTape, left_tape);
 
-            Self::branch(
-                context,
+            let entry = context.append_basic_block(left_tape, "entry");
+            builder.position_at_end(entry);
+            // only the generated code accesses the guard pages, so the C standard library is in a
+            // consistent state when this handler runs
+            Self::exit_with_error(
+                &format!(
+                    "error: the pointer left the tape of {GUARDED_TAPE_CELLS} cells reserved \
+                     with guard pages\n"
+                ),
+                &[],
                 builder,
-                is_index_negative,
-                |after_branch| {
-                    // the index is negative
+                functions,
+                module,
+                type_holder,
+            )
+        }
 
-                    // size_t difference = (size_t)(-index);
-                    let difference = builder.build_int_neg(index, "difference").unwrap();
-                    // (*offset) += difference;
-                    builder
-                        .build_store(
-                            offset_ptr,
-                            builder
-                                .build_int_add(
-                                    builder
-                                        .build_load(type_holder.size(), offset_ptr, "offset")
-                                        .unwrap()
-                                        .into_int_value(),
-                                    difference,
-                                    "new_offset",
-                                )
-                                .unwrap(),
-                        )
-                        .unwrap();
+        // Turns off the echo of the terminal if stdin is one, until the program exits or is
+        // terminated by a signal.
+        fn generate_function_disable_echo(
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            /*
+            static struct termios terminal, silent;
 
-                    // size_t new_capacity = *capacity_ptr + difference;
-                    let new_capacity = builder
-                        .build_int_add(
-                            builder
-                                .build_load(type_holder.size(), capacity_ptr, "capacity")
-                                .unwrap()
-                                .into_int_value(),
-                            difference,
-                            "new_capacity",
-                        )
-                        .unwrap();
+            static void restore_echo(void) {
+                tcsetattr(0, TCSANOW, &terminal);
+            }
 
-                    // char* new_memory_ptr = malloc(*memory_ptr_ptr, new_capacity);
-                    let new_memory_ptr = builder
-                        .build_direct_call(
-                            Self::function(FunctionDeclaration::Realloc, functions),
-                            &[
-                                builder
-                                    .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")
-                                    .unwrap()
-                                    .into_pointer_value()
+            static void restore_echo_and_raise(int signal_number) {
+                restore_echo();
+                signal(signal_number, SIG_DFL);
+                raise(signal_number);
+            }
+
+            static void disable_echo(void) {
+                if (!isatty(0) || tcgetattr(0, &terminal) != 0 || tcgetattr(0, &silent) != 0) {
+                    return;
+                }
+                silent.c_lflag &= ~ECHO;
+                atexit(restore_echo);
+                signal(SIGHUP, restore_echo_and_raise); // and the other terminating signals
+                tcsetattr(0, TCSANOW, &silent);
+            }
+             */
+            let int = type_holder.int();
+            let pointer = type_holder.pointer();
+            let tcgetattr = Self::external_function(
+                "tcgetattr",
+                &[int.into(), pointer.into()],
+                Some(&int),
+                false,
+                module,
+                type_holder,
+            );
+            let tcsetattr = Self::external_function(
+                "tcsetattr",
+                &[int.into(), int.into(), pointer.into()],
+                Some(&int),
+                false,
+                module,
+                type_holder,
+            );
+            let signal = Self::external_function(
+                "signal",
+                &[int.into(), pointer.into()],
+                Some(&pointer),
+                false,
+                module,
+                type_holder,
+            );
+            let raise = Self::external_function(
+                "raise",
+                &[int.into()],
+                Some(&int),
+                false,
+                module,
+                type_holder,
+            );
+            let atexit = Self::external_function(
+                "atexit",
+                &[pointer.into()],
+                Some(&int),
+                false,
+                module,
+                type_holder,
+            );
+            let termios = |name: &str| {
+                let termios_type = type_holder.char().array_type(TERMIOS_SIZE);
+                let global = module.add_global(termios_type, None, name);
+                global.set_linkage(Linkage::Internal);
+                global.set_initializer(&termios_type.const_zero());
+                global.set_alignment(8);
+                global.as_pointer_value()
+            };
+            let terminal = termios("terminal");
+            let silent = termios("silent");
+            let stdin = int.const_zero();
+            let set_terminal = |settings: PointerValue<'a>| -> Result<(), BuilderError> {
+                builder.build_direct_call(
+                    tcsetattr,
+                    &[
+                        stdin.into(),
+                        int.const_int(TCSANOW, false).into(),
+                        settings.into(),
+                    ],
+                    "",
+                )?;
+                Ok(())
+            };
+
+            let restore_echo = Self::create_function(
+                "restore_echo",
+                &[],
+                None,
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
+            );
+            builder.position_at_end(context.append_basic_block(restore_echo, "entry"));
+            set_terminal(terminal)?;
+            builder.build_return(None)?;
+
+            let restore_echo_and_raise = Self::create_function(
+                "restore_echo_and_raise",
+                &[int.into()],
+                None,
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
+            );
+            builder.position_at_end(context.append_basic_block(restore_echo_and_raise, "entry"));
+            let signal_number = restore_echo_and_raise.get_nth_param(0).unwrap();
+            builder.build_direct_call(restore_echo, &[], "")?;
+            // SIG_DFL
+            builder.build_direct_call(
+                signal,
+                &[signal_number.into(), pointer.const_zero().into()],
+                "",
+            )?;
+            builder.build_direct_call(raise, &[signal_number.into()], "")?;
+            builder.build_return(None)?;
+
+            let disable_echo = Self::create_function(
+                "disable_echo",
+                &[],
+                None,
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
+            );
+            functions.insert(FunctionDeclaration::DisableEcho, disable_echo);
+            builder.position_at_end(context.append_basic_block(disable_echo, "entry"));
+            let control_flow = ControlFlowBuilder::new(context, builder);
+            let call = |function: FunctionValue<'a>,
+                        arguments: &[BasicMetadataValueEnum<'a>]|
+             -> Result<IntValue<'a>, BuilderError> {
+                Ok(builder
+                    .build_direct_call(function, arguments, "result")?
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_int_value())
+            };
+            let is_terminal = call(
+                Self::function(FunctionDeclaration::Isatty, functions),
+                &[stdin.into()],
+            )?;
+            let is_not_terminal =
+                builder.build_int_compare(IntPredicate::EQ, is_terminal, int.const_zero(), "")?;
+            control_flow.if_then(is_not_terminal, || control_flow.return_early(None))?;
+            for settings in [terminal, silent] {
+                let result = call(tcgetattr, &[stdin.into(), settings.into()])?;
+                let failed =
+                    builder.build_int_compare(IntPredicate::NE, result, int.const_zero(), "")?;
+                control_flow.if_then(failed, || control_flow.return_early(None))?;
+            }
+            let flag_type = context.custom_width_int_type(TERMIOS_FLAG_BITS);
+            let local_flags_ptr = unsafe {
+                builder.build_gep(
+                    type_holder.char(),
+                    silent,
+                    &[type_holder
+                        .size()
+                        .const_int(TERMIOS_LOCAL_FLAGS_OFFSET, false)],
+                    "local_flags_ptr",
+                )?
+            };
+            let local_flags = builder
+                .build_load(flag_type, local_flags_ptr, "local_flags")?
+                .into_int_value();
+            builder.build_store(
+                local_flags_ptr,
+                builder.build_and(
+                    local_flags,
+                    flag_type.const_int(!ECHO, false),
+                    "without_echo",
+                )?,
+            )?;
+            builder.build_direct_call(
+                atexit,
+                &[restore_echo.as_global_value().as_pointer_value().into()],
+                "",
+            )?;
+            for signal_number in TERMINATING_SIGNALS {
+                builder.build_direct_call(
+                    signal,
+                    &[
+                        int.const_int(signal_number, false).into(),
+                        restore_echo_and_raise
+                            .as_global_value()
+                            .as_pointer_value()
+                            .into(),
+                    ],
+                    "",
+                )?;
+            }
+            set_terminal(silent)?;
+            builder.build_return(None)?;
+            Ok(())
+        }
+
+        // Maps the tape of `--guard-pages` between two inaccessible guard regions and installs
+        // the handler for accesses of them. Returns the address of the first cell of the tape.
+        fn reserve_guarded_tape(
+            context: &Context,
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<PointerValue<'a>, BuilderError> {
+            let mmap = Self::external_function(
+                "mmap",
+                &[
+                    type_holder.pointer().into(),
+                    type_holder.size().into(),
+                    type_holder.int().into(),
+                    type_holder.int().into(),
+                    type_holder.int().into(),
+                    type_holder.size().into(),
+                ],
+                Some(&type_holder.pointer()),
+                false,
+                module,
+                type_holder,
+            );
+            let mprotect = Self::external_function(
+                "mprotect",
+                &[
+                    type_holder.pointer().into(),
+                    type_holder.size().into(),
+                    type_holder.int().into(),
+                ],
+                Some(&type_holder.int()),
+                false,
+                module,
+                type_holder,
+            );
+            let signal = Self::external_function(
+                "signal",
+                &[type_holder.int().into(), type_holder.pointer().into()],
+                Some(&type_holder.pointer()),
+                false,
+                module,
+                type_holder,
+            );
+
+            let tape_bytes = Self::cells_to_bytes(
+                type_holder.size().const_int(GUARDED_TAPE_CELLS, false),
+                builder,
+                type_holder,
+            )?;
+            let guard_bytes = type_holder.size().const_int(GUARD_BYTES, false);
+            let mapping_bytes = builder.build_int_add(
+                tape_bytes,
+                type_holder.size().const_int(2 * GUARD_BYTES, false),
+                "mapping_bytes",
+            )?;
+            // the pages of the tape are only backed by memory once they are written to
+            let mapping = builder
+                .build_direct_call(
+                    mmap,
+                    &[
+                        type_holder.pointer().const_zero().into(),
+                        mapping_bytes.into(),
+                        type_holder.int().const_int(PROT_NONE, false).into(),
+                        type_holder
+                            .int()
+                            .const_int(MAP_PRIVATE | MAP_ANONYMOUS_NORESERVE, false)
+                            .into(),
+                        type_holder.int().const_all_ones().into(),
+                        type_holder.size().const_zero().into(),
+                    ],
+                    "mapping",
+                )?
+                .try_as_basic_value()
+                .unwrap_left()
+                .into_pointer_value();
+            let memory = unsafe {
+                builder.build_gep(type_holder.char(), mapping, &[guard_bytes], "memory")?
+            };
+            let protection = builder
+                .build_direct_call(
+                    mprotect,
+                    &[
+                        memory.into(),
+                        tape_bytes.into(),
+                        type_holder.int().const_int(PROT_READ_WRITE, false).into(),
+                    ],
+                    "protection",
+                )?
+                .try_as_basic_value()
+                .unwrap_left()
+                .into_int_value();
+            // mmap signals failure with the address -1
+            let is_unmapped = builder.build_int_compare(
+                IntPredicate::EQ,
+                builder.build_ptr_to_int(mapping, type_holder.size(), "mapping_address")?,
+                type_holder.size().const_all_ones(),
+                "is_unmapped",
+            )?;
+            let is_unprotected = builder.build_int_compare(
+                IntPredicate::NE,
+                protection,
+                type_holder.int().const_zero(),
+                "is_unprotected",
+            )?;
+            let is_failed = builder.build_or(is_unmapped, is_unprotected, "is_failed")?;
+            ControlFlowBuilder::new(context, builder).if_then(is_failed, || {
+                Self::exit_with_error(
+                    "error: failed to reserve the tape\n",
+                    &[],
+                    builder,
+                    functions,
+                    module,
+                    type_holder,
+                )
+            })?;
+
+            let left_tape = Self::function(FunctionDeclaration::LeftTape, functions)
+                .as_global_value()
+                .as_pointer_value();
+            for signal_number in [SIGSEGV, SIGBUS] {
+                builder.build_direct_call(
+                    signal,
+                    &[
+                        type_holder.int().const_int(signal_number, false).into(),
+                        left_tape.into(),
+                    ],
+                    "",
+                )?;
+            }
+            Ok(memory)
+        }
+
+        // The address of the cell that is accessed instead of the cell at the given address, see
+        // `RuntimeConfig::tape_address`. Accessing a cell outside of a trapping tape exits.
+        fn tape_address(
+            address: IntValue<'a>,
+            runtime: RuntimeConfig,
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<IntValue<'a>, BuilderError> {
+            let Some(tape_size) = runtime.fixed_tape_size() else {
+                return Ok(address);
+            };
+            let tape_size = tape_size.min(i64::MAX as u64);
+            let last = type_holder.size().const_int(tape_size - 1, false);
+            match runtime.out_of_bounds {
+                OutOfBounds::Grow => Ok(address),
+                OutOfBounds::Trap => {
+                    // negative addresses are greater than every size when compared unsigned
+                    let is_outside = builder.build_int_compare(
+                        IntPredicate::UGT,
+                        address,
+                        last,
+                        "is_outside",
+                    )?;
+                    ControlFlowBuilder::new(context, builder).if_then(is_outside, || {
+                        Self::exit_with_error(
+                            &format!(
+                                "error: address %lld is outside the tape of {tape_size} cells\n"
+                            ),
+                            &[address],
+                            builder,
+                            functions,
+                            module,
+                            type_holder,
+                        )
+                    })?;
+                    Ok(address)
+                }
+                OutOfBounds::Clamp => {
+                    let is_below = builder.build_int_compare(
+                        IntPredicate::SLT,
+                        address,
+                        type_holder.size().const_zero(),
+                        "is_below",
+                    )?;
+                    let is_above =
+                        builder.build_int_compare(IntPredicate::SGT, address, last, "is_above")?;
+                    let clamped = builder
+                        .build_select(is_above, last, address, "clamped")?
+                        .into_int_value();
+                    Ok(builder
+                        .build_select(
+                            is_below,
+                            type_holder.size().const_zero(),
+                            clamped,
+                            "clamped",
+                        )?
+                        .into_int_value())
+                }
+                // without branches, so that accesses stay cheap
+                OutOfBounds::Wrap if tape_size.is_power_of_two() => {
+                    builder.build_and(address, last, "wrapped")
+                }
+                OutOfBounds::Wrap => {
+                    let size = type_holder.size().const_int(tape_size, false);
+                    let remainder = builder.build_int_signed_rem(address, size, "remainder")?;
+                    let is_negative = builder.build_int_compare(
+                        IntPredicate::SLT,
+                        remainder,
+                        type_holder.size().const_zero(),
+                        "is_negative",
+                    )?;
+                    let adjusted = builder.build_int_add(remainder, size, "adjusted")?;
+                    Ok(builder
+                        .build_select(is_negative, adjusted, remainder, "wrapped")?
+                        .into_int_value())
+                }
+            }
+        }
+
+        fn generate_function_ensure_sufficient_memory_capacity(
+            max_memory: Option<u64>,
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            /* void ensure_sufficient_memory_capacity(
+                    char** memory_ptr_ptr,
+                    size_t* capacity_ptr,
+                    size_t* offset_ptr,
+                    int64_t address
+               )
+            */
+            let ensure_sufficient_memory_capacity = Self::create_function(
+                "ensure_sufficient_memory_capacity",
+                &[
+                    type_holder.pointer().into(), // memory_ptr_ptr
+                    type_holder.pointer().into(), // capacity_ptr
+                    type_holder.pointer().into(), // offset_ptr
+                    type_holder.size().into(),    // address
+                ],
+                None,
+                Some(Linkage::Internal),
+                false,
+                &module,
+                type_holder,
+            );
+
+            functions.insert(
+                FunctionDeclaration::EnsureSufficientMemoryCapacity,
+                ensure_sufficient_memory_capacity,
+            );
+
+            let memory_ptr_ptr = ensure_sufficient_memory_capacity
+                .get_nth_param(0)
+                .unwrap()
+                .into_pointer_value();
+            let capacity_ptr = ensure_sufficient_memory_capacity
+                .get_nth_param(1)
+                .unwrap()
+                .into_pointer_value();
+            let offset_ptr = ensure_sufficient_memory_capacity
+                .get_nth_param(2)
+                .unwrap()
+                .into_pointer_value();
+            let address = ensure_sufficient_memory_capacity
+                .get_nth_param(3)
+                .unwrap()
+                .into_int_value();
+
+            let entry = context.append_basic_block(ensure_sufficient_memory_capacity, "entry");
+            builder.position_at_end(entry);
+
+            let index = builder
+                .build_direct_call(
+                    Self::function(FunctionDeclaration::AddressToIndex, functions),
+                    &[
+                        builder
+                            .build_load(type_holder.size(), offset_ptr, "offset")?
+                            .into(),
+                        address.into(),
+                    ],
+                    "index",
+                )?
+                .try_as_basic_value()
+                .unwrap_left()
+                .into_int_value();
+
+            let is_index_negative = builder.build_int_compare(
+                IntPredicate::SLT,
+                index,
+                type_holder.size().const_zero(),
+                "is_index_negative",
+            )?;
+
+            /*
+            if (new_capacity > max_memory) {
+                fflush(NULL);
+                fprintf(stderr, "error: tape limit of <max_memory> cells exceeded at address %lld\n", address);
+                exit(1);
+            }
+             */
+            let exit_if_limit_exceeded = |new_capacity: IntValue<'a>| -> Result<(), BuilderError> {
+                let Some(max_memory) = max_memory else {
+                    return Ok(());
+                };
+                let limit_exceeded = builder.build_int_compare(
+                    IntPredicate::UGT,
+                    new_capacity,
+                    type_holder.size().const_int(max_memory, false),
+                    "limit_exceeded",
+                )?;
+                ControlFlowBuilder::new(context, builder).if_then(limit_exceeded, || {
+                    Self::exit_with_error(
+                        &format!(
+                            "error: tape limit of {max_memory} cells exceeded at address %lld\n"
+                        ),
+                        &[address],
+                        builder,
+                        functions,
+                        module,
+                        type_holder,
+                    )
+                })
+            };
+
+            let control_flow = ControlFlowBuilder::new(context, builder);
+            control_flow.if_then_else(
+                is_index_negative,
+                || {
+                    // the index is negative
+
+                    // size_t difference = (size_t)(-index);
+                    let difference = builder.build_int_neg(index, "difference")?;
+
+                    // size_t new_capacity = *capacity_ptr + difference;
+                    let new_capacity = builder.build_int_add(
+                        builder
+                            .build_load(type_holder.size(), capacity_ptr, "capacity")?
+                            .into_int_value(),
+                        difference,
+                        "new_capacity",
+                    )?;
+                    exit_if_limit_exceeded(new_capacity)?;
+
+                    // (*offset) += difference;
+                    builder.build_store(
+                        offset_ptr,
+                        builder.build_int_add(
+                            builder
+                                .build_load(type_holder.size(), offset_ptr, "offset")?
+                                .into_int_value(),
+                            difference,
+                            "new_offset",
+                        )?,
+                    )?;
+
+                    // cell* new_memory_ptr = realloc(*memory_ptr_ptr, new_capacity * sizeof(cell))
+                    let new_memory_ptr = builder
+                        .build_direct_call(
+                            Self::function(FunctionDeclaration::Realloc, functions),
+                            &[
+                                builder
+                                    .build_load(
+                                        type_holder.pointer(),
+                                        memory_ptr_ptr,
+                                        "memory_ptr",
+                                    )?
+                                    .into_pointer_value()
                                     .into(),
-                                new_capacity.into(),
+                                Self::cells_to_bytes(new_capacity, builder, type_holder)?.into(),
                             ],
                             "new_memory_ptr",
-                        )
-                        .unwrap()
+                        )?
                         .try_as_basic_value()
                         .unwrap_left()
                         .into_pointer_value();
 
-                    // char* dest = &new_memory_ptr[difference];
+                    Self::abort_if_null(new_memory_ptr, context, builder, functions)?;
+
+                    // cell* dest = &new_memory_ptr[difference];
                     let dest = unsafe {
-                        builder
-                            .build_gep(type_holder.char(), new_memory_ptr, &[difference], "dest")
-                            .unwrap()
+                        builder.build_gep(
+                            type_holder.cell(),
+                            new_memory_ptr,
+                            &[difference],
+                            "dest",
+                        )?
                     };
 
-                    // memmove(dest, new_memory_ptr, *capacity_ptr)
-                    builder
-                        .build_direct_call(
-                            Self::function(FunctionDeclaration::Memmove, functions),
-                            &[
-                                dest.into(),
-                                new_memory_ptr.into(),
+                    // memmove(dest, new_memory_ptr, *capacity_ptr * sizeof(cell))
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Memmove, functions),
+                        &[
+                            dest.into(),
+                            new_memory_ptr.into(),
+                            Self::cells_to_bytes(
                                 builder
-                                    .build_load(type_holder.size(), capacity_ptr, "capacity")
-                                    .unwrap()
-                                    .into(),
-                            ],
-                            "",
-                        )
-                        .unwrap();
+                                    .build_load(type_holder.size(), capacity_ptr, "capacity")?
+                                    .into_int_value(),
+                                builder,
+                                type_holder,
+                            )?
+                            .into(),
+                        ],
+                        "",
+                    )?;
 
-                    // memset(new_memory_ptr, 0, difference)
-                    builder
-                        .build_direct_call(
-                            Self::function(Memset, functions),
-                            &[
-                                new_memory_ptr.into(),
-                                type_holder.int().const_int(0, false).into(),
-                                difference.into(),
-                            ],
-                            "",
-                        )
-                        .unwrap();
+                    // memset(new_memory_ptr, 0, difference * sizeof(cell))
+                    builder.build_direct_call(
+                        Self::function(Memset, functions),
+                        &[
+                            new_memory_ptr.into(),
+                            type_holder.int().const_int(0, false).into(),
+                            Self::cells_to_bytes(difference, builder, type_holder)?.into(),
+                        ],
+                        "",
+                    )?;
 
                     // *capacity_ptr = new_capacity;
-                    builder.build_store(capacity_ptr, new_capacity).unwrap();
+                    builder.build_store(capacity_ptr, new_capacity)?;
 
                     // *memory_ptr_ptr = new_memory_ptr;
-                    builder.build_store(memory_ptr_ptr, new_memory_ptr).unwrap();
-
-                    builder.build_unconditional_branch(after_branch).unwrap();
+                    builder.build_store(memory_ptr_ptr, new_memory_ptr)?;
+                    Ok(())
                 },
-                |after_branch| {
-                    let index_is_greater_than_or_equal_to_capacity = builder
-                        .build_int_compare(
-                            IntPredicate::UGE,
+                || {
+                    let index_is_greater_than_or_equal_to_capacity = builder.build_int_compare(
+                        IntPredicate::UGE,
+                        index,
+                        builder
+                            .build_load(type_holder.size(), capacity_ptr, "capacity")?
+                            .into_int_value(),
+                        "index_is_greater_than_or_equal_to_capacity",
+                    )?;
+
+                    control_flow.if_then(index_is_greater_than_or_equal_to_capacity, || {
+                        // size_t new_capacity = index + 1;
+                        let new_capacity = builder.build_int_add(
                             index,
+                            type_holder.size().const_int(1, false),
+                            "new_capacity",
+                        )?;
+                        exit_if_limit_exceeded(new_capacity)?;
+
+                        // cell* new_memory_ptr = realloc(memory_ptr, new_capacity * sizeof(cell))
+                        let new_memory_ptr = builder
+                            .build_direct_call(
+                                Self::function(FunctionDeclaration::Realloc, functions),
+                                &[
+                                    builder
+                                        .build_load(
+                                            type_holder.pointer(),
+                                            memory_ptr_ptr,
+                                            "memory_ptr",
+                                        )?
+                                        .into_pointer_value()
+                                        .into(),
+                                    Self::cells_to_bytes(new_capacity, builder, type_holder)?
+                                        .into(),
+                                ],
+                                "new_memory_ptr",
+                            )?
+                            .try_as_basic_value()
+                            .unwrap_left()
+                            .into_pointer_value();
+
+                        Self::abort_if_null(new_memory_ptr, context, builder, functions)?;
+
+                        // size_t difference = new_capacity - capacity;
+                        let difference = builder.build_int_sub(
+                            new_capacity,
                             builder
-                                .build_load(type_holder.size(), capacity_ptr, "capacity")
-                                .unwrap()
+                                .build_load(type_holder.size(), capacity_ptr, "capacity")?
                                 .into_int_value(),
-                            "index_is_greater_than_or_equal_to_capacity",
-                        )
-                        .unwrap();
+                            "difference",
+                        )?;
+
+                        // cell* dest = &new_memory_ptr[*capacity_ptr];
+                        let dest = unsafe {
+                            builder.build_gep(
+                                type_holder.cell(),
+                                new_memory_ptr,
+                                &[builder
+                                    .build_load(type_holder.size(), capacity_ptr, "capacity")?
+                                    .into_int_value()],
+                                "dest",
+                            )?
+                        };
+
+                        // memset(dest, 0, difference * sizeof(cell));
+                        builder.build_direct_call(
+                            Self::function(FunctionDeclaration::Memset, functions),
+                            &[
+                                dest.into(),
+                                type_holder.int().const_int(0, false).into(),
+                                Self::cells_to_bytes(difference, builder, type_holder)?.into(),
+                            ],
+                            "",
+                        )?;
 
-                    Self::branch(
-                        context,
-                        builder,
-                        index_is_greater_than_or_equal_to_capacity,
-                        |after_branch| {
-                            // size_t new_capacity = index + 1;
-                            let new_capacity = builder
-                                .build_int_add(
-                                    index,
-                                    type_holder.size().const_int(1, false),
-                                    "new_capacity",
-                                )
-                                .unwrap();
-
-                            // char* new_memory_ptr = realloc(memory_ptr, new_capacity);
-                            let new_memory_ptr = builder
-                                .build_direct_call(
-                                    Self::function(FunctionDeclaration::Realloc, functions),
-                                    &[
-                                        builder
-                                            .build_load(
-                                                type_holder.pointer(),
-                                                memory_ptr_ptr,
-                                                "memory_ptr",
-                                            )
-                                            .unwrap()
-                                            .into_pointer_value()
-                                            .into(),
-                                        new_capacity.into(),
-                                    ],
-                                    "new_memory_ptr",
-                                )
-                                .unwrap()
-                                .try_as_basic_value()
-                                .unwrap_left()
-                                .into_pointer_value();
+                        // *memory_ptr_ptr = new_memory_ptr;
+                        builder.build_store(memory_ptr_ptr, new_memory_ptr)?;
 
-                            // size_t difference = new_capacity - capacity;
-                            let difference = builder
-                                .build_int_sub(
-                                    new_capacity,
-                                    builder
-                                        .build_load(type_holder.size(), capacity_ptr, "capacity")
-                                        .unwrap()
-                                        .into_int_value(),
-                                    "difference",
-                                )
-                                .unwrap();
-
-                            // char* dest = &new_memory_ptr[*capacity_ptr];
-                            let dest = unsafe {
-                                builder
-                                    .build_gep(
-                                        type_holder.char(),
-                                        new_memory_ptr,
-                                        &[builder
-                                            .build_load(
-                                                type_holder.size(),
-                                                capacity_ptr,
-                                                "capacity",
-                                            )
-                                            .unwrap()
-                                            .into_int_value()],
-                                        "dest",
-                                    )
-                                    .unwrap()
-                            };
-
-                            // memset(dest, 0, difference);
-                            builder
-                                .build_direct_call(
-                                    Self::function(FunctionDeclaration::Memset, functions),
-                                    &[
-                                        dest.into(),
-                                        type_holder.int().const_int(0, false).into(),
-                                        difference.into(),
-                                    ],
-                                    "",
-                                )
-                                .unwrap();
+                        // *capacity_ptr = new_capacity;
+                        builder.build_store(capacity_ptr, new_capacity)?;
+                        Ok(())
+                    })
+                },
+            )?;
+
+            builder.build_return(None)?;
+            Ok(())
+        }
+
+        fn generate_function_mem_dump(
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            let mem_dump = Self::create_function(
+                "mem_dump",
+                &[type_holder.pointer().into(), type_holder.size().into()],
+                None,
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
+            );
 
-                            // *memory_ptr_ptr = new_memory_ptr;
-                            builder.build_store(memory_ptr_ptr, new_memory_ptr).unwrap();
+            functions.insert(FunctionDeclaration::MemDump, mem_dump);
 
-                            // *capacity_ptr = new_capacity;
-                            builder.build_store(capacity_ptr, new_capacity).unwrap();
+            let memory_ptr = mem_dump.get_nth_param(0).unwrap().into_pointer_value();
+            let capacity = mem_dump.get_nth_param(1).unwrap().into_int_value();
 
-                            builder.build_unconditional_branch(after_branch).unwrap();
-                        },
-                        |_| {
-                            builder.build_return(None).unwrap();
-                        },
-                    );
+            let entry = context.append_basic_block(mem_dump, "entry");
+            builder.position_at_end(entry);
+
+            builder.build_direct_call(
+                Self::function(FunctionDeclaration::Putchar, functions),
+                &[type_holder.int().const_int(77, false).into()],
+                "",
+            )?;
+
+            builder.build_direct_call(
+                Self::function(FunctionDeclaration::Putchar, functions),
+                &[type_holder.int().const_int(58, false).into()],
+                "",
+            )?;
+
+            let i_ptr = builder.build_alloca(type_holder.size(), "i")?;
+            builder.build_store(i_ptr, type_holder.size().const_zero())?;
+
+            let control_flow = ControlFlowBuilder::new(context, builder);
+            control_flow.while_loop(
+                || {
+                    builder.build_int_compare(
+                        IntPredicate::ULT,
+                        builder
+                            .build_load(type_holder.size(), i_ptr, "i")?
+                            .into_int_value(),
+                        capacity,
+                        "i_is_less_than_capacity",
+                    )
+                },
+                |_| {
+                    // cell* address = &memory_ptr[i]
+                    let address = unsafe {
+                        builder.build_gep(
+                            type_holder.cell(),
+                            memory_ptr,
+                            &[builder
+                                .build_load(type_holder.size(), i_ptr, "i")?
+                                .into_int_value()],
+                            "address",
+                        )?
+                    };
+                    let value = builder
+                        .build_load(type_holder.cell(), address, "value")?
+                        .into_int_value();
 
-                    builder.build_unconditional_branch(after_branch).unwrap();
+                    let printable_value = builder.build_int_add(
+                        value,
+                        type_holder.cell().const_int(48, false),
+                        "printable_value",
+                    )?;
+
+                    let printable_value_int = builder.build_int_cast(
+                        printable_value,
+                        type_holder.int(),
+                        "printable_value_int",
+                    )?;
+
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Putchar, functions),
+                        &[printable_value_int.into()],
+                        "",
+                    )?;
+                    let new_i = builder.build_int_add(
+                        builder
+                            .build_load(type_holder.size(), i_ptr, "i")?
+                            .into_int_value(),
+                        type_holder.size().const_int(1, false),
+                        "new_i",
+                    )?;
+                    builder.build_store(i_ptr, new_i)?;
+                    Ok(())
                 },
+            )?;
+
+            builder.build_direct_call(
+                Self::function(FunctionDeclaration::Putchar, functions),
+                &[type_holder.int().const_int(10, false).into()],
+                "",
+            )?;
+            builder.build_return(None)?;
+            Ok(())
+        }
+
+        fn generate_printf(
+            format_string: &str,
+            args: &[BasicMetadataValueEnum],
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+        ) -> Result<(), BuilderError> {
+            let string = unsafe { builder.build_global_string(format_string, "string")? };
+            let first: &[BasicMetadataValueEnum] = &[string.as_pointer_value().into()];
+            builder.build_direct_call(
+                Self::function(FunctionDeclaration::Printf, functions),
+                &[first, args].concat(),
+                "",
+            )?;
+            Ok(())
+        }
+
+        // Takes the value of the current cell and returns the value `,` stores in it, which is the
+        // current value itself if the input has ended and the cell has to stay unchanged.
+        fn generate_function_read_char(
+            prompt: Option<&str>,
+            runtime: RuntimeConfig,
+            context: &Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            let read_char = Self::create_function(
+                "read_char",
+                &[type_holder.cell().into()],
+                Some(&type_holder.cell()),
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
             );
+            functions.insert(FunctionDeclaration::ReadChar, read_char);
+            let current_value = read_char.get_nth_param(0).unwrap().into_int_value();
+
+            let entry = context.append_basic_block(read_char, "entry");
+            builder.position_at_end(entry);
+
+            let get_char = || -> Result<IntValue<'a>, BuilderError> {
+                Ok(builder
+                    .build_direct_call(
+                        Self::function(FunctionDeclaration::GetChar, functions),
+                        &[],
+                        "value",
+                    )?
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_int_value())
+            };
+            // converts the result of `getchar` to the value of the cell
+            let cell_value = |value: IntValue<'a>| -> Result<IntValue<'a>, BuilderError> {
+                let eof_value = match runtime.eof_value() {
+                    Some(value) => type_holder.cell().const_int(value, false),
+                    None => current_value,
+                };
+                let is_eof = builder.build_int_compare(
+                    IntPredicate::EQ,
+                    value,
+                    type_holder.int().const_all_ones(),
+                    "is_eof",
+                )?;
+                let byte =
+                    builder.build_int_cast_sign_flag(value, type_holder.cell(), false, "byte")?;
+                Ok(builder
+                    .build_select(is_eof, eof_value, byte, "cell_value")?
+                    .into_int_value())
+            };
+
+            let Some(prompt) = prompt else {
+                builder.build_return(Some(&cell_value(get_char()?)?))?;
+                return Ok(());
+            };
+
+            let at_line_start = module.add_global(type_holder.char(), None, "at_line_start");
+            at_line_start.set_linkage(Linkage::Internal);
+            at_line_start.set_initializer(&type_holder.char().const_int(1, false));
+            let at_line_start_ptr = at_line_start.as_pointer_value();
 
-            builder.build_return(None).unwrap();
+            let is_at_line_start = builder.build_int_compare(
+                IntPredicate::NE,
+                builder
+                    .build_load(type_holder.char(), at_line_start_ptr, "at_line_start")?
+                    .into_int_value(),
+                type_holder.char().const_zero(),
+                "is_at_line_start",
+            )?;
+            let control_flow = ControlFlowBuilder::new(context, builder);
+            control_flow.if_then(is_at_line_start, || {
+                let is_terminal = builder.build_int_compare(
+                    IntPredicate::NE,
+                    builder
+                        .build_direct_call(
+                            Self::function(FunctionDeclaration::Isatty, functions),
+                            &[type_holder.int().const_zero().into()],
+                            "is_terminal",
+                        )?
+                        .try_as_basic_value()
+                        .unwrap_left()
+                        .into_int_value(),
+                    type_holder.int().const_zero(),
+                    "is_terminal",
+                )?;
+                control_flow.if_then(is_terminal, || {
+                    let prompt = unsafe { builder.build_global_string(prompt, "prompt")? };
+                    Self::generate_printf(
+                        "%s",
+                        &[prompt.as_pointer_value().into()],
+                        builder,
+                        functions,
+                    )?;
+                    // a null pointer flushes all output streams
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Fflush, functions),
+                        &[type_holder.pointer().const_zero().into()],
+                        "",
+                    )?;
+                    Ok(())
+                })?;
+                Ok(())
+            })?;
+
+            let value = get_char()?;
+            let is_newline = builder.build_int_compare(
+                IntPredicate::EQ,
+                value,
+                type_holder.int().const_int(b'\n' as u64, false),
+                "is_newline",
+            )?;
+            builder.build_store(
+                at_line_start_ptr,
+                builder.build_int_z_extend(is_newline, type_holder.char(), "at_line_start")?,
+            )?;
+            builder.build_return(Some(&cell_value(value)?))?;
+            Ok(())
         }
 
-        fn generate_function_mem_dump(
+        fn generate_function_write_char(
+            max_output: Option<u64>,
             context: &'a Context,
             builder: &Builder<'a>,
             functions: &mut Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
-            let mem_dump = Self::create_function(
-                "mem_dump",
-                &[type_holder.pointer().into(), type_holder.size().into()],
+        ) -> Result<(), BuilderError> {
+            let Some(max_output) = max_output else {
+                let putchar = Self::function(FunctionDeclaration::Putchar, functions);
+                functions.insert(FunctionDeclaration::WriteChar, putchar);
+                return Ok(());
+            };
+            /*
+            static size_t output_size = 0;
+
+            void write_char(int value) {
+                if (output_size == max_output) {
+                    fflush(NULL);
+                    fputs("error: program exceeded the output limit of <max_output> bytes\n", stderr);
+                    exit(1);
+                }
+                output_size += 1;
+                putchar(value);
+            }
+             */
+            let fputs = Self::external_function(
+                "fputs",
+                &[type_holder.pointer().into(), type_holder.pointer().into()],
+                Some(&type_holder.int()),
+                false,
+                module,
+                type_holder,
+            );
+            let exit = Self::external_function(
+                "exit",
+                &[type_holder.int().into()],
+                None,
+                false,
+                module,
+                type_holder,
+            );
+            let stderr = Self::stream_global("stderr", module, type_holder);
+            let output_size = module.add_global(type_holder.size(), None, "output_size");
+            output_size.set_linkage(Linkage::Internal);
+            output_size.set_initializer(&type_holder.size().const_zero());
+            let output_size_ptr = output_size.as_pointer_value();
+
+            let write_char = Self::create_function(
+                "write_char",
+                &[type_holder.int().into()],
                 None,
                 Some(Linkage::Internal),
                 false,
                 module,
                 type_holder,
             );
+            functions.insert(FunctionDeclaration::WriteChar, write_char);
+            let functions = &*functions;
 
-            functions.insert(FunctionDeclaration::MemDump, mem_dump);
-
-            let memory_ptr = mem_dump.get_nth_param(0).unwrap().into_pointer_value();
-            let capacity = mem_dump.get_nth_param(1).unwrap().into_int_value();
-
-            let entry = context.append_basic_block(mem_dump, "entry");
-            let loop_start = context.append_basic_block(mem_dump, "loop_start");
-            let loop_body = context.append_basic_block(mem_dump, "loop_body");
-            let after_loop = context.append_basic_block(mem_dump, "after_loop");
+            let entry = context.append_basic_block(write_char, "entry");
             builder.position_at_end(entry);
 
-            builder
-                .build_direct_call(
-                    Self::function(FunctionDeclaration::Putchar, functions),
-                    &[type_holder.int().const_int(77, false).into()],
+            let size = builder
+                .build_load(type_holder.size(), output_size_ptr, "output_size")?
+                .into_int_value();
+            let limit_reached = builder.build_int_compare(
+                IntPredicate::EQ,
+                size,
+                type_holder.size().const_int(max_output, false),
+                "limit_reached",
+            )?;
+            ControlFlowBuilder::new(context, builder).if_then(limit_reached, || {
+                builder.build_direct_call(
+                    Self::function(FunctionDeclaration::Fflush, functions),
+                    &[type_holder.pointer().const_zero().into()],
                     "",
-                )
-                .unwrap();
-
-            builder
-                .build_direct_call(
-                    Self::function(FunctionDeclaration::Putchar, functions),
-                    &[type_holder.int().const_int(58, false).into()],
+                )?;
+                let message = unsafe {
+                    builder.build_global_string(
+                        &format!(
+                            "error: program exceeded the output limit of {max_output} bytes\n"
+                        ),
+                        "output_limit_message",
+                    )?
+                };
+                let stream = builder.build_load(
+                    type_holder.pointer(),
+                    stderr.as_pointer_value(),
+                    "stderr",
+                )?;
+                builder.build_direct_call(
+                    fputs,
+                    &[message.as_pointer_value().into(), stream.into()],
                     "",
-                )
-                .unwrap();
-
-            let i_ptr = builder.build_alloca(type_holder.size(), "i").unwrap();
-            builder
-                .build_store(i_ptr, type_holder.size().const_zero())
-                .unwrap();
-
-            builder.build_unconditional_branch(loop_start).unwrap();
-
-            builder.position_at_end(loop_start);
+                )?;
+                builder.build_direct_call(
+                    exit,
+                    &[type_holder.int().const_int(1, false).into()],
+                    "",
+                )?;
+                builder.build_unreachable()?;
+                Ok(())
+            })?;
+
+            let new_size = builder.build_int_add(
+                size,
+                type_holder.size().const_int(1, false),
+                "new_output_size",
+            )?;
+            builder.build_store(output_size_ptr, new_size)?;
+            builder.build_direct_call(
+                Self::function(FunctionDeclaration::Putchar, functions),
+                &[write_char.get_nth_param(0).unwrap().into()],
+                "",
+            )?;
+            builder.build_return(None)?;
+            Ok(())
+        }
 
-            let i_is_less_than_capacity = builder
-                .build_int_compare(
-                    IntPredicate::ULT,
-                    builder
-                        .build_load(type_holder.size(), i_ptr, "i")
-                        .unwrap()
-                        .into_int_value(),
-                    capacity,
-                    "i_is_less_than_capacity",
-                )
-                .unwrap();
+        // Checks that the tape keeps its contents when growing in both directions and that cells
+        // wrap around. Returns the exit code.
+        fn generate_function_selftest(
+            context: &Context,
+            builder: &Builder<'a>,
+            functions: &mut Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            const DISTANCE: i64 = 100_000;
 
-            Self::branch(
-                context,
-                builder,
-                i_is_less_than_capacity,
-                |after_branch| {
-                    builder.build_unconditional_branch(after_branch).unwrap();
-                },
-                |_| {
-                    builder.build_return(None).unwrap();
-                },
+            let selftest = Self::create_function(
+                "selftest",
+                &[
+                    type_holder.pointer().into(), // memory_ptr_ptr (char**)
+                    type_holder.pointer().into(), // capacity_ptr (size_t*)
+                    type_holder.pointer().into(), // offset_ptr (size_t*)
+                ],
+                Some(&type_holder.int()),
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
             );
-            builder.build_unconditional_branch(loop_body).unwrap();
-
-            builder.position_at_end(loop_body);
-            // char* address = &memory_ptr[i]
-            let address = unsafe {
-                builder
-                    .build_gep(
-                        type_holder.char(),
-                        memory_ptr,
-                        &[builder
-                            .build_load(type_holder.size(), i_ptr, "i")
-                            .unwrap()
-                            .into_int_value()],
-                        "address",
-                    )
-                    .unwrap()
-            };
-            let value = builder
-                .build_load(type_holder.char(), address, "value")
-                .unwrap()
-                .into_int_value();
-
-            let printable_value = builder
-                .build_int_add(
-                    value,
-                    type_holder.char().const_int(48, false),
-                    "printable_value",
-                )
-                .unwrap();
-
-            let printable_value_int = builder
-                .build_int_cast(printable_value, type_holder.int(), "printable_value_int")
-                .unwrap();
+            functions.insert(FunctionDeclaration::Selftest, selftest);
+            let functions = &*functions;
 
-            builder
-                .build_direct_call(
-                    Self::function(FunctionDeclaration::Putchar, functions),
-                    &[printable_value_int.into()],
-                    "",
-                )
-                .unwrap();
-            let new_i = builder
-                .build_int_add(
-                    builder
-                        .build_load(type_holder.size(), i_ptr, "i")
-                        .unwrap()
-                        .into_int_value(),
-                    type_holder.size().const_int(1, false),
-                    "new_i",
-                )
-                .unwrap();
-            builder.build_store(i_ptr, new_i).unwrap();
-            builder.build_unconditional_branch(loop_start).unwrap();
+            let entry = context.append_basic_block(selftest, "entry");
+            builder.position_at_end(entry);
 
-            builder.position_at_end(after_loop);
-            builder
-                .build_direct_call(
-                    Self::function(FunctionDeclaration::Putchar, functions),
-                    &[type_holder.int().const_int(10, false).into()],
-                    "",
-                )
-                .unwrap();
-            builder.build_return(None).unwrap();
-        }
+            let memory_ptr_ptr = selftest.get_nth_param(0).unwrap().into_pointer_value();
+            let capacity_ptr = selftest.get_nth_param(1).unwrap().into_pointer_value();
+            let offset_ptr = selftest.get_nth_param(2).unwrap().into_pointer_value();
 
-        #[allow(dead_code)]
-        fn generate_printf(
-            format_string: &str,
-            args: &[BasicMetadataValueEnum],
-            builder: &Builder<'a>,
-            functions: &Functions<'a>,
-        ) {
-            let string = unsafe {
-                builder
-                    .build_global_string(format_string, "string")
-                    .unwrap()
+            let read = |address: i64| -> Result<IntValue<'a>, BuilderError> {
+                Ok(builder
+                    .build_direct_call(
+                        Self::function(FunctionDeclaration::Read, functions),
+                        &[
+                            type_holder.size().const_int(address as u64, true).into(),
+                            memory_ptr_ptr.into(),
+                            capacity_ptr.into(),
+                            offset_ptr.into(),
+                        ],
+                        "value",
+                    )?
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_int_value())
             };
-            let first: &[BasicMetadataValueEnum] = &[string.as_pointer_value().into()];
-            builder
-                .build_direct_call(
-                    Self::function(FunctionDeclaration::Printf, functions),
-                    &[first, args].concat(),
+            let write = |address: i64, value: IntValue<'a>| -> Result<(), BuilderError> {
+                builder.build_direct_call(
+                    Self::function(FunctionDeclaration::Write, functions),
+                    &[
+                        type_holder.size().const_int(address as u64, true).into(),
+                        value.into(),
+                        memory_ptr_ptr.into(),
+                        capacity_ptr.into(),
+                        offset_ptr.into(),
+                    ],
                     "",
-                )
-                .unwrap();
+                )?;
+                Ok(())
+            };
+            let cell = |value: i64| Self::cell_constant(value, type_holder);
+            let control_flow = ControlFlowBuilder::new(context, builder);
+            let check =
+                |description: &str, address: i64, expected: i64| -> Result<(), BuilderError> {
+                    let failed = builder.build_int_compare(
+                        IntPredicate::NE,
+                        read(address)?,
+                        cell(expected),
+                        "failed",
+                    )?;
+                    control_flow.if_then(failed, || {
+                        let description =
+                            unsafe { builder.build_global_string(description, "check")? };
+                        Self::generate_printf(
+                            "bf-selftest: check failed: %s\n",
+                            &[description.as_pointer_value().into()],
+                            builder,
+                            functions,
+                        )?;
+                        control_flow.return_early(Some(&type_holder.int().const_int(1, false)))
+                    })
+                };
+
+            write(0, cell(42))?;
+            write(DISTANCE, cell(1))?;
+            write(-DISTANCE, cell(2))?;
+            check("tape grows to the right", DISTANCE, 1)?;
+            check("tape grows to the left", -DISTANCE, 2)?;
+            check("cells are kept when the tape grows", 0, 42)?;
+            check("new cells are zero", DISTANCE / 2, 0)?;
+            check("new cells are zero", -DISTANCE / 2, 0)?;
+
+            write(1, cell(-1))?;
+            let incremented = builder.build_int_add(read(1)?, cell(1), "sum")?;
+            write(1, incremented)?;
+            check("incrementing the largest value wraps around to 0", 1, 0)?;
+            let decremented = builder.build_int_sub(read(1)?, cell(1), "difference")?;
+            write(1, decremented)?;
+            check("decrementing 0 wraps around to the largest value", 1, -1)?;
+
+            Self::generate_printf("bf-selftest: all checks passed\n", &[], builder, functions)?;
+            builder.build_return(Some(&type_holder.int().const_zero()))?;
+            Ok(())
         }
 
+        #[allow(clippy::too_many_arguments)]
         fn generate_function_main(
+            name: &str,
             run_function: FunctionValue<'a>,
+            options: &BackendOptions,
             context: &Context,
             builder: &Builder<'a>,
             functions: &mut Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
+        ) -> Result<(), BuilderError> {
+            let parameters: &[BasicMetadataTypeEnum] = if options.embed_selftest {
+                &[type_holder.int().into(), type_holder.pointer().into()] // argc, argv
+            } else {
+                &[]
+            };
             let main = Self::create_function(
-                "main",
-                &[],
+                name,
+                parameters,
                 Some(&type_holder.int()),
                 Some(Linkage::External),
                 false,
@@ -1275,123 +3102,310 @@ mod state {
             let entry = context.append_basic_block(main, "entry");
             builder.position_at_end(entry);
 
-            let memory_ptr_ptr = builder
-                .build_alloca(type_holder.pointer(), "memory")
-                .unwrap();
-            builder
-                .build_store(memory_ptr_ptr, type_holder.pointer().const_zero())
-                .unwrap();
-
-            let capacity_ptr = builder
-                .build_alloca(type_holder.size(), "capacity")
-                .unwrap();
-            builder
-                .build_store(capacity_ptr, type_holder.size().const_zero())
-                .unwrap();
+            if let Some(&disable_echo) = functions.get(&FunctionDeclaration::DisableEcho) {
+                builder.build_direct_call(disable_echo, &[], "")?;
+            }
 
-            let offset_ptr = builder.build_alloca(type_holder.size(), "offset").unwrap();
-            builder
-                .build_store(offset_ptr, type_holder.size().const_zero())
-                .unwrap();
+            let memory_ptr_ptr = builder.build_alloca(type_holder.pointer(), "memory")?;
+            builder.build_store(memory_ptr_ptr, type_holder.pointer().const_zero())?;
 
-            let address_ptr = builder.build_alloca(type_holder.size(), "address").unwrap();
-            builder
-                .build_store(address_ptr, type_holder.size().const_zero())
-                .unwrap();
+            let capacity_ptr = builder.build_alloca(type_holder.size(), "capacity")?;
+            builder.build_store(capacity_ptr, type_holder.size().const_zero())?;
 
-            let ensure_address = |index: i64| {
-                builder
-                    .build_direct_call(
-                        Self::function(
-                            FunctionDeclaration::EnsureSufficientMemoryCapacity,
-                            functions,
-                        ),
-                        &[
-                            memory_ptr_ptr.into(),
-                            capacity_ptr.into(),
-                            offset_ptr.into(),
-                            if index < 0 {
-                                builder
-                                    .build_int_neg(
-                                        type_holder.size().const_int((-index) as u64, false),
-                                        "",
-                                    )
-                                    .unwrap()
-                                    .into()
-                            } else {
-                                type_holder.size().const_int(index as u64, false).into()
-                            },
-                        ],
-                        "",
-                    )
-                    .unwrap()
-            };
+            let offset_ptr = builder.build_alloca(type_holder.size(), "offset")?;
+            builder.build_store(offset_ptr, type_holder.size().const_zero())?;
 
-            ensure_address(15000);
-            ensure_address(-15000);
+            let address_ptr = builder.build_alloca(type_holder.size(), "address")?;
+            builder.build_store(address_ptr, type_holder.size().const_zero())?;
 
-            builder
-                .build_direct_call(
-                    run_function,
+            let ensure_address = |index: i64| -> Result<(), BuilderError> {
+                builder.build_direct_call(
+                    Self::function(
+                        FunctionDeclaration::EnsureSufficientMemoryCapacity,
+                        functions,
+                    ),
                     &[
-                        address_ptr.into(),
                         memory_ptr_ptr.into(),
                         capacity_ptr.into(),
                         offset_ptr.into(),
+                        if index < 0 {
+                            builder
+                                .build_int_neg(
+                                    type_holder.size().const_int((-index) as u64, false),
+                                    "",
+                                )?
+                                .into()
+                        } else {
+                            type_holder.size().const_int(index as u64, false).into()
+                        },
                     ],
                     "",
-                )
-                .unwrap();
+                )?;
+                Ok(())
+            };
 
-            builder
-                .build_direct_call(
+            if options.guard_pages {
+                let memory =
+                    Self::reserve_guarded_tape(context, builder, functions, module, type_holder)?;
+                builder.build_store(memory_ptr_ptr, memory)?;
+                builder.build_store(
+                    capacity_ptr,
+                    type_holder.size().const_int(GUARDED_TAPE_CELLS, false),
+                )?;
+                builder.build_store(
+                    offset_ptr,
+                    type_holder.size().const_int(GUARDED_TAPE_CELLS / 2, false),
+                )?;
+            } else if options.bare_metal {
+                let tape = Self::generate_static_tape(options, module, type_holder);
+                let tape_size = Self::static_tape_size(options).expect("checked by State::new");
+                builder.build_store(memory_ptr_ptr, tape.as_pointer_value())?;
+                builder.build_store(
+                    capacity_ptr,
+                    type_holder.size().const_int(tape_size.into(), false),
+                )?;
+            } else {
+                let initial_extent = options.runtime.initial_tape_extent() as i64;
+                ensure_address(initial_extent)?;
+                ensure_address(-initial_extent)?;
+            }
+
+            let free_memory = || -> Result<(), BuilderError> {
+                // the mapping of guard pages is released when the program exits
+                if options.guard_pages || options.bare_metal {
+                    return Ok(());
+                }
+                builder.build_direct_call(
                     Self::function(FunctionDeclaration::Free, functions),
                     &[builder
-                        .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_address")
-                        .unwrap()
+                        .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_address")?
                         .into()],
                     "",
-                )
-                .unwrap();
+                )?;
+                Ok(())
+            };
 
-            builder
-                .build_return(Some(&type_holder.int().const_zero()))
-                .unwrap();
+            if options.embed_selftest {
+                let argc = main.get_nth_param(0).unwrap().into_int_value();
+                let argv = main.get_nth_param(1).unwrap().into_pointer_value();
+                let has_argument = builder.build_int_compare(
+                    IntPredicate::SGT,
+                    argc,
+                    type_holder.int().const_int(1, false),
+                    "has_argument",
+                )?;
+                let control_flow = ControlFlowBuilder::new(context, builder);
+                control_flow.if_then(has_argument, || {
+                    let argument_ptr = unsafe {
+                        builder.build_gep(
+                            type_holder.pointer(),
+                            argv,
+                            &[type_holder.int().const_int(1, false)],
+                            "argument_ptr",
+                        )?
+                    };
+                    let argument =
+                        builder.build_load(type_holder.pointer(), argument_ptr, "argument")?;
+                    let flag = unsafe { builder.build_global_string("--bf-selftest", "flag")? };
+                    let comparison = builder
+                        .build_direct_call(
+                            Self::function(FunctionDeclaration::Strcmp, functions),
+                            &[argument.into(), flag.as_pointer_value().into()],
+                            "comparison",
+                        )?
+                        .try_as_basic_value()
+                        .unwrap_left()
+                        .into_int_value();
+                    let is_selftest = builder.build_int_compare(
+                        IntPredicate::EQ,
+                        comparison,
+                        type_holder.int().const_zero(),
+                        "is_selftest",
+                    )?;
+                    control_flow.if_then(is_selftest, || {
+                        let exit_code = builder
+                            .build_direct_call(
+                                Self::function(FunctionDeclaration::Selftest, functions),
+                                &[
+                                    memory_ptr_ptr.into(),
+                                    capacity_ptr.into(),
+                                    offset_ptr.into(),
+                                ],
+                                "exit_code",
+                            )?
+                            .try_as_basic_value()
+                            .unwrap_left()
+                            .into_int_value();
+                        free_memory()?;
+                        control_flow.return_early(Some(&exit_code))
+                    })?;
+                    Ok(())
+                })?;
+            }
+
+            // the static tape starts out with the initial contents
+            if !options.initial_tape.is_empty() && !options.bare_metal {
+                let length = options.initial_tape.len();
+                if !options.guard_pages {
+                    ensure_address(length as i64 - 1)?;
+                }
+                let values: Vec<_> = options
+                    .initial_tape
+                    .iter()
+                    .map(|&byte| type_holder.cell().const_int(u64::from(byte), false))
+                    .collect();
+                let initial_tape = module.add_global(
+                    type_holder.cell().array_type(length as u32),
+                    None,
+                    "initial_tape",
+                );
+                initial_tape.set_linkage(Linkage::Internal);
+                initial_tape.set_constant(true);
+                initial_tape.set_initializer(&type_holder.cell().const_array(&values));
+                // the index of address 0 is the offset
+                let destination = unsafe {
+                    builder.build_gep(
+                        type_holder.cell(),
+                        builder
+                            .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")?
+                            .into_pointer_value(),
+                        &[builder
+                            .build_load(type_holder.size(), offset_ptr, "offset")?
+                            .into_int_value()],
+                        "destination",
+                    )?
+                };
+                builder.build_direct_call(
+                    Self::function(FunctionDeclaration::Memmove, functions),
+                    &[
+                        destination.into(),
+                        initial_tape.as_pointer_value().into(),
+                        Self::cells_to_bytes(
+                            type_holder.size().const_int(length as u64, false),
+                            builder,
+                            type_holder,
+                        )?
+                        .into(),
+                    ],
+                    "",
+                )?;
+            }
+
+            builder.build_direct_call(
+                run_function,
+                &[
+                    address_ptr.into(),
+                    memory_ptr_ptr.into(),
+                    capacity_ptr.into(),
+                    offset_ptr.into(),
+                ],
+                "",
+            )?;
+
+            free_memory()?;
+
+            builder.build_return(Some(&type_holder.int().const_zero()))?;
+            Ok(())
         }
 
-        fn branch<ThenEmitter: FnOnce(BasicBlock<'a>), ElseEmitter: FnOnce(BasicBlock<'a>)>(
-            context: &'a Context,
+        // The tape of `--bare-metal`, an array that is placed in `.bss` unless the tape has initial
+        // contents.
+        fn generate_static_tape(
+            options: &BackendOptions,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> GlobalValue<'a> {
+            let tape_size = Self::static_tape_size(options).expect("checked by State::new");
+            let tape_type = type_holder.cell().array_type(tape_size);
+            let tape = module.add_global(tape_type, None, "tape");
+            tape.set_linkage(Linkage::Internal);
+            if options.initial_tape.is_empty() {
+                tape.set_initializer(&tape_type.const_zero());
+            } else {
+                let values: Vec<_> = options
+                    .initial_tape
+                    .iter()
+                    .map(|&byte| u64::from(byte))
+                    .chain(std::iter::repeat(0))
+                    .take(tape_size as usize)
+                    .map(|value| type_holder.cell().const_int(value, false))
+                    .collect();
+                tape.set_initializer(&type_holder.cell().const_array(&values));
+            }
+            tape
+        }
+
+        // The address of the cell at the given offset relative to the pointer.
+        fn cell_address(
+            offset: i64,
+            address_ptr: PointerValue<'a>,
             builder: &Builder<'a>,
-            condition: IntValue<'a>,
-            then_emitter: ThenEmitter,
-            else_emitter: ElseEmitter,
-        ) {
-            let surrounding_function = builder.get_insert_block().unwrap().get_parent().unwrap();
-            let then_block = context.append_basic_block(surrounding_function, "then");
-            let else_block = context.append_basic_block(surrounding_function, "else");
-            let after_branch_block =
-                context.append_basic_block(surrounding_function, "after_branch");
-            builder
-                .build_conditional_branch(condition, then_block, else_block)
-                .unwrap();
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<IntValue<'a>, BuilderError> {
+            let address = builder
+                .build_load(type_holder.size(), address_ptr, "address")?
+                .into_int_value();
+            if offset == 0 {
+                return Ok(address);
+            }
+            builder.build_int_add(
+                address,
+                type_holder.size().const_int(offset as u64, true),
+                "cell_address",
+            )
+        }
 
-            builder.position_at_end(then_block);
-            then_emitter(after_branch_block);
+        // The number of bytes taken by the given number of cells.
+        fn cells_to_bytes(
+            cells: IntValue<'a>,
+            builder: &Builder<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<IntValue<'a>, BuilderError> {
+            match type_holder.cell().get_bit_width() / 8 {
+                1 => Ok(cells),
+                cell_bytes => builder.build_int_mul(
+                    cells,
+                    type_holder.size().const_int(cell_bytes as u64, false),
+                    "bytes",
+                ),
+            }
+        }
 
-            builder.position_at_end(else_block);
-            else_emitter(after_branch_block);
+        // A cell holding the value truncated to the width of a cell, e.g. -1 becomes the largest
+        // value.
+        fn cell_constant(value: i64, type_holder: &dyn TypeHolder<'a>) -> IntValue<'a> {
+            let bits = type_holder.cell().get_bit_width();
+            type_holder
+                .cell()
+                .const_int(value as u64 & (u64::MAX >> (64 - bits)), false)
+        }
 
-            builder.position_at_end(after_branch_block);
+        fn register_global(
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> PointerValue<'a> {
+            let register = module.get_global("register").unwrap_or_else(|| {
+                let register = module.add_global(type_holder.cell(), None, "register");
+                register.set_linkage(Linkage::Internal);
+                register.set_initializer(&type_holder.cell().const_zero());
+                register
+            });
+            register.as_pointer_value()
         }
 
+        // `outline_loops` compiles every loop into a function of its own, named after the offset
+        // of the loop in the source
+        #[allow(clippy::too_many_arguments)]
         fn emit_code_for_statement(
             statement: &Statement,
+            innermost_loop: Option<LoopBlocks<'a>>,
+            outline_loops: bool,
             context: &'a Context,
             builder: &Builder<'a>,
             functions: &Functions<'a>,
             module: &Module<'a>,
             type_holder: &dyn TypeHolder<'a>,
-        ) {
+        ) -> Result<(), BuilderError> {
             /*
             types.pointer().into(), // address_ptr (size_t*)
             types.pointer().into(), // memory_ptr_ptr (char**)
@@ -1416,85 +3430,167 @@ mod state {
                 .unwrap()
                 .into_pointer_value();
 
-            match statement {
-                Statement::IncrementPointer => {
-                    let address = builder
-                        .build_load(type_holder.size(), address_ptr, "address")
-                        .unwrap()
-                        .into_int_value();
-                    let incremented = builder
-                        .build_int_add(
-                            address,
-                            type_holder.size().const_int(1, false),
-                            "incremented",
-                        )
-                        .unwrap();
-                    builder.build_store(address_ptr, incremented).unwrap();
-                }
-                Statement::DecrementPointer => {
+            match statement.kind() {
+                StatementKind::Move(amount) => {
                     let address = builder
-                        .build_load(type_holder.size(), address_ptr, "address")
-                        .unwrap()
+                        .build_load(type_holder.size(), address_ptr, "address")?
                         .into_int_value();
-                    let decremented = builder
-                        .build_int_sub(
-                            address,
-                            type_holder.size().const_int(1, false),
-                            "decremented",
-                        )
-                        .unwrap();
-                    builder.build_store(address_ptr, decremented).unwrap();
+                    let moved = builder.build_int_add(
+                        address,
+                        type_holder.size().const_int(*amount as u64, true),
+                        "moved",
+                    )?;
+                    builder.build_store(address_ptr, moved)?;
                 }
-                Statement::IncrementValue => {
+                StatementKind::Add { offset, amount } => {
+                    let address = Self::cell_address(*offset, address_ptr, builder, type_holder)?;
                     let value = builder
                         .build_direct_call(
                             Self::function(FunctionDeclaration::Read, functions),
                             &[
-                                builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
-                                    .into_int_value()
-                                    .into(),
+                                address.into(),
                                 memory_ptr_ptr.into(),
                                 capacity_ptr.into(),
                                 offset_ptr.into(),
                             ],
                             "value",
-                        )
-                        .unwrap()
+                        )?
                         .try_as_basic_value()
                         .unwrap_left()
                         .into_int_value();
-                    let incremented = builder
-                        .build_int_add(value, type_holder.char().const_int(1, false), "incremented")
-                        .unwrap();
-
-                    builder
-                        .build_direct_call(
-                            Self::function(FunctionDeclaration::Write, functions),
-                            &[
-                                builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
-                                    .into_int_value()
-                                    .into(),
-                                incremented.into(),
-                                memory_ptr_ptr.into(),
-                                capacity_ptr.into(),
-                                offset_ptr.into(),
-                            ],
-                            "",
-                        )
-                        .unwrap();
+                    let sum = builder.build_int_add(
+                        value,
+                        Self::cell_constant(*amount, type_holder),
+                        "sum",
+                    )?;
+                    // only declared when overflows trap, the sum overflowed if it moved in the
+                    // wrong direction
+                    if let Some(&cell_overflow) = functions.get(&FunctionDeclaration::CellOverflow)
+                    {
+                        let predicate = if *amount < 0 {
+                            IntPredicate::UGT
+                        } else {
+                            IntPredicate::ULT
+                        };
+                        let overflowed =
+                            builder.build_int_compare(predicate, sum, value, "overflowed")?;
+                        ControlFlowBuilder::new(context, builder).if_then(overflowed, || {
+                            builder.build_direct_call(
+                                cell_overflow,
+                                &[
+                                    address.into(),
+                                    type_holder
+                                        .size()
+                                        .const_int(statement.span().start as u64, false)
+                                        .into(),
+                                ],
+                                "",
+                            )?;
+                            builder.build_unreachable()?;
+                            Ok(())
+                        })?;
+                    }
+
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Write, functions),
+                        &[
+                            address.into(),
+                            sum.into(),
+                            memory_ptr_ptr.into(),
+                            capacity_ptr.into(),
+                            offset_ptr.into(),
+                        ],
+                        "",
+                    )?;
+                }
+                StatementKind::Set { offset, value } => {
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Write, functions),
+                        &[
+                            Self::cell_address(*offset, address_ptr, builder, type_holder)?.into(),
+                            type_holder.cell().const_int(*value as u64, false).into(),
+                            memory_ptr_ptr.into(),
+                            capacity_ptr.into(),
+                            offset_ptr.into(),
+                        ],
+                        "",
+                    )?;
+                }
+                StatementKind::MultiplyAdd(targets) => {
+                    let read =
+                        |address: IntValue<'a>, name: &str| -> Result<IntValue<'a>, BuilderError> {
+                            Ok(builder
+                                .build_direct_call(
+                                    Self::function(FunctionDeclaration::Read, functions),
+                                    &[
+                                        address.into(),
+                                        memory_ptr_ptr.into(),
+                                        capacity_ptr.into(),
+                                        offset_ptr.into(),
+                                    ],
+                                    name,
+                                )?
+                                .try_as_basic_value()
+                                .unwrap_left()
+                                .into_int_value())
+                        };
+                    let write =
+                        |address: IntValue<'a>, value: IntValue<'a>| -> Result<(), BuilderError> {
+                            builder.build_direct_call(
+                                Self::function(FunctionDeclaration::Write, functions),
+                                &[
+                                    address.into(),
+                                    value.into(),
+                                    memory_ptr_ptr.into(),
+                                    capacity_ptr.into(),
+                                    offset_ptr.into(),
+                                ],
+                                "",
+                            )?;
+                            Ok(())
+                        };
+
+                    let address = builder
+                        .build_load(type_holder.size(), address_ptr, "address")?
+                        .into_int_value();
+                    let value = read(address, "value")?;
+                    let condition = builder.build_int_compare(
+                        IntPredicate::NE,
+                        value,
+                        type_holder.cell().const_zero(),
+                        "condition",
+                    )?;
+                    let control_flow = ControlFlowBuilder::new(context, builder);
+                    control_flow.if_then(condition, || {
+                        for &(offset, factor) in targets {
+                            let target_address = builder.build_int_add(
+                                address,
+                                type_holder.size().const_int(offset as u64, true),
+                                "target_address",
+                            )?;
+                            let product = builder.build_int_mul(
+                                value,
+                                Self::cell_constant(factor, type_holder),
+                                "product",
+                            )?;
+                            let sum = builder.build_int_add(
+                                read(target_address, "target_value")?,
+                                product,
+                                "sum",
+                            )?;
+                            write(target_address, sum)?;
+                        }
+                        write(address, type_holder.cell().const_zero())?;
+                        Ok(())
+                    })?;
                 }
-                Statement::DecrementValue => {
+                StatementKind::PutChar => {
                     let value = builder
                         .build_direct_call(
                             Self::function(FunctionDeclaration::Read, functions),
                             &[
                                 builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
+                                    .build_load(type_holder.size(), address_ptr, "address")?
                                     .into_int_value()
                                     .into(),
                                 memory_ptr_ptr.into(),
@@ -1502,41 +3598,89 @@ mod state {
                                 offset_ptr.into(),
                             ],
                             "value",
-                        )
-                        .unwrap()
+                        )?
                         .try_as_basic_value()
                         .unwrap_left()
                         .into_int_value();
-                    let decremented = builder
-                        .build_int_sub(value, type_holder.char().const_int(1, false), "decremented")
-                        .unwrap();
 
-                    builder
+                    let int_value =
+                        builder.build_int_cast(value, type_holder.int(), "int_value")?;
+
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::WriteChar, functions),
+                        &[int_value.into()],
+                        "",
+                    )?;
+                }
+                StatementKind::GetChar => {
+                    let address = builder
+                        .build_load(type_holder.size(), address_ptr, "address")?
+                        .into_int_value();
+                    let current_value = builder
                         .build_direct_call(
-                            Self::function(FunctionDeclaration::Write, functions),
+                            Self::function(FunctionDeclaration::Read, functions),
                             &[
-                                builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
-                                    .into_int_value()
-                                    .into(),
-                                decremented.into(),
+                                address.into(),
                                 memory_ptr_ptr.into(),
                                 capacity_ptr.into(),
                                 offset_ptr.into(),
                             ],
-                            "",
-                        )
-                        .unwrap();
+                            "current_value",
+                        )?
+                        .try_as_basic_value()
+                        .unwrap_left()
+                        .into_int_value();
+                    let value = builder
+                        .build_direct_call(
+                            Self::function(FunctionDeclaration::ReadChar, functions),
+                            &[current_value.into()],
+                            "value",
+                        )?
+                        .try_as_basic_value()
+                        .unwrap_left()
+                        .into_int_value();
+
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Write, functions),
+                        &[
+                            address.into(),
+                            value.into(),
+                            memory_ptr_ptr.into(),
+                            capacity_ptr.into(),
+                            offset_ptr.into(),
+                        ],
+                        "",
+                    )?;
+                }
+                StatementKind::Loop(statements) if outline_loops => {
+                    Self::emit_loop_function(
+                        statement.span().start,
+                        statements,
+                        context,
+                        builder,
+                        functions,
+                        module,
+                        type_holder,
+                    )?;
                 }
-                Statement::PutChar => {
+                StatementKind::Loop(statements) => {
+                    Self::emit_loop(
+                        statements,
+                        false,
+                        context,
+                        builder,
+                        functions,
+                        module,
+                        type_holder,
+                    )?;
+                }
+                StatementKind::If(statements) => {
                     let value = builder
                         .build_direct_call(
                             Self::function(FunctionDeclaration::Read, functions),
                             &[
                                 builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
+                                    .build_load(type_holder.size(), address_ptr, "address")?
                                     .into_int_value()
                                     .into(),
                                 memory_ptr_ptr.into(),
@@ -1544,73 +3688,128 @@ mod state {
                                 offset_ptr.into(),
                             ],
                             "value",
-                        )
-                        .unwrap()
+                        )?
                         .try_as_basic_value()
                         .unwrap_left()
                         .into_int_value();
-
-                    let int_value = builder
-                        .build_int_cast(value, type_holder.int(), "int_value")
-                        .unwrap();
-
-                    builder
-                        .build_direct_call(
-                            Self::function(FunctionDeclaration::Putchar, functions),
-                            &[int_value.into()],
-                            "",
-                        )
-                        .unwrap();
+                    let condition = builder.build_int_compare(
+                        IntPredicate::NE,
+                        value,
+                        type_holder.cell().const_zero(),
+                        "condition",
+                    )?;
+                    let control_flow = ControlFlowBuilder::new(context, builder);
+                    control_flow.if_then(condition, || {
+                        for statement in statements {
+                            Self::emit_code_for_statement(
+                                statement,
+                                innermost_loop,
+                                outline_loops,
+                                context,
+                                builder,
+                                functions,
+                                module,
+                                type_holder,
+                            )?;
+                        }
+                        Ok(())
+                    })?;
                 }
-                Statement::GetChar => {
+                StatementKind::Store => {
                     let value = builder
                         .build_direct_call(
-                            Self::function(FunctionDeclaration::GetChar, functions),
-                            &[],
-                            "value",
-                        )
-                        .unwrap()
-                        .try_as_basic_value()
-                        .unwrap_left()
-                        .into_int_value();
-
-                    let char_value = builder
-                        .build_int_cast(value, type_holder.char(), "char_value")
-                        .unwrap();
-
-                    builder
-                        .build_direct_call(
-                            Self::function(FunctionDeclaration::Write, functions),
+                            Self::function(FunctionDeclaration::Read, functions),
                             &[
                                 builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
+                                    .build_load(type_holder.size(), address_ptr, "address")?
                                     .into_int_value()
                                     .into(),
-                                char_value.into(),
                                 memory_ptr_ptr.into(),
                                 capacity_ptr.into(),
                                 offset_ptr.into(),
                             ],
-                            "",
-                        )
-                        .unwrap();
+                            "value",
+                        )?
+                        .try_as_basic_value()
+                        .unwrap_left()
+                        .into_int_value();
+                    builder.build_store(Self::register_global(module, type_holder), value)?;
+                }
+                StatementKind::Load => {
+                    let value = builder
+                        .build_load(
+                            type_holder.cell(),
+                            Self::register_global(module, type_holder),
+                            "register",
+                        )?
+                        .into_int_value();
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::Write, functions),
+                        &[
+                            builder
+                                .build_load(type_holder.size(), address_ptr, "address")?
+                                .into_int_value()
+                                .into(),
+                            value.into(),
+                            memory_ptr_ptr.into(),
+                            capacity_ptr.into(),
+                            offset_ptr.into(),
+                        ],
+                        "",
+                    )?;
+                }
+                StatementKind::DebugDump => {
+                    builder.build_direct_call(
+                        Self::function(FunctionDeclaration::MemDump, functions),
+                        &[
+                            builder
+                                .build_load(type_holder.pointer(), memory_ptr_ptr, "memory_ptr")?
+                                .into(),
+                            builder
+                                .build_load(type_holder.size(), capacity_ptr, "capacity")?
+                                .into(),
+                        ],
+                        "",
+                    )?;
+                }
+                StatementKind::Break => {
+                    let control_flow = ControlFlowBuilder::new(context, builder);
+                    control_flow.break_loop(innermost_loop.expect("checked by the parser"))?;
                 }
-                Statement::Loop(statements) => {
-                    let current_function =
-                        builder.get_insert_block().unwrap().get_parent().unwrap();
-                    let loop_start = context.append_basic_block(current_function, "loop_start");
-                    let after_loop = context.append_basic_block(current_function, "after_loop");
-                    builder.build_unconditional_branch(loop_start).unwrap();
-
-                    builder.position_at_end(loop_start);
+                StatementKind::Continue => {
+                    let control_flow = ControlFlowBuilder::new(context, builder);
+                    control_flow.continue_loop(innermost_loop.expect("checked by the parser"))?;
+                }
+            }
+            Ok(())
+        }
+
+        fn emit_loop(
+            statements: &[Statement],
+            outline_loops: bool,
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            let current_function = builder.get_insert_block().unwrap().get_parent().unwrap();
+            let [address_ptr, memory_ptr_ptr, capacity_ptr, offset_ptr] =
+                [0, 1, 2, 3].map(|index| {
+                    current_function
+                        .get_nth_param(index)
+                        .unwrap()
+                        .into_pointer_value()
+                });
+            let control_flow = ControlFlowBuilder::new(context, builder);
+            control_flow.while_loop(
+                || {
                     let value = builder
                         .build_direct_call(
                             Self::function(FunctionDeclaration::Read, functions),
                             &[
                                 builder
-                                    .build_load(type_holder.size(), address_ptr, "address")
-                                    .unwrap()
+                                    .build_load(type_holder.size(), address_ptr, "address")?
                                     .into_int_value()
                                     .into(),
                                 memory_ptr_ptr.into(),
@@ -1618,90 +3817,414 @@ mod state {
                                 offset_ptr.into(),
                             ],
                             "value",
-                        )
-                        .unwrap()
+                        )?
                         .try_as_basic_value()
                         .unwrap_left()
                         .into_int_value();
 
-                    let condition = builder
-                        .build_int_compare(
-                            IntPredicate::NE,
-                            value,
-                            type_holder.char().const_zero(),
-                            "condition",
-                        )
-                        .unwrap();
+                    builder.build_int_compare(
+                        IntPredicate::NE,
+                        value,
+                        type_holder.cell().const_zero(),
+                        "condition",
+                    )
+                },
+                |loop_blocks| {
+                    for statement in statements {
+                        Self::emit_code_for_statement(
+                            statement,
+                            Some(loop_blocks),
+                            outline_loops,
+                            context,
+                            builder,
+                            functions,
+                            module,
+                            type_holder,
+                        )?;
+                    }
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        }
 
-                    Self::branch(
-                        context,
-                        builder,
-                        condition,
-                        |_| {
-                            for statement in statements {
-                                Self::emit_code_for_statement(
-                                    statement,
-                                    context,
-                                    builder,
-                                    functions,
-                                    module,
-                                    type_holder,
-                                );
-                            }
-                            builder.build_unconditional_branch(loop_start).unwrap();
-                        },
-                        |after_branch| {
-                            builder.build_unconditional_branch(after_branch).unwrap();
-                        },
-                    );
-                    builder.build_unconditional_branch(after_loop).unwrap();
+        // Emits the loop into a function of its own, which takes the same parameters as `run`,
+        // and calls it. The function is never inlined, so that profilers attribute the time
+        // spent in the loop to it.
+        fn emit_loop_function(
+            offset: usize,
+            statements: &[Statement],
+            context: &'a Context,
+            builder: &Builder<'a>,
+            functions: &Functions<'a>,
+            module: &Module<'a>,
+            type_holder: &dyn TypeHolder<'a>,
+        ) -> Result<(), BuilderError> {
+            let caller = builder.get_insert_block().unwrap();
+            let parameters: Vec<_> = caller.get_parent().unwrap().get_params();
+            let function = Self::create_function(
+                &format!("loop_{offset}"),
+                &[type_holder.pointer().into(); 4],
+                None,
+                Some(Linkage::Internal),
+                false,
+                module,
+                type_holder,
+            );
+            function.add_attribute(
+                AttributeLoc::Function,
+                context.create_enum_attribute(Attribute::get_named_enum_kind_id("noinline"), 0),
+            );
+            builder.position_at_end(context.append_basic_block(function, "entry"));
+            Self::emit_loop(
+                statements,
+                true,
+                context,
+                builder,
+                functions,
+                module,
+                type_holder,
+            )?;
+            builder.build_return(None)?;
+
+            builder.position_at_end(caller);
+            let arguments: Vec<_> = parameters.into_iter().map(Into::into).collect();
+            builder.build_direct_call(function, &arguments, "")?;
+            Ok(())
+        }
+    }
+}
+
+pub(crate) struct LlvmBackend {
+    target: EmitTarget,
+}
+
+impl LlvmBackend {
+    pub(crate) fn new(target: EmitTarget) -> Self {
+        Self { target }
+    }
+
+    // whether the object file is linked or archived into the artifact
+    fn packages_object_file(&self) -> bool {
+        matches!(
+            self.target,
+            EmitTarget::Executable | EmitTarget::SharedLibrary | EmitTarget::StaticLibrary
+        )
+    }
+
+    fn compiler_output_filename(&self, options: &BackendOptions) -> PathBuf {
+        let mut result = options.output_filename.clone();
+        if self.packages_object_file() {
+            result.set_extension(object_file_extension());
+            // the linker must not overwrite its own input (e.g. `-o program.o`)
+            if result == options.output_filename {
+                result.as_mut_os_string().push(".");
+                result.as_mut_os_string().push(object_file_extension());
+            }
+        }
+        result
+    }
+}
+
+impl Backend for LlvmBackend {
+    fn name(&self) -> &'static str {
+        self.target.backend_name()
+    }
+
+    fn description(&self) -> &'static str {
+        match self.target {
+            EmitTarget::Assembly => "Assembler code (via LLVM)",
+            EmitTarget::ObjectFile => "Object file (via LLVM)",
+            EmitTarget::Executable => "Executable (via LLVM, linked using cc, gcc, clang or lld)",
+            EmitTarget::LlvmIr => "LLVM intermediate representation",
+            EmitTarget::Bitcode => "LLVM bitcode",
+            EmitTarget::SharedLibrary => "Shared library exporting bf_run (via LLVM)",
+            EmitTarget::StaticLibrary => {
+                "Static library exporting bf_run (via LLVM, archived using ar)"
+            }
+            EmitTarget::Jit => "Run the program right away (via LLVM's JIT compiler)",
+        }
+    }
+
+    fn default_output_filename(&self) -> &'static str {
+        default_output_filename(self.target)
+    }
 
-                    builder.position_at_end(after_loop);
+    fn emit(&self, program: &Program, options: &BackendOptions) -> anyhow::Result<()> {
+        let library = matches!(
+            self.target,
+            EmitTarget::SharedLibrary | EmitTarget::StaticLibrary
+        );
+        if library {
+            // the library must neither bring its own entry point nor take over the process
+            for (enabled, option) in [
+                (options.platform != Platform::Host, "--target"),
+                (options.embed_selftest, "--embed-selftest"),
+                (options.guard_pages, "--guard-pages"),
+                (options.no_libc, "--no-libc"),
+                (options.bare_metal, "--bare-metal"),
+                (options.static_executable, "--static"),
+                (options.no_echo, "--no-echo"),
+            ] {
+                if enabled {
+                    bail!("a library cannot be built with {option}");
                 }
             }
         }
+        let context = Context::create();
+        let state = build_module(&context, program, options, self.target)?;
+
+        if self.target == EmitTarget::Jit {
+            let _span = trace::span("llvm", "run (jit)");
+            let _echo_suppression = options.no_echo.then(EchoSuppression::start).flatten();
+            let exit_code =
+                state.run_jit(&module_name(&options.input_filename), options.jit_profiling)?;
+            if exit_code != 0 {
+                bail!("program exited with exit code {exit_code}");
+            }
+            return Ok(());
+        }
+
+        let filename = self.compiler_output_filename(options);
+        {
+            let _span = trace::span("llvm", format!("write {}", filename.display()));
+            match self.target {
+                EmitTarget::Assembly => state.emit_assembly(&filename)?,
+                EmitTarget::ObjectFile
+                | EmitTarget::Executable
+                | EmitTarget::SharedLibrary
+                | EmitTarget::StaticLibrary => state.emit_object_file(&filename)?,
+                EmitTarget::LlvmIr => state.emit_llvm_ir(&filename)?,
+                EmitTarget::Bitcode => state.emit_bitcode(&filename)?,
+                EmitTarget::Jit => unreachable!("handled above"),
+            }
+        }
+        match self.target {
+            EmitTarget::Executable | EmitTarget::SharedLibrary => {
+                let _span = trace::span("linker", "link");
+                link(&filename, options, self.target == EmitTarget::SharedLibrary)?;
+            }
+            EmitTarget::StaticLibrary => {
+                let _span = trace::span("linker", "archive");
+                archive(&filename, options)?;
+            }
+            _ => {}
+        }
+        if self.target == EmitTarget::Executable && options.platform == Platform::Wasm32Browser {
+            let filename = browser_glue_filename(options);
+            let _span = trace::span("llvm", format!("write {}", filename.display()));
+            write_output_file(&filename, browser_glue(options).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn output_files(&self, options: &BackendOptions) -> Vec<PathBuf> {
+        if self.target == EmitTarget::Jit {
+            return Vec::new();
+        }
+        let mut files = vec![self.compiler_output_filename(options)];
+        if self.packages_object_file() {
+            files.push(options.output_filename.clone());
+        }
+        if self.target == EmitTarget::Executable && options.platform == Platform::Wasm32Browser {
+            files.push(browser_glue_filename(options));
+        }
+        files
+    }
+
+    fn plan(&self, options: &BackendOptions) -> Vec<String> {
+        let mut steps = vec![
+            format!(
+                "generate LLVM module '{}'",
+                module_name(&options.input_filename)
+            ),
+            "verify module".to_string(),
+            format!(
+                "optimize module at level {:?} using the default pipeline followed by the passes {}",
+                optimization_level(options.optimization_level),
+                state::module_pass_names(options.jit_profiling)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ];
+        if options.no_libc {
+            steps.insert(
+                1,
+                "link the freestanding runtime into the module".to_string(),
+            );
+        }
+        let artifact = match self.target {
+            EmitTarget::Assembly => "assembly",
+            EmitTarget::ObjectFile
+            | EmitTarget::Executable
+            | EmitTarget::SharedLibrary
+            | EmitTarget::StaticLibrary => "object file",
+            EmitTarget::LlvmIr => "LLVM IR",
+            EmitTarget::Bitcode => "LLVM bitcode",
+            EmitTarget::Jit => {
+                steps.push(if options.jit_profiling {
+                    "compile module in memory, write a perf jitdump file and run its main function"
+                        .to_string()
+                } else {
+                    "compile module in memory and run its main function".to_string()
+                });
+                return steps;
+            }
+        };
+        let compiler_output_filename = self.compiler_output_filename(options);
+        steps.push(format!(
+            "write {artifact} to '{}'",
+            compiler_output_filename.display()
+        ));
+        match self.target {
+            EmitTarget::Executable => steps.push(format!(
+                "link executable: {}",
+                planned_link_command(&compiler_output_filename, options, false)
+            )),
+            EmitTarget::SharedLibrary => steps.push(format!(
+                "link shared library: {}",
+                planned_link_command(&compiler_output_filename, options, true)
+            )),
+            EmitTarget::StaticLibrary => steps.push(format!(
+                "archive static library: {}",
+                planned_archive_command(&compiler_output_filename, options)
+            )),
+            _ => {}
+        }
+        if self.target == EmitTarget::Executable && options.platform == Platform::Wasm32Browser {
+            steps.push(format!(
+                "write JavaScript glue code to '{}'",
+                browser_glue_filename(options).display()
+            ));
+        }
+        steps
+    }
+}
+
+fn build_module<'a>(
+    context: &'a Context,
+    program: &Program,
+    options: &BackendOptions,
+    target: EmitTarget,
+) -> Result<State<'a>, EmitError> {
+    let state = {
+        let _span = trace::span("llvm", "generate module");
+        let state = State::new(
+            context,
+            &module_name(&options.input_filename),
+            program,
+            options,
+            target,
+        )?;
+        state.embed_environment(&options.environment);
+        state
+    };
+
+    if options.no_libc {
+        let _span = trace::span("llvm", "link freestanding runtime");
+        state.link_freestanding_runtime()?;
+    }
+
+    {
+        let _span = trace::span("llvm", "verify module");
+        state.verify()?;
     }
+
+    {
+        let _span = trace::span("llvm", "optimize module");
+        state.optimize(
+            optimization_level(options.optimization_level),
+            options.verbose,
+            options.jit_profiling,
+        );
+    }
+    Ok(state)
+}
+
+/// Compiles a program into an object file for the host and returns its contents instead of
+/// writing it to disk. The output filename of the options is not used.
+pub fn compile_to_object(program: &Program, options: &BackendOptions) -> anyhow::Result<Vec<u8>> {
+    let context = Context::create();
+    let state = build_module(&context, program, options, EmitTarget::ObjectFile)?;
+    Ok(state.object_code()?)
 }
 
-pub(crate) fn emit(program: &Program, arguments: &CommandLineArguments) -> anyhow::Result<PathBuf> {
-    let module_name = arguments
-        .input_filename
+fn module_name(input_filename: &Path) -> String {
+    input_filename
         .file_prefix()
         .unwrap_or_default()
         .to_string_lossy()
-        .to_ascii_lowercase();
+        .to_ascii_lowercase()
+}
 
-    let context = Context::create();
-    let state = State::new(&context, &module_name, program);
+// the glue code of `--target wasm32-unknown-unknown`, which imports the module by its filename
+const BROWSER_GLUE: &str = include_str!("browser_glue.js");
+
+// the C standard library of executables compiled with `--no-libc`, as LLVM IR
+const FREESTANDING_RUNTIME: &str = include_str!("freestanding.ll");
+
+fn browser_glue_filename(options: &BackendOptions) -> PathBuf {
+    options.output_filename.with_extension("js")
+}
+
+fn browser_glue(options: &BackendOptions) -> String {
+    let filename = |path: &Path| {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+    BROWSER_GLUE
+        .replace("{module}", &filename(&options.output_filename))
+        .replace("{glue}", &filename(&browser_glue_filename(options)))
+}
+
+// Creates missing parent directories, so that e.g. `-o build/out` works in a clean checkout.
+fn write_output_file(filename: &Path, contents: &[u8]) -> Result<(), EmitError> {
+    let to_emit_error = |error: std::io::Error| EmitError::FailedToWriteToFile {
+        filename: filename.to_path_buf(),
+        error_message: error.to_string(),
+    };
+    if let Some(parent) = filename.parent() {
+        std::fs::create_dir_all(parent).map_err(to_emit_error)?;
+    }
+    std::fs::write(filename, contents).map_err(to_emit_error)
+}
 
-    match state.verify() {
-        Ok(_) => {}
-        Err(error) => eprintln!("{error:?}"),
+fn optimization_level(level: u8) -> OptimizationLevel {
+    match level {
+        0 => OptimizationLevel::None,
+        1 => OptimizationLevel::Less,
+        2 => OptimizationLevel::Default,
+        _ => OptimizationLevel::Aggressive,
     }
+}
 
-    state.optimize(arguments.optimization_level());
+#[cfg(target_os = "windows")]
+fn default_output_filename(target: EmitTarget) -> &'static str {
+    match target {
+        EmitTarget::Assembly => "out.asm",
+        EmitTarget::ObjectFile => "out.obj",
+        EmitTarget::Executable => "a.exe",
+        EmitTarget::LlvmIr => "out.ll",
+        EmitTarget::Bitcode => "out.bc",
+        EmitTarget::SharedLibrary => "out.dll",
+        EmitTarget::StaticLibrary => "out.lib",
+        EmitTarget::Jit => "",
+    }
+}
 
-    match arguments.emit_target() {
-        EmitTarget::Assembly => {
-            state.emit_assembly(&arguments.output_filename())?;
-            Ok(arguments.output_filename().clone())
-        }
-        EmitTarget::ObjectFile | EmitTarget::Executable => {
-            let filename = match arguments.only_compile_and_assemble {
-                true => arguments.output_filename(),
-                false => {
-                    let mut result = arguments.output_filename().clone();
-                    result.set_extension(object_file_extension());
-                    result
-                }
-            };
-            state.emit_object_file(&filename)?;
-            Ok(filename)
-        }
-        EmitTarget::LlvmIr => {
-            state.emit_llvm_ir(&arguments.output_filename())?;
-            Ok(arguments.output_filename().clone())
-        }
+#[cfg(target_os = "linux")]
+fn default_output_filename(target: EmitTarget) -> &'static str {
+    match target {
+        EmitTarget::Assembly => "out.asm",
+        EmitTarget::ObjectFile => "out.o",
+        EmitTarget::Executable => "a.out",
+        EmitTarget::LlvmIr => "out.ll",
+        EmitTarget::Bitcode => "out.bc",
+        EmitTarget::SharedLibrary => "libout.so",
+        EmitTarget::StaticLibrary => "libout.a",
+        EmitTarget::Jit => "",
     }
 }
 
@@ -1714,3 +4237,302 @@ fn object_file_extension() -> &'static str {
 fn object_file_extension() -> &'static str {
     "o"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emitter::control_flow::ControlFlowBuilder;
+    use crate::parser::{Dialect, Parser};
+    use inkwell::builder::Builder;
+    use inkwell::values::{AnyValue, IntValue};
+    use inkwell::IntPredicate;
+
+    fn compiler_output_filename(target: EmitTarget, output_filename: &str) -> PathBuf {
+        LlvmBackend::new(target)
+            .compiler_output_filename(&BackendOptions::for_tests(Platform::Host, output_filename))
+    }
+
+    #[test]
+    fn executables_are_linked_from_an_object_file_next_to_them() {
+        let object = |name: &str| PathBuf::from(format!("{name}.{}", object_file_extension()));
+        assert_eq!(
+            compiler_output_filename(EmitTarget::Executable, "build/prog"),
+            object("build/prog")
+        );
+        assert_eq!(
+            compiler_output_filename(EmitTarget::SharedLibrary, "libprog.so"),
+            object("libprog")
+        );
+        // the linker must not overwrite its own input
+        let executable = object("prog");
+        assert_eq!(
+            compiler_output_filename(EmitTarget::Executable, executable.to_str().unwrap()),
+            object(executable.to_str().unwrap())
+        );
+    }
+
+    #[test]
+    fn other_targets_are_written_to_the_output_file() {
+        for target in [
+            EmitTarget::ObjectFile,
+            EmitTarget::Assembly,
+            EmitTarget::LlvmIr,
+        ] {
+            assert_eq!(compiler_output_filename(target, "prog"), Path::new("prog"));
+        }
+    }
+
+    #[test]
+    fn browser_glue_is_written_next_to_the_module() {
+        let options = BackendOptions::for_tests(Platform::Wasm32Browser, "web/prog.wasm");
+        assert_eq!(browser_glue_filename(&options), Path::new("web/prog.js"));
+        let files = LlvmBackend::new(EmitTarget::Executable).output_files(&options);
+        assert!(files.contains(&PathBuf::from("web/prog.js")));
+    }
+
+    #[test]
+    fn module_name_is_the_lowercase_file_prefix() {
+        assert_eq!(module_name(Path::new("programs/Hello.World.b")), "hello");
+        assert_eq!(module_name(Path::new("prog")), "prog");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_files_are_written_to_any_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let directory =
+            std::env::temp_dir().join(format!("brainrust-emitter-{}", std::process::id()));
+        let filename = directory
+            .join(OsStr::from_bytes(b"\xff\xfe"))
+            .join("missing")
+            .join("out");
+        write_output_file(&filename, b"contents").unwrap();
+        assert_eq!(std::fs::read(&filename).unwrap(), b"contents");
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    // Builds `i32 function(i32 argument)` with the given body and returns its verified IR.
+    fn function_ir(
+        body: impl FnOnce(&ControlFlowBuilder, &Builder, IntValue) -> Result<(), BuilderError>,
+    ) -> String {
+        let context = Context::create();
+        let module = context.create_module("test");
+        let builder = context.create_builder();
+        let int = context.i32_type();
+        let function = module.add_function("function", int.fn_type(&[int.into()], false), None);
+        builder.position_at_end(context.append_basic_block(function, "entry"));
+        let argument = function.get_first_param().unwrap().into_int_value();
+        body(
+            &ControlFlowBuilder::new(&context, &builder),
+            &builder,
+            argument,
+        )
+        .unwrap();
+        module.verify().unwrap();
+        function.print_to_string().to_string()
+    }
+
+    fn is_zero<'a>(builder: &Builder<'a>, value: IntValue<'a>) -> IntValue<'a> {
+        builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                value,
+                value.get_type().const_zero(),
+                "is_zero",
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn if_then_else_joins_both_arms() {
+        let ir = function_ir(|control_flow, builder, argument| {
+            let result = builder.build_alloca(argument.get_type(), "result")?;
+            control_flow.if_then_else(
+                is_zero(builder, argument),
+                || {
+                    builder
+                        .build_store(result, argument.get_type().const_int(1, false))
+                        .map(drop)
+                },
+                || builder.build_store(result, argument).map(drop),
+            )?;
+            let result = builder.build_load(argument.get_type(), result, "result")?;
+            builder.build_return(Some(&result)).map(drop)
+        });
+        assert_eq!(
+            ir,
+            r#"define i32 @function(i32 %0) {
+entry:
+  %result = alloca i32, align 4
+  %is_zero = icmp eq i32 %0, 0
+  br i1 %is_zero, label %then, label %else
+
+then:                                             ; preds = %entry
+  store i32 1, ptr %result, align 4
+  br label %after_if
+
+else:                                             ; preds = %entry
+  store i32 %0, ptr %result, align 4
+  br label %after_if
+
+after_if:                                         ; preds = %else, %then
+  %result1 = load i32, ptr %result, align 4
+  ret i32 %result1
+}
+"#
+        );
+    }
+
+    #[test]
+    fn arms_that_return_early_do_not_branch_to_the_join_block() {
+        let ir = function_ir(|control_flow, builder, argument| {
+            control_flow.if_then(is_zero(builder, argument), || {
+                control_flow.return_early(Some(&argument.get_type().const_int(1, false)))
+            })?;
+            builder.build_return(Some(&argument)).map(drop)
+        });
+        assert_eq!(
+            ir,
+            r#"define i32 @function(i32 %0) {
+entry:
+  %is_zero = icmp eq i32 %0, 0
+  br i1 %is_zero, label %then, label %after_if
+
+then:                                             ; preds = %entry
+  ret i32 1
+
+after_if:                                         ; preds = %entry
+  ret i32 %0
+}
+"#
+        );
+    }
+
+    #[test]
+    fn loops_branch_back_to_their_header() {
+        let ir = function_ir(|control_flow, builder, argument| {
+            let counter = builder.build_alloca(argument.get_type(), "counter")?;
+            builder.build_store(counter, argument)?;
+            let load = || {
+                builder
+                    .build_load(argument.get_type(), counter, "counter")
+                    .map(|value| value.into_int_value())
+            };
+            control_flow.while_loop(
+                || builder.build_not(is_zero(builder, load()?), "is_not_zero"),
+                |_| {
+                    let one = argument.get_type().const_int(1, false);
+                    builder.build_store(counter, builder.build_int_sub(load()?, one, "next")?)?;
+                    Ok(())
+                },
+            )?;
+            builder.build_return(Some(&load()?)).map(drop)
+        });
+        assert_eq!(
+            ir,
+            r#"define i32 @function(i32 %0) {
+entry:
+  %counter = alloca i32, align 4
+  store i32 %0, ptr %counter, align 4
+  br label %loop_header
+
+loop_header:                                      ; preds = %loop_body, %entry
+  %counter1 = load i32, ptr %counter, align 4
+  %is_zero = icmp eq i32 %counter1, 0
+  %is_not_zero = xor i1 %is_zero, true
+  br i1 %is_not_zero, label %loop_body, label %after_loop
+
+loop_body:                                        ; preds = %loop_header
+  %counter2 = load i32, ptr %counter, align 4
+  %next = sub i32 %counter2, 1
+  store i32 %next, ptr %counter, align 4
+  br label %loop_header
+
+after_loop:                                       ; preds = %loop_header
+  %counter3 = load i32, ptr %counter, align 4
+  ret i32 %counter3
+}
+"#
+        );
+    }
+
+    #[test]
+    fn break_and_continue_jump_out_of_the_innermost_loop() {
+        let ir = function_ir(|control_flow, builder, argument| {
+            let always = argument
+                .get_type()
+                .get_context()
+                .bool_type()
+                .const_all_ones();
+            control_flow.while_loop(
+                || Ok(always),
+                |outer| {
+                    control_flow.while_loop(
+                        || Ok(always),
+                        |inner| {
+                            control_flow.if_then(is_zero(builder, argument), || {
+                                control_flow.break_loop(inner)
+                            })?;
+                            control_flow.continue_loop(inner)
+                        },
+                    )?;
+                    control_flow.break_loop(outer)
+                },
+            )?;
+            builder.build_return(Some(&argument)).map(drop)
+        });
+        assert_eq!(
+            ir,
+            r#"define i32 @function(i32 %0) {
+entry:
+  br label %loop_header
+
+loop_header:                                      ; preds = %entry
+  br i1 true, label %loop_body, label %after_loop
+
+loop_body:                                        ; preds = %loop_header
+  br label %loop_header1
+
+after_loop:                                       ; preds = %after_loop3, %loop_header
+  ret i32 %0
+
+loop_header1:                                     ; preds = %after_if, %loop_body
+  br i1 true, label %loop_body2, label %after_loop3
+
+loop_body2:                                       ; preds = %loop_header1
+  %is_zero = icmp eq i32 %0, 0
+  br i1 %is_zero, label %then, label %after_if
+
+after_loop3:                                      ; preds = %then, %loop_header1
+  br label %after_loop
+
+then:                                             ; preds = %loop_body2
+  br label %after_loop3
+
+after_if:                                         ; preds = %loop_body2
+  br label %loop_header1
+}
+"#
+        );
+    }
+
+    #[test]
+    fn only_compiled_programs_control_the_echo_themselves() {
+        let program = Parser::new(b",[.,]", Dialect::default()).parse().unwrap();
+        let options = BackendOptions {
+            no_echo: true,
+            ..BackendOptions::for_tests(Platform::Host, "a.out")
+        };
+        let context = Context::create();
+        for (target, controls_echo) in [(EmitTarget::Executable, true), (EmitTarget::Jit, false)] {
+            let state = build_module(&context, &program, &options, target).unwrap();
+            assert_eq!(
+                state.module.get_function("tcsetattr").is_some(),
+                controls_echo,
+                "{target:?}"
+            );
+        }
+    }
+}
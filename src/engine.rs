@@ -0,0 +1,269 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+use crate::command_line_arguments::{EmitTarget, Engine, RunArguments};
+use crate::environment::EnvironmentReport;
+use crate::hash::fnv1a;
+use crate::interpreter::{interpret, InterpreterOptions};
+use crate::linker::host_linker_available;
+use crate::program::Program;
+use crate::registry::{Backend, BackendOptions, Platform, Registry};
+use crate::stats::Statistics;
+use crate::usage_log;
+
+// Compiling takes much longer than interpreting a small program, so compilation only pays
+// off for larger programs or when the executable is already cached.
+const MAX_INTERPRETED_STATEMENTS: usize = 1000;
+
+const CACHE_OPTIMIZATION_LEVEL: u8 = 2;
+
+// the engines brainrust was built with and the tools they need
+#[derive(Clone, Copy)]
+struct Availability {
+    compiler: bool,
+    jit: bool,
+    linker: bool,
+}
+
+struct Decision {
+    engine: Engine,
+    reason: String,
+}
+
+pub(crate) fn run(
+    registry: &Registry,
+    arguments: &RunArguments,
+    source: &[u8],
+    program: &Program,
+) -> Result<()> {
+    let compiler = registry.backend(EmitTarget::Executable.backend_name());
+    let jit = registry.backend(EmitTarget::Jit.backend_name());
+    let cached_executable =
+        cache_directory().join(format!("{:016x}", cache_key(arguments, source)));
+    let decision = decide(
+        arguments.engine,
+        Availability {
+            compiler: compiler.is_some(),
+            jit: jit.is_some(),
+            // looking for a linker only matters once there is something to link
+            linker: compiler.is_some() && host_linker_available(),
+        },
+        cached_executable.is_file(),
+        Statistics::of(program).statements,
+    )?;
+    let engine = match decision.engine {
+        Engine::Compiled => "compiled",
+        Engine::Jit => "jit",
+        _ => "interpreter",
+    };
+    usage_log::record_engine(engine);
+    if arguments.verbose {
+        eprintln!("using the {engine} engine: {}", decision.reason);
+    }
+
+    match (decision.engine, compiler, jit) {
+        (Engine::Compiled, Some(compiler), _) => {
+            if !cached_executable.is_file() {
+                compile(compiler, arguments, source, program, &cached_executable)?;
+            }
+            let status = Command::new(&cached_executable)
+                .status()
+                .with_context(|| format!("while running {}", cached_executable.display()))?;
+            if !status.success() {
+                bail!("program exited with {status}");
+            }
+            Ok(())
+        }
+        (Engine::Jit, _, Some(jit)) => jit.emit(
+            program,
+            &backend_options(arguments, source, arguments.input_filename.clone()),
+        ),
+        _ => Ok(interpret(
+            program,
+            &InterpreterOptions {
+                prompt: arguments.prompt.clone(),
+                no_echo: arguments.no_echo,
+                max_output: arguments.max_output,
+                runtime: arguments.runtime_config(),
+                ..Default::default()
+            },
+        )?),
+    }
+}
+
+// Large programs are compiled into a cached executable. Without a linker to produce it, they
+// are compiled in memory by the JIT compiler instead, which has to compile them on every run.
+fn decide(
+    engine: Engine,
+    available: Availability,
+    cached: bool,
+    statements: usize,
+) -> Result<Decision> {
+    let (engine, reason) = match engine {
+        Engine::Interpreter => (Engine::Interpreter, "requested using --engine".to_string()),
+        Engine::Compiled if !available.compiler => {
+            bail!("the compiled engine is not available (brainrust was built without the 'llvm' feature)")
+        }
+        Engine::Compiled => (Engine::Compiled, "requested using --engine".to_string()),
+        Engine::Jit if !available.jit => {
+            bail!("the jit engine is not available (brainrust was built without the 'llvm' feature)")
+        }
+        Engine::Jit => (Engine::Jit, "requested using --engine".to_string()),
+        Engine::Auto if !available.compiler && !available.jit => (
+            Engine::Interpreter,
+            "no compiler available (brainrust was built without the 'llvm' feature)".to_string(),
+        ),
+        Engine::Auto if cached && available.compiler => (
+            Engine::Compiled,
+            "a compiled executable is cached".to_string(),
+        ),
+        Engine::Auto if statements <= MAX_INTERPRETED_STATEMENTS => (
+            Engine::Interpreter,
+            format!("the program has at most {MAX_INTERPRETED_STATEMENTS} statements and no compiled executable is cached"),
+        ),
+        Engine::Auto if available.compiler && available.linker => (
+            Engine::Compiled,
+            format!("the program has more than {MAX_INTERPRETED_STATEMENTS} statements"),
+        ),
+        Engine::Auto if available.jit => (
+            Engine::Jit,
+            format!("the program has more than {MAX_INTERPRETED_STATEMENTS} statements, but no linker was found to compile it into an executable"),
+        ),
+        Engine::Auto => (
+            Engine::Interpreter,
+            format!("the program has more than {MAX_INTERPRETED_STATEMENTS} statements, but no linker was found and the jit engine is not available"),
+        ),
+    };
+    Ok(Decision { engine, reason })
+}
+
+fn compile(
+    compiler: &dyn Backend,
+    arguments: &RunArguments,
+    source: &[u8],
+    program: &Program,
+    executable: &Path,
+) -> Result<()> {
+    let directory = executable
+        .parent()
+        .expect("cached executables are inside a directory");
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("while creating {}", directory.display()))?;
+    // the executable is renamed into place afterwards so that an interrupted compilation
+    // never leaves a broken cache entry behind
+    let temporary = executable.with_extension("tmp");
+    compiler.emit(
+        program,
+        &backend_options(arguments, source, temporary.clone()),
+    )?;
+    std::fs::rename(&temporary, executable)
+        .with_context(|| format!("while moving {} into the cache", temporary.display()))
+}
+
+fn backend_options(
+    arguments: &RunArguments,
+    source: &[u8],
+    output_filename: PathBuf,
+) -> BackendOptions {
+    BackendOptions {
+        input_filename: arguments.input_filename.clone(),
+        output_filename,
+        optimization_level: CACHE_OPTIMIZATION_LEVEL,
+        verbose: arguments.verbose,
+        environment: EnvironmentReport::collect(Some(source)),
+        platform: Platform::Host,
+        prompt: arguments.prompt.clone(),
+        no_echo: arguments.no_echo,
+        embed_selftest: false,
+        harden: false,
+        constant_output: None,
+        max_output: arguments.max_output,
+        runtime: arguments.runtime_config(),
+        initial_tape: Vec::new(),
+        guard_pages: false,
+        no_libc: false,
+        bare_metal: false,
+        linker: None,
+        static_executable: false,
+        link_arguments: Vec::new(),
+        jit_profiling: false,
+    }
+}
+
+pub(crate) fn cache_directory() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("brainrust")
+}
+
+// The key covers everything that influences the generated executable.
+fn cache_key(arguments: &RunArguments, source: &[u8]) -> u64 {
+    let mut key = Vec::from(source);
+    key.extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes());
+    let dialect = arguments.dialect();
+    key.push(dialect.loop_control as u8);
+    if let Some(register) = dialect.register {
+        key.extend_from_slice(&[register.store, register.load]);
+    }
+    if let Some(prompt) = &arguments.prompt {
+        key.push(1);
+        key.extend_from_slice(prompt.as_bytes());
+    }
+    if let Some(max_output) = arguments.max_output {
+        key.push(2);
+        key.extend_from_slice(&max_output.to_le_bytes());
+    }
+    key.extend_from_slice(&[3, arguments.eof_behavior as u8]);
+    if let Some(max_memory) = arguments.max_memory {
+        key.push(4);
+        key.extend_from_slice(&max_memory.to_le_bytes());
+    }
+    if dialect.debug_dump {
+        key.push(5);
+    }
+    if arguments.no_echo {
+        key.push(6);
+    }
+    fnv1a(&key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVERYTHING: Availability = Availability {
+        compiler: true,
+        jit: true,
+        linker: true,
+    };
+
+    fn engine(available: Availability, cached: bool, statements: usize) -> Engine {
+        decide(Engine::Auto, available, cached, statements)
+            .unwrap()
+            .engine
+    }
+
+    #[test]
+    fn large_programs_are_compiled_in_memory_without_a_linker() {
+        let large = MAX_INTERPRETED_STATEMENTS + 1;
+        assert_eq!(engine(EVERYTHING, false, 10), Engine::Interpreter);
+        assert_eq!(engine(EVERYTHING, true, 10), Engine::Compiled);
+        assert_eq!(engine(EVERYTHING, false, large), Engine::Compiled);
+        let without_linker = Availability {
+            linker: false,
+            ..EVERYTHING
+        };
+        assert_eq!(engine(without_linker, false, large), Engine::Jit);
+        let without_llvm = Availability {
+            compiler: false,
+            jit: false,
+            linker: true,
+        };
+        assert_eq!(engine(without_llvm, false, large), Engine::Interpreter);
+        assert!(decide(Engine::Jit, without_llvm, false, 10).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt::{Display, Formatter};
+
+use crate::hash::fnv1a;
+
+#[cfg(feature = "llvm")]
+use inkwell::support::get_llvm_version;
+#[cfg(feature = "llvm")]
+use inkwell::targets::TargetMachine;
+
+pub struct EnvironmentReport {
+    version: &'static str,
+    llvm_version: Option<(u32, u32, u32)>,
+    target: String,
+    flags: Vec<String>,
+    source_hash: Option<u64>,
+}
+
+impl EnvironmentReport {
+    /// Describes the running brainrust and, if given, the source code of the program.
+    pub fn collect(source: Option<&[u8]>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            llvm_version: llvm_version(),
+            target: target(),
+            flags: std::env::args_os()
+                .skip(1)
+                .map(|flag| flag.to_string_lossy().into_owned())
+                .collect(),
+            source_hash: source.map(fnv1a),
+        }
+    }
+
+    pub fn producer(&self) -> String {
+        match self.llvm_version {
+            Some((major, minor, patch)) => {
+                format!("brainrust {} (LLVM {major}.{minor}.{patch})", self.version)
+            }
+            None => format!("brainrust {}", self.version),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let llvm_version = match self.llvm_version {
+            Some((major, minor, patch)) => format!("{major}.{minor}.{patch}"),
+            None => "none (built without the 'llvm' feature)".to_string(),
+        };
+        let mut entries = vec![
+            ("brainrust version", self.version.to_string()),
+            ("LLVM version", llvm_version),
+            ("target", self.target.clone()),
+            ("flags", self.flags.join(" ")),
+        ];
+        if let Some(hash) = self.source_hash {
+            entries.push(("source hash (FNV-1a)", format!("{hash:016x}")));
+        }
+        entries
+    }
+}
+
+impl Display for EnvironmentReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in self.entries() {
+            writeln!(f, "{key}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "llvm")]
+fn llvm_version() -> Option<(u32, u32, u32)> {
+    Some(get_llvm_version())
+}
+
+#[cfg(not(feature = "llvm"))]
+fn llvm_version() -> Option<(u32, u32, u32)> {
+    None
+}
+
+#[cfg(feature = "llvm")]
+fn target() -> String {
+    TargetMachine::get_default_triple()
+        .as_str()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(not(feature = "llvm"))]
+fn target() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
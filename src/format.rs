@@ -0,0 +1,171 @@
+use crate::command_line_arguments::FormatStyle;
+use crate::parser::Dialect;
+use crate::program::{Program, Statement, StatementKind};
+
+const LINE_WIDTH: usize = 80;
+const INDENTATION: usize = 2;
+// deeply nested loops still get some room per line, since they are indented no further than
+// that leaves
+const MIN_LINE_WIDTH: usize = 20;
+
+/// Formats a parsed program without its comments. The output ends with a newline unless the
+/// program is empty.
+pub(crate) fn format(program: &Program, dialect: Dialect, style: FormatStyle) -> Vec<u8> {
+    let mut lines = Vec::new();
+    match style {
+        FormatStyle::Compact => {
+            let mut commands = Vec::new();
+            write_commands(program.statements(), dialect, &mut commands);
+            wrap(&commands, 0, &mut lines);
+        }
+        FormatStyle::Canonical => format_canonical(program.statements(), dialect, &mut lines),
+    }
+    lines
+        .into_iter()
+        .flat_map(|line| line.into_iter().chain(*b"\n"))
+        .collect()
+}
+
+// Straight-line code is wrapped at a fixed width. Every loop starts on a new line and is kept
+// on a single line if it contains no other loop and fits, otherwise its brackets get lines of
+// their own and its body is indented.
+fn format_canonical(statements: &[Statement], dialect: Dialect, lines: &mut Vec<Vec<u8>>) {
+    // the blocks that are being formatted, innermost last, each with its straight-line code
+    // since the last loop
+    let mut blocks = vec![(statements.iter(), Vec::new())];
+    while !blocks.is_empty() {
+        let depth = blocks.len() - 1;
+        let (statements, straight_line_code) = blocks.last_mut().expect("checked above");
+        let Some(statement) = statements.next() else {
+            wrap(straight_line_code, depth, lines);
+            blocks.pop();
+            if depth > 0 {
+                lines.push(indented(b"]", depth - 1));
+            }
+            continue;
+        };
+        let body = match statement.kind() {
+            StatementKind::Loop(body) | StatementKind::If(body) => body,
+            _ => {
+                write_commands(std::slice::from_ref(statement), dialect, straight_line_code);
+                continue;
+            }
+        };
+        wrap(straight_line_code, depth, lines);
+        straight_line_code.clear();
+
+        if !contains_loop(body) {
+            let mut commands = Vec::new();
+            write_commands(std::slice::from_ref(statement), dialect, &mut commands);
+            if commands.len() <= line_width(depth) {
+                lines.push(indented(&commands, depth));
+                continue;
+            }
+        }
+        lines.push(indented(b"[", depth));
+        blocks.push((body.iter(), Vec::new()));
+    }
+}
+
+fn write_commands(statements: &[Statement], dialect: Dialect, commands: &mut Vec<u8>) {
+    let register = dialect.register.unwrap_or_default();
+    // the blocks that are being written, innermost last
+    let mut blocks = vec![statements.iter()];
+    while let Some(statements) = blocks.last_mut() {
+        let Some(statement) = statements.next() else {
+            blocks.pop();
+            // the body of a loop has ended
+            if !blocks.is_empty() {
+                commands.push(b']');
+            }
+            continue;
+        };
+        match statement.kind() {
+            StatementKind::Move(amount) => write_repeated(commands, *amount, b'>', b'<'),
+            StatementKind::Add { offset, amount } => {
+                write_repeated(commands, *offset, b'>', b'<');
+                write_repeated(commands, *amount, b'+', b'-');
+                write_repeated(commands, -offset, b'>', b'<');
+            }
+            StatementKind::PutChar => commands.push(b'.'),
+            StatementKind::GetChar => commands.push(b','),
+            StatementKind::Loop(body) | StatementKind::If(body) => {
+                commands.push(b'[');
+                blocks.push(body.iter());
+            }
+            StatementKind::Store => commands.push(register.store),
+            StatementKind::Load => commands.push(register.load),
+            StatementKind::Break => commands.push(b'@'),
+            StatementKind::Continue => commands.push(b'&'),
+            StatementKind::DebugDump => commands.push(b'#'),
+            StatementKind::Set { .. } | StatementKind::MultiplyAdd(_) => {
+                unreachable!("only created by the optimizer")
+            }
+        }
+    }
+}
+
+fn write_repeated(commands: &mut Vec<u8>, amount: i64, positive: u8, negative: u8) {
+    let command = if amount >= 0 { positive } else { negative };
+    commands.extend(std::iter::repeat_n(command, amount.unsigned_abs() as usize));
+}
+
+fn contains_loop(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| {
+        matches!(
+            statement.kind(),
+            StatementKind::Loop(_) | StatementKind::If(_)
+        )
+    })
+}
+
+fn wrap(commands: &[u8], depth: usize, lines: &mut Vec<Vec<u8>>) {
+    for chunk in commands.chunks(line_width(depth)) {
+        lines.push(indented(chunk, depth));
+    }
+}
+
+fn line_width(depth: usize) -> usize {
+    LINE_WIDTH - indentation(depth)
+}
+
+fn indentation(depth: usize) -> usize {
+    (depth * INDENTATION).min(LINE_WIDTH - MIN_LINE_WIDTH)
+}
+
+fn indented(line: &[u8], depth: usize) -> Vec<u8> {
+    let mut indented = vec![b' '; indentation(depth)];
+    indented.extend_from_slice(line);
+    indented
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn deeply_nested_loops_are_formatted() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+
+        let compact = format(&program, Dialect::default(), FormatStyle::Compact);
+        let commands: Vec<u8> = compact.into_iter().filter(|&char| char != b'\n').collect();
+        assert_eq!(commands, source.as_bytes());
+
+        let canonical = format(&program, Dialect::default(), FormatStyle::Canonical);
+        let lines: Vec<_> = canonical.split(|&char| char == b'\n').collect();
+        // `+`, the brackets of all but the innermost loop, which fits on a line, and the
+        // trailing newline
+        assert_eq!(lines.len(), 1 + 2 * (DEPTH - 1) + 1 + 1);
+        assert!(lines.iter().all(|line| line.len() <= LINE_WIDTH));
+        let reformatted = Parser::new(&canonical, Dialect::default()).parse().unwrap();
+        assert_eq!(
+            format(&reformatted, Dialect::default(), FormatStyle::Canonical),
+            canonical
+        );
+    }
+}
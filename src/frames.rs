@@ -0,0 +1,144 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::interpreter::InterpreterError;
+
+// the number of cells in a frame, which only scrolls once the pointer leaves it
+const CELLS_PER_FRAME: i64 = 16;
+const CELL_SIZE: i64 = 48;
+const MARGIN: i64 = 16;
+
+/// Renders the cells around the pointer to an SVG file every `interval` steps, e.g. to turn an
+/// execution into an animation. The frames are numbered in the order they are written.
+pub(crate) struct FrameExporter {
+    directory: PathBuf,
+    interval: u64,
+    steps: u64,
+    frames: u64,
+}
+
+impl FrameExporter {
+    pub(crate) fn new(directory: PathBuf, interval: u64) -> Self {
+        Self {
+            directory,
+            interval,
+            steps: 0,
+            frames: 0,
+        }
+    }
+
+    pub(crate) fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Called before an op that takes the given number of steps. Writes a frame of the state
+    /// before the op if it is the first one since the last multiple of the interval.
+    pub(crate) fn observe(
+        &mut self,
+        steps: u64,
+        pointer: i64,
+        cell: impl Fn(i64) -> u64,
+    ) -> Result<(), InterpreterError> {
+        if self.steps >= self.frames * self.interval {
+            self.write_frame(pointer, cell)?;
+        }
+        self.steps += steps;
+        Ok(())
+    }
+
+    /// Writes a frame of the state the program ended with.
+    pub(crate) fn finish(
+        &mut self,
+        pointer: i64,
+        cell: impl Fn(i64) -> u64,
+    ) -> Result<(), InterpreterError> {
+        self.write_frame(pointer, cell)
+    }
+
+    fn write_frame(
+        &mut self,
+        pointer: i64,
+        cell: impl Fn(i64) -> u64,
+    ) -> Result<(), InterpreterError> {
+        let filename = self.directory.join(format!("frame-{:06}.svg", self.frames));
+        std::fs::write(&filename, self.render(pointer, cell)).map_err(|error| {
+            InterpreterError::FailedToWriteFrame {
+                filename,
+                error_message: error.to_string(),
+            }
+        })?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn render(&self, pointer: i64, cell: impl Fn(i64) -> u64) -> String {
+        let first_address = pointer.div_euclid(CELLS_PER_FRAME) * CELLS_PER_FRAME;
+        let width = 2 * MARGIN + CELLS_PER_FRAME * CELL_SIZE;
+        let height = 2 * MARGIN + 3 * CELL_SIZE;
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             font-family=\"monospace\" text-anchor=\"middle\">"
+        );
+        let _ = writeln!(
+            svg,
+            "<rect width=\"{width}\" height=\"{height}\" fill=\"white\"/>\n\
+             <text x=\"{MARGIN}\" y=\"{}\" text-anchor=\"start\">step {}</text>",
+            MARGIN + 16,
+            self.steps
+        );
+        for index in 0..CELLS_PER_FRAME {
+            let address = first_address + index;
+            let value = cell(address);
+            let x = MARGIN + index * CELL_SIZE;
+            let y = MARGIN + CELL_SIZE;
+            let center = x + CELL_SIZE / 2;
+            let fill = if address == pointer {
+                "#ffd54f"
+            } else {
+                "white"
+            };
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" \
+                 fill=\"{fill}\" stroke=\"black\"/>\n\
+                 <text x=\"{center}\" y=\"{}\">{value}</text>\n\
+                 <text x=\"{center}\" y=\"{}\" font-size=\"10\" fill=\"gray\">{address}</text>",
+                y + 22,
+                y + CELL_SIZE + 14
+            );
+            if let Some(char) = printable(value) {
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{center}\" y=\"{}\" font-size=\"10\" fill=\"gray\">{char}</text>",
+                    y + 38
+                );
+            }
+            if address == pointer {
+                let tip = y + CELL_SIZE + 20;
+                let _ = writeln!(
+                    svg,
+                    "<polygon points=\"{center},{tip} {},{} {},{}\"/>",
+                    center - 8,
+                    tip + 12,
+                    center + 8,
+                    tip + 12
+                );
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+// the character a cell holds, escaped for SVG, if it is printable ASCII
+fn printable(value: u64) -> Option<String> {
+    match u8::try_from(value).ok()? {
+        b'&' => Some("&amp;".to_string()),
+        b'<' => Some("&lt;".to_string()),
+        b'>' => Some("&gt;".to_string()),
+        value if value.is_ascii_graphic() => Some(char::from(value).to_string()),
+        _ => None,
+    }
+}
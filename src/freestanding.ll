@@ -0,0 +1,454 @@
+; The functions of the C standard library that the generated code uses, implemented with system
+; calls of Linux on x86-64 for executables compiled with --no-libc, and the entry point that
+; calls `main`. The module is linked into the generated module before it is optimized.
+;
+; A stream (`FILE *`) is its file descriptor. Output to stdout is buffered until it is flushed,
+; the program exits or it reads input, output to stderr is written right away. Every allocation
+; is a mapping of its own, preceded by its size.
+
+@stdout = global ptr inttoptr (i64 1 to ptr)
+@stderr = global ptr inttoptr (i64 2 to ptr)
+
+@output_buffer = internal global [4096 x i8] zeroinitializer
+@output_length = internal global i64 0
+@input_buffer = internal global [4096 x i8] zeroinitializer
+@input_start = internal global i64 0
+@input_end = internal global i64 0
+
+; keeps the symbols that `_start` and the code generator refer to when everything else becomes
+; internal
+@llvm.used = appending global [4 x ptr] [ptr @bf_start, ptr @memcpy, ptr @memmove, ptr @memset], section "llvm.metadata"
+
+; the stack pointer points at argc, followed by argv, and has to be aligned to 16 bytes at calls
+module asm ".globl _start"
+module asm "_start:"
+module asm "    xorl %ebp, %ebp"
+module asm "    movq %rsp, %rdi"
+module asm "    andq $-16, %rsp"
+module asm "    callq bf_start"
+module asm "    hlt"
+
+declare i32 @main(i32, ptr)
+
+declare void @llvm.va_start(ptr)
+declare void @llvm.va_end(ptr)
+declare { i64, i1 } @llvm.umul.with.overflow.i64(i64, i64)
+
+define void @bf_start(ptr %stack) #1 {
+  %argc = load i64, ptr %stack
+  %argc32 = trunc i64 %argc to i32
+  %argv = getelementptr i8, ptr %stack, i64 8
+  %status = call i32 @main(i32 %argc32, ptr %argv)
+  call void @exit(i32 %status)
+  unreachable
+}
+
+define internal i64 @syscall(i64 %number, i64 %a, i64 %b, i64 %c, i64 %d, i64 %e, i64 %f) #0 {
+  %result = call i64 asm sideeffect "syscall", "={rax},{rax},{rdi},{rsi},{rdx},{r10},{r8},{r9},~{rcx},~{r11},~{memory}"(i64 %number, i64 %a, i64 %b, i64 %c, i64 %d, i64 %e, i64 %f)
+  ret i64 %result
+}
+
+; stops at the first error, there is nobody to report it to
+define internal void @write_all(i64 %fd, ptr %data, i64 %length) #0 {
+entry:
+  br label %loop
+loop:
+  %written = phi i64 [ 0, %entry ], [ %new_written, %wrote ]
+  %done = icmp uge i64 %written, %length
+  br i1 %done, label %end, label %write
+write:
+  %pointer = getelementptr i8, ptr %data, i64 %written
+  %address = ptrtoint ptr %pointer to i64
+  %remaining = sub i64 %length, %written
+  %result = call i64 @syscall(i64 1, i64 %fd, i64 %address, i64 %remaining, i64 0, i64 0, i64 0)
+  %failed = icmp slt i64 %result, 1
+  br i1 %failed, label %end, label %wrote
+wrote:
+  %new_written = add i64 %written, %result
+  br label %loop
+end:
+  ret void
+}
+
+define internal void @flush_output() #0 {
+  %length = load i64, ptr @output_length
+  call void @write_all(i64 1, ptr @output_buffer, i64 %length)
+  store i64 0, ptr @output_length
+  ret void
+}
+
+define internal void @write_bytes(i64 %fd, ptr %data, i64 %length) #0 {
+entry:
+  %is_stdout = icmp eq i64 %fd, 1
+  br i1 %is_stdout, label %loop, label %unbuffered
+unbuffered:
+  call void @write_all(i64 %fd, ptr %data, i64 %length)
+  ret void
+loop:
+  %index = phi i64 [ 0, %entry ], [ %next_index, %append ]
+  %done = icmp uge i64 %index, %length
+  br i1 %done, label %end, label %check_space
+check_space:
+  %buffered = load i64, ptr @output_length
+  %is_full = icmp eq i64 %buffered, 4096
+  br i1 %is_full, label %flush, label %append
+flush:
+  call void @flush_output()
+  br label %append
+append:
+  %position = load i64, ptr @output_length
+  %source = getelementptr i8, ptr %data, i64 %index
+  %byte = load i8, ptr %source
+  %destination = getelementptr [4096 x i8], ptr @output_buffer, i64 0, i64 %position
+  store i8 %byte, ptr %destination
+  %new_position = add i64 %position, 1
+  store i64 %new_position, ptr @output_length
+  %next_index = add i64 %index, 1
+  br label %loop
+end:
+  ret void
+}
+
+define internal void @write_byte(i64 %fd, i8 %byte) #0 {
+  %data = alloca i8
+  store i8 %byte, ptr %data
+  call void @write_bytes(i64 %fd, ptr %data, i64 1)
+  ret void
+}
+
+define internal void @write_decimal(i64 %fd, i64 %value) #0 {
+entry:
+  %digits = alloca [20 x i8]
+  %is_negative = icmp slt i64 %value, 0
+  br i1 %is_negative, label %negative, label %loop
+negative:
+  call void @write_byte(i64 %fd, i8 45)
+  %negated = sub i64 0, %value
+  br label %loop
+loop:
+  %magnitude = phi i64 [ %value, %entry ], [ %negated, %negative ], [ %quotient, %loop ]
+  %position = phi i64 [ 20, %entry ], [ 20, %negative ], [ %digit_position, %loop ]
+  %remainder = urem i64 %magnitude, 10
+  %quotient = udiv i64 %magnitude, 10
+  %digit_position = sub i64 %position, 1
+  %digit = trunc i64 %remainder to i8
+  %character = add i8 %digit, 48
+  %destination = getelementptr [20 x i8], ptr %digits, i64 0, i64 %digit_position
+  store i8 %character, ptr %destination
+  %done = icmp eq i64 %quotient, 0
+  br i1 %done, label %end, label %loop
+end:
+  %start = getelementptr [20 x i8], ptr %digits, i64 0, i64 %digit_position
+  %length = sub i64 20, %digit_position
+  call void @write_bytes(i64 %fd, ptr %start, i64 %length)
+  ret void
+}
+
+define internal i64 @strlen(ptr %string) #0 {
+entry:
+  br label %loop
+loop:
+  %length = phi i64 [ 0, %entry ], [ %next_length, %loop ]
+  %pointer = getelementptr i8, ptr %string, i64 %length
+  %character = load i8, ptr %pointer
+  %next_length = add i64 %length, 1
+  %is_end = icmp eq i8 %character, 0
+  br i1 %is_end, label %end, label %loop
+end:
+  ret i64 %length
+}
+
+; supports the conversions the generated code uses: %s, %d, %lld and %%
+define internal void @format(i64 %fd, ptr %format, ptr %arguments) #0 {
+entry:
+  br label %loop
+loop:
+  %index = phi i64 [ 0, %entry ], [ %after_character, %character ], [ %after_conversion, %string ], [ %after_long_long, %long_long ], [ %after_conversion, %int ], [ %after_conversion, %percent ]
+  %pointer = getelementptr i8, ptr %format, i64 %index
+  %current = load i8, ptr %pointer
+  switch i8 %current, label %character [
+    i8 0, label %end
+    i8 37, label %conversion
+  ]
+character:
+  call void @write_byte(i64 %fd, i8 %current)
+  %after_character = add i64 %index, 1
+  br label %loop
+conversion:
+  %specifier_pointer = getelementptr i8, ptr %pointer, i64 1
+  %specifier = load i8, ptr %specifier_pointer
+  %after_conversion = add i64 %index, 2
+  switch i8 %specifier, label %percent [
+    i8 115, label %string
+    i8 108, label %long_long
+    i8 100, label %int
+  ]
+string:
+  %string_argument = va_arg ptr %arguments, ptr
+  %string_length = call i64 @strlen(ptr %string_argument)
+  call void @write_bytes(i64 %fd, ptr %string_argument, i64 %string_length)
+  br label %loop
+long_long:
+  %long_long_argument = va_arg ptr %arguments, i64
+  call void @write_decimal(i64 %fd, i64 %long_long_argument)
+  %after_long_long = add i64 %index, 4
+  br label %loop
+int:
+  %int_argument = va_arg ptr %arguments, i32
+  %extended = sext i32 %int_argument to i64
+  call void @write_decimal(i64 %fd, i64 %extended)
+  br label %loop
+percent:
+  call void @write_byte(i64 %fd, i8 37)
+  br label %loop
+end:
+  ret void
+}
+
+define i32 @printf(ptr %format, ...) #0 {
+  %arguments = alloca { i32, i32, ptr, ptr }
+  call void @llvm.va_start(ptr %arguments)
+  call void @format(i64 1, ptr %format, ptr %arguments)
+  call void @llvm.va_end(ptr %arguments)
+  ret i32 0
+}
+
+define i32 @fprintf(ptr %stream, ptr %format, ...) #0 {
+  %arguments = alloca { i32, i32, ptr, ptr }
+  %fd = ptrtoint ptr %stream to i64
+  call void @llvm.va_start(ptr %arguments)
+  call void @format(i64 %fd, ptr %format, ptr %arguments)
+  call void @llvm.va_end(ptr %arguments)
+  ret i32 0
+}
+
+define i32 @putchar(i32 %character) #0 {
+  %byte = trunc i32 %character to i8
+  call void @write_byte(i64 1, i8 %byte)
+  %result = zext i8 %byte to i32
+  ret i32 %result
+}
+
+define i32 @fputs(ptr %string, ptr %stream) #0 {
+  %fd = ptrtoint ptr %stream to i64
+  %length = call i64 @strlen(ptr %string)
+  call void @write_bytes(i64 %fd, ptr %string, i64 %length)
+  ret i32 0
+}
+
+define i64 @fwrite(ptr %data, i64 %size, i64 %count, ptr %stream) #0 {
+  %fd = ptrtoint ptr %stream to i64
+  %length = mul i64 %size, %count
+  call void @write_bytes(i64 %fd, ptr %data, i64 %length)
+  ret i64 %count
+}
+
+define i32 @fflush(ptr %stream) #0 {
+  call void @flush_output()
+  ret i32 0
+}
+
+; flushes stdout before waiting for input, so that interactive programs show their questions
+define i32 @getchar() #0 {
+entry:
+  %start = load i64, ptr @input_start
+  %end = load i64, ptr @input_end
+  %is_empty = icmp eq i64 %start, %end
+  br i1 %is_empty, label %fill, label %take
+fill:
+  call void @flush_output()
+  %buffer = ptrtoint ptr @input_buffer to i64
+  %result = call i64 @syscall(i64 0, i64 0, i64 %buffer, i64 4096, i64 0, i64 0, i64 0)
+  %is_end = icmp slt i64 %result, 1
+  br i1 %is_end, label %eof, label %filled
+filled:
+  store i64 %result, ptr @input_end
+  br label %take
+take:
+  %index = phi i64 [ %start, %entry ], [ 0, %filled ]
+  %pointer = getelementptr [4096 x i8], ptr @input_buffer, i64 0, i64 %index
+  %byte = load i8, ptr %pointer
+  %next_index = add i64 %index, 1
+  store i64 %next_index, ptr @input_start
+  %character = zext i8 %byte to i32
+  ret i32 %character
+eof:
+  store i64 0, ptr @input_start
+  store i64 0, ptr @input_end
+  ret i32 -1
+}
+
+; the TCGETS request only succeeds for terminals
+define i32 @isatty(i32 %fd) #0 {
+  %termios = alloca [64 x i8]
+  %fd64 = sext i32 %fd to i64
+  %address = ptrtoint ptr %termios to i64
+  %result = call i64 @syscall(i64 16, i64 %fd64, i64 21505, i64 %address, i64 0, i64 0, i64 0)
+  %is_terminal = icmp eq i64 %result, 0
+  %value = zext i1 %is_terminal to i32
+  ret i32 %value
+}
+
+define void @exit(i32 %status) #1 {
+  call void @flush_output()
+  %status64 = sext i32 %status to i64
+  %result = call i64 @syscall(i64 231, i64 %status64, i64 0, i64 0, i64 0, i64 0, i64 0)
+  unreachable
+}
+
+define void @abort() #1 {
+  %result = call i64 @syscall(i64 231, i64 134, i64 0, i64 0, i64 0, i64 0, i64 0)
+  unreachable
+}
+
+; system calls return -4095 to -1 for errors
+define internal ptr @mapping_to_allocation(i64 %mapping, i64 %size) #0 {
+entry:
+  %is_error = icmp ugt i64 %mapping, -4096
+  br i1 %is_error, label %failed, label %succeeded
+failed:
+  ret ptr null
+succeeded:
+  %header = inttoptr i64 %mapping to ptr
+  store i64 %size, ptr %header
+  %allocation = getelementptr i8, ptr %header, i64 16
+  ret ptr %allocation
+}
+
+define ptr @malloc(i64 %size) #0 {
+  %mapping_size = add i64 %size, 16
+  ; PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS
+  %mapping = call i64 @syscall(i64 9, i64 0, i64 %mapping_size, i64 3, i64 34, i64 -1, i64 0)
+  %allocation = call ptr @mapping_to_allocation(i64 %mapping, i64 %size)
+  ret ptr %allocation
+}
+
+; mappings are zeroed by the kernel
+define ptr @calloc(i64 %count, i64 %size) #0 {
+entry:
+  %product = call { i64, i1 } @llvm.umul.with.overflow.i64(i64 %count, i64 %size)
+  %overflows = extractvalue { i64, i1 } %product, 1
+  br i1 %overflows, label %failed, label %allocate
+failed:
+  ret ptr null
+allocate:
+  %total = extractvalue { i64, i1 } %product, 0
+  %allocation = call ptr @malloc(i64 %total)
+  ret ptr %allocation
+}
+
+define ptr @realloc(ptr %allocation, i64 %size) #0 {
+entry:
+  %is_null = icmp eq ptr %allocation, null
+  br i1 %is_null, label %allocate, label %remap
+allocate:
+  %new_allocation = call ptr @malloc(i64 %size)
+  ret ptr %new_allocation
+remap:
+  %header = getelementptr i8, ptr %allocation, i64 -16
+  %old_size = load i64, ptr %header
+  %old_mapping_size = add i64 %old_size, 16
+  %new_mapping_size = add i64 %size, 16
+  %address = ptrtoint ptr %header to i64
+  ; MREMAP_MAYMOVE
+  %mapping = call i64 @syscall(i64 25, i64 %address, i64 %old_mapping_size, i64 %new_mapping_size, i64 1, i64 0, i64 0)
+  %remapped = call ptr @mapping_to_allocation(i64 %mapping, i64 %size)
+  ret ptr %remapped
+}
+
+define void @free(ptr %allocation) #0 {
+entry:
+  %is_null = icmp eq ptr %allocation, null
+  br i1 %is_null, label %end, label %unmap
+unmap:
+  %header = getelementptr i8, ptr %allocation, i64 -16
+  %size = load i64, ptr %header
+  %mapping_size = add i64 %size, 16
+  %address = ptrtoint ptr %header to i64
+  %result = call i64 @syscall(i64 11, i64 %address, i64 %mapping_size, i64 0, i64 0, i64 0, i64 0)
+  br label %end
+end:
+  ret void
+}
+
+; not optimized, the legacy loop vectorizer of LLVM crashes on loops whose accesses may overlap
+define ptr @memmove(ptr %destination, ptr %source, i64 %count) #2 {
+entry:
+  %is_before = icmp ult ptr %destination, %source
+  br i1 %is_before, label %forward, label %backward
+forward:
+  %forward_index = phi i64 [ 0, %entry ], [ %next_forward_index, %copy_forward ]
+  %forward_done = icmp eq i64 %forward_index, %count
+  br i1 %forward_done, label %end, label %copy_forward
+copy_forward:
+  %forward_source = getelementptr i8, ptr %source, i64 %forward_index
+  %forward_byte = load i8, ptr %forward_source
+  %forward_destination = getelementptr i8, ptr %destination, i64 %forward_index
+  store i8 %forward_byte, ptr %forward_destination
+  %next_forward_index = add i64 %forward_index, 1
+  br label %forward
+backward:
+  %backward_index = phi i64 [ %count, %entry ], [ %previous_index, %copy_backward ]
+  %backward_done = icmp eq i64 %backward_index, 0
+  br i1 %backward_done, label %end, label %copy_backward
+copy_backward:
+  %previous_index = sub i64 %backward_index, 1
+  %backward_source = getelementptr i8, ptr %source, i64 %previous_index
+  %backward_byte = load i8, ptr %backward_source
+  %backward_destination = getelementptr i8, ptr %destination, i64 %previous_index
+  store i8 %backward_byte, ptr %backward_destination
+  br label %backward
+end:
+  ret ptr %destination
+}
+
+define ptr @memcpy(ptr %destination, ptr %source, i64 %count) #0 {
+  %result = call ptr @memmove(ptr %destination, ptr %source, i64 %count)
+  ret ptr %result
+}
+
+define ptr @memset(ptr %destination, i32 %value, i64 %count) #0 {
+entry:
+  %byte = trunc i32 %value to i8
+  br label %loop
+loop:
+  %index = phi i64 [ 0, %entry ], [ %next_index, %store ]
+  %done = icmp eq i64 %index, %count
+  br i1 %done, label %end, label %store
+store:
+  %pointer = getelementptr i8, ptr %destination, i64 %index
+  store i8 %byte, ptr %pointer
+  %next_index = add i64 %index, 1
+  br label %loop
+end:
+  ret ptr %destination
+}
+
+define i32 @strcmp(ptr %first, ptr %second) #0 {
+entry:
+  br label %loop
+loop:
+  %index = phi i64 [ 0, %entry ], [ %next_index, %same ]
+  %first_pointer = getelementptr i8, ptr %first, i64 %index
+  %first_character = load i8, ptr %first_pointer
+  %second_pointer = getelementptr i8, ptr %second, i64 %index
+  %second_character = load i8, ptr %second_pointer
+  %is_different = icmp ne i8 %first_character, %second_character
+  br i1 %is_different, label %different, label %same
+same:
+  %next_index = add i64 %index, 1
+  %is_end = icmp eq i8 %first_character, 0
+  br i1 %is_end, label %equal, label %loop
+equal:
+  ret i32 0
+different:
+  %first_value = zext i8 %first_character to i32
+  %second_value = zext i8 %second_character to i32
+  %difference = sub i32 %first_value, %second_value
+  ret i32 %difference
+}
+
+; the loops above must not be turned into calls of the functions they implement
+attributes #0 = { nounwind "no-builtins" }
+attributes #1 = { noreturn nounwind "no-builtins" }
+attributes #2 = { noinline nounwind optnone "no-builtins" }
@@ -0,0 +1,7 @@
+// FNV-1a is used instead of `DefaultHasher` because its output must not change between
+// Rust releases, so that hashes can be stored and compared later.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
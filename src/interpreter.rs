@@ -1,107 +1,2051 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{empty, sink, stdin, stdout, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::checkpoint::{Checkpoint, Checkpointing};
+use crate::command_line_arguments::{FlushMode, LoopLimitAction, TapeKind};
+use crate::diagnostics::{LineIndex, SourceLocation};
+use crate::frames::FrameExporter;
+use crate::hash::fnv1a;
 use crate::interpreter::state::State;
-use crate::program::{Program, Statement};
+use crate::program::{Program, Span, Statement, StatementKind};
+use crate::semantics::RuntimeConfig;
+use crate::terminal::EchoSuppression;
+
+#[derive(Error, Debug)]
+pub enum InterpreterError {
+    LoopIterationLimitExceeded {
+        limit: u64,
+    },
+    FailedToOpenInputFile {
+        filename: PathBuf,
+        error_message: String,
+    },
+    ReadsInput,
+    InputRequired,
+    OutputLimitExceeded {
+        limit: u64,
+    },
+    StepLimitExceeded {
+        limit: u64,
+    },
+    TapeLimitExceeded {
+        limit: u64,
+        address: i64,
+    },
+    OutsideTape {
+        address: i64,
+        tape_size: u64,
+    },
+    CellOverflow {
+        address: i64,
+        source_offset: usize,
+    },
+    TapeAssertionFailed {
+        address: i64,
+        expected: u64,
+        actual: u64,
+    },
+    FailedToWriteTrace(String),
+    FailedToWriteFrame {
+        filename: PathBuf,
+        error_message: String,
+    },
+    ReadAfterEndOfInput,
+    InputNotConsumed {
+        remaining: u64,
+    },
+    FailedToWriteOutput(String),
+    FailedToReadInput(String),
+    FailedToRecordInput {
+        filename: PathBuf,
+        error_message: String,
+    },
+    FailedToWriteCheckpoint {
+        filename: PathBuf,
+        error_message: String,
+    },
+    FailedToReadCheckpoint {
+        filename: PathBuf,
+        error_message: String,
+    },
+    CheckpointMismatch {
+        filename: PathBuf,
+    },
+    FailedToDumpTape {
+        filename: PathBuf,
+        error_message: String,
+    },
+}
+
+impl Display for InterpreterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpreterError::LoopIterationLimitExceeded { limit } => {
+                write!(f, "loop exceeded the limit of {limit} iterations")
+            }
+            InterpreterError::FailedToOpenInputFile {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to open input file '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+            InterpreterError::ReadsInput => {
+                write!(
+                    f,
+                    "the program reads input and cannot be evaluated in advance"
+                )
+            }
+            InterpreterError::InputRequired => {
+                write!(f, "the program reads input, pass it using --input")
+            }
+            InterpreterError::OutputLimitExceeded { limit } => {
+                write!(f, "program exceeded the output limit of {limit} bytes")
+            }
+            InterpreterError::StepLimitExceeded { limit } => {
+                write!(f, "program exceeded the limit of {limit} steps")
+            }
+            InterpreterError::TapeLimitExceeded { limit, address } => {
+                write!(
+                    f,
+                    "tape limit of {limit} cells exceeded at address {address}"
+                )
+            }
+            InterpreterError::OutsideTape { address, tape_size } => {
+                write!(
+                    f,
+                    "address {address} is outside the tape of {tape_size} cells"
+                )
+            }
+            InterpreterError::CellOverflow {
+                address,
+                source_offset,
+            } => {
+                write!(
+                    f,
+                    "cell overflow at address {address} (offset {source_offset} of the source)"
+                )
+            }
+            InterpreterError::TapeAssertionFailed {
+                address,
+                expected,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "the cell at address {address} holds {actual} instead of {expected}"
+                )
+            }
+            InterpreterError::FailedToWriteTrace(error_message) => {
+                write!(f, "failed to write trace: {error_message}")
+            }
+            InterpreterError::FailedToWriteFrame {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to write frame '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+            InterpreterError::ReadAfterEndOfInput => {
+                write!(f, "the program executed ',' after the end of its input")
+            }
+            InterpreterError::InputNotConsumed { remaining } => {
+                write!(
+                    f,
+                    "the program ended with {remaining} byte(s) of its input left unread"
+                )
+            }
+            InterpreterError::FailedToWriteOutput(error_message) => {
+                write!(f, "failed to write output: {error_message}")
+            }
+            InterpreterError::FailedToReadInput(error_message) => {
+                write!(f, "failed to read input: {error_message}")
+            }
+            InterpreterError::FailedToRecordInput {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to record input to '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+            InterpreterError::FailedToWriteCheckpoint {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to write checkpoint '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+            InterpreterError::FailedToReadCheckpoint {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to read checkpoint '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+            InterpreterError::CheckpointMismatch { filename } => {
+                write!(
+                    f,
+                    "checkpoint '{}' was written for a different program or different options",
+                    filename.display()
+                )
+            }
+            InterpreterError::FailedToDumpTape {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to dump the tape to '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+        }
+    }
+}
 
 mod state {
-    use std::io::{stdin, Read};
+    use std::collections::{HashMap, VecDeque};
+    use std::fs::File;
+    use std::io::{BufRead, Read, Write};
+    use std::path::PathBuf;
+
+    use crate::checkpoint::Checkpoint;
+    use crate::command_line_arguments::{FlushMode, TapeKind};
+    use crate::interpreter::InterpreterError;
+    use crate::semantics::RuntimeConfig;
+    use crate::tape_dump::TapeDump;
+
+    const PAGE_SIZE: usize = 4096;
+    // the number of cells on each side of the pointer that `#` prints
+    const DUMP_RADIUS: i64 = 8;
+
+    // cells are stored with 64 bits regardless of their size, which is enforced by `State`
+    enum Tape {
+        // a deque, so that growing the tape to the left does not shift all existing cells
+        Contiguous { cells: VecDeque<u64>, offset: usize },
+        // only the pages that have been accessed are allocated, keyed by their first address
+        // divided by the page size
+        Sparse(HashMap<i64, Box<[u64; PAGE_SIZE]>>),
+    }
+
+    impl Tape {
+        fn new(kind: TapeKind) -> Self {
+            match kind {
+                TapeKind::Contiguous => Tape::Contiguous {
+                    cells: VecDeque::new(),
+                    offset: 0,
+                },
+                TapeKind::Sparse => Tape::Sparse(HashMap::new()),
+            }
+        }
+
+        fn get(&self, address: i64) -> u64 {
+            match self {
+                Tape::Contiguous { cells, offset } => usize::try_from(address + *offset as i64)
+                    .ok()
+                    .and_then(|index| cells.get(index).copied())
+                    .unwrap_or(0),
+                Tape::Sparse(pages) => pages
+                    .get(&address.div_euclid(PAGE_SIZE as i64))
+                    .map_or(0, |page| {
+                        page[address.rem_euclid(PAGE_SIZE as i64) as usize]
+                    }),
+            }
+        }
+
+        fn len(&self) -> u64 {
+            match self {
+                Tape::Contiguous { cells, .. } => cells.len() as u64,
+                Tape::Sparse(pages) => (pages.len() * PAGE_SIZE) as u64,
+            }
+        }
+
+        // the cells that have been allocated so far, in the order of their addresses
+        fn cells(&self) -> Vec<(i64, u64)> {
+            match self {
+                Tape::Contiguous { cells, offset } => cells
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &value)| (index as i64 - *offset as i64, value))
+                    .collect(),
+                Tape::Sparse(pages) => {
+                    let mut page_numbers: Vec<_> = pages.keys().copied().collect();
+                    page_numbers.sort_unstable();
+                    page_numbers
+                        .into_iter()
+                        .flat_map(|page_number| {
+                            pages[&page_number]
+                                .iter()
+                                .enumerate()
+                                .map(move |(index, &value)| {
+                                    (page_number * PAGE_SIZE as i64 + index as i64, value)
+                                })
+                        })
+                        .collect()
+                }
+            }
+        }
+
+        // Returns `None` if the tape would have to grow beyond `max_cells`. Sparse tapes grow by
+        // whole pages.
+        fn cell(&mut self, address: i64, max_cells: Option<u64>) -> Option<&mut u64> {
+            match self {
+                Tape::Contiguous { cells, offset } => {
+                    let index = address + *offset as i64;
+                    let exceeds_limit = |length: usize| {
+                        max_cells.is_some_and(|max_cells| length as u64 > max_cells)
+                    };
+                    if index < 0 {
+                        let difference = (-index) as usize;
+                        if exceeds_limit(cells.len() + difference) {
+                            return None;
+                        }
+                        *offset += difference;
+                        cells.reserve(difference);
+                        for _ in 0..difference {
+                            cells.push_front(0);
+                        }
+                    } else if index as usize >= cells.len() {
+                        if exceeds_limit(index as usize + 1) {
+                            return None;
+                        }
+                        cells.resize(index as usize + 1, 0);
+                    }
+                    Some(&mut cells[(address + *offset as i64) as usize])
+                }
+                Tape::Sparse(pages) => {
+                    let page_number = address.div_euclid(PAGE_SIZE as i64);
+                    let required = (pages.len() as u64 + 1) * PAGE_SIZE as u64;
+                    if max_cells.is_some_and(|max_cells| required > max_cells)
+                        && !pages.contains_key(&page_number)
+                    {
+                        return None;
+                    }
+                    let page = pages
+                        .entry(page_number)
+                        .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+                    Some(&mut page[address.rem_euclid(PAGE_SIZE as i64) as usize])
+                }
+            }
+        }
+    }
 
-    #[derive(Default)]
-    pub(super) struct State {
-        memory: Vec<u8>,
-        memory_offset: usize,
+    pub(super) struct State<I, O> {
+        tape: Tape,
         pointer_address: i64,
+        input: I,
+        prompt: Option<String>,
+        at_line_start: bool,
+        register: u64,
+        output: O,
+        flush_mode: FlushMode,
+        output_size: u64,
+        input_size: u64,
+        max_output: Option<u64>,
+        runtime: RuntimeConfig,
+        fail_on_eof: bool,
+        // unbuffered, so that the recording is complete even if the program is interrupted
+        input_recording: Option<(PathBuf, File)>,
     }
 
-    impl State {
-        pub(super) fn increment_pointer(&mut self) {
-            self.pointer_address += 1;
+    impl<I: BufRead, O: Write> State<I, O> {
+        pub(super) fn new(
+            input: I,
+            output: O,
+            prompt: Option<String>,
+            max_output: Option<u64>,
+            tape: TapeKind,
+            flush_mode: FlushMode,
+            runtime: RuntimeConfig,
+        ) -> Self {
+            let mut tape = Tape::new(tape);
+            // the cells the generated programs allocate up front count towards the memory
+            // limit, which is the only way to observe them
+            if let (Tape::Contiguous { .. }, Some(_), None) =
+                (&tape, runtime.max_memory, runtime.fixed_tape_size())
+            {
+                let extent = runtime.initial_tape_extent() as i64;
+                tape.cell(-extent, None);
+                tape.cell(extent, None);
+            }
+            Self {
+                tape,
+                pointer_address: 0,
+                input,
+                prompt,
+                at_line_start: true,
+                register: 0,
+                output,
+                flush_mode,
+                output_size: 0,
+                input_size: 0,
+                max_output,
+                runtime,
+                fail_on_eof: false,
+                input_recording: None,
+            }
         }
 
-        pub(super) fn decrement_pointer(&mut self) {
-            self.pointer_address -= 1;
+        /// Makes `,` fail instead of applying the EOF behavior once the input has ended.
+        pub(super) fn with_fail_on_eof(mut self, fail_on_eof: bool) -> Self {
+            self.fail_on_eof = fail_on_eof;
+            self
         }
 
-        pub(super) fn increment_value(&mut self) {
-            let index = self.checked_index();
-            self.memory[index] = self.memory[index].wrapping_add(1);
+        /// Writes every byte read by `,` to the given file.
+        pub(super) fn with_input_recording(mut self, recording: Option<(PathBuf, File)>) -> Self {
+            self.input_recording = recording;
+            self
         }
 
-        pub(super) fn decrement_value(&mut self) {
-            let index = self.checked_index();
-            self.memory[index] = self.memory[index].wrapping_sub(1);
+        /// Saves everything but the tape and the input, given the position of the execution.
+        pub(super) fn checkpoint(
+            &self,
+            code_hash: u64,
+            program_counter: usize,
+            iterations: &[u64],
+        ) -> Checkpoint {
+            // only runs of non-zero cells are saved
+            let mut cells: Vec<(i64, Vec<u64>)> = Vec::new();
+            for (address, value) in self.tape.cells() {
+                if value == 0 {
+                    continue;
+                }
+                match cells.last_mut() {
+                    Some((start, values)) if *start + values.len() as i64 == address => {
+                        values.push(value)
+                    }
+                    _ => cells.push((address, vec![value])),
+                }
+            }
+            Checkpoint {
+                code_hash,
+                program_counter,
+                iterations: iterations.to_vec(),
+                pointer: self.pointer_address,
+                register: self.register,
+                at_line_start: self.at_line_start,
+                input_size: self.input_size,
+                cell_size: self.runtime.cell_size,
+                cells,
+            }
         }
 
-        pub(super) fn put_char(&mut self) {
-            let index = self.checked_index();
-            print!("{}", self.memory[index] as char)
+        /// Stores the bytes in the cells starting at address 0.
+        pub(super) fn load_tape(&mut self, bytes: &[u8]) {
+            for (address, &byte) in (0..).zip(bytes) {
+                self.set_cell_at(address, u64::from(byte));
+            }
         }
 
-        pub(super) fn get_char(&mut self) {
-            let input = stdin().lock().bytes().next().unwrap().unwrap();
-            let index = self.checked_index();
-            self.memory[index] = input;
+        /// Restores the tape and the pointer from a checkpoint. If `skip_input` is set, the
+        /// input the program had already read is skipped.
+        pub(super) fn restore(
+            &mut self,
+            checkpoint: &Checkpoint,
+            skip_input: bool,
+        ) -> Result<(), InterpreterError> {
+            for (start, values) in &checkpoint.cells {
+                for (address, &value) in (*start..).zip(values) {
+                    self.set_cell_at(address, value);
+                }
+            }
+            self.pointer_address = checkpoint.pointer;
+            self.register = checkpoint.register;
+            self.at_line_start = checkpoint.at_line_start;
+            if skip_input {
+                std::io::copy(
+                    &mut (&mut self.input).take(checkpoint.input_size),
+                    &mut std::io::sink(),
+                )
+                .map_err(|error| InterpreterError::FailedToReadInput(error.to_string()))?;
+            }
+            self.input_size = checkpoint.input_size;
+            Ok(())
         }
 
-        pub(super) fn read_value(&mut self) -> u8 {
-            let index = self.checked_index();
-            self.memory[index]
+        /// Reads the rest of the input and returns its length.
+        pub(super) fn skip_remaining_input(&mut self) -> Result<u64, InterpreterError> {
+            std::io::copy(&mut self.input, &mut std::io::sink())
+                .map_err(|error| InterpreterError::FailedToReadInput(error.to_string()))
         }
 
-        fn checked_index(&mut self) -> usize {
-            self.ensure_sufficient_memory_size();
-            self.current_address_to_index() as usize
+        pub(super) fn into_output(self) -> O {
+            self.output
         }
 
-        fn current_address_to_index(&self) -> i64 {
-            self.pointer_address + self.memory_offset as i64
+        pub(super) fn output(&self) -> &O {
+            &self.output
         }
 
-        fn ensure_sufficient_memory_size(&mut self) {
-            let target_index = self.current_address_to_index();
-            if target_index < 0 {
-                let difference = (-target_index) as usize;
-                self.memory_offset += difference;
-                self.memory.resize(self.memory.len() + difference, b'\0');
-                for i in (difference..self.memory.len()).rev() {
-                    self.memory[i] = self.memory[i - 1];
+        pub(super) fn pointer(&self) -> i64 {
+            self.pointer_address
+        }
+
+        pub(super) fn cell_at(&self, address: i64) -> u64 {
+            self.tape.get(address)
+        }
+
+        pub(super) fn set_cell_at(&mut self, address: i64, value: u64) {
+            *self
+                .tape
+                .cell(address, None)
+                .expect("the tape can always grow without a limit") = value;
+        }
+
+        pub(super) fn cells(&self) -> Vec<(i64, u64)> {
+            self.tape.cells()
+        }
+
+        /// The whole tape of a fixed size, otherwise the cells from the lowest to the highest
+        /// address that has been allocated.
+        pub(super) fn tape_dump(&self) -> TapeDump {
+            let addresses = match self.runtime.fixed_tape_size() {
+                Some(tape_size) => 0..i64::try_from(tape_size).unwrap_or(i64::MAX),
+                None => {
+                    let cells = self.tape.cells();
+                    match (cells.first(), cells.last()) {
+                        (Some(&(first, _)), Some(&(last, _))) => first..last + 1,
+                        _ => 0..0,
+                    }
                 }
-                for i in 0..difference {
-                    self.memory[i] = 0;
+            };
+            TapeDump {
+                first_address: addresses.start,
+                cell_size: self.runtime.cell_size,
+                cells: addresses.map(|address| self.tape.get(address)).collect(),
+            }
+        }
+
+        /// The number of cells that have been allocated, which never decreases.
+        pub(super) fn tape_size(&self) -> u64 {
+            self.tape.len()
+        }
+
+        pub(super) fn input_size(&self) -> u64 {
+            self.input_size
+        }
+
+        pub(super) fn output_size(&self) -> u64 {
+            self.output_size
+        }
+
+        pub(super) fn flush_output(&mut self) -> Result<(), InterpreterError> {
+            self.output.flush().map_err(output_error)
+        }
+
+        pub(super) fn move_pointer(&mut self, amount: i64) {
+            self.pointer_address += amount;
+        }
+
+        pub(super) fn add_value(
+            &mut self,
+            offset: i64,
+            amount: i64,
+        ) -> Result<(), InterpreterError> {
+            let runtime = self.runtime;
+            let address = self.pointer_address + offset;
+            let cell = self.cell(offset)?;
+            // the source offset is filled in by `execute`, which knows the op
+            *cell = runtime
+                .add_to_cell(*cell, amount)
+                .ok_or(InterpreterError::CellOverflow {
+                    address,
+                    source_offset: 0,
+                })?;
+            Ok(())
+        }
+
+        pub(super) fn set_value(
+            &mut self,
+            offset: i64,
+            value: u64,
+        ) -> Result<(), InterpreterError> {
+            *self.cell(offset)? = value;
+            Ok(())
+        }
+
+        pub(super) fn multiply_add(
+            &mut self,
+            targets: &[(i64, i64)],
+        ) -> Result<(), InterpreterError> {
+            let value = self.read_value()?;
+            if value == 0 {
+                return Ok(());
+            }
+            for &(offset, factor) in targets {
+                self.add_value(offset, (value as i64).wrapping_mul(factor))?;
+            }
+            self.set_value(0, 0)
+        }
+
+        pub(super) fn put_char(&mut self) -> Result<(), InterpreterError> {
+            if let Some(limit) = self.max_output.filter(|&limit| self.output_size == limit) {
+                self.flush_output()?;
+                return Err(InterpreterError::OutputLimitExceeded { limit });
+            }
+            self.output_size += 1;
+            // only the lowest byte of wider cells is written
+            let value = *self.cell(0)? as u8;
+            self.output.write_all(&[value]).map_err(output_error)?;
+            let flush = match self.flush_mode {
+                FlushMode::Byte => true,
+                FlushMode::Line => value == b'\n',
+                FlushMode::Full => false,
+            };
+            if flush {
+                self.flush_output()?;
+            }
+            Ok(())
+        }
+
+        pub(super) fn get_char(&mut self) -> Result<(), InterpreterError> {
+            if let Some(prompt) = self.prompt.as_ref().filter(|_| self.at_line_start) {
+                self.output
+                    .write_all(prompt.as_bytes())
+                    .map_err(output_error)?;
+            }
+            // whoever types the input should see all output up to this point
+            self.flush_output()?;
+            let buffer = self
+                .input
+                .fill_buf()
+                .map_err(|error| InterpreterError::FailedToReadInput(error.to_string()))?;
+            let input = match buffer.first() {
+                Some(&input) => {
+                    self.input.consume(1);
+                    self.input_size += 1;
+                    if let Some((filename, recording)) = &mut self.input_recording {
+                        recording.write_all(&[input]).map_err(|error| {
+                            InterpreterError::FailedToRecordInput {
+                                filename: filename.clone(),
+                                error_message: error.to_string(),
+                            }
+                        })?;
+                    }
+                    u64::from(input)
+                }
+                None if self.fail_on_eof => return Err(InterpreterError::ReadAfterEndOfInput),
+                None => match self.runtime.eof_value() {
+                    Some(value) => value,
+                    None => return Ok(()),
+                },
+            };
+            self.at_line_start = input == u64::from(b'\n');
+            *self.cell(0)? = input;
+            Ok(())
+        }
+
+        pub(super) fn debug_dump(&mut self) -> Result<(), InterpreterError> {
+            // the dump should appear after everything the program has written so far
+            self.flush_output()?;
+            let cells: Vec<_> = (-DUMP_RADIUS..=DUMP_RADIUS)
+                .map(|offset| {
+                    let value = self.tape.get(self.pointer_address + offset);
+                    if offset == 0 {
+                        format!("[{value}]")
+                    } else {
+                        value.to_string()
+                    }
+                })
+                .collect();
+            eprintln!("# pointer {}: {}", self.pointer_address, cells.join(" "));
+            Ok(())
+        }
+
+        pub(super) fn store(&mut self) -> Result<(), InterpreterError> {
+            self.register = self.read_value()?;
+            Ok(())
+        }
+
+        pub(super) fn load(&mut self) -> Result<(), InterpreterError> {
+            self.set_value(0, self.register)
+        }
+
+        pub(super) fn read_value(&mut self) -> Result<u64, InterpreterError> {
+            Ok(*self.cell(0)?)
+        }
+
+        fn cell(&mut self, offset: i64) -> Result<&mut u64, InterpreterError> {
+            let address = self.pointer_address + offset;
+            let address =
+                self.runtime
+                    .tape_address(address)
+                    .ok_or(InterpreterError::OutsideTape {
+                        address,
+                        tape_size: self.runtime.tape_size.unwrap_or_default(),
+                    })?;
+            let max_memory = self.runtime.max_memory;
+            self.tape
+                .cell(address, max_memory)
+                .ok_or(InterpreterError::TapeLimitExceeded {
+                    limit: max_memory.unwrap_or_default(),
+                    address,
+                })
+        }
+    }
+
+    fn output_error(error: std::io::Error) -> InterpreterError {
+        InterpreterError::FailedToWriteOutput(error.to_string())
+    }
+}
+
+struct LoopIterationGuard {
+    max_iterations: Option<u64>,
+    action: LoopLimitAction,
+    warning_emitted: bool,
+}
+
+impl LoopIterationGuard {
+    fn check(&mut self, iterations: u64) -> Result<(), InterpreterError> {
+        match self.max_iterations {
+            Some(limit) if iterations > limit => match self.action {
+                LoopLimitAction::Abort => {
+                    Err(InterpreterError::LoopIterationLimitExceeded { limit })
+                }
+                LoopLimitAction::Warn => {
+                    if !self.warning_emitted {
+                        eprintln!(
+                            "warning: {}",
+                            InterpreterError::LoopIterationLimitExceeded { limit }
+                        );
+                        self.warning_emitted = true;
+                    }
+                    Ok(())
+                }
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A single step of the flattened program. Loops become conditional jumps whose targets are
+/// resolved in advance, so that the program can be executed by a single loop.
+enum Op {
+    Move(i64),
+    Add {
+        offset: i64,
+        amount: i64,
+    },
+    Set {
+        offset: i64,
+        value: u8,
+    },
+    MultiplyAdd(Box<[(i64, i64)]>),
+    PutChar,
+    GetChar,
+    Store,
+    Load,
+    DebugDump,
+    // jumps to `end` if the current cell is zero, otherwise starts counting the iterations of
+    // the loop with the given index
+    LoopStart {
+        end: usize,
+        index: usize,
+    },
+    // jumps back to `start` if the current cell is not zero
+    LoopEnd {
+        start: usize,
+        index: usize,
+    },
+    JumpIfZero(usize),
+    Jump(usize),
+    // superinstructions, created by `Code::fuse`
+    AddMove {
+        offset: i64,
+        amount: i64,
+        distance: i64,
+    },
+    ClearAdd {
+        clear_offset: i64,
+        offset: i64,
+        amount: i64,
+    },
+    MovePutChar(i64),
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Op::Move(amount) => write!(f, "move {amount}"),
+            Op::Add { offset, amount } => write!(f, "add {amount} at {offset}"),
+            Op::Set { offset, value } => write!(f, "set {value} at {offset}"),
+            Op::MultiplyAdd(targets) => {
+                write!(f, "multiply-add")?;
+                for (index, (offset, factor)) in targets.iter().enumerate() {
+                    let separator = if index == 0 { " " } else { ", " };
+                    write!(f, "{separator}{factor} at {offset}")?;
                 }
-            } else if target_index as usize >= self.memory.len() {
-                let difference = target_index as usize - self.memory.len() + 1;
-                self.memory.resize(self.memory.len() + difference, b'\0');
+                Ok(())
             }
-            debug_assert!(
-                self.current_address_to_index() >= 0
-                    && (self.current_address_to_index() as usize) < self.memory.len()
-            );
+            Op::PutChar => write!(f, "put"),
+            Op::GetChar => write!(f, "get"),
+            Op::Store => write!(f, "store"),
+            Op::Load => write!(f, "load"),
+            Op::DebugDump => write!(f, "dump"),
+            Op::LoopStart { .. } => write!(f, "loop start"),
+            Op::LoopEnd { .. } => write!(f, "loop end"),
+            Op::JumpIfZero(_) => write!(f, "if"),
+            Op::Jump(_) => write!(f, "jump"),
+            Op::AddMove {
+                offset,
+                amount,
+                distance,
+            } => write!(f, "add {amount} at {offset}, move {distance}"),
+            Op::ClearAdd {
+                clear_offset,
+                offset,
+                amount,
+            } => write!(f, "set 0 at {clear_offset}, add {amount} at {offset}"),
+            Op::MovePutChar(distance) => write!(f, "move {distance}, put"),
         }
     }
 }
 
-fn interpret_statement(statement: &Statement, state: &mut State) {
-    match statement {
-        Statement::IncrementPointer => state.increment_pointer(),
-        Statement::DecrementPointer => state.decrement_pointer(),
-        Statement::IncrementValue => state.increment_value(),
-        Statement::DecrementValue => state.decrement_value(),
-        Statement::PutChar => state.put_char(),
-        Statement::GetChar => state.get_char(),
-        Statement::Loop(statements) => {
-            while state.read_value() != 0 {
-                interpret_block(statements, state);
+impl Op {
+    fn fuse(&self, next: &Op) -> Option<Op> {
+        match (self, next) {
+            (Op::Add { offset, amount }, Op::Move(distance)) => Some(Op::AddMove {
+                offset: *offset,
+                amount: *amount,
+                distance: *distance,
+            }),
+            (
+                Op::Set {
+                    offset: clear_offset,
+                    value: 0,
+                },
+                Op::Add { offset, amount },
+            ) => Some(Op::ClearAdd {
+                clear_offset: *clear_offset,
+                offset: *offset,
+                amount: *amount,
+            }),
+            (Op::Move(distance), Op::PutChar) => Some(Op::MovePutChar(*distance)),
+            _ => None,
+        }
+    }
+
+    // the number of ops before fusing, so that fusing does not change when a step limit is hit
+    fn steps(&self) -> u64 {
+        match self {
+            Op::AddMove { .. } | Op::ClearAdd { .. } | Op::MovePutChar(_) => 2,
+            _ => 1,
+        }
+    }
+
+    // calls `visit` with the offset (relative to the pointer before the op) of every cell the
+    // op reads or writes and how, given the value of the current cell
+    fn for_each_access(&self, cell: u64, mut visit: impl FnMut(i64, Access)) {
+        match self {
+            // dumps are not part of what the program does
+            Op::Move(_) | Op::Jump(_) | Op::DebugDump => {}
+            Op::Add { offset, .. } | Op::Set { offset, .. } | Op::AddMove { offset, .. } => {
+                visit(*offset, Access::Write)
+            }
+            Op::MultiplyAdd(targets) => {
+                visit(0, Access::Read);
+                if cell != 0 {
+                    targets
+                        .iter()
+                        .for_each(|&(offset, _)| visit(offset, Access::Write));
+                    visit(0, Access::Write);
+                }
             }
+            Op::PutChar
+            | Op::Store
+            | Op::LoopStart { .. }
+            | Op::LoopEnd { .. }
+            | Op::JumpIfZero(_) => visit(0, Access::Read),
+            Op::GetChar | Op::Load => visit(0, Access::Write),
+            Op::ClearAdd {
+                clear_offset,
+                offset,
+                ..
+            } => {
+                visit(*clear_offset, Access::Write);
+                visit(*offset, Access::Write);
+            }
+            Op::MovePutChar(distance) => visit(*distance, Access::Read),
+        }
+    }
+
+    // the offset of the command in the source, given the span of its statement; the end of a
+    // loop is located at its `]`
+    fn source_offset(&self, span: Span) -> usize {
+        match self {
+            Op::LoopEnd { .. } => span.end - 1,
+            _ => span.start,
+        }
+    }
+
+    // neither performs I/O nor writes to the tape
+    fn is_idle(&self) -> bool {
+        matches!(
+            self,
+            Op::Move(_)
+                | Op::LoopStart { .. }
+                | Op::LoopEnd { .. }
+                | Op::JumpIfZero(_)
+                | Op::Jump(_)
+                | Op::Store
+        )
+    }
+
+    fn jump_target(&mut self) -> Option<&mut usize> {
+        match self {
+            Op::LoopStart { end: target, .. }
+            | Op::LoopEnd { start: target, .. }
+            | Op::JumpIfZero(target)
+            | Op::Jump(target) => Some(target),
+            _ => None,
         }
     }
 }
 
-fn interpret_block(statements: &[Statement], state: &mut State) {
-    for statement in statements {
-        interpret_statement(statement, state);
+struct Code {
+    ops: Vec<Op>,
+    loop_count: usize,
+    // the span of the statement every op was compiled from
+    spans: Vec<Span>,
+}
+
+// jumps out of a loop whose targets are not known until the loop has been compiled
+#[derive(Default)]
+struct PendingJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+// a block of statements that is being compiled
+struct Block<'a> {
+    statements: std::slice::Iter<'a, Statement>,
+    kind: BlockKind,
+}
+
+enum BlockKind {
+    Program,
+    Loop {
+        start: usize,
+        index: usize,
+        pending_jumps: PendingJumps,
+    },
+    If {
+        start: usize,
+    },
+}
+
+impl Code {
+    // identifies the code a checkpoint was written for
+    fn hash(&self) -> u64 {
+        let ops: String = self.ops.iter().map(|op| format!("{op}\n")).collect();
+        fnv1a(ops.as_bytes())
+    }
+
+    fn compile(program: &Program) -> Self {
+        let mut code = Self {
+            ops: Vec::new(),
+            loop_count: 0,
+            spans: Vec::new(),
+        };
+        // an explicit stack of blocks instead of recursion, so that the nesting depth of loops is
+        // not limited by the call stack
+        let mut blocks = vec![Block {
+            statements: program.statements().iter(),
+            kind: BlockKind::Program,
+        }];
+        while let Some(block) = blocks.last_mut() {
+            let Some(statement) = block.statements.next() else {
+                let block = blocks.pop().expect("checked above");
+                code.finish_block(block.kind);
+                continue;
+            };
+            let op = match statement.kind() {
+                StatementKind::Move(amount) => Op::Move(*amount),
+                StatementKind::Add { offset, amount } => Op::Add {
+                    offset: *offset,
+                    amount: *amount,
+                },
+                StatementKind::Set { offset, value } => Op::Set {
+                    offset: *offset,
+                    value: *value,
+                },
+                StatementKind::MultiplyAdd(targets) => Op::MultiplyAdd(targets.as_slice().into()),
+                StatementKind::PutChar => Op::PutChar,
+                StatementKind::GetChar => Op::GetChar,
+                StatementKind::Store => Op::Store,
+                StatementKind::Load => Op::Load,
+                StatementKind::DebugDump => Op::DebugDump,
+                StatementKind::Loop(body) => {
+                    let index = code.loop_count;
+                    code.loop_count += 1;
+                    let start = code.push(Op::LoopStart { end: 0, index }, statement.span());
+                    blocks.push(Block {
+                        statements: body.iter(),
+                        kind: BlockKind::Loop {
+                            start,
+                            index,
+                            pending_jumps: PendingJumps::default(),
+                        },
+                    });
+                    continue;
+                }
+                StatementKind::If(body) => {
+                    let start = code.push(Op::JumpIfZero(0), statement.span());
+                    blocks.push(Block {
+                        statements: body.iter(),
+                        kind: BlockKind::If { start },
+                    });
+                    continue;
+                }
+                StatementKind::Break | StatementKind::Continue => {
+                    let op = code.push(Op::Jump(0), statement.span());
+                    let pending_jumps = blocks
+                        .iter_mut()
+                        .rev()
+                        .find_map(|block| match &mut block.kind {
+                            BlockKind::Loop { pending_jumps, .. } => Some(pending_jumps),
+                            _ => None,
+                        })
+                        .expect("checked by the parser");
+                    if matches!(statement.kind(), StatementKind::Break) {
+                        pending_jumps.breaks.push(op);
+                    } else {
+                        pending_jumps.continues.push(op);
+                    }
+                    continue;
+                }
+            };
+            code.push(op, statement.span());
+        }
+        code
+    }
+
+    fn finish_block(&mut self, kind: BlockKind) {
+        match kind {
+            BlockKind::Program => {}
+            BlockKind::If { start } => self.ops[start] = Op::JumpIfZero(self.ops.len()),
+            BlockKind::Loop {
+                start,
+                index,
+                pending_jumps,
+            } => {
+                let end = self.push(
+                    Op::LoopEnd {
+                        start: start + 1,
+                        index,
+                    },
+                    self.spans[start],
+                );
+                self.ops[start] = Op::LoopStart {
+                    end: end + 1,
+                    index,
+                };
+                for op in pending_jumps.breaks {
+                    self.ops[op] = Op::Jump(end + 1);
+                }
+                // the condition is checked (and the iteration counted) at the end of the loop
+                for op in pending_jumps.continues {
+                    self.ops[op] = Op::Jump(end);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, op: Op, span: Span) -> usize {
+        self.ops.push(op);
+        self.spans.push(span);
+        self.ops.len() - 1
     }
+
+    /// Replaces common pairs of ops by a single op to reduce the number of dispatches. Pairs
+    /// whose second op is the target of a jump are kept.
+    fn fuse(mut self) -> Self {
+        let mut is_jump_target = vec![false; self.ops.len() + 1];
+        for op in &mut self.ops {
+            if let Some(target) = op.jump_target() {
+                is_jump_target[*target] = true;
+            }
+        }
+
+        // maps the index of every original op to the index of the op that replaces it
+        let mut new_indices = vec![0; self.ops.len() + 1];
+        let mut ops = Vec::with_capacity(self.ops.len());
+        let mut spans = Vec::with_capacity(self.ops.len());
+        let mut original_ops = self.ops.into_iter().zip(self.spans).enumerate().peekable();
+        while let Some((index, (op, span))) = original_ops.next() {
+            new_indices[index] = ops.len();
+            let fused = match original_ops.peek() {
+                Some((next_index, (next, _))) if !is_jump_target[*next_index] => op.fuse(next),
+                _ => None,
+            };
+            match fused {
+                Some(fused) => {
+                    let (next_index, (_, next_span)) = original_ops.next().expect("peeked above");
+                    new_indices[next_index] = ops.len();
+                    ops.push(fused);
+                    spans.push(Span::new(span.start, next_span.end));
+                }
+                None => {
+                    ops.push(op);
+                    spans.push(span);
+                }
+            }
+        }
+        new_indices[is_jump_target.len() - 1] = ops.len();
+
+        for op in &mut ops {
+            if let Some(target) = op.jump_target() {
+                *target = new_indices[*target];
+            }
+        }
+        Self {
+            ops,
+            loop_count: self.loop_count,
+            spans,
+        }
+    }
+
+    // Attributes the executions of unfused ops to the loops that contain them.
+    fn profile(&self, executions: &[u64]) -> Profile {
+        // the steps before every op, so that the steps of a loop are summed up without
+        // visiting the ops of the loops nested in it again
+        let steps_before: Vec<u64> = std::iter::once(0)
+            .chain(executions.iter().scan(0, |steps, &count| {
+                *steps += count;
+                Some(*steps)
+            }))
+            .collect();
+        let loops = self
+            .ops
+            .iter()
+            .enumerate()
+            .filter_map(|(start, op)| match op {
+                Op::LoopStart { end, .. } => Some(LoopProfile {
+                    span: self.spans[start],
+                    reached: executions[start],
+                    // every iteration starts with the op after the condition check
+                    iterations: executions[start + 1],
+                    steps: steps_before[*end] - steps_before[start],
+                }),
+                _ => None,
+            })
+            .collect();
+        let mut executed = Vec::with_capacity(self.ops.len());
+        for ((op, span), &count) in self.ops.iter().zip(&self.spans).zip(executions) {
+            let first = Span::new(span.start, span.start + 1);
+            let last = Span::new(span.end - 1, span.end);
+            match op {
+                Op::LoopStart { .. } => executed.push((first, count)),
+                Op::LoopEnd { .. } => executed.push((last, count)),
+                // a conditional is only checked at its start
+                Op::JumpIfZero(_) => executed.extend([(first, count), (last, count)]),
+                _ => executed.push((*span, count)),
+            }
+        }
+        Profile {
+            steps: steps_before[executions.len()],
+            loops,
+            executed,
+            statements: self
+                .ops
+                .iter()
+                .zip(&self.spans)
+                .zip(executions)
+                .map(|((op, span), &count)| match op {
+                    Op::LoopEnd { .. } => (span.end - 1, count),
+                    _ => (span.start, count),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How often a loop was executed during a profiled run.
+pub(crate) struct LoopProfile {
+    pub(crate) span: Span,
+    pub(crate) reached: u64,
+    pub(crate) iterations: u64,
+    /// Includes the steps of nested loops and the checks of the loop condition.
+    pub(crate) steps: u64,
+}
+
+pub(crate) struct Profile {
+    pub(crate) steps: u64,
+    pub(crate) loops: Vec<LoopProfile>,
+    /// How often each op was executed, together with the source it was compiled from. Loops are
+    /// attributed to their brackets.
+    pub(crate) executed: Vec<(Span, u64)>,
+    /// How often each op was executed, together with the offset of the source it was compiled
+    /// from. Unlike in `executed`, every step is counted once.
+    pub(crate) statements: Vec<(usize, u64)>,
+}
+
+/// What an interpreted program did. Addresses are relative to the initial cell.
+#[derive(Debug, Default)]
+pub(crate) struct RuntimeStatistics {
+    pub(crate) steps: u64,
+    touched_cells: HashSet<i64>,
+    /// The lowest and the highest address the pointer reached or a cell was accessed at.
+    pub(crate) lowest_address: i64,
+    pub(crate) highest_address: i64,
+    pub(crate) bytes_read: u64,
+    pub(crate) bytes_written: u64,
+    /// In cells. Sparse tapes grow by whole pages.
+    pub(crate) peak_tape_size: u64,
+}
+
+impl RuntimeStatistics {
+    pub(crate) fn cells_touched(&self) -> usize {
+        self.touched_cells.len()
+    }
+
+    fn observe(&mut self, op: &Op, pointer: i64, cell: u64) {
+        self.steps += op.steps();
+        self.reach(pointer);
+        op.for_each_access(cell, |offset, _| {
+            self.touched_cells.insert(pointer + offset);
+            self.reach(pointer + offset);
+        });
+    }
+
+    fn reach(&mut self, address: i64) {
+        self.lowest_address = self.lowest_address.min(address);
+        self.highest_address = self.highest_address.max(address);
+    }
+
+    fn finish(&mut self, state: &State<impl BufRead, impl Write>) {
+        self.reach(state.pointer());
+        self.bytes_read = state.input_size();
+        self.bytes_written = state.output_size();
+        // the tape never shrinks
+        self.peak_tape_size = state.tape_size();
+    }
+}
+
+// Optional observers of an execution, which cost nothing when they are absent.
+#[derive(Default)]
+struct Instrumentation<'a, 'b> {
+    executions: Option<&'a mut [u64]>,
+    tracer: Option<&'a mut Tracer<'b>>,
+    statistics: Option<&'a mut RuntimeStatistics>,
+    frames: Option<&'a mut FrameExporter>,
+}
+
+const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+// Sleeps briefly whenever a number of consecutive ops has been idle, so that a program that
+// waits in an endless loop does not keep a CPU core busy. Reading at the end of the input counts
+// as idle, since a loop that polls for input gets nothing but the end of input from then on.
+struct IdleDetector {
+    window: u64,
+    idle_ops: u64,
+}
+
+impl IdleDetector {
+    fn observe(&mut self, op: &Op, consumed_input: bool) {
+        let unproductive_read = matches!(op, Op::GetChar) && !consumed_input;
+        if !op.is_idle() && !unproductive_read {
+            self.idle_ops = 0;
+            return;
+        }
+        self.idle_ops += 1;
+        if self.idle_ops >= self.window {
+            std::thread::sleep(IDLE_SLEEP);
+            self.idle_ops = 0;
+        }
+    }
+}
+
+/// Writes a line for every executed op whose statement lies within `range`, with the address of
+/// the current cell and its value before the op is executed.
+pub(crate) struct Tracer<'a> {
+    output: Box<dyn Write>,
+    lines: LineIndex<'a>,
+    range: Option<Span>,
+    // computed on demand, since hot loops execute the same statements over and over
+    locations: HashMap<usize, SourceLocation>,
+}
+
+impl<'a> Tracer<'a> {
+    pub(crate) fn new(output: Box<dyn Write>, source: &'a [u8], range: Option<Span>) -> Self {
+        Self {
+            output,
+            lines: LineIndex::new(source),
+            range,
+            locations: HashMap::new(),
+        }
+    }
+
+    fn trace(
+        &mut self,
+        op: &Op,
+        span: Span,
+        pointer: i64,
+        cell: u64,
+    ) -> Result<(), InterpreterError> {
+        if self
+            .range
+            .is_some_and(|range| span.start < range.start || span.end > range.end)
+        {
+            return Ok(());
+        }
+        let offset = op.source_offset(span);
+        let location = *self
+            .locations
+            .entry(offset)
+            .or_insert_with(|| self.lines.location(offset));
+        writeln!(
+            self.output,
+            "{location}: {op} (pointer {pointer}, cell {cell})"
+        )
+        .map_err(|error| InterpreterError::FailedToWriteTrace(error.to_string()))
+    }
+
+    fn flush(&mut self) -> Result<(), InterpreterError> {
+        self.output
+            .flush()
+            .map_err(|error| InterpreterError::FailedToWriteTrace(error.to_string()))
+    }
+}
+
+// Returns the number of dispatched ops. If `executions` is given, the executions of every op are
+// counted in it.
+fn execute(
+    code: &Code,
+    state: &mut State<impl BufRead, impl Write>,
+    loop_iteration_guard: &mut LoopIterationGuard,
+    idle_window: Option<u64>,
+    max_steps: Option<u64>,
+    instrumentation: &mut Instrumentation,
+    checkpointing: &mut Checkpointing,
+) -> Result<u64, InterpreterError> {
+    let (mut program_counter, mut iterations) = match checkpointing.resume() {
+        Some(checkpoint) => (checkpoint.program_counter, checkpoint.iterations.clone()),
+        None => (0, vec![0; code.loop_count]),
+    };
+    let mut dispatches = 0;
+    let mut steps = 0;
+    let mut idle_detector = idle_window.map(|window| IdleDetector {
+        window,
+        idle_ops: 0,
+    });
+    while let Some(op) = code.ops.get(program_counter) {
+        dispatches += 1;
+        if max_steps.is_some() || checkpointing.is_enabled() {
+            steps += op.steps();
+        }
+        let exceeded_limit = max_steps.filter(|&limit| steps > limit);
+        // the op has not been executed yet, so it is where a resumed execution starts
+        if exceeded_limit.is_some() || checkpointing.is_due(steps) {
+            checkpointing.save(&state.checkpoint(code.hash(), program_counter, &iterations))?;
+        }
+        if let Some(limit) = exceeded_limit {
+            return Err(InterpreterError::StepLimitExceeded { limit });
+        }
+        if let Some(executions) = &mut instrumentation.executions {
+            executions[program_counter] += 1;
+        }
+        if let Some(statistics) = &mut instrumentation.statistics {
+            let pointer = state.pointer();
+            statistics.observe(op, pointer, state.cell_at(pointer));
+        }
+        if let Some(frames) = &mut instrumentation.frames {
+            frames.observe(op.steps(), state.pointer(), |address| {
+                state.cell_at(address)
+            })?;
+        }
+        if let Some(tracer) = &mut instrumentation.tracer {
+            let pointer = state.pointer();
+            tracer.trace(
+                op,
+                code.spans[program_counter],
+                pointer,
+                state.cell_at(pointer),
+            )?;
+        }
+        let input_size = state.input_size();
+        program_counter = execute_op(
+            op,
+            program_counter + 1,
+            state,
+            &mut iterations,
+            loop_iteration_guard,
+        )
+        .map_err(|error| match error {
+            InterpreterError::CellOverflow { address, .. } => InterpreterError::CellOverflow {
+                address,
+                source_offset: op.source_offset(code.spans[program_counter]),
+            },
+            error => error,
+        })?;
+        if let Some(idle_detector) = &mut idle_detector {
+            idle_detector.observe(op, state.input_size() > input_size);
+        }
+    }
+    Ok(dispatches)
+}
+
+// Executes a single op, given the index of the op after it, and returns the index of the next
+// op to execute.
+#[inline(always)]
+fn execute_op(
+    op: &Op,
+    mut program_counter: usize,
+    state: &mut State<impl BufRead, impl Write>,
+    iterations: &mut [u64],
+    loop_iteration_guard: &mut LoopIterationGuard,
+) -> Result<usize, InterpreterError> {
+    match op {
+        Op::Move(amount) => state.move_pointer(*amount),
+        Op::Add { offset, amount } => state.add_value(*offset, *amount)?,
+        Op::Set { offset, value } => state.set_value(*offset, u64::from(*value))?,
+        Op::MultiplyAdd(targets) => state.multiply_add(targets)?,
+        Op::PutChar => state.put_char()?,
+        Op::GetChar => state.get_char()?,
+        Op::Store => state.store()?,
+        Op::Load => state.load()?,
+        Op::DebugDump => state.debug_dump()?,
+        Op::LoopStart { end, index } => {
+            if state.read_value()? == 0 {
+                program_counter = *end;
+            } else {
+                iterations[*index] = 1;
+                loop_iteration_guard.check(1)?;
+            }
+        }
+        Op::LoopEnd { start, index } => {
+            if state.read_value()? != 0 {
+                iterations[*index] += 1;
+                loop_iteration_guard.check(iterations[*index])?;
+                program_counter = *start;
+            }
+        }
+        Op::JumpIfZero(target) => {
+            if state.read_value()? == 0 {
+                program_counter = *target;
+            }
+        }
+        Op::Jump(target) => program_counter = *target,
+        Op::AddMove {
+            offset,
+            amount,
+            distance,
+        } => {
+            state.add_value(*offset, *amount)?;
+            state.move_pointer(*distance);
+        }
+        Op::ClearAdd {
+            clear_offset,
+            offset,
+            amount,
+        } => {
+            state.set_value(*clear_offset, 0)?;
+            state.add_value(*offset, *amount)?;
+        }
+        Op::MovePutChar(distance) => {
+            state.move_pointer(*distance);
+            state.put_char()?;
+        }
+    }
+    Ok(program_counter)
+}
+
+/// Whether a program run by an [`Interpreter`] can continue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    Finished,
+}
+
+/// How a step accesses a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    /// Also used for `+` and `-`, which read the cell as well.
+    Write,
+}
+
+/// Runs a program incrementally, e.g. for debuggers, visualizers or tests. A step executes a
+/// single statement or checks the condition of a loop. Cells have 8 bits, and once the input
+/// has ended, `,` stores 0.
+pub struct Interpreter<I, O> {
+    code: Code,
+    state: State<I, O>,
+    program_counter: usize,
+    iterations: Vec<u64>,
+    loop_iteration_guard: LoopIterationGuard,
+}
+
+impl<I: BufRead, O: Write> Interpreter<I, O> {
+    pub fn new(program: &Program, input: I, output: O) -> Self {
+        let code = Code::compile(program);
+        Self {
+            iterations: vec![0; code.loop_count],
+            code,
+            state: State::new(
+                input,
+                output,
+                None,
+                None,
+                TapeKind::Contiguous,
+                FlushMode::Full,
+                RuntimeConfig::default(),
+            ),
+            program_counter: 0,
+            loop_iteration_guard: LoopIterationGuard {
+                max_iterations: None,
+                action: LoopLimitAction::Abort,
+                warning_emitted: false,
+            },
+        }
+    }
+
+    pub fn step(&mut self) -> Result<Status, InterpreterError> {
+        let Some(op) = self.code.ops.get(self.program_counter) else {
+            return Ok(Status::Finished);
+        };
+        self.program_counter = execute_op(
+            op,
+            self.program_counter + 1,
+            &mut self.state,
+            &mut self.iterations,
+            &mut self.loop_iteration_guard,
+        )?;
+        Ok(self.status())
+    }
+
+    /// Executes at most the given number of steps.
+    pub fn run_for(&mut self, steps: u64) -> Result<Status, InterpreterError> {
+        for _ in 0..steps {
+            if self.step()? == Status::Finished {
+                break;
+            }
+        }
+        Ok(self.status())
+    }
+
+    pub fn status(&self) -> Status {
+        if self.program_counter < self.code.ops.len() {
+            Status::Running
+        } else {
+            Status::Finished
+        }
+    }
+
+    /// The address of the current cell, relative to the initial cell.
+    pub fn pointer(&self) -> i64 {
+        self.state.pointer()
+    }
+
+    pub fn cell(&self, address: i64) -> u8 {
+        self.state.cell_at(address) as u8
+    }
+
+    pub fn set_cell(&mut self, address: i64, value: u8) {
+        self.state.set_cell_at(address, u64::from(value));
+    }
+
+    /// The offset in the source of the command that is executed next, the `]` for the
+    /// condition check at the end of a loop.
+    pub fn location(&self) -> Option<usize> {
+        let span = *self.code.spans.get(self.program_counter)?;
+        Some(self.code.ops[self.program_counter].source_offset(span))
+    }
+
+    /// The addresses of the cells the next step reads or writes, in the order it accesses them.
+    pub fn next_accesses(&self) -> Vec<(i64, Access)> {
+        let mut accesses = Vec::new();
+        if let Some(op) = self.code.ops.get(self.program_counter) {
+            let pointer = self.pointer();
+            op.for_each_access(self.state.cell_at(pointer), |offset, access| {
+                accesses.push((pointer + offset, access))
+            });
+        }
+        accesses
+    }
+
+    /// The cells that have been accessed so far together with their addresses, in ascending
+    /// order of the addresses. All other cells are zero.
+    pub fn tape(&self) -> Vec<(i64, u8)> {
+        self.state
+            .cells()
+            .into_iter()
+            .map(|(address, value)| (address, value as u8))
+            .collect()
+    }
+
+    pub fn output(&self) -> &O {
+        self.state.output()
+    }
+
+    pub fn into_output(mut self) -> Result<O, InterpreterError> {
+        self.state.flush_output()?;
+        Ok(self.state.into_output())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct InterpreterOptions {
+    pub(crate) max_loop_iterations: Option<u64>,
+    pub(crate) loop_limit_action: LoopLimitAction,
+    pub(crate) input: Option<PathBuf>,
+    pub(crate) prompt: Option<String>,
+    pub(crate) no_echo: bool,
+    pub(crate) max_output: Option<u64>,
+    pub(crate) idle_window: Option<u64>,
+    pub(crate) max_steps: Option<u64>,
+    /// Addresses and the values their cells have to hold once the program has ended.
+    pub(crate) assert_tape: Vec<(i64, u64)>,
+    /// A file to write the tape to once the program has ended.
+    pub(crate) dump_tape: Option<PathBuf>,
+    pub(crate) fail_on_eof: bool,
+    pub(crate) require_input_consumed: bool,
+    /// A file to write the bytes read by `,` to.
+    pub(crate) record_input: Option<PathBuf>,
+    /// Where to write checkpoints and the number of steps between them. A checkpoint is also
+    /// written once the program exceeds `max_steps`.
+    pub(crate) checkpoint_out: Option<(PathBuf, u64)>,
+    /// A checkpoint to continue from instead of starting the program from the beginning.
+    pub(crate) resume: Option<PathBuf>,
+    pub(crate) tape: TapeKind,
+    pub(crate) flush_mode: FlushMode,
+    pub(crate) runtime: RuntimeConfig,
+    /// Bytes stored in the cells starting at address 0 before the program starts, unless it is
+    /// resumed from a checkpoint.
+    pub(crate) initial_tape: Vec<u8>,
+}
+
+pub(crate) fn interpret(
+    program: &Program,
+    options: &InterpreterOptions,
+) -> Result<(), InterpreterError> {
+    run(
+        &Code::compile(program).fuse(),
+        options,
+        &mut Instrumentation::default(),
+    )
+}
+
+/// Interprets a program like [`interpret`] and counts how often each loop is executed. The
+/// profile is also returned if the program fails, e.g. because it exceeded a limit.
+pub(crate) fn interpret_profiled(
+    program: &Program,
+    options: &InterpreterOptions,
+) -> (Profile, Result<(), InterpreterError>) {
+    // fusing would attribute the executions of two statements to one op
+    let code = Code::compile(program);
+    let mut executions = vec![0; code.ops.len()];
+    let result = run(
+        &code,
+        options,
+        &mut Instrumentation {
+            executions: Some(&mut executions),
+            ..Instrumentation::default()
+        },
+    );
+    (code.profile(&executions), result)
+}
+
+/// Interprets a program like [`interpret`] and traces every executed statement.
+pub(crate) fn interpret_traced(
+    program: &Program,
+    options: &InterpreterOptions,
+    tracer: &mut Tracer,
+) -> Result<(), InterpreterError> {
+    // fused ops would combine two statements in one line
+    let result = run(
+        &Code::compile(program),
+        options,
+        &mut Instrumentation {
+            tracer: Some(&mut *tracer),
+            ..Instrumentation::default()
+        },
+    );
+    tracer.flush()?;
+    result
+}
+
+/// Interprets a program like [`interpret`] and collects [`RuntimeStatistics`]. They are also
+/// returned if the program fails, e.g. because it exceeded a limit.
+pub(crate) fn interpret_with_statistics(
+    program: &Program,
+    options: &InterpreterOptions,
+) -> (RuntimeStatistics, Result<(), InterpreterError>) {
+    let mut statistics = RuntimeStatistics::default();
+    let result = run(
+        &Code::compile(program).fuse(),
+        options,
+        &mut Instrumentation {
+            statistics: Some(&mut statistics),
+            ..Instrumentation::default()
+        },
+    );
+    (statistics, result)
 }
 
-pub(crate) fn interpret(program: &Program) {
-    let mut interpreter_state = State::default();
-    interpret_block(program.statements(), &mut interpreter_state);
+/// Interprets a program like [`interpret`] and renders the tape to a frame every few steps.
+pub(crate) fn interpret_with_frames(
+    program: &Program,
+    options: &InterpreterOptions,
+    frames: &mut FrameExporter,
+) -> Result<(), InterpreterError> {
+    run(
+        &Code::compile(program).fuse(),
+        options,
+        &mut Instrumentation {
+            frames: Some(frames),
+            ..Instrumentation::default()
+        },
+    )
+}
+
+fn run(
+    code: &Code,
+    options: &InterpreterOptions,
+    instrumentation: &mut Instrumentation,
+) -> Result<(), InterpreterError> {
+    let input: Box<dyn BufRead> = match &options.input {
+        Some(filename) => Box::new(BufReader::new(File::open(filename).map_err(|error| {
+            InterpreterError::FailedToOpenInputFile {
+                filename: filename.clone(),
+                error_message: error.to_string(),
+            }
+        })?)),
+        None => Box::new(stdin().lock()),
+    };
+    let input_recording = match &options.record_input {
+        Some(filename) => Some((
+            filename.clone(),
+            File::create(filename).map_err(|error| InterpreterError::FailedToRecordInput {
+                filename: filename.clone(),
+                error_message: error.to_string(),
+            })?,
+        )),
+        None => None,
+    };
+    // piped input is not typed by a user, so there is nobody to prompt
+    let prompt = options
+        .prompt
+        .clone()
+        .filter(|_| options.input.is_none() && stdin().is_terminal());
+    // the echo is turned back on once the program has ended
+    let _echo_suppression = (options.no_echo && options.input.is_none())
+        .then(EchoSuppression::start)
+        .flatten();
+    let mut interpreter_state = State::new(
+        input,
+        BufWriter::new(stdout().lock()),
+        prompt,
+        options.max_output,
+        options.tape,
+        options.flush_mode,
+        options.runtime,
+    )
+    .with_fail_on_eof(options.fail_on_eof)
+    .with_input_recording(input_recording);
+    let resume = match &options.resume {
+        Some(filename) => {
+            let checkpoint = Checkpoint::read(filename).map_err(|error| {
+                InterpreterError::FailedToReadCheckpoint {
+                    filename: filename.clone(),
+                    error_message: error.to_string(),
+                }
+            })?;
+            if checkpoint.code_hash != code.hash()
+                || checkpoint.cell_size != options.runtime.cell_size
+            {
+                return Err(InterpreterError::CheckpointMismatch {
+                    filename: filename.clone(),
+                });
+            }
+            // an input file is read from its start again, unlike stdin
+            interpreter_state.restore(&checkpoint, options.input.is_some())?;
+            Some(checkpoint)
+        }
+        None => {
+            interpreter_state.load_tape(&options.initial_tape);
+            None
+        }
+    };
+    let mut loop_iteration_guard = LoopIterationGuard {
+        max_iterations: options.max_loop_iterations,
+        action: options.loop_limit_action,
+        warning_emitted: false,
+    };
+    let result = execute(
+        code,
+        &mut interpreter_state,
+        &mut loop_iteration_guard,
+        options.idle_window,
+        options.max_steps,
+        instrumentation,
+        &mut Checkpointing::new(resume, options.checkpoint_out.clone()),
+    );
+    if let Some(statistics) = &mut instrumentation.statistics {
+        statistics.finish(&interpreter_state);
+    }
+    if let (Some(frames), Ok(_)) = (&mut instrumentation.frames, &result) {
+        frames.finish(interpreter_state.pointer(), |address| {
+            interpreter_state.cell_at(address)
+        })?;
+    }
+    interpreter_state.flush_output()?;
+    result?;
+    if let Some(filename) = &options.dump_tape {
+        interpreter_state
+            .tape_dump()
+            .write(filename)
+            .map_err(|error| InterpreterError::FailedToDumpTape {
+                filename: filename.clone(),
+                error_message: error.to_string(),
+            })?;
+    }
+    if options.require_input_consumed {
+        let remaining = interpreter_state.skip_remaining_input()?;
+        if remaining > 0 {
+            return Err(InterpreterError::InputNotConsumed { remaining });
+        }
+    }
+    for &(address, expected) in &options.assert_tape {
+        let actual = interpreter_state.cell_at(address);
+        if actual != expected {
+            return Err(InterpreterError::TapeAssertionFailed {
+                address,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Runs a program that does not read any input and returns its output instead of writing it
+/// to stdout.
+pub(crate) fn evaluate(
+    program: &Program,
+    options: &InterpreterOptions,
+) -> Result<Vec<u8>, InterpreterError> {
+    if reads_input(program.statements()) {
+        return Err(InterpreterError::ReadsInput);
+    }
+    let (output, result) = interpret_in_memory(program, &[], options);
+    result.map(|_| output)
+}
+
+/// Runs a program with the given input and returns its output, which is also returned if the
+/// program fails, e.g. because it exceeded a limit.
+pub(crate) fn interpret_in_memory(
+    program: &Program,
+    input: &[u8],
+    options: &InterpreterOptions,
+) -> (Vec<u8>, Result<(), InterpreterError>) {
+    let mut interpreter_state = State::new(
+        input,
+        Vec::new(),
+        None,
+        options.max_output,
+        options.tape,
+        FlushMode::Full,
+        options.runtime,
+    );
+    interpreter_state.load_tape(&options.initial_tape);
+    let mut loop_iteration_guard = LoopIterationGuard {
+        max_iterations: options.max_loop_iterations,
+        action: options.loop_limit_action,
+        warning_emitted: false,
+    };
+    let result = execute(
+        &Code::compile(program).fuse(),
+        &mut interpreter_state,
+        &mut loop_iteration_guard,
+        options.idle_window,
+        options.max_steps,
+        &mut Instrumentation::default(),
+        &mut Checkpointing::default(),
+    );
+    (interpreter_state.into_output(), result.map(|_| ()))
+}
+
+/// Runs a program that reads from `input` and writes to `output`, e.g. in-memory buffers,
+/// files or sockets. Every byte is written separately, so unbuffered writers should be wrapped
+/// in a [`BufWriter`]. Once the input has ended, `,` stores 0.
+pub fn interpret_with_io(
+    program: &Program,
+    input: impl BufRead,
+    output: impl Write,
+) -> Result<(), InterpreterError> {
+    let mut interpreter_state = State::new(
+        input,
+        output,
+        None,
+        None,
+        TapeKind::Contiguous,
+        FlushMode::Full,
+        RuntimeConfig::default(),
+    );
+    let mut loop_iteration_guard = LoopIterationGuard {
+        max_iterations: None,
+        action: LoopLimitAction::Abort,
+        warning_emitted: false,
+    };
+    let result = execute(
+        &Code::compile(program).fuse(),
+        &mut interpreter_state,
+        &mut loop_iteration_guard,
+        None,
+        None,
+        &mut Instrumentation::default(),
+        &mut Checkpointing::default(),
+    );
+    interpreter_state.flush_output()?;
+    result?;
+    Ok(())
+}
+
+/// Runs a program with the given input and returns its output instead of writing it to stdout.
+/// Once the input has ended, `,` stores 0.
+pub fn capture_output(program: &Program, input: &[u8]) -> Result<Vec<u8>, InterpreterError> {
+    let mut output = Vec::new();
+    interpret_with_io(program, input, &mut output)?;
+    Ok(output)
+}
+
+pub(crate) struct Measurement {
+    pub(crate) dispatches: u64,
+    pub(crate) duration: Duration,
+}
+
+/// Runs a program with its output discarded and measures how many ops were dispatched, either
+/// with or without superinstructions.
+pub(crate) fn measure(
+    program: &Program,
+    input: Option<&Path>,
+    superinstructions: bool,
+) -> Result<Measurement, InterpreterError> {
+    let input: Box<dyn BufRead> = match input {
+        Some(filename) => Box::new(BufReader::new(File::open(filename).map_err(|error| {
+            InterpreterError::FailedToOpenInputFile {
+                filename: filename.to_path_buf(),
+                error_message: error.to_string(),
+            }
+        })?)),
+        None if reads_input(program.statements()) => return Err(InterpreterError::InputRequired),
+        None => Box::new(empty()),
+    };
+    let mut code = Code::compile(program);
+    if superinstructions {
+        code = code.fuse();
+    }
+    let mut interpreter_state = State::new(
+        input,
+        sink(),
+        None,
+        None,
+        TapeKind::Contiguous,
+        FlushMode::Full,
+        RuntimeConfig::default(),
+    );
+    let mut loop_iteration_guard = LoopIterationGuard {
+        max_iterations: None,
+        action: LoopLimitAction::Abort,
+        warning_emitted: false,
+    };
+    let start = Instant::now();
+    let dispatches = execute(
+        &code,
+        &mut interpreter_state,
+        &mut loop_iteration_guard,
+        None,
+        None,
+        &mut Instrumentation::default(),
+        &mut Checkpointing::default(),
+    )?;
+    Ok(Measurement {
+        dispatches,
+        duration: start.elapsed(),
+    })
+}
+
+fn reads_input(statements: &[Statement]) -> bool {
+    let mut blocks = vec![statements];
+    while let Some(statements) = blocks.pop() {
+        for statement in statements {
+            match statement.kind() {
+                StatementKind::GetChar => return true,
+                StatementKind::Loop(body) | StatementKind::If(body) => blocks.push(body),
+                _ => {}
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+    use crate::semantics::EofBehavior;
+
+    #[test]
+    fn deeply_nested_programs_do_not_overflow_the_stack() {
+        const DEPTH: usize = 50_000;
+        let source = format!("+{}-{}+++.", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let (output, result) = interpret_in_memory(&program, &[], &InterpreterOptions::default());
+        result.unwrap();
+        assert_eq!(output, b"\x03");
+    }
+
+    #[test]
+    fn loops_polling_for_input_after_its_end_yield() {
+        const ITERATIONS: u64 = 100;
+        // reads the input until it ends and keeps polling for more afterwards
+        let program = Parser::new(b"+[,]", Dialect::default()).parse().unwrap();
+        let options = InterpreterOptions {
+            idle_window: Some(2),
+            max_steps: Some(2 * ITERATIONS),
+            runtime: RuntimeConfig {
+                eof_behavior: EofBehavior::Unchanged,
+                ..RuntimeConfig::default()
+            },
+            ..InterpreterOptions::default()
+        };
+        let start = Instant::now();
+        let (_, result) = interpret_in_memory(&program, b"ab", &options);
+        assert!(matches!(
+            result,
+            Err(InterpreterError::StepLimitExceeded { .. })
+        ));
+        assert!(start.elapsed() >= IDLE_SLEEP * (ITERATIONS as u32 - 10));
+    }
 }
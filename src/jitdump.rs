@@ -0,0 +1,373 @@
+use std::ffi::c_void;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the format is described in tools/perf/Documentation/jitdump-specification.txt of the Linux
+// sources
+const MAGIC: u32 = 0x4a695444;
+const VERSION: u32 = 1;
+const HEADER_SIZE: u32 = 40;
+const RECORD_HEADER_SIZE: u32 = 16;
+const JIT_CODE_LOAD: u32 = 0;
+const JIT_CODE_CLOSE: u32 = 3;
+
+const CLOCK_MONOTONIC: i32 = 1;
+const PROT_READ: i32 = 1;
+const PROT_EXEC: i32 = 4;
+const MAP_PRIVATE: i32 = 2;
+
+// the section and symbol types of ELF that are needed to find the functions of an object
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+#[cfg(test)]
+const JIT_REGISTER_FN: u32 = 1;
+
+// the list of the GDB JIT interface, see "JIT Compilation Interface" in the GDB manual. LLVM's JIT
+// compiler defines `__jit_debug_descriptor` and `__jit_debug_register_code` and registers every
+// object it loads in the list, with its sections moved to the addresses they were loaded at. GDB
+// reads the symbols of the JIT-compiled functions from there, and so are the functions of the
+// jitdump file. Defining the interface a second time would clash with LLVM's definitions.
+#[repr(C)]
+struct JitCodeEntry {
+    next_entry: *const JitCodeEntry,
+    previous_entry: *const JitCodeEntry,
+    symbol_file_address: *const u8,
+    symbol_file_size: u64,
+}
+
+#[repr(C)]
+struct JitDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *const JitCodeEntry,
+    first_entry: *const JitCodeEntry,
+}
+
+#[repr(C)]
+struct Timespec {
+    seconds: i64,
+    nanoseconds: i64,
+}
+
+extern "C" {
+    static __jit_debug_descriptor: JitDescriptor;
+    #[cfg(test)]
+    fn __jit_debug_register_code();
+    fn clock_gettime(clock: i32, time: *mut Timespec) -> i32;
+    fn mmap(
+        address: *mut c_void,
+        length: usize,
+        protection: i32,
+        flags: i32,
+        file_descriptor: i32,
+        offset: i64,
+    ) -> *mut c_void;
+}
+
+/// A function of the JIT-compiled code.
+struct Function {
+    name: String,
+    address: u64,
+    size: u64,
+}
+
+/// Whether the JIT compiler has registered the code at an address with the GDB JIT interface,
+/// which lets GDB show the JIT-compiled functions in backtraces.
+pub(crate) fn registered_with_gdb(address: u64) -> io::Result<bool> {
+    Ok(registered_functions()?
+        .1
+        .iter()
+        .any(|function| (function.address..function.address + function.size).contains(&address)))
+}
+
+// The machine of the registered objects and the functions they define.
+fn registered_functions() -> io::Result<(u32, Vec<Function>)> {
+    let mut machine = 0;
+    let mut functions = Vec::new();
+    // SAFETY: the objects stay registered, and thereby in memory, while the JIT compiler exists
+    unsafe {
+        let mut entry = __jit_debug_descriptor.first_entry;
+        while !entry.is_null() {
+            let object = std::slice::from_raw_parts(
+                (*entry).symbol_file_address,
+                (*entry).symbol_file_size as usize,
+            );
+            machine = elf_machine(object)?;
+            functions.extend(elf_functions(object)?);
+            entry = (*entry).next_entry;
+        }
+    }
+    Ok((machine, functions))
+}
+
+/// Writes a jitdump file of the functions that have been compiled so far, which lets
+/// `perf inject --jit` attribute the samples of a `perf record -k mono` session to them. perf
+/// only picks up files named `jit-<pid>.dump`.
+pub(crate) fn write_jitdump(filename: &Path) -> io::Result<()> {
+    let (machine, functions) = registered_functions()?;
+
+    // mapping the file requires it to be readable
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(filename)?;
+    let mut header = Vec::new();
+    header.extend(MAGIC.to_ne_bytes());
+    header.extend(VERSION.to_ne_bytes());
+    header.extend(HEADER_SIZE.to_ne_bytes());
+    header.extend(machine.to_ne_bytes());
+    header.extend(0u32.to_ne_bytes());
+    header.extend(std::process::id().to_ne_bytes());
+    header.extend(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_micros() as u64)
+            .to_ne_bytes(),
+    );
+    header.extend(0u64.to_ne_bytes());
+    file.write_all(&header)?;
+
+    // perf finds the file through this mapping, which it records like that of a library
+    // SAFETY: the mapping is never written to, and it is not unmapped, so that it outlives the
+    // recording
+    let mapping = unsafe {
+        mmap(
+            std::ptr::null_mut(),
+            HEADER_SIZE as usize,
+            PROT_READ | PROT_EXEC,
+            MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if mapping as isize == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    for (index, function) in functions.iter().enumerate() {
+        // SAFETY: the code of the function is mapped for as long as the JIT compiler exists
+        let code = unsafe {
+            std::slice::from_raw_parts(function.address as *const u8, function.size as usize)
+        };
+        let mut record = Vec::new();
+        record.extend(std::process::id().to_ne_bytes());
+        // the code is compiled on the main thread, whose id is the process id
+        record.extend(std::process::id().to_ne_bytes());
+        record.extend(function.address.to_ne_bytes());
+        record.extend(function.address.to_ne_bytes());
+        record.extend(function.size.to_ne_bytes());
+        record.extend((index as u64).to_ne_bytes());
+        record.extend(function.name.as_bytes());
+        record.push(0);
+        record.extend(code);
+        write_record(&mut file, JIT_CODE_LOAD, &record)?;
+    }
+    write_record(&mut file, JIT_CODE_CLOSE, &[])
+}
+
+fn write_record(file: &mut File, id: u32, body: &[u8]) -> io::Result<()> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_SIZE as usize + body.len());
+    record.extend(id.to_ne_bytes());
+    record.extend((RECORD_HEADER_SIZE + body.len() as u32).to_ne_bytes());
+    record.extend(timestamp().to_ne_bytes());
+    record.extend(body);
+    file.write_all(&record)
+}
+
+// the time in nanoseconds of the clock `perf record -k mono` uses
+fn timestamp() -> u64 {
+    let mut time = Timespec {
+        seconds: 0,
+        nanoseconds: 0,
+    };
+    // SAFETY: the time is written to a valid timespec
+    unsafe {
+        clock_gettime(CLOCK_MONOTONIC, &mut time);
+    }
+    time.seconds as u64 * 1_000_000_000 + time.nanoseconds as u64
+}
+
+fn invalid_object() -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        "the JIT compiler registered an object that is not a 64-bit ELF file",
+    )
+}
+
+fn read<const N: usize>(object: &[u8], offset: u64) -> io::Result<[u8; N]> {
+    object
+        .get(offset as usize..offset as usize + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(invalid_object)
+}
+
+fn read_u16(object: &[u8], offset: u64) -> io::Result<u16> {
+    read(object, offset).map(u16::from_ne_bytes)
+}
+
+fn read_u32(object: &[u8], offset: u64) -> io::Result<u32> {
+    read(object, offset).map(u32::from_ne_bytes)
+}
+
+fn read_u64(object: &[u8], offset: u64) -> io::Result<u64> {
+    read(object, offset).map(u64::from_ne_bytes)
+}
+
+fn elf_machine(object: &[u8]) -> io::Result<u32> {
+    // only 64-bit objects for the byte order of this machine are supported
+    if !object.starts_with(b"\x7fELF\x02") {
+        return Err(invalid_object());
+    }
+    read_u16(object, 18).map(u32::from)
+}
+
+// The functions defined in an object. Its sections have been given the addresses they were
+// loaded at.
+fn elf_functions(object: &[u8]) -> io::Result<Vec<Function>> {
+    let section_headers = read_u64(object, 40)?;
+    let section_header_size = u64::from(read_u16(object, 58)?);
+    let section_count = u64::from(read_u16(object, 60)?);
+    let section = |index: u64| section_headers + index * section_header_size;
+
+    let mut functions = Vec::new();
+    for index in 0..section_count {
+        if read_u32(object, section(index) + 4)? != SHT_SYMTAB {
+            continue;
+        }
+        let symbols = read_u64(object, section(index) + 24)?;
+        let symbols_size = read_u64(object, section(index) + 32)?;
+        let symbol_size = read_u64(object, section(index) + 56)?;
+        let names = read_u64(
+            object,
+            section(u64::from(read_u32(object, section(index) + 40)?)) + 24,
+        )?;
+        for symbol in (symbols..symbols + symbols_size).step_by(symbol_size.max(1) as usize) {
+            let info = read::<1>(object, symbol + 4)?[0];
+            let section_index = u64::from(read_u16(object, symbol + 6)?);
+            let size = read_u64(object, symbol + 16)?;
+            if info & 0xf != STT_FUNC || section_index == 0 || size == 0 {
+                continue;
+            }
+            let name_start = names + u64::from(read_u32(object, symbol)?);
+            let name = object
+                .get(name_start as usize..)
+                .and_then(|name| name.split(|&byte| byte == 0).next())
+                .ok_or_else(invalid_object)?;
+            functions.push(Function {
+                name: String::from_utf8_lossy(name).into_owned(),
+                address: read_u64(object, section(section_index) + 16)?
+                    + read_u64(object, symbol + 8)?,
+                size,
+            });
+        }
+    }
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inkwell::context::Context;
+    use inkwell::targets::{InitializationConfig, Target};
+    use inkwell::OptimizationLevel;
+
+    fn u32_at(dump: &[u8], offset: usize) -> u32 {
+        u32::from_ne_bytes(dump[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(dump: &[u8], offset: usize) -> u64 {
+        u64::from_ne_bytes(dump[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn compiled_functions_are_registered_with_gdb() {
+        Target::initialize_native(&InitializationConfig::default()).unwrap();
+        let context = Context::create();
+        let module = context.create_module("gdb");
+        let builder = context.create_builder();
+        let int = context.i32_type();
+        let function = module.add_function("loop_7", int.fn_type(&[], false), None);
+        builder.position_at_end(context.append_basic_block(function, "entry"));
+        builder
+            .build_return(Some(&int.const_int(7, false)))
+            .unwrap();
+        let execution_engine = module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .unwrap();
+        let address = execution_engine.get_function_address("loop_7").unwrap() as u64;
+
+        assert!(registered_with_gdb(address).unwrap());
+        // SAFETY: the descriptor is only read
+        let descriptor = unsafe { &*std::ptr::addr_of!(__jit_debug_descriptor) };
+        assert_eq!(descriptor.version, 1);
+        assert_eq!(descriptor.action_flag, JIT_REGISTER_FN);
+        assert!(!descriptor.relevant_entry.is_null());
+        // GDB sets a breakpoint in the function to be notified of new objects, which does nothing
+        // SAFETY: the function has no effect
+        unsafe { __jit_debug_register_code() };
+        assert!(!registered_with_gdb(0).unwrap());
+    }
+
+    #[test]
+    fn jitdump_lists_the_compiled_functions() {
+        Target::initialize_native(&InitializationConfig::default()).unwrap();
+        let context = Context::create();
+        let module = context.create_module("jitdump");
+        let builder = context.create_builder();
+        let int = context.i32_type();
+        let function = module.add_function("loop_98", int.fn_type(&[], false), None);
+        builder.position_at_end(context.append_basic_block(function, "entry"));
+        builder
+            .build_return(Some(&int.const_int(42, false)))
+            .unwrap();
+        let execution_engine = module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .unwrap();
+        // looking up a function compiles the module
+        let address = execution_engine.get_function_address("loop_98").unwrap() as u64;
+
+        let filename = std::env::temp_dir().join(format!("jit-{}.dump", std::process::id()));
+        write_jitdump(&filename).unwrap();
+        let dump = std::fs::read(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+
+        assert_eq!(u32_at(&dump, 0), MAGIC);
+        assert_eq!(u32_at(&dump, 4), VERSION);
+        assert_eq!(u32_at(&dump, 8), HEADER_SIZE);
+        assert_eq!(u32_at(&dump, 20), std::process::id());
+        let mut loaded = Vec::new();
+        let mut offset = HEADER_SIZE as usize;
+        let mut closed = false;
+        while offset < dump.len() {
+            assert!(!closed, "records follow the close record");
+            let id = u32_at(&dump, offset);
+            let size = u32_at(&dump, offset + 4) as usize;
+            let body = &dump[offset + RECORD_HEADER_SIZE as usize..offset + size];
+            match id {
+                JIT_CODE_LOAD => {
+                    let code_address = u64_at(body, 16);
+                    let code_size = u64_at(body, 24) as usize;
+                    let name_length = body[40..].iter().position(|&byte| byte == 0).unwrap();
+                    let name = String::from_utf8(body[40..40 + name_length].to_vec()).unwrap();
+                    let code = &body[40 + name_length + 1..];
+                    assert_eq!(code.len(), code_size, "{name}");
+                    loaded.push((name, code_address));
+                }
+                JIT_CODE_CLOSE => closed = true,
+                _ => panic!("unexpected record {id}"),
+            }
+            offset += size;
+        }
+        assert!(closed);
+        assert!(
+            loaded.contains(&("loop_98".to_string(), address)),
+            "{loaded:?}"
+        );
+    }
+}
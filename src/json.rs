@@ -0,0 +1,331 @@
+use std::fmt::{Display, Formatter, Write};
+
+use thiserror::Error;
+
+/// A JSON value, e.g. a message of the Debug Adapter Protocol. Objects keep the order of their
+/// members.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Error, Debug)]
+pub(crate) struct JsonError {
+    offset: usize,
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid JSON at byte {}", self.offset)
+    }
+}
+
+impl Json {
+    pub(crate) fn parse(text: &str) -> Result<Self, JsonError> {
+        let mut parser = JsonParser {
+            text: text.as_bytes(),
+            offset: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.offset < parser.text.len() {
+            return Err(parser.error());
+        }
+        Ok(value)
+    }
+
+    /// Builds an object from its members.
+    pub(crate) fn object<const N: usize>(members: [(&str, Json); N]) -> Self {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// The member of an object with the given key.
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(number) if number.fract() == 0.0 => Some(*number as i64),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(string: &str) -> Self {
+        Json::String(string.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Self {
+        Json::String(string)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(bool: bool) -> Self {
+        Json::Bool(bool)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(number: i64) -> Self {
+        Json::Number(number as f64)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(bool) => write!(f, "{bool}"),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{}", *number as i64)
+            }
+            Json::Number(number) => write!(f, "{number}"),
+            Json::String(string) => write!(f, "\"{}\"", escape(string)),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "\"{}\":{value}", escape(key))?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            char if char.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", char as u32);
+            }
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    offset: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self) -> JsonError {
+        JsonError {
+            offset: self.offset,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .get(self.offset)
+            .is_some_and(|byte| byte.is_ascii_whitespace())
+        {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: &[u8]) -> Result<(), JsonError> {
+        if !self.text[self.offset..].starts_with(expected) {
+            return Err(self.error());
+        }
+        self.offset += expected.len();
+        Ok(())
+    }
+
+    // skips whitespace and consumes the byte if it is the next one
+    fn accept(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.text.get(self.offset) == Some(&byte) {
+            self.offset += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.text.get(self.offset) {
+            Some(b'n') => self.expect(b"null").map(|_| Json::Null),
+            Some(b't') => self.expect(b"true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect(b"false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.offset += 1;
+                let mut values = Vec::new();
+                if self.accept(b']') {
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    if self.accept(b']') {
+                        return Ok(Json::Array(values));
+                    }
+                    if !self.accept(b',') {
+                        return Err(self.error());
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.offset += 1;
+                let mut members = Vec::new();
+                if self.accept(b'}') {
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    if !self.accept(b':') {
+                        return Err(self.error());
+                    }
+                    members.push((key, self.value()?));
+                    if self.accept(b'}') {
+                        return Ok(Json::Object(members));
+                    }
+                    if !self.accept(b',') {
+                        return Err(self.error());
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.offset;
+        while self
+            .text
+            .get(self.offset)
+            .is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.offset += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.offset])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or(JsonError { offset: start })
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b"\"")?;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&byte) = self.text.get(self.offset) else {
+                return Err(self.error());
+            };
+            self.offset += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escaped) = self.text.get(self.offset) else {
+                        return Err(self.error());
+                    };
+                    self.offset += 1;
+                    match escaped {
+                        b'"' | b'\\' | b'/' => bytes.push(escaped),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let char = self.unicode_escape()?;
+                            bytes.extend_from_slice(char.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => return Err(self.error()),
+                    }
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        // the input is a `str`, so everything between escapes is valid UTF-8
+        Ok(String::from_utf8(bytes).expect("the text is valid UTF-8"))
+    }
+
+    // the character of a `\u` escape whose `\u` has been consumed, combining surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex_digits()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high).ok_or(self.error());
+        }
+        self.expect(b"\\u")?;
+        let low = self.hex_digits()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err(self.error());
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)).ok_or(self.error())
+    }
+
+    fn hex_digits(&mut self) -> Result<u32, JsonError> {
+        let digits = self
+            .text
+            .get(self.offset..self.offset + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or(self.error())?;
+        self.offset += 4;
+        Ok(digits)
+    }
+}
@@ -0,0 +1,57 @@
+#![feature(path_file_prefix)]
+
+mod analysis;
+mod bench;
+mod bundle;
+mod bytecode;
+mod c_backend;
+mod check;
+mod checkpoint;
+mod clean;
+mod conformance;
+mod corpus;
+mod coverage;
+#[cfg(feature = "cranelift")]
+mod cranelift_backend;
+mod crash;
+mod dap;
+mod debugger;
+mod detect;
+mod direct_backend;
+mod diagnostics;
+mod driver;
+#[cfg(feature = "llvm")]
+mod emitter;
+mod engine;
+mod environment;
+mod format;
+mod frames;
+mod hash;
+mod interpreter;
+#[cfg(feature = "llvm")]
+mod jitdump;
+mod json;
+mod linker;
+mod loops;
+mod pass_plugin;
+mod profile;
+pub mod registry;
+pub mod semantics;
+mod slice;
+mod stats;
+mod suggestions;
+mod superopt;
+mod tape_dump;
+mod terminal;
+mod trace;
+mod usage_log;
+mod verify;
+
+mod command_line_arguments;
+
+pub use brainrust_core::{generator, optimizer, parser, plugin, program};
+pub use driver::run;
+#[cfg(feature = "llvm")]
+pub use emitter::compile_to_object;
+pub use environment::EnvironmentReport;
+pub use interpreter::{capture_output, interpret_with_io, Interpreter, InterpreterError, Status};
@@ -0,0 +1,486 @@
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use anyhow::{bail, Context, Result};
+use thiserror::Error;
+
+use crate::diagnostics::{report_tool_output, Severity};
+use crate::registry::{BackendOptions, Platform};
+
+// where the WASI SDK installs wasi-libc, unless WASI_SYSROOT says otherwise
+const DEFAULT_WASI_SYSROOT: &str = "/opt/wasi-sdk/share/wasi-sysroot";
+
+#[derive(Error, Debug)]
+pub(crate) enum LinkerError {
+    NotFound {
+        candidates: &'static [&'static str],
+    },
+    RequestedNotFound {
+        linker: PathBuf,
+    },
+    NeedsLibc {
+        linker: PathBuf,
+    },
+    // only the LLVM backend writes object files, which are archived into static libraries
+    #[cfg(feature = "llvm")]
+    ArchiverNotFound {
+        candidates: &'static [&'static str],
+    },
+}
+
+impl Display for LinkerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkerError::NotFound { candidates } => {
+                write!(
+                    f,
+                    "no linker found, looked for {} in the PATH and the LLVM installation; install one of them or pass --linker",
+                    alternatives(candidates)
+                )
+            }
+            LinkerError::RequestedNotFound { linker } => {
+                write!(f, "linker '{}' not found", linker.display())
+            }
+            LinkerError::NeedsLibc { linker } => {
+                write!(
+                    f,
+                    "'{}' cannot link against the C standard library, pass a C compiler like cc to --linker or use --no-libc",
+                    linker.display()
+                )
+            }
+            #[cfg(feature = "llvm")]
+            LinkerError::ArchiverNotFound { candidates } => {
+                write!(
+                    f,
+                    "no archiver found, looked for {} in the PATH and the LLVM installation; install one of them",
+                    alternatives(candidates)
+                )
+            }
+        }
+    }
+}
+
+// e.g. "cc, gcc or clang"
+fn alternatives(names: &[&str]) -> String {
+    match names.split_last() {
+        Some((last, others)) if !others.is_empty() => format!("{} or {last}", others.join(", ")),
+        _ => names.join(""),
+    }
+}
+
+/// How a linker is invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flavor {
+    /// A C compiler (cc, gcc or clang), which adds the C runtime and the C standard library.
+    Compiler,
+    /// A linker for ELF files like ld.lld, which only links programs without the C standard
+    /// library.
+    Elf,
+    /// wasm-ld, lld's linker for WebAssembly.
+    Wasm,
+}
+
+/// The program that links executables, given by `--linker` or found in the `PATH`.
+struct Linker {
+    path: PathBuf,
+    flavor: Flavor,
+}
+
+impl Linker {
+    fn find(options: &BackendOptions) -> Result<Self, LinkerError> {
+        let flavor = |path: &Path| match options.platform {
+            Platform::Host => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let name = name.trim_end_matches(env::consts::EXE_SUFFIX);
+                if name == "ld" || name.starts_with("ld.") {
+                    Flavor::Elf
+                } else {
+                    Flavor::Compiler
+                }
+            }
+            Platform::Wasm32Wasi | Platform::Wasm32Browser => Flavor::Wasm,
+            Platform::ThumbV7em | Platform::Riscv32 => {
+                unreachable!("code for microcontrollers is only compiled, see --bare-metal")
+            }
+        };
+        let linker = match &options.linker {
+            Some(path) => Linker {
+                path: path.clone(),
+                flavor: flavor(path),
+            },
+            None => {
+                let candidates = candidates(options);
+                let path =
+                    find_executable(candidates).ok_or(LinkerError::NotFound { candidates })?;
+                Linker {
+                    flavor: flavor(&path),
+                    path,
+                }
+            }
+        };
+        if linker.flavor == Flavor::Elf && !options.no_libc {
+            return Err(LinkerError::NeedsLibc {
+                linker: linker.path,
+            });
+        }
+        Ok(linker)
+    }
+
+    fn command(
+        &self,
+        input_file: &Path,
+        options: &BackendOptions,
+        shared_library: bool,
+    ) -> Command {
+        let mut command = Command::new(&self.path);
+        match (self.flavor, options.platform) {
+            (Flavor::Compiler, _) => {
+                command
+                    .arg("-o")
+                    .arg(&options.output_filename)
+                    .arg(input_file)
+                    .args(&options.link_arguments);
+                if shared_library {
+                    command.arg("-shared");
+                } else if options.harden {
+                    command.arg(if options.static_executable {
+                        "-static-pie"
+                    } else {
+                        "-pie"
+                    });
+                } else if options.static_executable {
+                    command.arg("-static");
+                }
+                if options.harden {
+                    command.arg("-Wl,-z,relro,-z,now");
+                }
+                // the module brings its own entry point and library functions
+                if options.no_libc {
+                    command.args(["-nostdlib", "-static"]);
+                }
+            }
+            (Flavor::Elf, _) => {
+                command
+                    .args(["-static", "-o"])
+                    .arg(&options.output_filename)
+                    .arg(input_file)
+                    .args(&options.link_arguments);
+                if options.harden {
+                    command.args(["-z", "relro", "-z", "now"]);
+                }
+            }
+            (Flavor::Wasm, Platform::Wasm32Wasi) => {
+                // wasi-libc implements the C standard library on top of WASI's fd_read,
+                // fd_write etc.
+                let sysroot = env::var_os("WASI_SYSROOT")
+                    .map_or_else(|| PathBuf::from(DEFAULT_WASI_SYSROOT), PathBuf::from);
+                let libraries = sysroot.join("lib").join("wasm32-wasi");
+                command
+                    .arg("-o")
+                    .arg(&options.output_filename)
+                    .arg(libraries.join("crt1-command.o"))
+                    .arg(input_file)
+                    // libraries of the user may need libc, so they have to come first
+                    .args(&options.link_arguments)
+                    .arg("-L")
+                    .arg(&libraries)
+                    .arg("-lc");
+            }
+            (Flavor::Wasm, _) => {
+                // the functions of the C standard library are imported from the JavaScript glue
+                // code
+                command
+                    .args([
+                        "--no-entry",
+                        "--export=main",
+                        "--export=__heap_base",
+                        "--allow-undefined",
+                        "-o",
+                    ])
+                    .arg(&options.output_filename)
+                    .arg(input_file)
+                    .args(&options.link_arguments);
+            }
+        }
+        command
+    }
+}
+
+// the archivers that are looked for, in order of preference
+#[cfg(feature = "llvm")]
+const ARCHIVERS: &[&str] = &["ar", "llvm-ar"];
+
+// the linkers for the host that are looked for, in order of preference
+const HOST_LINKERS: &[&str] = &["cc", "gcc", "clang"];
+
+// the linkers that are looked for, in order of preference
+fn candidates(options: &BackendOptions) -> &'static [&'static str] {
+    match options.platform {
+        Platform::Host if options.no_libc => &["cc", "gcc", "clang", "ld.lld"],
+        // musl is made for static linking, unlike glibc
+        Platform::Host if options.static_executable => &["musl-gcc", "cc", "gcc", "clang"],
+        Platform::Host => HOST_LINKERS,
+        Platform::Wasm32Wasi | Platform::Wasm32Browser => &["wasm-ld"],
+        Platform::ThumbV7em | Platform::Riscv32 => {
+            unreachable!("code for microcontrollers is only compiled, see --bare-metal")
+        }
+    }
+}
+
+// looks in the PATH first and then next to the tools of LLVM, which come with lld
+fn find_executable(names: &[&str]) -> Option<PathBuf> {
+    let find_in = |directories: &[PathBuf]| {
+        names.iter().find_map(|name| {
+            directories
+                .iter()
+                .map(|directory| directory.join(format!("{name}{}", env::consts::EXE_SUFFIX)))
+                .find(|path| path.is_file())
+        })
+    };
+    let path = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect::<Vec<_>>())
+        .unwrap_or_default();
+    find_in(&path).or_else(|| find_in(&[llvm_bin_directory()?]))
+}
+
+/// Whether executables for the host can be linked without `--linker`.
+pub(crate) fn host_linker_available() -> bool {
+    find_executable(HOST_LINKERS).is_some()
+}
+
+fn llvm_bin_directory() -> Option<PathBuf> {
+    let output = Command::new("llvm-config").arg("--bindir").output().ok()?;
+    let directory = String::from_utf8(output.stdout).ok()?;
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(directory.trim()))
+}
+
+pub(crate) fn link(
+    input_file: &Path,
+    options: &BackendOptions,
+    shared_library: bool,
+) -> Result<()> {
+    let context = format!(
+        "while linking {} into {}",
+        input_file.display(),
+        options.output_filename.display()
+    );
+    let linker = Linker::find(options)?;
+    let mut command = linker.command(input_file, options, shared_library);
+    if options.verbose {
+        eprintln!("{}", command_line(&command));
+    }
+    let output = match command.output() {
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            return Err(LinkerError::RequestedNotFound {
+                linker: linker.path,
+            }
+            .into())
+        }
+        output => output.with_context(|| context.clone())?,
+    };
+    check_tool_output(&output, "linker", &context)
+}
+
+/// Puts the object file into a static library, replacing an existing one.
+#[cfg(feature = "llvm")]
+pub(crate) fn archive(object_file: &Path, options: &BackendOptions) -> Result<()> {
+    let context = format!(
+        "while archiving {} into {}",
+        object_file.display(),
+        options.output_filename.display()
+    );
+    let archiver = find_executable(ARCHIVERS).ok_or(LinkerError::ArchiverNotFound {
+        candidates: ARCHIVERS,
+    })?;
+    // ar adds to an existing archive, which may still contain an object file of another program
+    match std::fs::remove_file(&options.output_filename) {
+        Err(error) if error.kind() != ErrorKind::NotFound => {
+            return Err(error).with_context(|| context.clone())
+        }
+        _ => {}
+    }
+    let mut command = archive_command(&archiver, object_file, options);
+    if options.verbose {
+        eprintln!("{}", command_line(&command));
+    }
+    let output = command.output().with_context(|| context.clone())?;
+    check_tool_output(&output, "archiver", &context)
+}
+
+#[cfg(feature = "llvm")]
+fn archive_command(archiver: &Path, object_file: &Path, options: &BackendOptions) -> Command {
+    let mut command = Command::new(archiver);
+    // adds the object file, creating the archive, and writes an index of its symbols
+    command
+        .arg("rcs")
+        .arg(&options.output_filename)
+        .arg(object_file);
+    command
+}
+
+/// The command `archive` would run, for `--dry-run`.
+#[cfg(feature = "llvm")]
+pub(crate) fn planned_archive_command(object_file: &Path, options: &BackendOptions) -> String {
+    match find_executable(ARCHIVERS) {
+        Some(archiver) => command_line(&archive_command(&archiver, object_file, options)),
+        None => format!(
+            "fails, {}",
+            LinkerError::ArchiverNotFound {
+                candidates: ARCHIVERS
+            }
+        ),
+    }
+}
+
+fn check_tool_output(output: &Output, tool: &str, context: &str) -> Result<()> {
+    if output.status.success() {
+        if !output.stderr.is_empty() {
+            report_tool_output(Severity::Warning, context, &output.stderr);
+        }
+        Ok(())
+    } else {
+        report_tool_output(Severity::Error, context, &output.stderr);
+        bail!("{tool} exited with {}", output.status)
+    }
+}
+
+/// The command `link` would run, for `--dry-run`.
+pub(crate) fn planned_link_command(
+    input_file: &Path,
+    options: &BackendOptions,
+    shared_library: bool,
+) -> String {
+    match Linker::find(options) {
+        Ok(linker) => command_line(&linker.command(input_file, options, shared_library)),
+        Err(error) => format!("fails, {error}"),
+    }
+}
+
+/// The command as it would be typed into a POSIX shell.
+pub(crate) fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|argument| shell_quote(&argument.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// single quotes keep everything literal, a single quote itself has to be put outside of them
+fn shell_quote(argument: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !argument.is_empty() && argument.chars().all(is_plain) {
+        argument.to_string()
+    } else {
+        format!("'{}'", argument.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(linker: &Linker, options: &BackendOptions) -> Vec<PathBuf> {
+        linker
+            .command(Path::new("prog.o"), options, false)
+            .get_args()
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    fn linker(path: &str, options: &mut BackendOptions) -> Result<Linker, LinkerError> {
+        options.linker = Some(PathBuf::from(path));
+        Linker::find(options)
+    }
+
+    #[test]
+    fn linkers_are_looked_for_depending_on_the_platform() {
+        let mut host = BackendOptions::for_tests(Platform::Host, "a.out");
+        assert_eq!(candidates(&host), ["cc", "gcc", "clang"]);
+        host.static_executable = true;
+        assert_eq!(candidates(&host)[0], "musl-gcc");
+        host.no_libc = true;
+        assert!(candidates(&host).contains(&"ld.lld"));
+        for platform in [Platform::Wasm32Wasi, Platform::Wasm32Browser] {
+            assert_eq!(
+                candidates(&BackendOptions::for_tests(platform, "out.wasm")),
+                ["wasm-ld"]
+            );
+        }
+    }
+
+    #[test]
+    fn flavor_follows_the_name_of_the_linker() {
+        let mut host = BackendOptions::for_tests(Platform::Host, "a.out");
+        assert_eq!(
+            linker("/usr/bin/clang", &mut host).unwrap().flavor,
+            Flavor::Compiler
+        );
+        assert!(matches!(
+            linker("ld.lld", &mut host),
+            Err(LinkerError::NeedsLibc { .. })
+        ));
+        host.no_libc = true;
+        assert_eq!(linker("ld.lld", &mut host).unwrap().flavor, Flavor::Elf);
+        let mut wasm = BackendOptions::for_tests(Platform::Wasm32Wasi, "out.wasm");
+        assert_eq!(linker("cc", &mut wasm).unwrap().flavor, Flavor::Wasm);
+    }
+
+    #[test]
+    fn link_commands_depend_on_the_platform() {
+        let mut host = BackendOptions::for_tests(Platform::Host, "build/out");
+        let host_linker = linker("cc", &mut host).unwrap();
+        assert_eq!(
+            arguments(&host_linker, &host),
+            ["-o", "build/out", "prog.o"].map(PathBuf::from)
+        );
+        let mut wasi = BackendOptions::for_tests(Platform::Wasm32Wasi, "out.wasm");
+        let wasi_linker = linker("wasm-ld", &mut wasi).unwrap();
+        let wasi_arguments = arguments(&wasi_linker, &wasi);
+        assert!(wasi_arguments[2].ends_with("crt1-command.o"));
+        assert_eq!(wasi_arguments[3], Path::new("prog.o"));
+        assert_eq!(wasi_arguments.last().unwrap(), Path::new("-lc"));
+        let mut browser = BackendOptions::for_tests(Platform::Wasm32Browser, "out.wasm");
+        let browser_linker = linker("wasm-ld", &mut browser).unwrap();
+        let browser_arguments = arguments(&browser_linker, &browser);
+        assert!(browser_arguments.contains(&PathBuf::from("--no-entry")));
+        assert!(!browser_arguments.contains(&PathBuf::from("-lc")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_paths_are_passed_to_the_linker_unchanged() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let output_filename = Path::new(OsStr::from_bytes(b"build/\xff\xfe/prog"));
+        let mut host = BackendOptions::for_tests(Platform::Host, output_filename);
+        let host_linker = linker("cc", &mut host).unwrap();
+        assert_eq!(arguments(&host_linker, &host)[1], output_filename);
+    }
+
+    #[test]
+    fn plain_arguments_are_not_quoted() {
+        let mut command = Command::new("cc");
+        command.args(["-o", "a.out", "-Wl,-z,relro,-z,now", "/tmp/out.o"]);
+        assert_eq!(
+            command_line(&command),
+            "cc -o a.out -Wl,-z,relro,-z,now /tmp/out.o"
+        );
+    }
+
+    #[test]
+    fn arguments_with_special_characters_are_quoted() {
+        let mut command = Command::new("/opt/my tools/cc");
+        command.args(["-o", "my program", "", "$HOME", "it's"]);
+        assert_eq!(
+            command_line(&command),
+            r"'/opt/my tools/cc' -o 'my program' '' '$HOME' 'it'\''s'"
+        );
+    }
+}
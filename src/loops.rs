@@ -0,0 +1,175 @@
+use std::fmt::Write;
+
+use crate::analysis::classify_loop;
+use crate::diagnostics::LineIndex;
+use crate::program::{Program, Statement, StatementKind};
+
+/// Returns a JSON document that describes every loop of the program, in source order. Nested
+/// loops refer to the index of their enclosing loop.
+pub(crate) fn loop_summary(source: &[u8], program: &Program) -> String {
+    let lines = LineIndex::new(source);
+    let mut loops: Vec<String> = Vec::new();
+    let mut blocks = vec![Block::new(None, program.statements(), None, 0)];
+    loop {
+        let block = blocks
+            .last_mut()
+            .expect("the outermost block is popped last");
+        let Some(statement) = block.statements.next() else {
+            let body = blocks.pop().expect("checked above");
+            let Some((statement, index)) = body.parent else {
+                break;
+            };
+            let outer = blocks
+                .last_mut()
+                .expect("the block around the body is still open");
+            outer.commands += 2 + body.commands;
+            if body.pointer_delta != Some(0) {
+                outer.pointer_delta = None;
+            }
+            let (Some(index), StatementKind::Loop(loop_body)) = (index, statement.kind()) else {
+                continue;
+            };
+            let span = statement.span();
+            let location = lines.location(span.start);
+            let _ = write!(
+                loops[index],
+                "\n{{\"start\":{},\"end\":{},\"line\":{},\"column\":{},\"depth\":{},\"parent\":{},\
+                 \"body_size\":{},\"balanced\":{},\"pointer_delta\":{},\"kind\":\"{}\"}}",
+                span.start,
+                span.end,
+                location.line,
+                location.column,
+                outer.depth,
+                outer
+                    .enclosing_loop
+                    .map_or("null".to_string(), |parent| parent.to_string()),
+                body.commands,
+                body.pointer_delta == Some(0),
+                body.pointer_delta
+                    .map_or("null".to_string(), |delta| delta.to_string()),
+                classify_loop(loop_body).name()
+            );
+            continue;
+        };
+        match statement.kind() {
+            StatementKind::Loop(body) => {
+                // the entry is written once the body has been summarized
+                let index = loops.len();
+                loops.push(String::new());
+                let depth = block.depth + 1;
+                blocks.push(Block::new(
+                    Some((statement, Some(index))),
+                    body,
+                    Some(index),
+                    depth,
+                ));
+            }
+            StatementKind::If(body) => {
+                let (enclosing_loop, depth) = (block.enclosing_loop, block.depth);
+                blocks.push(Block::new(
+                    Some((statement, None)),
+                    body,
+                    enclosing_loop,
+                    depth,
+                ));
+            }
+            kind => {
+                block.commands += 1;
+                match kind {
+                    StatementKind::Move(amount) => {
+                        block.pointer_delta = block.pointer_delta.map(|delta| delta + amount)
+                    }
+                    // `@` and `&` must be reached with the pointer where it was at the start of
+                    // the loop body
+                    StatementKind::Break | StatementKind::Continue
+                        if block.pointer_delta != Some(0) =>
+                    {
+                        block.pointer_delta = None
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    format!("{{\"loops\":[{}\n]}}\n", loops.join(","))
+}
+
+// A block of statements that is being summarized. The size of a loop body and the amount by
+// which it moves the pointer are only known after all of its statements, including those of
+// nested loops, have been visited.
+struct Block<'a> {
+    // the statement the block is the body of, with its index in the summary if it is a loop
+    parent: Option<(&'a Statement, Option<usize>)>,
+    statements: std::slice::Iter<'a, Statement>,
+    // the index of the innermost loop containing the statements and the number of loops
+    // containing them
+    enclosing_loop: Option<usize>,
+    depth: usize,
+    commands: usize,
+    pointer_delta: Option<i64>,
+}
+
+impl<'a> Block<'a> {
+    fn new(
+        parent: Option<(&'a Statement, Option<usize>)>,
+        statements: &'a [Statement],
+        enclosing_loop: Option<usize>,
+        depth: usize,
+    ) -> Self {
+        Self {
+            parent,
+            statements: statements.iter(),
+            enclosing_loop,
+            depth,
+            commands: 0,
+            pointer_delta: Some(0),
+        }
+    }
+}
+
+// the number of commands between the brackets of a loop, including nested loops
+pub(crate) fn command_count(statements: &[Statement]) -> usize {
+    let mut count = 0;
+    let mut blocks = vec![statements];
+    while let Some(statements) = blocks.pop() {
+        for statement in statements {
+            count += match statement.kind() {
+                StatementKind::Loop(body) | StatementKind::If(body) => {
+                    blocks.push(body);
+                    2
+                }
+                _ => 1,
+            };
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_loops_are_summarized() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let summary = loop_summary(source.as_bytes(), &program);
+        assert_eq!(summary.matches("\"kind\"").count(), DEPTH);
+        assert!(summary.contains(&format!(
+            "{{\"start\":1,\"end\":{},\"line\":1,\"column\":2,\"depth\":0,\"parent\":null,\
+             \"body_size\":{},\"balanced\":true,\"pointer_delta\":0,\"kind\":\"unknown\"}}",
+            source.len(),
+            source.len() - 3
+        )));
+        assert!(summary.contains(&format!(
+            "\"depth\":{},\"parent\":{},\"body_size\":1,\"balanced\":true,\"pointer_delta\":0,\
+             \"kind\":\"clear\"",
+            DEPTH - 1,
+            DEPTH - 2
+        )));
+    }
+}
@@ -0,0 +1,154 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use libloading::Library;
+use thiserror::Error;
+
+use crate::plugin::{
+    decode, encode, DecodeError, RawStatement, RunFunction, ABI_VERSION, ABI_VERSION_SYMBOL,
+    NAME_SYMBOL, RUN_SYMBOL,
+};
+use crate::program::{CellSize, Program};
+
+#[derive(Error, Debug)]
+pub(crate) enum PluginError {
+    FailedToLoad {
+        filename: PathBuf,
+        error_message: String,
+    },
+    IncompatibleAbi {
+        filename: PathBuf,
+        version: u32,
+    },
+    PassFailed {
+        name: String,
+        status: i32,
+    },
+    InvalidOutput {
+        name: String,
+        error: DecodeError,
+    },
+}
+
+impl Display for PluginError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginError::FailedToLoad {
+                filename,
+                error_message,
+            } => {
+                write!(
+                    f,
+                    "failed to load pass plugin '{}': {}",
+                    filename.display(),
+                    error_message
+                )
+            }
+            PluginError::IncompatibleAbi { filename, version } => {
+                write!(
+                    f,
+                    "pass plugin '{}' was built for ABI version {version}, but brainrust expects version {ABI_VERSION}",
+                    filename.display()
+                )
+            }
+            PluginError::PassFailed { name, status } => {
+                write!(f, "external pass '{name}' failed with status {status}")
+            }
+            PluginError::InvalidOutput { name, error } => {
+                write!(
+                    f,
+                    "external pass '{name}' returned an invalid program: {error}"
+                )
+            }
+        }
+    }
+}
+
+/// An optimization pass loaded from a dynamic library (see [`crate::plugin`]).
+pub(crate) struct PassPlugin {
+    name: String,
+    run: RunFunction,
+    // the function pointers are only valid as long as the library is loaded
+    _library: Library,
+}
+
+impl PassPlugin {
+    pub(crate) fn load(filename: &Path) -> Result<Self, PluginError> {
+        let failed_to_load = |error: libloading::Error| PluginError::FailedToLoad {
+            filename: filename.to_path_buf(),
+            error_message: error.to_string(),
+        };
+        // loading a library runs its initialization code, which is what the user asked for
+        let library = unsafe { Library::new(filename) }.map_err(failed_to_load)?;
+        // the symbols have the types documented in `crate::plugin`
+        let (name, run) = unsafe {
+            let abi_version = library
+                .get::<extern "C" fn() -> u32>(ABI_VERSION_SYMBOL.as_bytes())
+                .map_err(failed_to_load)?;
+            let version = abi_version();
+            if version != ABI_VERSION {
+                return Err(PluginError::IncompatibleAbi {
+                    filename: filename.to_path_buf(),
+                    version,
+                });
+            }
+            let name = library
+                .get::<extern "C" fn() -> *const c_char>(NAME_SYMBOL.as_bytes())
+                .map_err(failed_to_load)?;
+            let run = library
+                .get::<RunFunction>(RUN_SYMBOL.as_bytes())
+                .map_err(failed_to_load)?;
+            (CStr::from_ptr(name()).to_string_lossy().into_owned(), *run)
+        };
+        Ok(Self {
+            name,
+            run,
+            _library: library,
+        })
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn run(
+        &self,
+        program: &Program,
+        cell_size: CellSize,
+    ) -> Result<Program, PluginError> {
+        unsafe extern "C" fn emit(
+            context: *mut c_void,
+            statements: *const RawStatement,
+            length: usize,
+        ) {
+            let result = &mut *context.cast::<Vec<RawStatement>>();
+            if length > 0 {
+                result.extend_from_slice(std::slice::from_raw_parts(statements, length));
+            }
+        }
+
+        let statements = encode(program);
+        let mut result: Vec<RawStatement> = Vec::new();
+        // the plugin only reads the statements and passes the context back to `emit`
+        let status = unsafe {
+            (self.run)(
+                statements.as_ptr(),
+                statements.len(),
+                cell_size.bits(),
+                emit,
+                (&mut result as *mut Vec<RawStatement>).cast(),
+            )
+        };
+        if status != 0 {
+            return Err(PluginError::PassFailed {
+                name: self.name.clone(),
+                status,
+            });
+        }
+        decode(&result).map_err(|error| PluginError::InvalidOutput {
+            name: self.name.clone(),
+            error,
+        })
+    }
+}
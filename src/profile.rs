@@ -0,0 +1,249 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::diagnostics::{LineIndex, SourceLocation};
+use crate::interpreter::{LoopProfile, Profile, RuntimeStatistics};
+
+// the number of loops listed in the report
+const HOTTEST_LOOPS: usize = 10;
+
+/// Prints the loops that took the most steps together with their share of all steps. The steps
+/// of nested loops count towards their enclosing loops as well.
+pub(crate) fn print_profile(filename: &Path, source: &[u8], profile: &Profile) {
+    eprintln!("profile: {} steps", profile.steps);
+    let mut loops: Vec<_> = profile
+        .loops
+        .iter()
+        .filter(|loop_profile| loop_profile.reached > 0)
+        .collect();
+    loops.sort_by_key(|loop_profile| {
+        (
+            std::cmp::Reverse(loop_profile.steps),
+            loop_profile.span.start,
+        )
+    });
+    for loop_profile in loops.iter().take(HOTTEST_LOOPS) {
+        eprintln!(
+            "{}:{}: {:.1}% of steps ({} steps, reached {} time(s), {} iteration(s))",
+            filename.display(),
+            SourceLocation::from_offset(source, loop_profile.span.start),
+            loop_profile.steps as f64 * 100.0 / profile.steps as f64,
+            loop_profile.steps,
+            loop_profile.reached,
+            loop_profile.iterations
+        );
+    }
+    match loops.len() {
+        0 => eprintln!("no loops were executed"),
+        count if count > HOTTEST_LOOPS => {
+            eprintln!("{} more loop(s) were executed", count - HOTTEST_LOOPS)
+        }
+        _ => {}
+    }
+}
+
+/// Formats the profile for callgrind tools like KCachegrind. Every executed loop is a function
+/// that is called by the loop around it, or by `main` at the top level, so the inclusive cost of
+/// a loop includes the steps of its nested loops. Costs are attributed to the byte offsets of
+/// the statements, which callgrind calls instruction addresses, and to their lines.
+pub(crate) fn callgrind(filename: &Path, source: &[u8], profile: &Profile) -> String {
+    let lines = LineIndex::new(source);
+    let nesting = LoopNesting::new(profile);
+    // the function of a statement is the innermost loop containing it, `main` is function 0
+    let mut costs = vec![Vec::new(); nesting.loops.len() + 1];
+    for (function, offset, count) in nesting.innermost_loops(profile) {
+        costs[function_of(function)].push((offset, count));
+    }
+    let mut callees = vec![Vec::new(); costs.len()];
+    for (index, parent) in nesting.parents.iter().enumerate() {
+        callees[function_of(*parent)].push(index + 1);
+    }
+    let mut inclusive: Vec<u64> = costs
+        .iter()
+        .map(|costs| costs.iter().map(|&(_, count)| count).sum())
+        .collect();
+    // nested loops come after the loops around them
+    for (index, parent) in nesting.parents.iter().enumerate().rev() {
+        inclusive[function_of(*parent)] += inclusive[index + 1];
+    }
+    let function_name = |function| nesting.function_name(&lines, function);
+
+    let mut output = String::new();
+    let _ = writeln!(output, "# callgrind format");
+    let _ = writeln!(output, "version: 1");
+    let _ = writeln!(output, "creator: brainrust");
+    let _ = writeln!(output, "cmd: {}", filename.display());
+    let _ = writeln!(output, "positions: instr line");
+    let _ = writeln!(output, "events: Steps");
+    let _ = writeln!(output, "summary: {}", profile.steps);
+    let _ = writeln!(output, "\nfl={}", filename.display());
+    for (function, costs) in costs.iter().enumerate() {
+        let _ = writeln!(output, "\nfn={}", function_name(function));
+        for &(offset, count) in costs {
+            let _ = writeln!(
+                output,
+                "{offset:#x} {} {count}",
+                lines.location(offset).line
+            );
+        }
+        for &callee in &callees[function] {
+            let called = nesting.loops[callee - 1];
+            let offset = called.span.start;
+            let line = lines.location(offset).line;
+            let _ = writeln!(output, "cfn={}", function_name(callee));
+            let _ = writeln!(output, "calls={} {offset:#x} {line}", called.reached);
+            let _ = writeln!(output, "{offset:#x} {line} {}", inclusive[callee]);
+        }
+    }
+    output
+}
+
+/// Formats the profile as folded stacks for flame graph tools like `flamegraph.pl` or inferno.
+/// The stack of a statement consists of `main` and the loops containing it, from the outermost
+/// to the innermost one, and every stack is listed with the number of steps taken in it.
+pub(crate) fn folded_stacks(source: &[u8], profile: &Profile) -> String {
+    let lines = LineIndex::new(source);
+    let nesting = LoopNesting::new(profile);
+    let mut steps = vec![0; nesting.loops.len() + 1];
+    for (innermost, _, count) in nesting.innermost_loops(profile) {
+        steps[function_of(innermost)] += count;
+    }
+    let mut stacks = vec![nesting.function_name(&lines, 0)];
+    // the stack of a loop extends that of the loop around it, which comes first
+    for (index, parent) in nesting.parents.iter().enumerate() {
+        let stack = format!(
+            "{};{}",
+            stacks[function_of(*parent)],
+            nesting.function_name(&lines, index + 1)
+        );
+        stacks.push(stack);
+    }
+    let mut output = String::new();
+    for (stack, steps) in stacks.iter().zip(steps) {
+        if steps > 0 {
+            let _ = writeln!(output, "{stack} {steps}");
+        }
+    }
+    output
+}
+
+// the function of `main` is 0, that of the loop with the given index is the index plus one
+fn function_of(loop_index: Option<usize>) -> usize {
+    loop_index.map_or(0, |index| index + 1)
+}
+
+/// The executed loops of a profile, ordered by their position in the source, and the loop
+/// around each of them.
+struct LoopNesting<'a> {
+    loops: Vec<&'a LoopProfile>,
+    parents: Vec<Option<usize>>,
+}
+
+impl<'a> LoopNesting<'a> {
+    fn new(profile: &'a Profile) -> Self {
+        let mut loops: Vec<_> = profile
+            .loops
+            .iter()
+            .filter(|loop_profile| loop_profile.reached > 0)
+            .collect();
+        loops.sort_by_key(|loop_profile| loop_profile.span.start);
+        let mut parents = Vec::with_capacity(loops.len());
+        let mut open: Vec<usize> = Vec::new();
+        for (index, loop_profile) in loops.iter().enumerate() {
+            while open
+                .last()
+                .is_some_and(|&outer| loops[outer].span.end <= loop_profile.span.start)
+            {
+                open.pop();
+            }
+            parents.push(open.last().copied());
+            open.push(index);
+        }
+        Self { loops, parents }
+    }
+
+    fn function_name(&self, lines: &LineIndex, function: usize) -> String {
+        match function {
+            0 => "main".to_string(),
+            function => format!(
+                "loop at {}",
+                lines.location(self.loops[function - 1].span.start)
+            ),
+        }
+    }
+
+    /// The executed statements of the profile ordered by their offsets, together with the index
+    /// of the innermost loop containing them.
+    fn innermost_loops(&self, profile: &Profile) -> Vec<(Option<usize>, usize, u64)> {
+        let mut executed: Vec<_> = profile
+            .statements
+            .iter()
+            .copied()
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        executed.sort_by_key(|&(offset, _)| offset);
+        let mut result = Vec::with_capacity(executed.len());
+        let mut open: Vec<usize> = Vec::new();
+        let mut next_loop = 0;
+        for (offset, count) in executed {
+            while next_loop < self.loops.len() && self.loops[next_loop].span.start <= offset {
+                self.close_loops(&mut open, self.loops[next_loop].span.start);
+                open.push(next_loop);
+                next_loop += 1;
+            }
+            self.close_loops(&mut open, offset);
+            result.push((open.last().copied(), offset, count));
+        }
+        result
+    }
+
+    // Removes the loops that ended before the offset, which cannot contain it nor any later
+    // offset. The open loops are nested in each other, so those are the innermost ones.
+    fn close_loops(&self, open: &mut Vec<usize>, offset: usize) {
+        while open
+            .last()
+            .is_some_and(|&index| self.loops[index].span.end <= offset)
+        {
+            open.pop();
+        }
+    }
+}
+
+pub(crate) fn print_runtime_statistics(statistics: &RuntimeStatistics) {
+    eprintln!("runtime statistics:");
+    eprintln!("  steps:             {}", statistics.steps);
+    eprintln!("  cells touched:     {}", statistics.cells_touched());
+    eprintln!(
+        "  pointer excursion: {} to the right, {} to the left",
+        statistics.highest_address, -statistics.lowest_address
+    );
+    eprintln!("  bytes read:        {}", statistics.bytes_read);
+    eprintln!("  bytes written:     {}", statistics.bytes_written);
+    eprintln!("  peak tape size:    {} cells", statistics.peak_tape_size);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{interpret_profiled, InterpreterOptions};
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_loops_are_profiled() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let (profile, result) = interpret_profiled(&program, &InterpreterOptions::default());
+        result.unwrap();
+        assert_eq!(profile.loops.len(), DEPTH);
+        assert!(profile
+            .loops
+            .iter()
+            .all(|loop_profile| loop_profile.reached == 1 && loop_profile.iterations == 1));
+        print_profile(Path::new("prog.b"), source.as_bytes(), &profile);
+        let callgrind = callgrind(Path::new("prog.b"), source.as_bytes(), &profile);
+        assert_eq!(callgrind.matches("\ncfn=").count(), DEPTH);
+    }
+}
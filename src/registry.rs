@@ -0,0 +1,246 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::c_backend::CBackend;
+#[cfg(feature = "llvm")]
+use crate::command_line_arguments::EmitTarget;
+#[cfg(feature = "cranelift")]
+use crate::cranelift_backend::CraneliftBackend;
+use crate::direct_backend::DirectBackend;
+#[cfg(feature = "llvm")]
+use crate::emitter::LlvmBackend;
+use crate::environment::EnvironmentReport;
+use crate::parser::{Dialect, Parser};
+use crate::program::Program;
+use crate::semantics::RuntimeConfig;
+
+/// Turns source code into a [`Program`].
+pub trait Frontend {
+    /// The name used to select this frontend on the command line (`--from`).
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    fn parse(&self, source: &[u8], dialect: Dialect) -> anyhow::Result<Program>;
+}
+
+/// Turns a [`Program`] into an artifact, e.g. a file containing generated code.
+pub trait Backend {
+    /// The name used to select this backend on the command line (`--to`).
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Used when no output filename is given on the command line.
+    fn default_output_filename(&self) -> &'static str;
+
+    fn emit(&self, program: &Program, options: &BackendOptions) -> anyhow::Result<()>;
+
+    /// Describes the steps `emit` would perform, used for `--dry-run`.
+    fn plan(&self, options: &BackendOptions) -> Vec<String>;
+
+    /// The files `emit` writes, which the `clean` subcommand removes again.
+    fn output_files(&self, options: &BackendOptions) -> Vec<PathBuf> {
+        vec![options.output_filename.clone()]
+    }
+}
+
+pub struct BackendOptions {
+    pub input_filename: PathBuf,
+    pub output_filename: PathBuf,
+    pub optimization_level: u8,
+    pub verbose: bool,
+    pub environment: EnvironmentReport,
+    pub platform: Platform,
+    /// Printed by the generated program whenever it starts reading a line of input from a
+    /// terminal.
+    pub prompt: Option<String>,
+    /// Turns off the echo of the terminal while the generated program runs. The echo is turned
+    /// back on when the program exits or is terminated by a signal.
+    pub no_echo: bool,
+    /// Makes the generated program check its runtime instead of running when it is started with
+    /// `--bf-selftest`.
+    pub embed_selftest: bool,
+    /// Enables the stack protector, aborts on out-of-bounds memory accesses and failed
+    /// allocations, and links executables with full RELRO.
+    pub harden: bool,
+    /// The output of the program if it has been evaluated at compile time (`--const-eval`).
+    /// The generated program then only writes this output instead of running the program.
+    pub constant_output: Option<Vec<u8>>,
+    /// Makes the generated program terminate with an error once it tries to write more than
+    /// this many bytes.
+    pub max_output: Option<u64>,
+    pub runtime: RuntimeConfig,
+    /// Bytes the generated program stores in the cells starting at address 0 before it runs.
+    pub initial_tape: Vec<u8>,
+    /// Reserves a large tape between inaccessible guard pages instead of checking on every
+    /// access whether the tape has to grow. Leaving the tape is reported by a signal handler.
+    pub guard_pages: bool,
+    /// Implements the few functions of the C standard library the generated code uses with
+    /// system calls and links a static executable with its own entry point.
+    pub no_libc: bool,
+    /// Generates code for targets without an operating system: the tape is a static array of
+    /// `--tape-size` cells, I/O goes through `bf_putchar` and `bf_getchar`, and the program is
+    /// run by calling `bf_main`.
+    pub bare_metal: bool,
+    /// The program executables are linked with (`--linker`). Without it, the first of cc, gcc
+    /// and clang (or ld.lld with `no_libc`) found in the `PATH` or the LLVM installation is used.
+    pub linker: Option<PathBuf>,
+    /// Links an executable that needs neither shared libraries nor the dynamic loader, using
+    /// musl-gcc if it is installed.
+    pub static_executable: bool,
+    /// Passed to the linker after the object file (`-L`, `-l` and `--link-arg`), e.g. to link
+    /// against other libraries.
+    pub link_arguments: Vec<OsString>,
+    /// Compiles every loop into a function of its own and, when running the program right away,
+    /// writes a perf jitdump file of the JIT-compiled functions.
+    pub jit_profiling: bool,
+}
+
+#[cfg(test)]
+impl BackendOptions {
+    /// Compiles `prog.b` for the platform without any of the optional features.
+    pub(crate) fn for_tests(platform: Platform, output_filename: impl Into<PathBuf>) -> Self {
+        Self {
+            input_filename: PathBuf::from("prog.b"),
+            output_filename: output_filename.into(),
+            optimization_level: 0,
+            verbose: false,
+            environment: EnvironmentReport::collect(None),
+            platform,
+            prompt: None,
+            no_echo: false,
+            embed_selftest: false,
+            harden: false,
+            constant_output: None,
+            max_output: None,
+            runtime: RuntimeConfig::default(),
+            initial_tape: Vec::new(),
+            guard_pages: false,
+            no_libc: false,
+            bare_metal: false,
+            linker: None,
+            static_executable: false,
+            link_arguments: Vec::new(),
+            jit_profiling: false,
+        }
+    }
+}
+
+/// The platform the generated code runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Platform {
+    /// The platform brainrust runs on
+    #[default]
+    Host,
+    /// WebAssembly with the WebAssembly System Interface, e.g. to run programs with wasmtime
+    #[value(name = "wasm32-wasi")]
+    Wasm32Wasi,
+    /// WebAssembly for web pages, run by the JavaScript glue code written next to the module
+    #[value(name = "wasm32-unknown-unknown")]
+    Wasm32Browser,
+    /// Arm Cortex-M4 and Cortex-M7 microcontrollers without an operating system
+    #[value(name = "thumbv7em-none-eabi")]
+    ThumbV7em,
+    /// 32-bit RISC-V microcontrollers without an operating system
+    #[value(name = "riscv32-unknown-none-elf")]
+    Riscv32,
+}
+
+impl Platform {
+    /// The LLVM target triple, or `None` for the host, whose triple LLVM determines.
+    pub fn triple(self) -> Option<&'static str> {
+        match self {
+            Platform::Host => None,
+            Platform::Wasm32Wasi => Some("wasm32-unknown-wasi"),
+            Platform::Wasm32Browser => Some("wasm32-unknown-unknown"),
+            Platform::ThumbV7em => Some("thumbv7em-none-eabi"),
+            Platform::Riscv32 => Some("riscv32-unknown-none-elf"),
+        }
+    }
+}
+
+/// The frontends and backends available to the command line interface. Registering a
+/// frontend or backend replaces any previously registered one with the same name.
+pub struct Registry {
+    frontends: Vec<Box<dyn Frontend>>,
+    backends: Vec<Box<dyn Backend>>,
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self {
+            frontends: Vec::new(),
+            backends: Vec::new(),
+        }
+    }
+
+    pub fn register_frontend(&mut self, frontend: impl Frontend + 'static) {
+        self.frontends
+            .retain(|registered| registered.name() != frontend.name());
+        self.frontends.push(Box::new(frontend));
+    }
+
+    pub fn register_backend(&mut self, backend: impl Backend + 'static) {
+        self.backends
+            .retain(|registered| registered.name() != backend.name());
+        self.backends.push(Box::new(backend));
+    }
+
+    pub fn frontend(&self, name: &str) -> Option<&dyn Frontend> {
+        self.frontends().find(|frontend| frontend.name() == name)
+    }
+
+    pub fn backend(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends().find(|backend| backend.name() == name)
+    }
+
+    pub fn frontends(&self) -> impl Iterator<Item = &dyn Frontend> {
+        self.frontends.iter().map(Box::as_ref)
+    }
+
+    pub fn backends(&self) -> impl Iterator<Item = &dyn Backend> {
+        self.backends.iter().map(Box::as_ref)
+    }
+}
+
+pub(crate) struct BrainfuckFrontend;
+
+impl Frontend for BrainfuckFrontend {
+    fn name(&self) -> &'static str {
+        "brainfuck"
+    }
+
+    fn description(&self) -> &'static str {
+        "Brainfuck source code"
+    }
+
+    fn parse(&self, source: &[u8], dialect: Dialect) -> anyhow::Result<Program> {
+        Ok(Parser::new(source, dialect).parse()?)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register_frontend(BrainfuckFrontend);
+        registry.register_backend(CBackend);
+        registry.register_backend(DirectBackend);
+        #[cfg(feature = "cranelift")]
+        registry.register_backend(CraneliftBackend);
+        #[cfg(feature = "llvm")]
+        for target in [
+            EmitTarget::Executable,
+            EmitTarget::ObjectFile,
+            EmitTarget::Assembly,
+            EmitTarget::LlvmIr,
+            EmitTarget::Bitcode,
+            EmitTarget::SharedLibrary,
+            EmitTarget::StaticLibrary,
+            EmitTarget::Jit,
+        ] {
+            registry.register_backend(LlvmBackend::new(target));
+        }
+        registry
+    }
+}
@@ -0,0 +1,109 @@
+use crate::program::CellSize;
+
+// the generated programs allocate this many cells on each side of address 0 before they run
+const INITIAL_TAPE_EXTENT: u64 = 15000;
+
+/// What `,` stores in the current cell once the input has ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum EofBehavior {
+    /// Store 0
+    #[default]
+    Zero,
+    /// Store -1 truncated to a cell (255 for 8-bit cells)
+    MinusOne,
+    /// Leave the cell unchanged
+    Unchanged,
+}
+
+/// What happens when `+` or `-` takes a cell beyond its range of values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Overflow {
+    /// Wrap around, e.g. incrementing 255 yields 0 for 8-bit cells
+    #[default]
+    Wrap,
+    /// Terminate the program with an error naming the cell and the command
+    Trap,
+}
+
+/// What happens when a program accesses a cell beyond an end of a fixed-size tape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutOfBounds {
+    /// Grow the tape, as if it had no fixed size
+    #[default]
+    Grow,
+    /// Terminate the program with an error
+    Trap,
+    /// Access the cell at the nearest end of the tape instead
+    Clamp,
+    /// Wrap around to the other end of the tape, i.e. addresses are taken modulo its size
+    Wrap,
+}
+
+/// How a program behaves at the end of its input and at the limits of its cells and its tape.
+/// Both the interpreter and the generated programs follow it, so that a program behaves the
+/// same whether it is interpreted or compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RuntimeConfig {
+    pub eof_behavior: EofBehavior,
+    pub cell_size: CellSize,
+    pub overflow: Overflow,
+    /// Terminates the program with an error once its tape would grow beyond this many cells.
+    pub max_memory: Option<u64>,
+    /// The number of cells of a fixed-size tape, whose addresses start at 0. Without it, the tape
+    /// grows in both directions.
+    pub tape_size: Option<u64>,
+    pub out_of_bounds: OutOfBounds,
+}
+
+impl RuntimeConfig {
+    /// The value `,` stores once the input has ended, or `None` if it leaves the cell unchanged.
+    pub fn eof_value(&self) -> Option<u64> {
+        match self.eof_behavior {
+            EofBehavior::Zero => Some(0),
+            EofBehavior::MinusOne => Some(self.cell_size.max_value()),
+            EofBehavior::Unchanged => None,
+        }
+    }
+
+    /// The value of a cell after adding the amount to it, or `None` if that overflows the cell
+    /// and overflows trap.
+    pub fn add_to_cell(&self, value: u64, amount: i64) -> Option<u64> {
+        if self.overflow == Overflow::Trap {
+            let sum = i128::from(value) + i128::from(amount);
+            if sum < 0 || sum > i128::from(self.cell_size.max_value()) {
+                return None;
+            }
+        }
+        // truncating is the same as adding modulo the number of values of a cell
+        Some(self.cell_size.wrap((value as i64).wrapping_add(amount)))
+    }
+
+    /// The number of cells on each side of address 0 that a growing tape has from the start.
+    /// They count towards `max_memory`, which they never exceed.
+    pub fn initial_tape_extent(&self) -> u64 {
+        self.max_memory.map_or(INITIAL_TAPE_EXTENT, |max_memory| {
+            ((max_memory - 1) / 2).min(INITIAL_TAPE_EXTENT)
+        })
+    }
+
+    /// The size of the tape unless it may grow.
+    pub fn fixed_tape_size(&self) -> Option<u64> {
+        self.tape_size
+            .filter(|_| self.out_of_bounds != OutOfBounds::Grow)
+    }
+
+    /// The address of the cell that is accessed instead of the cell at the given address, or
+    /// `None` if accessing it is an error.
+    pub fn tape_address(&self, address: i64) -> Option<i64> {
+        let Some(tape_size) = self.fixed_tape_size() else {
+            return Some(address);
+        };
+        let tape_size = i64::try_from(tape_size).unwrap_or(i64::MAX);
+        match self.out_of_bounds {
+            OutOfBounds::Grow => Some(address),
+            OutOfBounds::Trap => (0..tape_size).contains(&address).then_some(address),
+            OutOfBounds::Clamp => Some(address.clamp(0, tape_size - 1)),
+            OutOfBounds::Wrap => Some(address.rem_euclid(tape_size)),
+        }
+    }
+}
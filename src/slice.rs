@@ -0,0 +1,295 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::ControlFlow;
+
+use anyhow::{bail, Result};
+
+use crate::analysis::pointer_delta;
+use crate::bytecode::{Bytecode, Instruction};
+use crate::program::{Program, Span, Statement, StatementKind};
+
+/// The value a slice has to preserve.
+pub(crate) enum Criterion {
+    /// The value of the cell at the given address (relative to the initial cell) at the end of
+    /// the program.
+    Cell(i64),
+    /// The output byte with the given index.
+    OutputByte(u64),
+}
+
+// What is still needed before a statement to compute the criterion. Addresses are relative to
+// the initial cell.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Live {
+    cells: BTreeSet<i64>,
+    // set once a cell is read at an address that is not known statically
+    all_cells: bool,
+    // the position in the input determines which byte a `,` reads
+    input: bool,
+    register: bool,
+}
+
+impl Live {
+    fn contains_cell(&self, address: Option<i64>) -> bool {
+        match address {
+            _ if self.all_cells => true,
+            Some(address) => self.cells.contains(&address),
+            None => !self.cells.is_empty(),
+        }
+    }
+
+    fn read_cell(&mut self, address: Option<i64>) {
+        match address {
+            Some(address) => {
+                self.cells.insert(address);
+            }
+            None => self.all_cells = true,
+        }
+    }
+
+    fn overwrite_cell(&mut self, address: Option<i64>) {
+        if let Some(address) = address {
+            self.cells.remove(&address);
+        }
+    }
+
+    fn union(&mut self, other: &Live) {
+        self.cells.extend(&other.cells);
+        self.all_cells |= other.all_cells;
+        self.input |= other.input;
+        self.register |= other.register;
+    }
+}
+
+// What is live after the innermost loop and at its start, which is where `@` and `&` continue.
+struct LoopContext<'a> {
+    after: &'a Live,
+    start: &'a Live,
+}
+
+// Statements are identified by the start of their span.
+#[derive(Default)]
+struct Slicer {
+    // the address of the current cell before each statement, if it is known statically
+    addresses: HashMap<usize, Option<i64>>,
+    // loops that leave the pointer where it was at their start
+    balanced_loops: HashSet<usize>,
+    // the `.` that writes the output byte of the criterion
+    target: Option<usize>,
+    kept: HashSet<usize>,
+}
+
+/// Removes all statements of a program that cannot influence the criterion. Pointer movements
+/// are always kept, and loops that move the pointer by an amount that is not known statically
+/// are kept together with everything their condition depends on.
+pub(crate) fn slice(
+    program: &Program,
+    criterion: Criterion,
+    input: &[u8],
+    max_steps: u64,
+) -> Result<Program> {
+    let mut slicer = Slicer::default();
+    slicer.locate(program.statements(), Some(0));
+    let mut live = Live::default();
+    match criterion {
+        Criterion::Cell(address) => live.read_cell(Some(address)),
+        Criterion::OutputByte(index) => {
+            slicer.target = Some(writer_of_output_byte(program, index, input, max_steps)?.start)
+        }
+    }
+    slicer.slice_block(program.statements(), live, None);
+
+    let mut statements = slicer.kept_statements(program.statements());
+    // moves at the end of the program have no effect
+    while let Some(StatementKind::Move(_)) = statements.last().map(Statement::kind) {
+        statements.pop();
+    }
+    Ok(Program::new(statements))
+}
+
+// Runs the program and returns the span of the `.` that writes the output byte.
+fn writer_of_output_byte(
+    program: &Program,
+    index: u64,
+    input: &[u8],
+    max_steps: u64,
+) -> Result<Span> {
+    let bytecode = Bytecode::compile(program);
+    let mut written = 0;
+    let mut writer = None;
+    let complete = bytecode.run(max_steps, input, |program_counter| {
+        if bytecode.instructions()[program_counter] != Instruction::PutChar {
+            return ControlFlow::Continue(());
+        }
+        if written == index {
+            writer = Some(bytecode.span(program_counter));
+            return ControlFlow::Break(());
+        }
+        written += 1;
+        ControlFlow::Continue(())
+    });
+    match writer {
+        Some(span) => Ok(span),
+        None if complete => bail!("the program only writes {written} byte(s)"),
+        None => bail!("the program did not write byte {index} within {max_steps} steps"),
+    }
+}
+
+impl Slicer {
+    // Records the address of the current cell before every statement and returns the address
+    // after the statements.
+    fn locate(&mut self, statements: &[Statement], mut address: Option<i64>) -> Option<i64> {
+        for statement in statements {
+            self.addresses.insert(statement.span().start, address);
+            match statement.kind() {
+                StatementKind::Move(amount) => address = address.map(|address| address + amount),
+                StatementKind::Loop(body) | StatementKind::If(body) => {
+                    if pointer_delta(body) == Some(0) {
+                        self.balanced_loops.insert(statement.span().start);
+                        self.locate(body, address);
+                    } else {
+                        self.locate(body, None);
+                        address = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        address
+    }
+
+    // Returns what is live before the statements, given what is live after them.
+    fn slice_block(
+        &mut self,
+        statements: &[Statement],
+        mut live: Live,
+        context: Option<&LoopContext>,
+    ) -> Live {
+        for statement in statements.iter().rev() {
+            let start = statement.span().start;
+            let address = self.addresses[&start];
+            match statement.kind() {
+                // dumps do not change the tape
+                StatementKind::Move(_) | StatementKind::DebugDump => {}
+                StatementKind::Add { offset, .. } => {
+                    if live.contains_cell(address.map(|address| address + offset)) {
+                        self.kept.insert(start);
+                    }
+                }
+                StatementKind::GetChar => {
+                    if live.contains_cell(address) || live.input {
+                        self.kept.insert(start);
+                        live.overwrite_cell(address);
+                        live.input = true;
+                    }
+                }
+                StatementKind::PutChar => {
+                    if self.target == Some(start) {
+                        self.kept.insert(start);
+                        live.read_cell(address);
+                    }
+                }
+                StatementKind::Store => {
+                    if live.register {
+                        self.kept.insert(start);
+                        live.register = false;
+                        live.read_cell(address);
+                    }
+                }
+                StatementKind::Load => {
+                    if live.contains_cell(address) {
+                        self.kept.insert(start);
+                        live.overwrite_cell(address);
+                        live.register = true;
+                    }
+                }
+                // kept whenever their loop is kept
+                StatementKind::Break => live.union(context.expect("checked by the parser").after),
+                StatementKind::Continue => {
+                    live.union(context.expect("checked by the parser").start)
+                }
+                StatementKind::Loop(body) | StatementKind::If(body) => {
+                    live = self.slice_loop(statement, body, live);
+                }
+                StatementKind::Set { .. } | StatementKind::MultiplyAdd(_) => {
+                    unreachable!("only created by the optimizer")
+                }
+            }
+        }
+        live
+    }
+
+    fn slice_loop(&mut self, statement: &Statement, body: &[Statement], after: Live) -> Live {
+        let start = statement.span().start;
+        let balanced = self.balanced_loops.contains(&start);
+        // dropping a loop that moves the pointer would shift everything that is kept after it
+        if !balanced && !self.kept.is_empty() {
+            self.kept.insert(start);
+        }
+        let condition_address = if balanced {
+            self.addresses[&start]
+        } else {
+            None
+        };
+        let mut live = after.clone();
+        loop {
+            let body_live = self.slice_block(
+                body,
+                live.clone(),
+                Some(&LoopContext {
+                    after: &after,
+                    start: &live,
+                }),
+            );
+            if self.contains_kept(body) {
+                self.kept.insert(start);
+            }
+            let mut next = after.clone();
+            next.union(&body_live);
+            if self.kept.contains(&start) {
+                next.read_cell(condition_address);
+            }
+            if next == live {
+                return live;
+            }
+            live = next;
+        }
+    }
+
+    fn contains_kept(&self, statements: &[Statement]) -> bool {
+        statements.iter().any(|statement| {
+            self.kept.contains(&statement.span().start)
+                || matches!(
+                    statement.kind(),
+                    StatementKind::Loop(body) | StatementKind::If(body) if self.contains_kept(body)
+                )
+        })
+    }
+
+    fn kept_statements(&self, statements: &[Statement]) -> Vec<Statement> {
+        let mut kept: Vec<Statement> = Vec::new();
+        for statement in statements {
+            let span = statement.span();
+            match statement.kind() {
+                StatementKind::Move(amount) => match kept.last().map(Statement::kind) {
+                    Some(&StatementKind::Move(previous)) => {
+                        let previous_start = kept.pop().expect("checked above").span().start;
+                        if previous + amount != 0 {
+                            kept.push(Statement::new(
+                                StatementKind::Move(previous + amount),
+                                Span::new(previous_start, span.end),
+                            ));
+                        }
+                    }
+                    _ => kept.push(statement.clone()),
+                },
+                StatementKind::Loop(body) if self.kept.contains(&span.start) => kept.push(
+                    Statement::new(StatementKind::Loop(self.kept_statements(body)), span),
+                ),
+                StatementKind::Break | StatementKind::Continue => kept.push(statement.clone()),
+                _ if self.kept.contains(&span.start) => kept.push(statement.clone()),
+                _ => {}
+            }
+        }
+        kept
+    }
+}
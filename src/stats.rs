@@ -0,0 +1,81 @@
+use crate::analysis::{for_each_loop, loop_termination, Termination};
+use crate::program::{Program, Statement, StatementKind};
+
+#[derive(Default)]
+pub(crate) struct Statistics {
+    pub(crate) statements: usize,
+    pub(crate) loops: usize,
+    pub(crate) max_nesting_depth: usize,
+    pub(crate) terminating_loops: usize,
+    pub(crate) non_terminating_loops: usize,
+}
+
+impl Statistics {
+    pub(crate) fn of(program: &Program) -> Self {
+        let mut statistics = Self::default();
+        statistics.count_statements(program.statements());
+        for_each_loop(program.statements(), &mut |_, body| {
+            statistics.loops += 1;
+            match loop_termination(body) {
+                Termination::Guaranteed => statistics.terminating_loops += 1,
+                Termination::ImpossibleIfEntered => statistics.non_terminating_loops += 1,
+                Termination::Unknown => {}
+            }
+        });
+        statistics
+    }
+
+    fn count_statements(&mut self, statements: &[Statement]) {
+        // the blocks that are left to count, with the number of loops around them
+        let mut blocks = vec![(statements, 0)];
+        while let Some((statements, depth)) = blocks.pop() {
+            self.max_nesting_depth = self.max_nesting_depth.max(depth);
+            for statement in statements {
+                self.statements += 1;
+                if let StatementKind::Loop(body) = statement.kind() {
+                    blocks.push((body, depth + 1));
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn stats(program: &Program) {
+    let statistics = Statistics::of(program);
+
+    println!("statements: {}", statistics.statements);
+    println!("loops: {}", statistics.loops);
+    println!(
+        "maximum loop nesting depth: {}",
+        statistics.max_nesting_depth
+    );
+    println!(
+        "loops proven to terminate: {}",
+        statistics.terminating_loops
+    );
+    println!(
+        "loops that cannot terminate if entered: {}",
+        statistics.non_terminating_loops
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Dialect, Parser};
+
+    #[test]
+    fn deeply_nested_loops_are_counted() {
+        const DEPTH: usize = 100_000;
+        let source = format!("+{}-{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let program = Parser::new(source.as_bytes(), Dialect::default())
+            .parse()
+            .unwrap();
+        let statistics = Statistics::of(&program);
+        assert_eq!(statistics.statements, DEPTH + 2);
+        assert_eq!(statistics.loops, DEPTH);
+        assert_eq!(statistics.max_nesting_depth, DEPTH);
+        assert_eq!(statistics.terminating_loops, 1);
+        assert_eq!(statistics.non_terminating_loops, 0);
+    }
+}
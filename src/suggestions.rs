@@ -0,0 +1,121 @@
+use crate::diagnostics::Diagnostic;
+use crate::loops::command_count;
+use crate::optimizer::Pass;
+use crate::program::{Program, Span, Statement, StatementKind};
+
+// the peephole passes whose result can be written without the optimizer's extra statements
+const PASSES: [Pass; 2] = [Pass::ClearLoops, Pass::RunLength];
+
+/// Suggests shorter ways to write runs of `+`/`-` and `>`/`<` (optionally preceded by a clear
+/// loop like `[-]`), found by running the peephole passes of the optimizer on each run.
+pub(crate) fn suggestions(program: &Program) -> Vec<Diagnostic> {
+    let mut suggestions = Vec::new();
+    suggest(program.statements(), &mut suggestions);
+    suggestions
+}
+
+fn suggest(statements: &[Statement], suggestions: &mut Vec<Diagnostic>) {
+    let mut blocks = vec![statements];
+    while let Some(statements) = blocks.pop() {
+        let mut index = 0;
+        while let Some(statement) = statements.get(index) {
+            if let StatementKind::Loop(body) | StatementKind::If(body) = statement.kind() {
+                blocks.push(body);
+            }
+            let run_start = index + usize::from(is_clear_loop(statement));
+            let run_end = run_start + run_length(&statements[run_start..]);
+            suggestions.extend(suggestion(&statements[index..run_end]));
+            index = run_end.max(index + 1);
+        }
+    }
+}
+
+fn is_clear_loop(statement: &Statement) -> bool {
+    // clear loops contain no other loops, so larger ones are not copied to find out
+    let (StatementKind::Loop(body) | StatementKind::If(body)) = statement.kind() else {
+        return false;
+    };
+    if body.iter().any(|statement| {
+        matches!(
+            statement.kind(),
+            StatementKind::Loop(_) | StatementKind::If(_)
+        )
+    }) {
+        return false;
+    }
+    let program = Program::new(vec![statement.clone()]);
+    matches!(
+        Pass::ClearLoops.run(&program).statements().as_slice(),
+        [cleared] if matches!(cleared.kind(), StatementKind::Set { .. })
+    )
+}
+
+// the number of statements at the start that all add to the current cell or all move the pointer
+fn run_length(statements: &[Statement]) -> usize {
+    let Some(first) = statements.first() else {
+        return 0;
+    };
+    statements
+        .iter()
+        .take_while(|statement| {
+            matches!(
+                (first.kind(), statement.kind()),
+                (StatementKind::Move(_), StatementKind::Move(_))
+                    | (
+                        StatementKind::Add { offset: 0, .. },
+                        StatementKind::Add { offset: 0, .. }
+                    )
+            )
+        })
+        .count()
+}
+
+fn suggestion(statements: &[Statement]) -> Option<Diagnostic> {
+    let (first, last) = (statements.first()?, statements.last()?);
+    let optimized = PASSES
+        .iter()
+        .fold(Program::new(statements.to_vec()), |program, pass| {
+            pass.run(&program)
+        });
+    let rewritten: String = optimized.statements().iter().map(commands).collect();
+    let count = command_count(statements);
+    if rewritten.len() >= count {
+        return None;
+    }
+    let span = Span::new(first.span().start, last.span().end);
+    Some(if rewritten.is_empty() {
+        Diagnostic::help(
+            span,
+            format!("these {count} commands cancel out and can be removed"),
+        )
+    } else {
+        Diagnostic::help(
+            span,
+            format!("these {count} commands can be written as `{rewritten}`"),
+        )
+    })
+}
+
+// the shortest commands for a statement of an optimized run
+fn commands(statement: &Statement) -> String {
+    match statement.kind() {
+        StatementKind::Move(amount) => repeated(*amount, '>', '<'),
+        StatementKind::Add { offset: 0, amount } => wrapping_addition(*amount as u8),
+        StatementKind::Set { offset: 0, value } => format!("[-]{}", wrapping_addition(*value)),
+        _ => unreachable!("runs only contain additions to the current cell and movements"),
+    }
+}
+
+// cells wrap around, so e.g. adding 255 is the same as subtracting 1
+fn wrapping_addition(amount: u8) -> String {
+    if amount <= 128 {
+        repeated(amount.into(), '+', '-')
+    } else {
+        repeated(i64::from(amount) - 256, '+', '-')
+    }
+}
+
+fn repeated(amount: i64, positive: char, negative: char) -> String {
+    let command = if amount >= 0 { positive } else { negative };
+    std::iter::repeat_n(command, amount.unsigned_abs() as usize).collect()
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use crate::bytecode::{Bytecode, Instruction};
+use crate::diagnostics::SourceLocation;
+use crate::program::Program;
+
+const SYMBOLS: [u8; 4] = *b"><+-";
+
+// Candidates are checked against every tape whose touched cells hold one of these values, which
+// covers both wrap-arounds.
+const CHECKED_VALUES: [u8; 3] = [0, 1, 255];
+
+/// The net effect of a sequence of `>`, `<`, `+` and `-`: the pointer movement and the amount
+/// added to every changed cell (relative to the initial pointer).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Effect {
+    pointer_delta: i64,
+    cell_deltas: BTreeMap<i64, u8>,
+}
+
+impl Effect {
+    fn of(sequence: &[u8]) -> Self {
+        let mut pointer_delta = 0;
+        let mut cell_deltas = BTreeMap::new();
+        for &symbol in sequence {
+            match symbol {
+                b'>' => pointer_delta += 1,
+                b'<' => pointer_delta -= 1,
+                b'+' => add(&mut cell_deltas, pointer_delta, 1),
+                b'-' => add(&mut cell_deltas, pointer_delta, u8::MAX),
+                _ => unreachable!("only straight-line instructions are part of a window"),
+            }
+        }
+        Self {
+            pointer_delta,
+            cell_deltas,
+        }
+    }
+}
+
+fn add(cell_deltas: &mut BTreeMap<i64, u8>, offset: i64, amount: u8) {
+    let delta = cell_deltas.entry(offset).or_insert(0);
+    *delta = delta.wrapping_add(amount);
+    if *delta == 0 {
+        cell_deltas.remove(&offset);
+    }
+}
+
+struct Window {
+    start: usize,
+    executions: u64,
+}
+
+pub(crate) fn superopt(
+    filename: &Path,
+    source: &[u8],
+    program: &Program,
+    window_size: usize,
+    max_steps: u64,
+) {
+    let bytecode = Bytecode::compile(program);
+    let (executions, complete) = trace(&bytecode, max_steps);
+
+    // Jumps only ever target the first instruction of a straight-line run, so all
+    // instructions of a window are executed equally often.
+    let instructions = bytecode.instructions();
+    let mut windows: HashMap<Vec<u8>, Window> = HashMap::new();
+    for run in instructions
+        .iter()
+        .enumerate()
+        .collect::<Vec<_>>()
+        .split(|(_, instruction)| symbol(instruction).is_none())
+    {
+        for window in run.windows(window_size) {
+            let (index, _) = window[0];
+            if executions[index] == 0 {
+                continue;
+            }
+            let sequence = window
+                .iter()
+                .map(|(_, instruction)| symbol(instruction).expect("split above"))
+                .collect();
+            let entry = windows.entry(sequence).or_insert(Window {
+                start: bytecode.span(index).start,
+                executions: 0,
+            });
+            entry.executions += executions[index];
+        }
+    }
+
+    let mut rewrites: Vec<_> = windows
+        .into_iter()
+        .filter_map(|(sequence, window)| {
+            let replacement = shortest_equivalent(&sequence)?;
+            Some((sequence, replacement, window))
+        })
+        .collect();
+    rewrites.sort_by_key(|(_, _, window)| (std::cmp::Reverse(window.executions), window.start));
+
+    for (sequence, replacement, window) in &rewrites {
+        println!(
+            "{}:{}: `{}` -> `{}` ({} executions)",
+            filename.display(),
+            SourceLocation::from_offset(source, window.start),
+            String::from_utf8_lossy(sequence),
+            String::from_utf8_lossy(replacement),
+            window.executions
+        );
+    }
+    if !complete {
+        println!("the trace was stopped after {max_steps} steps");
+    }
+    match rewrites.len() {
+        0 => println!("no shorter equivalent sequences found"),
+        count => println!("{count} candidate rewrite(s) found"),
+    }
+}
+
+fn symbol(instruction: &Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::IncrementPointer => Some(b'>'),
+        Instruction::DecrementPointer => Some(b'<'),
+        Instruction::IncrementValue => Some(b'+'),
+        Instruction::DecrementValue => Some(b'-'),
+        _ => None,
+    }
+}
+
+/// Runs the program (reading zeros as input and discarding its output) and counts how often
+/// every instruction is executed. Also returns whether the program ended within `max_steps`.
+fn trace(bytecode: &Bytecode, max_steps: u64) -> (Vec<u64>, bool) {
+    let mut executions = vec![0; bytecode.instructions().len()];
+    let complete = bytecode.run(max_steps, &[], |index| {
+        executions[index] += 1;
+        ControlFlow::Continue(())
+    });
+    (executions, complete)
+}
+
+/// Enumerates all sequences shorter than the given one (shortest first) and returns the first
+/// one with the same effect, provided it also passes the check on small states.
+fn shortest_equivalent(sequence: &[u8]) -> Option<Vec<u8>> {
+    let effect = Effect::of(sequence);
+    (0..sequence.len())
+        .flat_map(sequences_of_length)
+        .find(|candidate| Effect::of(candidate) == effect)
+        .filter(|candidate| equivalent_on_small_states(sequence, candidate))
+}
+
+fn sequences_of_length(length: usize) -> impl Iterator<Item = Vec<u8>> {
+    let count = SYMBOLS.len().pow(length as u32);
+    (0..count).map(move |mut number| {
+        (0..length)
+            .map(|_| {
+                let symbol = SYMBOLS[number % SYMBOLS.len()];
+                number /= SYMBOLS.len();
+                symbol
+            })
+            .collect()
+    })
+}
+
+fn equivalent_on_small_states(first: &[u8], second: &[u8]) -> bool {
+    let (lowest, highest) = [first, second]
+        .iter()
+        .flat_map(|sequence| visited_offsets(sequence))
+        .fold((0, 0), |(lowest, highest), offset| {
+            (lowest.min(offset), highest.max(offset))
+        });
+    let cells = (highest - lowest + 1) as usize;
+    let states = CHECKED_VALUES.len().pow(cells as u32);
+    (0..states).all(|mut number| {
+        let tape: Vec<u8> = (0..cells)
+            .map(|_| {
+                let value = CHECKED_VALUES[number % CHECKED_VALUES.len()];
+                number /= CHECKED_VALUES.len();
+                value
+            })
+            .collect();
+        execute(first, tape.clone(), -lowest) == execute(second, tape, -lowest)
+    })
+}
+
+fn visited_offsets(sequence: &[u8]) -> Vec<i64> {
+    let mut offset = 0;
+    let mut visited = vec![0];
+    for &symbol in sequence {
+        match symbol {
+            b'>' => offset += 1,
+            b'<' => offset -= 1,
+            _ => {}
+        }
+        visited.push(offset);
+    }
+    visited
+}
+
+fn execute(sequence: &[u8], mut tape: Vec<u8>, mut pointer: i64) -> (i64, Vec<u8>) {
+    for &symbol in sequence {
+        match symbol {
+            b'>' => pointer += 1,
+            b'<' => pointer -= 1,
+            b'+' => tape[pointer as usize] = tape[pointer as usize].wrapping_add(1),
+            b'-' => tape[pointer as usize] = tape[pointer as usize].wrapping_sub(1),
+            _ => unreachable!("only straight-line instructions are part of a window"),
+        }
+    }
+    (pointer, tape)
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::program::CellSize;
+
+// identifies tape dumps, the last byte is the version of the format
+const MAGIC: &[u8; 8] = b"BFTAPE\0\x01";
+
+/// The cells of a tape once a program has ended, written by `--dump-tape`. The file starts with
+/// a header of 25 bytes: the magic bytes `BFTAPE\0\x01`, the address of the first cell and the
+/// number of cells, both as 64-bit little-endian integers, and the number of bytes of a cell.
+/// The cells follow in the order of their addresses, each in little-endian byte order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TapeDump {
+    pub(crate) first_address: i64,
+    pub(crate) cell_size: CellSize,
+    pub(crate) cells: Vec<u64>,
+}
+
+impl TapeDump {
+    pub(crate) fn write(&self, filename: &Path) -> io::Result<()> {
+        let cell_bytes = (self.cell_size.bits() / 8) as usize;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(self.first_address.to_le_bytes());
+        bytes.extend((self.cells.len() as u64).to_le_bytes());
+        bytes.push(cell_bytes as u8);
+        for cell in &self.cells {
+            bytes.extend(&cell.to_le_bytes()[..cell_bytes]);
+        }
+        fs::write(filename, bytes)
+    }
+}
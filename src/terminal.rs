@@ -0,0 +1,104 @@
+/// Keeps the terminal from echoing the input typed while it exists, for programs that echo their
+/// input themselves. The echo is turned back on when it is dropped or the process is terminated
+/// by a signal.
+pub(crate) struct EchoSuppression(());
+
+impl EchoSuppression {
+    /// Turns off the echo if stdin is a terminal.
+    pub(crate) fn start() -> Option<Self> {
+        termios::disable_echo().then_some(Self(()))
+    }
+}
+
+impl Drop for EchoSuppression {
+    fn drop(&mut self) {
+        termios::restore();
+    }
+}
+
+#[cfg(unix)]
+mod termios {
+    use std::sync::OnceLock;
+
+    const STDIN: i32 = 0;
+    const TCSANOW: i32 = 0;
+    const ECHO: Flags = 0o10;
+    // the signals that terminate a program while it waits for input, which would leave the shell
+    // without echo
+    const TERMINATING_SIGNALS: [i32; 4] = [1, 2, 3, 15]; // SIGHUP, SIGINT, SIGQUIT, SIGTERM
+    const SIG_DFL: usize = 0;
+
+    #[cfg(target_os = "macos")]
+    type Flags = u64;
+    #[cfg(not(target_os = "macos"))]
+    type Flags = u32;
+
+    // the leading fields of `struct termios`, followed by enough space for the remaining ones
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Termios {
+        input_flags: Flags,
+        output_flags: Flags,
+        control_flags: Flags,
+        local_flags: Flags,
+        rest: [u64; 16],
+    }
+
+    extern "C" {
+        fn isatty(file_descriptor: i32) -> i32;
+        fn tcgetattr(file_descriptor: i32, termios: *mut Termios) -> i32;
+        fn tcsetattr(file_descriptor: i32, action: i32, termios: *const Termios) -> i32;
+        fn signal(signal: i32, handler: usize) -> usize;
+        fn raise(signal: i32) -> i32;
+    }
+
+    // the settings of the terminal before the echo was turned off for the first time
+    static ORIGINAL: OnceLock<Termios> = OnceLock::new();
+
+    pub(super) fn disable_echo() -> bool {
+        // SAFETY: the settings are only passed to the functions that fill and read them
+        unsafe {
+            let mut settings = std::mem::zeroed::<Termios>();
+            if isatty(STDIN) == 0 || tcgetattr(STDIN, &mut settings) != 0 {
+                return false;
+            }
+            let original = *ORIGINAL.get_or_init(|| settings);
+            for signal_number in TERMINATING_SIGNALS {
+                signal(
+                    signal_number,
+                    restore_and_raise as extern "C" fn(i32) as usize,
+                );
+            }
+            let silent = Termios {
+                local_flags: original.local_flags & !ECHO,
+                ..original
+            };
+            tcsetattr(STDIN, TCSANOW, &silent) == 0
+        }
+    }
+
+    pub(super) fn restore() {
+        if let Some(original) = ORIGINAL.get() {
+            // SAFETY: the settings have been filled by tcgetattr
+            unsafe { tcsetattr(STDIN, TCSANOW, original) };
+        }
+    }
+
+    extern "C" fn restore_and_raise(signal_number: i32) {
+        restore();
+        // SAFETY: both are async-signal-safe, and the default action terminates the process
+        unsafe {
+            signal(signal_number, SIG_DFL);
+            raise(signal_number);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod termios {
+    pub(super) fn disable_echo() -> bool {
+        false
+    }
+
+    pub(super) fn restore() {}
+}
@@ -0,0 +1,84 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+use crate::json::escape;
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+struct Trace {
+    start: Instant,
+    events: Vec<Event>,
+}
+
+struct Event {
+    name: String,
+    category: &'static str,
+    start: Instant,
+    end: Instant,
+}
+
+/// Starts recording the spans created by [`span`] (`--trace-compile`).
+pub(crate) fn start() {
+    *TRACE.lock().unwrap() = Some(Trace {
+        start: Instant::now(),
+        events: Vec::new(),
+    });
+}
+
+/// Measures the time until the returned guard is dropped. Nothing is recorded unless tracing
+/// has been started.
+pub(crate) fn span(category: &'static str, name: impl Into<String>) -> Span {
+    Span {
+        name: name.into(),
+        category,
+        start: Instant::now(),
+    }
+}
+
+pub(crate) struct Span {
+    name: String,
+    category: &'static str,
+    start: Instant,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(trace) = TRACE.lock().unwrap().as_mut() {
+            trace.events.push(Event {
+                name: std::mem::take(&mut self.name),
+                category: self.category,
+                start: self.start,
+                end: Instant::now(),
+            });
+        }
+    }
+}
+
+/// Writes the recorded spans as complete events in the Chrome trace event format, which can
+/// be viewed using chrome://tracing or Perfetto.
+pub(crate) fn write(filename: &Path) -> Result<()> {
+    let Some(trace) = TRACE.lock().unwrap().take() else {
+        return Ok(());
+    };
+    let mut json = String::from("{\"traceEvents\":[");
+    for (index, event) in trace.events.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            "\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":1}}",
+            escape(&event.name),
+            event.category,
+            event.start.duration_since(trace.start).as_micros(),
+            event.end.duration_since(event.start).as_micros()
+        );
+    }
+    json.push_str("\n],\"displayTimeUnit\":\"ms\"}\n");
+    std::fs::write(filename, json)
+        .with_context(|| format!("failed to write {}", filename.display()))
+}
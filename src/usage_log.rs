@@ -0,0 +1,56 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+static USAGE: Mutex<Usage> = Mutex::new(Usage {
+    program_size: None,
+    engine: None,
+});
+
+// Details of the current invocation that are only known to the individual commands.
+struct Usage {
+    program_size: Option<usize>,
+    engine: Option<String>,
+}
+
+pub(crate) fn record_program_size(size: usize) {
+    USAGE.lock().unwrap().program_size = Some(size);
+}
+
+/// Records how the program was executed or which backend generated code for it.
+pub(crate) fn record_engine(engine: impl Into<String>) {
+    USAGE.lock().unwrap().engine = Some(engine.into());
+}
+
+/// Appends one JSON line describing the invocation to the file (`--stats-log`). The file is
+/// only ever written locally. Failing to write it does not fail the command.
+pub(crate) fn append(filename: &Path, command: &str, duration: Duration, success: bool) {
+    let usage = USAGE.lock().unwrap();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let line = format!(
+        "{{\"timestamp\":{timestamp},\"command\":\"{command}\",\"success\":{success},\"duration_ms\":{:.3},\"program_size\":{},\"engine\":{}}}\n",
+        duration.as_secs_f64() * 1000.0,
+        usage
+            .program_size
+            .map_or("null".to_string(), |size| size.to_string()),
+        usage
+            .engine
+            .as_ref()
+            .map_or("null".to_string(), |engine| format!("\"{engine}\"")),
+    );
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(filename)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if let Err(error) = result {
+        eprintln!(
+            "warning: failed to write usage statistics to '{}': {error}",
+            filename.display()
+        );
+    }
+}
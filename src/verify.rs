@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::bail;
+
+use crate::bytecode::{Bytecode, Instruction};
+use crate::diagnostics::{report, Diagnostic};
+use crate::program::Program;
+
+// Upper bound for the total number of cells held by all visited states, which keeps the memory
+// consumption of the exploration in check.
+const MAX_STORED_CELLS: usize = 4_000_000;
+
+// The set of values a cell can hold, as a bit set over all 256 byte values. Keeping a set per cell
+// (instead of full symbolic expressions) loses correlations between cells, so results are an
+// over-approximation: a trap reported as reachable might not actually be reachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ValueSet([u64; 4]);
+
+impl ValueSet {
+    const ZERO: Self = Self([1, 0, 0, 0]);
+    const ALL: Self = Self([u64::MAX; 4]);
+    const EMPTY: Self = Self([0; 4]);
+
+    fn contains(&self, value: u8) -> bool {
+        self.0[usize::from(value / 64)] & (1 << (value % 64)) != 0
+    }
+
+    fn insert(&mut self, value: u8) {
+        self.0[usize::from(value / 64)] |= 1 << (value % 64);
+    }
+
+    fn without(mut self, value: u8) -> Self {
+        self.0[usize::from(value / 64)] &= !(1 << (value % 64));
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+
+    fn wrapping_add(&self, amount: u8) -> Self {
+        let mut result = Self::EMPTY;
+        for value in 0..=u8::MAX {
+            if self.contains(value) {
+                result.insert(value.wrapping_add(amount));
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SymbolicState {
+    program_counter: usize,
+    pointer: i64,
+    // cells that are not contained are known to be zero
+    tape: BTreeMap<i64, ValueSet>,
+    register: ValueSet,
+}
+
+impl SymbolicState {
+    fn cell(&self) -> ValueSet {
+        self.tape
+            .get(&self.pointer)
+            .copied()
+            .unwrap_or(ValueSet::ZERO)
+    }
+
+    fn with_cell(mut self, values: ValueSet) -> Self {
+        if values == ValueSet::ZERO {
+            self.tape.remove(&self.pointer);
+        } else {
+            self.tape.insert(self.pointer, values);
+        }
+        self
+    }
+
+    fn advanced_to(mut self, program_counter: usize) -> Self {
+        self.program_counter = program_counter;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Trap {
+    CellOverflow,
+    CellUnderflow,
+    PointerBeforeFirstCell,
+    PointerBeyondLastCell,
+}
+
+impl Display for Trap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trap::CellOverflow => write!(f, "incrementing a cell holding 255"),
+            Trap::CellUnderflow => write!(f, "decrementing a cell holding 0"),
+            Trap::PointerBeforeFirstCell => write!(f, "moving the pointer before the first cell"),
+            Trap::PointerBeyondLastCell => write!(f, "moving the pointer beyond the last cell"),
+        }
+    }
+}
+
+struct Exploration {
+    // maps every trap (and the instruction triggering it) to the fewest steps needed to reach it
+    traps: BTreeMap<(Trap, usize), u64>,
+    explored_states: usize,
+    step_limit_reached: bool,
+    state_limit_reached: bool,
+}
+
+fn explore(bytecode: &Bytecode, max_steps: u64, tape_size: Option<u64>) -> Exploration {
+    let instructions = bytecode.instructions();
+    let mut exploration = Exploration {
+        traps: BTreeMap::new(),
+        explored_states: 0,
+        step_limit_reached: false,
+        state_limit_reached: false,
+    };
+    let initial_state = SymbolicState {
+        program_counter: 0,
+        pointer: 0,
+        tape: BTreeMap::new(),
+        register: ValueSet::ZERO,
+    };
+    let mut stored_cells = 0;
+    let mut visited = HashSet::from([initial_state.clone()]);
+    let mut queue = VecDeque::from([(initial_state, 0)]);
+
+    while let Some((state, steps)) = queue.pop_front() {
+        exploration.explored_states += 1;
+        let Some(&instruction) = instructions.get(state.program_counter) else {
+            continue;
+        };
+        if steps == max_steps {
+            exploration.step_limit_reached = true;
+            continue;
+        }
+
+        let program_counter = state.program_counter;
+        let mut trap = |trap: Trap| {
+            exploration
+                .traps
+                .entry((trap, program_counter))
+                .or_insert(steps + 1);
+        };
+        let next = program_counter + 1;
+        let cell = state.cell();
+        let successors = match instruction {
+            Instruction::IncrementPointer | Instruction::DecrementPointer => {
+                let pointer = if instruction == Instruction::IncrementPointer {
+                    state.pointer + 1
+                } else {
+                    state.pointer - 1
+                };
+                if pointer < 0 {
+                    trap(Trap::PointerBeforeFirstCell);
+                    vec![]
+                } else if tape_size.is_some_and(|size| pointer as u64 >= size) {
+                    trap(Trap::PointerBeyondLastCell);
+                    vec![]
+                } else {
+                    vec![SymbolicState {
+                        pointer,
+                        ..state.advanced_to(next)
+                    }]
+                }
+            }
+            Instruction::IncrementValue | Instruction::DecrementValue => {
+                let (limit, amount, kind) = if instruction == Instruction::IncrementValue {
+                    (u8::MAX, 1, Trap::CellOverflow)
+                } else {
+                    (0, u8::MAX, Trap::CellUnderflow)
+                };
+                if cell.contains(limit) {
+                    trap(kind);
+                }
+                // only the executions that did not trap carry on
+                let remaining = cell.without(limit);
+                if remaining.is_empty() {
+                    vec![]
+                } else {
+                    vec![state
+                        .with_cell(remaining.wrapping_add(amount))
+                        .advanced_to(next)]
+                }
+            }
+            Instruction::PutChar => vec![state.advanced_to(next)],
+            Instruction::GetChar => vec![state.with_cell(ValueSet::ALL).advanced_to(next)],
+            Instruction::StoreRegister => vec![SymbolicState {
+                register: cell,
+                ..state.advanced_to(next)
+            }],
+            Instruction::LoadRegister => {
+                let register = state.register;
+                vec![state.with_cell(register).advanced_to(next)]
+            }
+            Instruction::JumpIfZero(target) | Instruction::JumpIfNotZero(target) => {
+                let (zero_target, non_zero_target) = match instruction {
+                    Instruction::JumpIfZero(_) => (target, next),
+                    _ => (next, target),
+                };
+                let mut successors = Vec::new();
+                if cell.contains(0) {
+                    successors.push(
+                        state
+                            .clone()
+                            .with_cell(ValueSet::ZERO)
+                            .advanced_to(zero_target),
+                    );
+                }
+                let non_zero = cell.without(0);
+                if !non_zero.is_empty() {
+                    successors.push(state.with_cell(non_zero).advanced_to(non_zero_target));
+                }
+                successors
+            }
+            Instruction::Jump(target) => vec![state.advanced_to(target)],
+        };
+
+        for successor in successors {
+            if visited.contains(&successor) {
+                continue;
+            }
+            stored_cells += 1 + successor.tape.len();
+            if stored_cells > MAX_STORED_CELLS {
+                exploration.state_limit_reached = true;
+                return exploration;
+            }
+            visited.insert(successor.clone());
+            queue.push_back((successor, steps + 1));
+        }
+    }
+    exploration
+}
+
+pub(crate) fn verify(
+    filename: &Path,
+    source: &[u8],
+    program: &Program,
+    max_steps: u64,
+    tape_size: Option<u64>,
+) -> anyhow::Result<()> {
+    let bytecode = Bytecode::compile(program);
+    let exploration = explore(&bytecode, max_steps, tape_size);
+
+    for (&(trap, instruction_index), steps) in &exploration.traps {
+        report(
+            filename,
+            source,
+            &Diagnostic::warning(
+                bytecode.span(instruction_index),
+                format!("{trap} may be possible (after {steps} steps)"),
+            ),
+        );
+    }
+
+    println!("explored {} states", exploration.explored_states);
+    if exploration.state_limit_reached {
+        println!("verification is incomplete: the state space is too large to be explored");
+    } else if exploration.step_limit_reached {
+        println!("verification is incomplete: some executions take more than {max_steps} steps");
+    } else {
+        println!("all executions terminate within {max_steps} steps");
+    }
+
+    if !exploration.traps.is_empty() {
+        bail!("{} potential trap(s) found", exploration.traps.len());
+    }
+    Ok(())
+}